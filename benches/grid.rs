@@ -0,0 +1,93 @@
+//! Per-frame costs of the grid at 10k wallpapers: render, scroll bookkeeping and search.
+//!
+//! Runs against a scratch home so the real config, session and caches are left alone.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use omarchy_wallpaper_picker::app::App;
+use omarchy_wallpaper_picker::browse::{self, Nav};
+use omarchy_wallpaper_picker::{ui, wallpaper};
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITEMS: usize = 10_000;
+
+/// A home directory holding `ITEMS` placeholder images. It's left behind, since thumbnail
+/// and header loaders may still be writing to it at exit; the next run starts it over.
+fn scratch_library() {
+    let home = std::env::temp_dir().join("owp-bench");
+    let _ = fs::remove_dir_all(&home);
+    let backgrounds = home.join("backgrounds");
+    fs::create_dir_all(&backgrounds).unwrap();
+    for i in 0..ITEMS {
+        fs::write(backgrounds.join(format!("wall-{:05}.jpg", i)), b"").unwrap();
+    }
+    // SAFETY: nothing else is running yet to read the environment concurrently
+    unsafe {
+        std::env::set_var("HOME", &home);
+        for var in ["XDG_CONFIG_HOME", "XDG_DATA_HOME", "XDG_CACHE_HOME", "XDG_STATE_HOME", "XDG_RUNTIME_DIR"] {
+            std::env::set_var(var, home.join(var.to_lowercase()));
+        }
+    }
+    wallpaper::set_backgrounds_dir(backgrounds);
+}
+
+/// An app that has finished listing the scratch library
+fn loaded_app() -> App {
+    let mut app = App::new().unwrap();
+    let started = Instant::now();
+    while !app.poll_scan() {
+        assert!(started.elapsed() < Duration::from_secs(60), "library scan didn't finish");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(app.wallpapers.len(), ITEMS);
+    app
+}
+
+fn render(c: &mut Criterion) {
+    let mut app = loaded_app();
+    let mut terminal = Terminal::new(TestBackend::new(160, 48)).unwrap();
+    c.bench_function("render 10k, scrolling a row per frame", |b| {
+        b.iter(|| {
+            app.browser.selected = (app.browser.selected + app.browser.columns) % ITEMS;
+            terminal.draw(|frame| ui::render(frame, &mut app)).unwrap();
+        })
+    });
+}
+
+fn bookkeeping(c: &mut Criterion) {
+    let filtered: Vec<usize> = (0..ITEMS).collect();
+    let (columns, rows) = (8, 6);
+    let last_row = ITEMS.div_ceil(columns);
+    let mut offset = 0;
+    c.bench_function("visible and retained cells 10k", |b| {
+        b.iter(|| {
+            offset = (offset + 1) % last_row;
+            let visible = browse::visible_range(filtered.len(), columns, offset, rows);
+            black_box(browse::retained_indices(&filtered, visible, columns, rows))
+        })
+    });
+    let mut selected = 0;
+    c.bench_function("navigate 10k", |b| {
+        b.iter(|| {
+            selected = browse::navigate(selected, ITEMS, columns, Nav::Down);
+            if selected + columns >= ITEMS {
+                selected = 0;
+            }
+            black_box(selected)
+        })
+    });
+    let names: Vec<String> = (0..ITEMS).map(|i| format!("wall-{:05}", i)).collect();
+    c.bench_function("search 10k", |b| b.iter(|| black_box(browse::filter(names.iter(), "w1 9"))));
+}
+
+fn benches(c: &mut Criterion) {
+    scratch_library();
+    bookkeeping(c);
+    render(c);
+}
+
+criterion_group!(grid, benches);
+criterion_main!(grid);
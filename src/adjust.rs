@@ -0,0 +1,173 @@
+use color_eyre::{eyre::eyre, Result};
+use image::{DynamicImage, GenericImageView};
+
+/// Blur radius used when blur is toggled from the preview
+pub const DEFAULT_BLUR: f32 = 8.0;
+/// Dim amount used when dimming is toggled from the preview
+pub const DEFAULT_DIM: f32 = 0.3;
+/// Longest edge used when previewing adjustments; blurring full-size images is slow
+const PREVIEW_MAX_EDGE: u32 = 1920;
+
+/// Processing applied to a wallpaper before it is installed
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Adjustments {
+    /// Gaussian blur sigma in source pixels; 0 disables
+    pub blur: f32,
+    /// 0.0 leaves brightness alone, 1.0 is black
+    pub dim: f32,
+    pub grayscale: bool,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270
+    pub rotation: u16,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Adjustments {
+    pub fn is_identity(&self) -> bool {
+        self.blur <= 0.0
+            && self.dim <= 0.0
+            && !self.grayscale
+            && self.rotation == 0
+            && !self.flip_horizontal
+            && !self.flip_vertical
+    }
+
+    /// Parse `--blur <sigma>`, `--dim <0..1>`, `--grayscale`, `--rotate <deg>` and `--flip h|v` flags
+    pub fn parse_args(args: &[String]) -> Result<Self> {
+        let mut adjustments = Self::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--blur" => {
+                    let value = iter.next().ok_or_else(|| eyre!("--blur needs a value"))?;
+                    adjustments.blur = value.parse()?;
+                }
+                "--dim" => {
+                    let value = iter.next().ok_or_else(|| eyre!("--dim needs a value"))?;
+                    adjustments.dim = value.parse::<f32>()?.clamp(0.0, 1.0);
+                }
+                "--grayscale" | "--gray" => adjustments.grayscale = true,
+                "--rotate" => {
+                    let value = iter.next().ok_or_else(|| eyre!("--rotate needs a value"))?;
+                    adjustments.rotate(value)?;
+                }
+                "--flip" => {
+                    let value = iter.next().ok_or_else(|| eyre!("--flip needs h or v"))?;
+                    adjustments.flip(value)?;
+                }
+                other => return Err(eyre!("Unknown option: {}", other)),
+            }
+        }
+        Ok(adjustments)
+    }
+
+    pub fn toggle_blur(&mut self) {
+        self.blur = if self.blur > 0.0 { 0.0 } else { DEFAULT_BLUR };
+    }
+
+    pub fn toggle_dim(&mut self) {
+        self.dim = if self.dim > 0.0 { 0.0 } else { DEFAULT_DIM };
+    }
+
+    pub fn toggle_grayscale(&mut self) {
+        self.grayscale = !self.grayscale;
+    }
+
+    /// Add a clockwise rotation given in degrees; negative values turn counter-clockwise
+    pub fn rotate(&mut self, degrees: &str) -> Result<()> {
+        let degrees: i32 = degrees.parse()?;
+        if degrees % 90 != 0 {
+            return Err(eyre!("Rotation must be a multiple of 90 degrees"));
+        }
+        self.rotation = (self.rotation as i32 + degrees).rem_euclid(360) as u16;
+        Ok(())
+    }
+
+    /// Toggle mirroring along `h` (left/right) or `v` (top/bottom)
+    pub fn flip(&mut self, axis: &str) -> Result<()> {
+        match axis {
+            "h" | "horizontal" => self.flip_horizontal = !self.flip_horizontal,
+            "v" | "vertical" => self.flip_vertical = !self.flip_vertical,
+            other => return Err(eyre!("Unknown flip axis: {} (use h or v)", other)),
+        }
+        Ok(())
+    }
+
+    /// Short description for titles, e.g. "blur 8, dim 0.3"
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.blur > 0.0 {
+            parts.push(format!("blur {}", self.blur));
+        }
+        if self.dim > 0.0 {
+            parts.push(format!("dim {}", self.dim));
+        }
+        if self.grayscale {
+            parts.push("grayscale".to_string());
+        }
+        if self.rotation != 0 {
+            parts.push(format!("rotate {}", self.rotation));
+        }
+        if self.flip_horizontal {
+            parts.push("flip h".to_string());
+        }
+        if self.flip_vertical {
+            parts.push("flip v".to_string());
+        }
+        parts.join(", ")
+    }
+
+    /// Process a full-resolution image
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        self.apply_scaled(img, 1.0)
+    }
+
+    /// Process an image that was downscaled by `scale` from the original,
+    /// shrinking the blur radius to match so previews look like the result
+    pub fn apply_scaled(&self, img: DynamicImage, scale: f32) -> DynamicImage {
+        let mut img = match self.rotation {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        };
+        if self.flip_horizontal {
+            img = img.fliph();
+        }
+        if self.flip_vertical {
+            img = img.flipv();
+        }
+        if self.grayscale {
+            img = DynamicImage::ImageRgba8(img.grayscale().to_rgba8());
+        }
+        if self.blur > 0.0 {
+            img = img.blur(self.blur * scale);
+        }
+        if self.dim > 0.0 {
+            let factor = 1.0 - self.dim;
+            let mut rgba = img.to_rgba8();
+            for pixel in rgba.pixels_mut() {
+                for channel in &mut pixel.0[..3] {
+                    *channel = (*channel as f32 * factor) as u8;
+                }
+            }
+            img = DynamicImage::ImageRgba8(rgba);
+        }
+        img
+    }
+}
+
+/// Downscale for preview and apply `adjustments`
+pub fn preview(img: DynamicImage, adjustments: &Adjustments) -> DynamicImage {
+    if adjustments.is_identity() {
+        return img;
+    }
+    let (w, h) = img.dimensions();
+    let longest = w.max(h).max(1);
+    if longest > PREVIEW_MAX_EDGE {
+        let scale = PREVIEW_MAX_EDGE as f32 / longest as f32;
+        adjustments.apply_scaled(img.thumbnail(PREVIEW_MAX_EDGE, PREVIEW_MAX_EDGE), scale)
+    } else {
+        adjustments.apply(img)
+    }
+}
@@ -0,0 +1,42 @@
+//! Screen-reader mode: images are left out and every change of state is put into words on a
+//! single line at the bottom of the screen, where terminal screen readers pick it up.
+
+use crate::config::Config;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+/// Turn screen-reader mode on (`--screen-reader`)
+pub fn set_enabled() {
+    FORCED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled(config: &Config) -> bool {
+    FORCED.load(Ordering::Relaxed) || config.screen_reader
+}
+
+/// The line read out, and the state it last described
+#[derive(Default)]
+pub struct Announcer {
+    pub message: String,
+    last_state: String,
+}
+
+impl Announcer {
+    /// Announce `state` if it differs from the last one described. Returns true if it did.
+    pub fn update_state(&mut self, state: String) -> bool {
+        if state == self.last_state {
+            return false;
+        }
+        self.message = state.clone();
+        self.last_state = state;
+        true
+    }
+
+    /// Announce an event, e.g. the result of an apply. `state` is taken as already described,
+    /// so a change the event itself caused doesn't talk over it.
+    pub fn say(&mut self, message: impl Into<String>, state: String) {
+        self.message = message.into();
+        self.last_state = state;
+    }
+}
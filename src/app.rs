@@ -1,9 +1,54 @@
+use crate::adjust::Adjustments;
+use crate::announce::{self, Announcer};
+use crate::bookmarks::Bookmarks;
+use crate::clipboard;
+use crate::browse::{self, DirSource, ScrollAnimation, SortOrder};
+use crate::browser::{Browser, CompletionSource, Effect, Input};
+use crate::commands::{self, Command, Setting};
+use crate::collections::{self, Collections};
+use crate::compare::Compare;
+use crate::config::{self, Config};
+use crate::crop::CropRect;
 use crate::encoder::ImageEncoder;
-use crate::wallpaper::{self, Wallpaper};
-use color_eyre::Result;
-use ratatui_image::picker::Picker;
+use crate::events::{AppEvent, EventBus};
+use crate::groups::{self, GroupBy};
+use crate::palette::{self, Tone};
+use crate::{backend, formats, history, hooks, keymap, library, lockscreen, login, net, notifications, svg, variants};
+use crate::loader::{self, ColorReader, HeaderReader, PreviewLoader, Scan, ScanReason, ThumbnailLoader};
+use crate::import::{Collision, ImportPlan};
+use crate::ipc::{IpcCommand, IpcServer};
+use crate::memory;
+use crate::metadata::Metadata;
+use crate::monitor::{self, Monitor};
+use crate::pins::Pins;
+use crate::downloads::{self, DownloadManager};
+use crate::potd::PotdFetch;
+use crate::queue::{self, Queue};
+use crate::reddit::{self, Post, RedditFetch};
+use crate::session::Session;
+use crate::terminal;
+use crate::theme::Theme;
+use crate::tags::{TagEditor, TagExpr};
+use crate::trashcan;
+use crate::undo::{self, Change, UndoStack};
+use crate::watcher::{DirWatcher, FileWatcher};
+use crate::wallpaper::{self, FillMode, MinSize, Wallpaper};
+use color_eyre::{eyre::eyre, Result};
+use image::DynamicImage;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use ratatui_image::picker::{Picker, ProtocolType};
 use ratatui_image::protocol::StatefulProtocol;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use trash::TrashItem;
+
+/// Minimum grid cell width in terminal columns, unless changed with +/-
+pub const DEFAULT_CELL_WIDTH: u16 = 30;
+const MIN_CELL_WIDTH: u16 = 12;
+const MAX_CELL_WIDTH: u16 = 80;
+const CELL_WIDTH_STEP: u16 = 4;
 
 pub enum Mode {
     Grid,
@@ -11,380 +56,2757 @@ pub enum Mode {
     Help,
     Search,
     Command,
+    Crop,
+    /// Asking how to handle name collisions for a pending import
+    ImportConflict,
+    Compare,
+    /// Picking a trashed wallpaper to restore
+    Trash,
+    /// Asking whether to apply an image that doesn't suit the monitors
+    ConfirmApply,
+    /// The download queue
+    Downloads,
+    /// A wallpaper is on the desktop for a trial, waiting to be kept or undone
+    Trying,
+    /// `:set` without an option: every setting and its value
+    Settings,
+    /// `:undolist`: changes `u` can take back
+    UndoList,
+    /// `:tag` alone: collections of the marked wallpapers, with tri-state toggles
+    TagEditor,
+    /// The backend couldn't show an applied wallpaper and the previous one was kept
+    ApplyFailed,
+}
+
+/// What to do with a file once its download finishes
+struct FollowUp {
+    note: Option<String>,
+    source: Option<String>,
+    apply: bool,
+}
+
+/// Seconds a trial lasts unless configured
+const DEFAULT_TRY_SECONDS: u64 = 10;
+/// How long the cursor rests on a wallpaper before hover-apply shows it
+const HOVER_DELAY: Duration = Duration::from_millis(400);
+/// How long a notice such as "Config reloaded" stays on the notification line
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+/// How often the stored queue is checked for a due wallpaper
+const QUEUE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Cell size assumed when the terminal won't report its font, e.g. over plain SSH
+const FALLBACK_FONT_SIZE: (u16, u16) = (8, 16);
+
+/// A wallpaper shown on the desktop without installing it, reverted at `deadline`
+pub struct Trial {
+    pub name: String,
+    pub deadline: Instant,
+    /// Whole seconds left when last drawn, to redraw the countdown only when it changes
+    shown_secs: u64,
+    from_preview: bool,
+}
+
+impl Trial {
+    pub fn remaining_secs(&self) -> u64 {
+        self.deadline.saturating_duration_since(Instant::now()).as_secs_f32().ceil() as u64
+    }
 }
 
 pub struct App {
     pub wallpapers: Vec<Wallpaper>,
-    pub filtered_indices: Vec<usize>,
-    pub selected: usize,
-    pub columns: usize,
+    /// Where each wallpaper sits in `wallpapers`, so background results find theirs quickly
+    wallpaper_index: HashMap<PathBuf, usize>,
+    /// Cursor, visible wallpapers, search and command prompt
+    pub browser: Browser,
+    /// Minimum width of a grid cell; the grid fits as many columns as this allows
+    pub cell_width: u16,
+    /// Grid cells show format, file size and animation badges
+    pub show_badges: bool,
+    pub sort: SortOrder,
     pub mode: Mode,
     pub should_quit: bool,
+    /// Advances while thumbnails or a scan are loading, to animate placeholders
+    pub spinner_tick: usize,
     pub current_wallpaper: Option<PathBuf>,
+    pub applied_at: Option<SystemTime>,
+    pub config: Config,
+    /// Colors from the omarchy theme and `[tui_colors]`
+    pub theme: Theme,
+    /// Set in screen-reader mode, which draws no images
+    pub announcer: Option<Announcer>,
     pub picker: Picker,
     pub encoder: ImageEncoder,
+    pub thumbnail_loader: ThumbnailLoader,
+    /// A `:preload` batch is loading, which Esc skips
+    pub preloading: bool,
+    /// Grid row offset drawn last frame
+    scroll_offset: usize,
+    /// Glide in progress towards a far-off row offset
+    scroll_animation: Option<ScrollAnimation>,
+    /// Header pass filling in `Wallpaper::dimensions` for the current listing
+    header_reader: Option<HeaderReader>,
+    /// Color pass filling in `Wallpaper::colors` for the current listing
+    color_reader: Option<ColorReader>,
     pub preview_state: Option<StatefulProtocol>,
-    pub search_query: String,
-    pub command_query: String,
-    pub completions: Vec<String>,
-    pub completion_index: usize,
-    pub completion_dir: Option<PathBuf>,
+    /// Full-size image for the preview being decoded
+    pub preview_load: Option<PreviewLoader>,
+    /// The decoded preview, kept to lay out the monitor preview from
+    pub preview_image: Option<DynamicImage>,
+    /// The cached thumbnail scaled up, shown while the full preview decodes
+    pub preview_placeholder: Option<StatefulProtocol>,
+    pub fill_mode: FillMode,
+    pub monitors: Vec<Monitor>,
+    pub monitor_preview: bool,
+    /// One encoded image per entry in `monitors`, rendered with `fill_mode`
+    pub monitor_preview_states: Vec<StatefulProtocol>,
+    /// Preview shows the current wallpaper beside the selection
+    pub before_after: bool,
+    /// The current wallpaper, for the before/after preview
+    pub before_state: Option<StatefulProtocol>,
+    before_load: Option<PreviewLoader>,
+    /// Processing applied to the preview and to applied wallpapers
+    pub adjustments: Adjustments,
+    pub crop: Option<CropRect>,
+    /// Downscaled copy of the image being cropped, for fast overlay redraws
+    pub crop_source: Option<DynamicImage>,
+    pub crop_state: Option<StatefulProtocol>,
+    /// First pick for an A/B comparison, waiting for a second
+    pub compare_mark: Option<PathBuf>,
+    pub compare: Option<Compare>,
+    /// Encoded left and right halves of the comparison
+    pub compare_states: Vec<StatefulProtocol>,
+    /// Only show wallpapers of this brightness (`:filter dark|light`)
+    pub tone_filter: Option<Tone>,
+    /// Only wallpapers with at least this many stars are shown
+    pub min_stars: Option<u8>,
+    /// Only wallpapers whose collections satisfy this (`:filter #a & !#b`)
+    pub tag_filter: Option<TagExpr>,
+    /// Saved collections, for filtering, grouping and completion; changes made here are kept
+    /// as they are saved, and edits by other processes are read back by `poll_collections`
+    pub collections: Collections,
+    collections_watcher: Option<FileWatcher>,
+    /// `:filter all`: list images below the configured minimum size too
+    pub show_all: bool,
+    /// `:group-by`: how the grid is split into sections
+    pub group_by: Option<GroupBy>,
+    /// `YYYY-MM` by path, so grouping by month reads each mtime once
+    month_labels: HashMap<PathBuf, String>,
+    /// The wallpaper last picked by the cursor, re-selected whenever a filter stops hiding it
+    selection_anchor: Option<PathBuf>,
+    /// Where the cursor was parked while the anchor is hidden; moving off it picks a new anchor
+    selection_fallback: Option<PathBuf>,
+    /// First help line shown; clamped to the content when drawn
+    pub help_scroll: usize,
+    /// Only help lines containing this are listed
+    pub help_query: String,
+    /// Typing goes to `help_query`
+    pub help_searching: bool,
+    /// The command prompt was opened over the preview and returns to it
+    command_from_preview: bool,
     pub current_view_dir: Option<PathBuf>,
+    /// Collection being browsed instead of a directory
+    pub active_collection: Option<String>,
+    /// Listing in progress for the view, replacing `wallpapers` when done
+    pub scan: Option<Scan>,
+    /// One per watched directory: the view dir, or every library root
+    pub watchers: Vec<DirWatcher>,
+    /// Notices other tools pointing the current-background link elsewhere
+    current_watcher: Option<FileWatcher>,
+    pub events: EventBus,
+    /// Failures to show the user, oldest first; dismissed one at a time
+    pub errors: VecDeque<String>,
+    /// Short-lived confirmation shown on the notification line when there are no errors
+    pub notice: Option<(String, Instant)>,
+    /// Notices edits to the config file so they apply without a restart
+    config_watcher: Option<FileWatcher>,
+    pub ipc: Option<IpcServer>,
+    /// Import waiting on a collision choice
+    pub pending_import: Option<ImportPlan>,
+    /// Trashed images offered by `:trash restore`, newest first
+    pub trash_items: Vec<TrashItem>,
+    pub trash_selected: usize,
+    /// F12 overlay with memory use and frame time
+    pub show_diagnostics: bool,
+    /// How long the last frame took to draw
+    pub frame_time: Duration,
+    /// Highlighted row of the `:set` list
+    pub settings_selected: usize,
+    /// Trashing, collection changes and imports made this session, for `u`
+    pub undo: UndoStack,
+    /// Wallpapers marked with `x` for `:tag`
+    pub marked: BTreeSet<PathBuf>,
+    pub tag_editor: Option<TagEditor>,
+    /// Why the selection may look poor, shown while confirming the apply
+    pub apply_warning: Option<String>,
+    /// Why the last apply failed, while `Mode::ApplyFailed` shows it
+    pub apply_failure: Option<String>,
+    pub trial: Option<Trial>,
+    /// Wallpapers lined up with `a`, as last read from disk
+    pub queue: Queue,
+    queue_checked: Instant,
+    /// The apply confirmation was opened over the preview and returns to it
+    confirm_from_preview: bool,
+    /// Apply without checking image size against the monitors
+    pub skip_resolution_warning: bool,
+    /// Files added by the last import, outlined in the grid
+    pub highlighted: HashSet<PathBuf>,
+    /// Notes and source URLs, shown in the preview and matched by search
+    pub metadata: Metadata,
+    /// `:reddit` listing being fetched
+    pub reddit_fetch: Option<RedditFetch>,
+    /// `:potd` download in progress
+    pub potd_fetch: Option<PotdFetch>,
+    pub downloads: DownloadManager,
+    follow_ups: HashMap<u64, FollowUp>,
+    /// Selection as last reported on the event bus
+    last_selection: Option<PathBuf>,
+    selection_changed_at: Instant,
+    /// What hover-apply has put on the desktop in place of the current wallpaper
+    hover_shown: Option<PathBuf>,
+    /// Hover-apply showing a wallpaper in the background
+    hover_job: Option<HoverJob>,
+}
+
+struct HoverJob {
+    rx: Receiver<Result<()>>,
 }
 
 impl App {
     pub fn new() -> Result<Self> {
-        let wallpapers = wallpaper::discover_wallpapers(None)?;
+        let session = Session::load();
+        let config = Config::load()?;
+        net::configure(&config);
+        // Before reading the current wallpaper, which may be behind a configured link
+        wallpaper::configure(&config);
         let current_wallpaper = wallpaper::get_current_wallpaper();
-        let picker = Picker::from_query_stdio()?;
-        let encoder = ImageEncoder::new(picker.clone());
-
-        // All indices visible initially
-        let filtered_indices: Vec<usize> = (0..wallpapers.len()).collect();
-
-        // Find the index of the current wallpaper
-        let selected = current_wallpaper
-            .as_ref()
-            .and_then(|current| {
-                wallpapers
-                    .iter()
-                    .position(|w| w.path == *current)
-            })
-            .unwrap_or(0);
+        let applied_at = wallpaper::get_current_wallpaper_applied_at();
+        let tone_filter = if config.match_system_tone { palette::system_tone() } else { None };
+        let picker = image_picker();
+        let encoder = ImageEncoder::new(picker);
+        let downloads =
+            DownloadManager::new(config.download_concurrency.unwrap_or(downloads::DEFAULT_CONCURRENCY));
 
-        Ok(Self {
-            wallpapers,
-            filtered_indices,
-            selected,
-            columns: 4,
+        let mut app = Self {
+            wallpapers: Vec::new(),
+            wallpaper_index: HashMap::new(),
+            browser: Browser::new(4),
+            cell_width: config.cell_width.unwrap_or(session.cell_width).clamp(MIN_CELL_WIDTH, MAX_CELL_WIDTH),
+            show_badges: session.show_badges,
+            sort: session.sort,
             mode: Mode::Grid,
             should_quit: false,
+            spinner_tick: 0,
             current_wallpaper,
+            applied_at,
+            theme: Theme::for_config(&config),
+            announcer: announce::is_enabled(&config).then(Announcer::default),
+            config,
             picker,
             encoder,
+            thumbnail_loader: ThumbnailLoader::new(),
+            preloading: false,
+            scroll_offset: 0,
+            scroll_animation: None,
+            header_reader: None,
+            color_reader: None,
             preview_state: None,
-            search_query: String::new(),
-            command_query: String::new(),
-            completions: Vec::new(),
-            completion_index: 0,
-            completion_dir: None,
+            preview_load: None,
+            preview_image: None,
+            preview_placeholder: None,
+            fill_mode: session.fill_mode,
+            // Detected up front so grid badges can flag images smaller than the outputs
+            monitors: monitor::detect_monitors(),
+            monitor_preview: session.monitor_preview,
+            monitor_preview_states: Vec::new(),
+            before_after: false,
+            before_state: None,
+            before_load: None,
+            adjustments: Adjustments::default(),
+            crop: None,
+            crop_source: None,
+            crop_state: None,
+            compare_mark: None,
+            compare: None,
+            compare_states: Vec::new(),
+            tone_filter,
+            min_stars: None,
+            tag_filter: None,
+            group_by: None,
+            month_labels: HashMap::new(),
+            show_all: false,
+            selection_anchor: None,
+            selection_fallback: None,
+            command_from_preview: false,
+            help_scroll: 0,
+            help_query: String::new(),
+            help_searching: false,
             current_view_dir: None,
-        })
+            active_collection: None,
+            scan: None,
+            watchers: Vec::new(),
+            current_watcher: FileWatcher::new(&wallpaper::get_current_background_path()).ok(),
+            events: EventBus::default(),
+            errors: VecDeque::new(),
+            notice: None,
+            config_watcher: FileWatcher::new(&config::get_config_path()).ok(),
+            collections: Collections::default(),
+            collections_watcher: FileWatcher::new(&collections::get_collections_path()).ok(),
+            ipc: None,
+            pending_import: None,
+            apply_warning: None,
+            apply_failure: None,
+            trial: None,
+            queue: Queue::default(),
+            queue_checked: Instant::now(),
+            confirm_from_preview: false,
+            skip_resolution_warning: session.skip_resolution_warning,
+            trash_items: Vec::new(),
+            trash_selected: 0,
+            show_diagnostics: false,
+            frame_time: Duration::ZERO,
+            settings_selected: 0,
+            undo: UndoStack::default(),
+            marked: BTreeSet::new(),
+            tag_editor: None,
+            highlighted: HashSet::new(),
+            metadata: Metadata::load(),
+            reddit_fetch: None,
+            potd_fetch: None,
+            downloads,
+            follow_ups: HashMap::new(),
+            last_selection: None,
+            selection_changed_at: Instant::now(),
+            hover_shown: None,
+            hover_job: None,
+        };
+        // The grid fills in once the listing arrives; a saved dir that has since
+        // disappeared falls back to the default one
+        match Collections::load() {
+            Ok(collections) => app.collections = collections,
+            Err(e) => app.push_error(format!("Collections: {}", e)),
+        }
+        let reason = ScanReason::Startup { selected: session.selected, query: session.search_query };
+        app.open_view(session.view_dir, session.collection, reason);
+        // The control socket is optional; another running instance may own it
+        app.ipc = IpcServer::start().ok().flatten();
+        app.subscribe_events();
+        let result = keymap::configure(&app.config.keys);
+        app.report(result);
+        match Queue::load() {
+            Ok(queue) => app.queue = queue,
+            Err(e) => app.push_error(format!("Queue: {}", e)),
+        }
+        if app.picker.protocol_type() == ProtocolType::Halfblocks {
+            app.show_notice("No terminal graphics protocol; showing images as color-block mosaics");
+        }
+        Ok(app)
     }
 
-    pub fn preload_thumbnails<F>(&mut self, mut progress: F)
-    where
-        F: FnMut(usize, usize, &str),
-    {
-        let total = self.wallpapers.len();
-        for i in 0..total {
-            let name = self.wallpapers[i].name.clone();
-            progress(i, total, &name);
-            self.wallpapers[i].load_thumbnail();
+    /// Hooks, history, variants, notifications and IPC clients, wired to a fresh event bus
+    fn subscribe_events(&mut self) {
+        self.events = EventBus::default();
+        hooks::subscribe(&mut self.events, &self.config.hooks);
+        history::subscribe(&mut self.events);
+        variants::subscribe(&mut self.events, &self.config);
+        notifications::subscribe(&mut self.events, &self.config);
+        if let Some(ipc) = self.ipc.as_ref() {
+            self.events.subscribe(ipc.event_forwarder());
         }
     }
 
-    pub fn update_filter(&mut self) {
-        let query = self.search_query.to_lowercase();
-        if query.is_empty() {
-            self.filtered_indices = (0..self.wallpapers.len()).collect();
-        } else {
-            self.filtered_indices = self
-                .wallpapers
-                .iter()
-                .enumerate()
-                .filter(|(_, w)| w.name.to_lowercase().contains(&query))
-                .map(|(i, _)| i)
-                .collect();
+    /// Read the collections again once another process has changed them, re-filtering if they
+    /// decide what's shown. Returns true if anything changed on screen.
+    pub fn poll_collections(&mut self) -> bool {
+        if !self.collections_watcher.as_ref().is_some_and(|w| w.poll_changed()) {
+            return false;
+        }
+        match Collections::load() {
+            Ok(collections) => self.collections = collections,
+            Err(e) => {
+                self.push_error(format!("Collections not reloaded: {}", e));
+                return true;
+            }
         }
-        // Reset selection if out of bounds
-        if self.selected >= self.filtered_indices.len() {
-            self.selected = 0;
+        if self.tag_filter.is_some() || self.group_by == Some(GroupBy::Tag) {
+            self.update_filter();
         }
+        true
     }
 
-    pub fn start_search(&mut self) {
-        self.mode = Mode::Search;
+    /// Apply the config file again once it has been edited. A file that no longer parses
+    /// leaves the running config alone. Returns true if anything changed on screen.
+    pub fn poll_config(&mut self) -> bool {
+        if !self.config_watcher.as_ref().is_some_and(|w| w.poll_changed()) {
+            return false;
+        }
+        match Config::load() {
+            Ok(config) => self.reload_config(config),
+            Err(e) => self.push_error(format!("Config not reloaded: {}", e)),
+        }
+        true
     }
 
-    pub fn search_input(&mut self, c: char) {
-        self.search_query.push(c);
-        self.update_filter();
+    fn reload_config(&mut self, config: Config) {
+        let old = std::mem::replace(&mut self.config, config);
+        net::configure(&self.config);
+        wallpaper::configure(&self.config);
+        let result = keymap::configure(&self.config.keys);
+        self.report(result);
+        self.theme = Theme::for_config(&self.config);
+        if announce::is_enabled(&self.config) != self.announcer.is_some() {
+            self.announcer = announce::is_enabled(&self.config).then(Announcer::default);
+            self.encoder.clear_cache();
+        }
+        if let Some(width) = self.config.cell_width
+            && old.cell_width != Some(width)
+        {
+            self.set_cell_width(width);
+        }
+        self.subscribe_events();
+        if old.current_link != self.config.current_link {
+            self.current_watcher = FileWatcher::new(&wallpaper::get_current_background_path()).ok();
+            self.current_wallpaper = wallpaper::get_current_wallpaper();
+            self.applied_at = wallpaper::get_current_wallpaper_applied_at();
+        }
+        let layout_changed =
+            old.library_roots != self.config.library_roots || old.backgrounds_dir != self.config.backgrounds_dir;
+        if layout_changed {
+            self.watch_view_dir();
+        }
+        if layout_changed || old.min_size != self.config.min_size || old.min_file_kb != self.config.min_file_kb {
+            self.refresh();
+        }
+        self.show_notice("Config reloaded");
     }
 
-    pub fn search_backspace(&mut self) {
-        self.search_query.pop();
-        self.update_filter();
+    /// Confirm something on the notification line for a few seconds
+    pub fn show_notice(&mut self, message: &str) {
+        self.announce(message.to_string());
+        self.notice = Some((message.to_string(), Instant::now()));
     }
 
-    pub fn confirm_search(&mut self) {
-        self.mode = Mode::Grid;
+    /// Drop the notice once it has been up long enough. Returns true if it was removed.
+    pub fn expire_notice(&mut self) -> bool {
+        if self.notice.as_ref().is_some_and(|(_, at)| at.elapsed() >= NOTICE_DURATION) {
+            self.notice = None;
+            return true;
+        }
+        false
     }
 
-    pub fn cancel_search(&mut self) {
-        self.search_query.clear();
+    /// Re-apply a saved search, keeping `selected` highlighted if it still matches
+    fn restore_filter(&mut self, query: String, selected: Option<PathBuf>) {
+        if query.is_empty() {
+            return;
+        }
+        self.browser.search_query = query;
         self.update_filter();
-        self.mode = Mode::Grid;
+        if let Some(path) = selected
+            && let Some(pos) = self.browser
+                .filtered_indices
+                .iter()
+                .position(|&i| self.wallpapers[i].path == path)
+        {
+            self.browser.selected = pos;
+        }
     }
 
-    pub fn start_command(&mut self) {
-        self.mode = Mode::Command;
-        self.command_query.clear();
-        self.completions.clear();
+    /// Persist the browsing state for the next launch
+    pub fn save_session(&self) -> Result<()> {
+        Session {
+            view_dir: self.current_view_dir.clone(),
+            selected: self.selected_wallpaper().map(|w| w.path.clone()),
+            search_query: self.browser.search_query.clone(),
+            fill_mode: self.fill_mode,
+            monitor_preview: self.monitor_preview,
+            collection: self.active_collection.clone(),
+            cell_width: self.cell_width,
+            sort: self.sort,
+            skip_resolution_warning: self.skip_resolution_warning,
+            show_badges: self.show_badges,
+        }
+        .save()
     }
 
-    pub fn command_input(&mut self, c: char) {
-        self.command_query.push(c);
-        self.completions.clear();
+    /// Queue a background load of the thumbnail for `index`, unless it's loaded or can't be
+    pub fn request_thumbnail(&mut self, index: usize) {
+        let Some(wallpaper) = self.wallpapers.get(index) else {
+            return;
+        };
+        if wallpaper.thumbnail.is_none() && !wallpaper.unsupported && !wallpaper.load_failed {
+            self.thumbnail_loader.request(&wallpaper.path);
+        }
     }
 
-    pub fn command_backspace(&mut self) {
-        self.command_query.pop();
-        self.completions.clear();
+    /// Queue thumbnails for the whole view instead of only what's on screen
+    pub fn preload_thumbnails(&mut self) {
+        for i in 0..self.browser.filtered_indices.len() {
+            self.request_thumbnail(self.browser.filtered_indices[i]);
+        }
+        self.preloading = self.thumbnail_loader.has_pending();
     }
 
-    pub fn command_autocomplete(&mut self) {
-        if !self.command_query.starts_with("cd ") {
-            return;
-        }
+    /// Skip the rest of a preload; thumbnails on screen are requested again as they're drawn
+    pub fn cancel_preload(&mut self) {
+        self.thumbnail_loader.cancel();
+        self.preloading = false;
+    }
 
-        let path_part = &self.command_query[3..];
-        
-        // Split into directory and partial name
-        let (dir_path_str, prefix) = if let Some(last_slash) = path_part.rfind('/') {
-            // If the slash is at the very end, we are looking INSIDE this directory
-            if last_slash == path_part.len() - 1 {
-                (path_part, "")
-            } else {
-                (&path_part[..=last_slash], &path_part[last_slash + 1..])
+    /// Store thumbnails the loader has finished; returns true if any arrived
+    pub fn poll_thumbnails(&mut self) -> bool {
+        let results = self.thumbnail_loader.poll_results();
+        if !self.thumbnail_loader.has_pending() {
+            self.preloading = false;
+        }
+        let arrived = !results.is_empty();
+        for result in results {
+            // Results for a view that has since been replaced find no match
+            if let Some(&i) = self.wallpaper_index.get(&result.path) {
+                let wallpaper = &mut self.wallpapers[i];
+                match result.thumbnail {
+                    Some(thumbnail) => wallpaper.thumbnail = Some(thumbnail),
+                    None => wallpaper.load_failed = true,
+                }
             }
-        } else {
-            ("", path_part)
+        }
+        arrived
+    }
+
+    /// Store image sizes, ratings and badge info read by the header pass, re-sorting once it
+    /// finishes if sorted by rating. Returns true if anything changed.
+    pub fn poll_headers(&mut self) -> bool {
+        let Some(reader) = self.header_reader.as_mut() else {
+            return false;
         };
+        let results = reader.poll_results();
+        let done = reader.is_done();
+        let moves = if done { reader.moves() } else { Vec::new() };
+        let mut rated = false;
+        for (path, header, info) in &results {
+            if let Some(&i) = self.wallpaper_index.get(path) {
+                let wallpaper = &mut self.wallpapers[i];
+                wallpaper.dimensions = header.dimensions.or(wallpaper.dimensions);
+                wallpaper.rating = header.rating;
+                wallpaper.file_info = Some(info.clone());
+                rated |= header.rating.is_some();
+            }
+        }
+        // Newly rated wallpapers may now pass the stars filter
+        if self.min_stars.is_some() && rated {
+            self.update_filter();
+        }
+        if done {
+            self.header_reader = None;
+            let result = self.follow_moves(&moves);
+            self.report(result);
+            if self.sort == SortOrder::Rating {
+                self.set_sort(SortOrder::Rating);
+                return true;
+            }
+        }
+        !results.is_empty()
+    }
 
-        let mut resolved_dir_str = dir_path_str.to_string();
-        if resolved_dir_str.starts_with('~') {
-            if let Some(home) = dirs::home_dir() {
-                resolved_dir_str = resolved_dir_str.replacen('~', &home.to_string_lossy(), 1);
+    /// Keep notes, ratings, collection membership, queue places and pins with files that were
+    /// renamed or moved while the picker wasn't looking
+    fn follow_moves(&mut self, moves: &[(PathBuf, PathBuf)]) -> Result<()> {
+        if moves.is_empty() {
+            return Ok(());
+        }
+        let mut collections = Collections::load()?;
+        let mut pins = Pins::load();
+        let (mut metadata_changed, mut collections_changed, mut pins_changed) = (false, false, false);
+        for (old, new) in moves {
+            metadata_changed |= self.metadata.rename(old, new);
+            collections_changed |= collections.rename(old, new);
+            pins_changed |= pins.rename(old, new);
+        }
+        if metadata_changed {
+            self.metadata.save()?;
+        }
+        if collections_changed {
+            collections.save()?;
+            self.collections = collections;
+        }
+        // Unchanged queues aren't written back
+        self.queue = queue::edit(|queue| {
+            for (old, new) in moves {
+                queue.rename(old, new);
             }
+            Ok(())
+        })?
+        .0;
+        if pins_changed {
+            pins.save()?;
         }
-        
-        let search_dir = if resolved_dir_str.is_empty() {
-            PathBuf::from(".")
-        } else {
-            PathBuf::from(resolved_dir_str)
+        Ok(())
+    }
+
+    /// Stars for a wallpaper: set with `:rate`, or else from the file
+    pub fn stars(&self, wallpaper: &Wallpaper) -> Option<u8> {
+        self.metadata.stars(&wallpaper.path, wallpaper.rating)
+    }
+
+    /// Store color stats from the background pass, re-sorting once it finishes if sorted by match.
+    /// Returns true if anything changed.
+    pub fn poll_colors(&mut self) -> bool {
+        let Some(reader) = self.color_reader.as_mut() else {
+            return false;
         };
+        let results = reader.poll_results();
+        let done = reader.is_done();
+        for (path, stats) in &results {
+            if let Some(&i) = self.wallpaper_index.get(path) {
+                self.wallpapers[i].colors = Some(*stats);
+            }
+        }
+        // Newly analyzed wallpapers may now pass the brightness filter
+        if self.tone_filter.is_some() && !results.is_empty() {
+            self.update_filter();
+        }
+        if done {
+            self.color_reader = None;
+            // Re-sorting as each score arrives would shuffle the grid constantly
+            if self.sort == SortOrder::Match {
+                self.set_sort(SortOrder::Match);
+                return true;
+            }
+        }
+        !results.is_empty()
+    }
+
+    /// Whether an image is smaller than the largest connected monitor in either direction
+    pub fn is_below_monitor_resolution(&self, (width, height): (u32, u32)) -> bool {
+        let max_width = self.monitors.iter().map(|m| m.width).max();
+        let max_height = self.monitors.iter().map(|m| m.height).max();
+        max_width.is_some_and(|w| width < w) || max_height.is_some_and(|h| height < h)
+    }
+
+    /// Row offset to draw the grid at this frame, gliding towards `target` on jumps of several rows
+    pub fn animate_scroll(&mut self, target: usize) -> usize {
+        if self.scroll_animation.as_ref().map(|a| a.to) != Some(target) {
+            // Start from wherever the grid is drawn now, even mid-glide
+            let far = self.scroll_offset.abs_diff(target) > 1;
+            self.scroll_animation = (far && !self.config.instant_scroll).then(|| ScrollAnimation {
+                from: self.scroll_offset,
+                to: target,
+                started: Instant::now(),
+            });
+        }
+        self.scroll_offset = self
+            .scroll_animation
+            .as_ref()
+            .and_then(ScrollAnimation::current)
+            .unwrap_or(target);
+        if self.scroll_offset == target {
+            self.scroll_animation = None;
+        }
+        self.scroll_offset
+    }
+
+    /// Whether the grid is mid-glide and needs redrawing every frame
+    pub fn is_scrolling(&self) -> bool {
+        self.scroll_animation.is_some()
+    }
+
+    /// Whether anything is loading in the background, to keep spinners turning
+    pub fn is_loading(&self) -> bool {
+        self.scan.is_some()
+            || self.reddit_fetch.is_some()
+            || self.potd_fetch.is_some()
+            || self.downloads.is_busy()
+            || self.thumbnail_loader.has_pending()
+            || self.encoder.has_pending()
+            || self.preview_load.is_some()
+            || self.before_load.is_some()
+    }
 
-        // If we are cycling (completions not empty and we are within the same search dir),
-        // just move to the next completion.
-        if !self.completions.is_empty() {
-            if let Some(ref last_dir) = self.completion_dir {
-                if *last_dir == search_dir {
-                    self.completion_index = (self.completion_index + 1) % self.completions.len();
-                    self.command_query = self.completions[self.completion_index].clone();
-                    return;
+    /// Recompute the visible wallpapers, keeping the cursor on the same wallpaper where possible
+    pub fn update_filter(&mut self) {
+        self.remember_selection();
+        self.apply_filter();
+    }
+
+    /// Take the cursor's wallpaper as the one to keep selected, unless it's only parked there
+    /// because a filter hid the real one
+    fn remember_selection(&mut self) {
+        let current = self.selected_wallpaper().map(|w| w.path.clone());
+        if current.is_some() && current != self.selection_fallback {
+            self.selection_anchor = current;
+        }
+    }
+
+    fn apply_filter(&mut self) {
+        self.browser.filtered_indices = browse::filter(
+            self.wallpapers.iter().map(|w| self.metadata.search_text(&w.path, &w.name)),
+            &self.browser.search_query,
+        );
+        // Wallpapers not analyzed yet stay hidden until their brightness is known
+        if let Some(tone) = self.tone_filter {
+            let wallpapers = &self.wallpapers;
+            self.browser.filtered_indices
+                .retain(|&i| wallpapers[i].colors.is_some_and(|c| c.tone() == tone));
+        }
+        if let Some(min) = self.min_stars {
+            let (wallpapers, metadata) = (&self.wallpapers, &self.metadata);
+            self.browser.filtered_indices
+                .retain(|&i| metadata.stars(&wallpapers[i].path, wallpapers[i].rating).is_some_and(|s| s >= min));
+        }
+        if let Some(expr) = self.tag_filter.as_ref() {
+            let (wallpapers, collections) = (&self.wallpapers, &self.collections);
+            self.browser.filtered_indices
+                .retain(|&i| expr.matches(&collections.containing(&wallpapers[i].path)));
+        }
+        self.regroup();
+        let anchor_pos = self.selection_anchor.as_ref().and_then(|path| {
+            self.browser.filtered_indices
+                .iter()
+                .position(|&i| self.wallpapers[i].path == *path)
+        });
+        match anchor_pos {
+            Some(pos) => {
+                self.browser.selected = pos;
+                self.selection_fallback = None;
+            }
+            None => {
+                // Reset selection if out of bounds
+                if self.browser.selected >= self.browser.filtered_indices.len() {
+                    self.browser.selected = 0;
                 }
+                self.selection_fallback = self.selected_wallpaper().map(|w| w.path.clone());
             }
         }
+    }
 
-        // Otherwise, fetch new completions
-        if let Ok(entries) = std::fs::read_dir(&search_dir) {
-            let mut matches = Vec::new();
-            for entry in entries.flatten() {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_dir() {
-                        if let Some(name) = entry.file_name().to_str() {
-                            if name.starts_with(prefix) {
-                                matches.push(format!("cd {}{}/", dir_path_str, name));
-                            }
-                        }
-                    }
+    pub fn start_search(&mut self) {
+        self.mode = Mode::Search;
+    }
+
+    pub fn start_command(&mut self) {
+        self.command_from_preview = matches!(self.mode, Mode::Preview);
+        self.mode = Mode::Command;
+        self.handle(Input::CommandOpen);
+    }
+
+    /// Feed `input` to the browser and carry out the effects it asks for
+    pub fn handle(&mut self, input: Input) {
+        let source = AppCompletions { wallpapers: &self.wallpapers, collections: &self.collections };
+        for effect in self.browser.update(input, &source) {
+            match effect {
+                Effect::Refilter => self.update_filter(),
+                Effect::CloseSearch => self.mode = Mode::Grid,
+                Effect::CloseCommand => self.leave_command(),
+                Effect::RunCommand(command) => {
+                    let result = self.run_command(&command);
+                    self.report(result);
                 }
             }
-            matches.sort();
-
-            if !matches.is_empty() {
-                self.completion_dir = Some(search_dir);
-                if matches.len() == 1 {
-                    // Unique match: complete it
-                    self.command_query = matches[0].clone();
-                    
-                    // Immediately look inside this new directory
-                    let next_path = &self.command_query[3..];
-                    let mut resolved_next = next_path.to_string();
-                    if resolved_next.starts_with('~') {
-                        if let Some(home) = dirs::home_dir() {
-                            resolved_next = resolved_next.replacen('~', &home.to_string_lossy(), 1);
-                        }
-                    }
-                    let next_dir = PathBuf::from(resolved_next);
-                    
-                    let mut sub_matches = Vec::new();
-                    if let Ok(sub_entries) = std::fs::read_dir(&next_dir) {
-                        for sub_entry in sub_entries.flatten() {
-                            if let Ok(sub_ft) = sub_entry.file_type() {
-                                if sub_ft.is_dir() {
-                                    if let Some(sub_name) = sub_entry.file_name().to_str() {
-                                        sub_matches.push(format!("cd {}{}/", next_path, sub_name));
-                                    }
-                                }
-                            }
-                        }
+        }
+    }
+
+    fn run_command(&mut self, cmd: &str) -> Result<()> {
+        let Some(command) = Command::parse(cmd)? else {
+            return Ok(());
+        };
+        match command {
+            Command::Cd(Some(path)) => {
+                let path = match path.strip_prefix('@') {
+                    Some(name) => Bookmarks::load().get(name)?.to_path_buf(),
+                    None => config::expand_home(Path::new(&path)),
+                };
+                self.open_view(Some(path), None, ScanReason::Open { highlight: Vec::new() });
+            }
+            Command::Cd(None) => self.reset_view_dir(),
+            Command::Apply(args) => {
+                let adjustments = Adjustments::parse_args(&args)?;
+                self.apply_with(adjustments)?;
+            }
+            Command::Lockscreen(args) => {
+                let mut adjustments = Adjustments::parse_args(&args)?;
+                // Without options, fall back to the configured blur
+                if args.is_empty() {
+                    adjustments.blur = self.config.lockscreen_blur.unwrap_or(0.0);
+                }
+                if let Some(wallpaper) = self.selected_wallpaper() {
+                    lockscreen::apply(&wallpaper.path, adjustments)?;
+                }
+            }
+            Command::SyncLogin => login::sync(&self.config)?,
+            Command::Bookmark(action, name) => {
+                let mut bookmarks = Bookmarks::load();
+                match action.as_str() {
+                    "add" => bookmarks.add(&name, &self.view_dir()),
+                    "remove" => bookmarks.remove(&name)?,
+                    other => return Err(eyre!("Unknown bookmark action: {}", other)),
+                }
+                bookmarks.save()?;
+            }
+            Command::Rotate(degrees) => {
+                self.adjustments.rotate(&degrees)?;
+                self.clear_preview();
+            }
+            Command::Flip(axis) => {
+                self.adjustments.flip(&axis)?;
+                self.clear_preview();
+            }
+            Command::ExportScaled(args) => self.export_scaled(&args)?,
+            Command::Pin(output) => self.pin_selected(&output)?,
+            Command::Unpin(output) => self.unpin(output.as_deref())?,
+            Command::Collection(args) => {
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                self.collection_command(&args)?;
+            }
+            Command::Tag { name, add } => {
+                let paths = self.tag_targets();
+                self.edit_tags(&paths, &[(name, add)])?;
+            }
+            Command::TagEditor => self.open_tag_editor()?,
+            Command::Import(args) => self.start_import(&args)?,
+            Command::ImportLibrary(file) => {
+                library::import(&config::expand_home(Path::new(&file)))?;
+                // Notes are searchable, so the filter may now match differently
+                self.metadata = Metadata::load();
+                self.update_filter();
+            }
+            Command::Export(file) => library::export(&config::expand_home(Path::new(&file)))?,
+            Command::Select(name) => self.select_by_name(&name)?,
+            Command::Goto(n) => self.goto(n)?,
+            Command::Reddit(subreddit) => {
+                net::ensure_online()?;
+                self.reddit_fetch = Some(RedditFetch::start(subreddit))
+            }
+            Command::Potd(source, apply) => {
+                net::ensure_online()?;
+                self.potd_fetch = Some(PotdFetch::start(source, apply, self.config.clone()))
+            }
+            Command::Download => {
+                let path = self.selected_path()?;
+                let post = reddit::post_for(&path).ok_or_else(|| eyre!("Not a :reddit preview"))?;
+                self.download_post(&post, false)?;
+            }
+            Command::Downloads => self.mode = Mode::Downloads,
+            Command::UndoList => self.mode = Mode::UndoList,
+            Command::QueuePlay(interval) => self.edit_queue(|queue| queue.play(interval))?,
+            Command::QueueStop => self.edit_queue(|queue| {
+                queue.stop();
+                Ok(())
+            })?,
+            Command::QueueClear => self.edit_queue(|queue| {
+                queue.clear();
+                Ok(())
+            })?,
+            Command::Note(text) => {
+                let path = self.selected_path()?;
+                self.metadata.set_note(&path, &text);
+                self.metadata.save()?;
+            }
+            Command::Source(url) => {
+                let path = self.selected_path()?;
+                self.metadata.set_source(&path, &url);
+                self.metadata.save()?;
+            }
+            Command::Sort(order) => self.set_sort(order),
+            Command::GroupBy(group_by) => {
+                self.group_by = group_by;
+                self.update_filter();
+            }
+            Command::Filter(query) => {
+                // A bare `:filter` clears everything
+                if query.is_empty() {
+                    self.tone_filter = None;
+                    self.min_stars = None;
+                    self.tag_filter = None;
+                    if self.show_all {
+                        self.show_all = false;
+                        self.refresh();
                     }
-                    sub_matches.sort();
-                    self.completions = sub_matches;
-                    self.completion_index = 0;
-                    // Note: We don't update command_query again so it stays at the completed dir
-                    // but shows the sub-options in the list.
-                } else {
-                    // Multiple matches: cycle through them
-                    self.completions = matches;
-                    self.completion_index = 0;
-                    self.command_query = self.completions[0].clone();
                 }
+                self.browser.search_query = query;
+                self.update_filter();
+            }
+            Command::FilterAll => {
+                self.show_all = true;
+                self.refresh();
+            }
+            Command::FilterTone(tone) => {
+                self.tone_filter = Some(tone);
+                self.update_filter();
+            }
+            Command::FilterTags(expr) => {
+                self.tag_filter = Some(expr);
+                self.update_filter();
+            }
+            Command::Rate(stars) => {
+                let path = self.selected_path()?;
+                self.metadata.set_stars(&path, stars);
+                self.metadata.save()?;
+                if self.min_stars.is_some() {
+                    self.update_filter();
+                }
+            }
+            Command::MinStars(min) => {
+                self.min_stars = min;
+                self.update_filter();
+            }
+            Command::Trash => self.trash_selected_wallpaper()?,
+            Command::TrashRestore => self.open_trash()?,
+            Command::Random => {
+                self.select_random()?;
+                self.apply_wallpaper()?;
             }
+            Command::Preload => self.preload_thumbnails(),
+            Command::Reload => self.reload_wallpapers(),
+            Command::Set(setting) => self.apply_setting(setting),
+            Command::Settings => self.mode = Mode::Settings,
+            Command::Quit => self.should_quit = true,
         }
+        Ok(())
     }
 
-    pub fn move_completion_down(&mut self) {
-        if !self.completions.is_empty() {
-            self.completion_index = (self.completion_index + 1) % self.completions.len();
-            self.command_query = self.completions[self.completion_index].clone();
+    /// `:export-scaled <path> [monitor] [mode]` — save the selection as the backend would show it.
+    /// Defaults to the first monitor and the current fill mode.
+    fn export_scaled(&mut self, args: &[String]) -> Result<()> {
+        let Some((dest, options)) = args.split_first() else {
+            return Ok(());
+        };
+        let Some(source) = self.selected_wallpaper().map(|w| w.path.clone()) else {
+            return Ok(());
+        };
+
+        let mut mode = self.fill_mode;
+        let mut monitor_name = None;
+        for option in options {
+            match FillMode::parse(option) {
+                Some(parsed) => mode = parsed,
+                None => monitor_name = Some(option.as_str()),
+            }
+        }
+
+        self.monitors = monitor::detect_monitors();
+        let monitor = match monitor_name {
+            Some(name) => self.monitors.iter().find(|m| m.name == name),
+            None => self.monitors.first(),
         }
+        .ok_or_else(|| eyre!("No matching monitor found"))?;
+
+        wallpaper::export_scaled(&source, &config::expand_home(Path::new(dest)), monitor, mode)
     }
 
-    pub fn move_completion_up(&mut self) {
-        if !self.completions.is_empty() {
-            if self.completion_index == 0 {
-                self.completion_index = self.completions.len() - 1;
-            } else {
-                self.completion_index -= 1;
+    /// `:pin <output>` — keep the selection on one monitor and show it there now
+    fn pin_selected(&mut self, output: &str) -> Result<()> {
+        let backend = backend::for_config(&self.config);
+        if !backend.shows_per_output() {
+            return Err(eyre!("{} can't show a different wallpaper per monitor", backend.name()));
+        }
+        self.monitors = monitor::detect_monitors();
+        if !self.monitors.is_empty() && !self.monitors.iter().any(|m| m.name == output) {
+            return Err(eyre!("No monitor named {}", output));
+        }
+        let path = self.selected_path()?;
+        let mut pins = Pins::load();
+        pins.pin(output, &path);
+        pins.save()?;
+        self.show_pins()
+    }
+
+    /// `:unpin [output]`
+    fn unpin(&mut self, output: Option<&str>) -> Result<()> {
+        let mut pins = Pins::load();
+        match output {
+            Some(output) => pins.unpin(output)?,
+            None => pins.clear(),
+        }
+        pins.save()?;
+        self.show_pins()
+    }
+
+    /// Put the current wallpaper back up with the pins as they are now
+    fn show_pins(&self) -> Result<()> {
+        if wallpaper::get_current_wallpaper().is_none() {
+            return Ok(());
+        }
+        self.show_current()
+    }
+
+    /// `:collection add|remove <name>` for the selected wallpaper, `:collection view <name>` to browse one
+    fn collection_command(&mut self, args: &[&str]) -> Result<()> {
+        let [action, name] = args else {
+            return Err(eyre!("Usage: collection add|remove|view <name>"));
+        };
+        let mut collections = Collections::load()?;
+        match *action {
+            "add" | "remove" => {
+                let Some(path) = self.selected_wallpaper().map(|w| w.path.clone()) else {
+                    return Ok(());
+                };
+                let added = *action == "add";
+                if added {
+                    collections.add(name, &path);
+                } else {
+                    collections.remove(name, &path)?;
+                }
+                collections.save()?;
+                self.collections = collections;
+                self.undo.push(Change::Collection { name: name.to_string(), path, added });
+                if self.active_collection.as_deref() == Some(*name) {
+                    self.reload_wallpapers();
+                }
+            }
+            "view" => {
+                // Validate before leaving the current view
+                collections.paths(name)?;
+                let reason = ScanReason::Open { highlight: Vec::new() };
+                self.open_view(None, Some(name.to_string()), reason);
             }
-            self.command_query = self.completions[self.completion_index].clone();
+            other => return Err(eyre!("Unknown collection action: {}", other)),
+        }
+        Ok(())
+    }
+
+    /// `x`: mark or unmark the selection for `:tag`
+    pub fn toggle_mark(&mut self) {
+        let Some(path) = self.selected_wallpaper().map(|w| w.path.clone()) else {
+            return;
+        };
+        if !self.marked.remove(&path) {
+            self.marked.insert(path);
+        }
+    }
+
+    pub fn is_marked(&self, index: usize) -> bool {
+        self.wallpapers.get(index).is_some_and(|w| self.marked.contains(&w.path))
+    }
+
+    /// What `:tag` works on: the marked wallpapers, or the selection when none are marked
+    fn tag_targets(&self) -> Vec<PathBuf> {
+        if self.marked.is_empty() {
+            self.selected_wallpaper().map(|w| w.path.clone()).into_iter().collect()
+        } else {
+            self.marked.iter().cloned().collect()
         }
     }
 
-    pub fn confirm_command(&mut self) -> Result<()> {
-        let cmd = self.command_query.trim();
-        if cmd.starts_with("cd ") {
-            let mut path_str = cmd[3..].trim().to_string();
-            if path_str.starts_with('~') {
-                if let Some(home) = dirs::home_dir() {
-                    path_str = path_str.replacen('~', &home.to_string_lossy(), 1);
+    /// Add (`true`) or remove each `(collection, add)` for every one of `paths`, as a single undo step
+    fn edit_tags(&mut self, paths: &[PathBuf], edits: &[(String, bool)]) -> Result<()> {
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let mut collections = Collections::load()?;
+        let mut changes = Vec::new();
+        for (name, add) in edits {
+            for path in paths {
+                if collections.contains(name, path) == *add {
+                    continue;
+                }
+                if *add {
+                    collections.add(name, path);
+                } else {
+                    collections.remove(name, path)?;
                 }
+                changes.push(Change::Collection { name: name.clone(), path: path.clone(), added: *add });
             }
-            let path = PathBuf::from(path_str);
-            self.current_view_dir = Some(path);
-            self.reload_wallpapers()?;
-        } else if cmd == "cd" {
-            self.current_view_dir = None;
-            self.reload_wallpapers()?;
         }
-        self.mode = Mode::Grid;
-        self.command_query.clear();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        collections.save()?;
+        self.collections = collections;
+        self.show_notice(&format!(
+            "{} collection change{} on {} wallpaper{}",
+            changes.len(),
+            if changes.len() == 1 { "" } else { "s" },
+            paths.len(),
+            if paths.len() == 1 { "" } else { "s" }
+        ));
+        self.undo.push(match changes.len() {
+            1 => changes.remove(0),
+            _ => Change::Batch(changes),
+        });
+        if self.active_collection.as_ref().is_some_and(|active| edits.iter().any(|(name, _)| name == active)) {
+            self.reload_wallpapers();
+        } else if self.tag_filter.is_some() || self.group_by == Some(GroupBy::Tag) {
+            self.update_filter();
+        }
         Ok(())
     }
 
-    pub fn reload_wallpapers(&mut self) -> Result<()> {
-        self.wallpapers = wallpaper::discover_wallpapers(self.current_view_dir.clone())?;
-        self.encoder.clear_cache();
-        self.preview_state = None;
-        self.update_filter();
-        self.selected = 0;
+    fn open_tag_editor(&mut self) -> Result<()> {
+        let paths = self.tag_targets();
+        if paths.is_empty() {
+            return Ok(());
+        }
+        if self.collections.names().next().is_none() {
+            return Err(eyre!("No collections yet; create one with :tag add <name>"));
+        }
+        self.tag_editor = Some(TagEditor::new(paths, &self.collections));
+        self.mode = Mode::TagEditor;
         Ok(())
     }
 
-    pub fn cancel_command(&mut self) {
-        self.command_query.clear();
+    pub fn move_tag_selection(&mut self, delta: isize) {
+        if let Some(editor) = self.tag_editor.as_mut() {
+            editor.move_selection(delta);
+        }
+    }
+
+    pub fn toggle_tag(&mut self) {
+        if let Some(editor) = self.tag_editor.as_mut() {
+            editor.toggle();
+        }
+    }
+
+    pub fn close_tag_editor(&mut self) {
+        self.tag_editor = None;
         self.mode = Mode::Grid;
     }
 
-    pub fn reset_view_dir(&mut self) -> Result<()> {
-        self.current_view_dir = None;
-        self.reload_wallpapers()
+    /// Enter in the tag editor: apply the rows switched to all or none
+    pub fn apply_tag_editor(&mut self) -> Result<()> {
+        let Some(editor) = self.tag_editor.take() else {
+            return Ok(());
+        };
+        self.mode = Mode::Grid;
+        self.edit_tags(&editor.paths, &editor.edits())
     }
 
-    pub fn move_up(&mut self) {
-        if self.selected >= self.columns {
-            self.selected -= self.columns;
+    /// `:import <path-or-glob> [--link] [--rename|--skip|--overwrite]` — add images to the
+    /// backgrounds dir, asking what to do about name collisions unless told up front
+    fn start_import(&mut self, args: &[String]) -> Result<()> {
+        let plan = ImportPlan::parse(args, wallpaper::get_backgrounds_dir())?;
+        match plan.collision {
+            Some(collision) => self.run_import(plan, collision),
+            None if plan.conflicts() == 0 => self.run_import(plan, Collision::Rename),
+            None => {
+                self.pending_import = Some(plan);
+                self.mode = Mode::ImportConflict;
+                Ok(())
+            }
         }
     }
 
-    pub fn move_down(&mut self) {
-        let new_pos = self.selected + self.columns;
-        if new_pos < self.filtered_indices.len() {
-            self.selected = new_pos;
+    /// Answer the collision prompt for the pending import
+    pub fn resolve_import(&mut self, collision: Collision) -> Result<()> {
+        self.mode = Mode::Grid;
+        match self.pending_import.take() {
+            Some(plan) => self.run_import(plan, collision),
+            None => Ok(()),
         }
     }
 
-    pub fn move_left(&mut self) {
-        if self.selected > 0 {
-            self.selected -= 1;
+    pub fn cancel_import(&mut self) {
+        self.pending_import = None;
+        self.mode = Mode::Grid;
+    }
+
+    /// Import, then show the library with the new files highlighted and the first one selected
+    fn run_import(&mut self, plan: ImportPlan, collision: Collision) -> Result<()> {
+        let imported = plan.run(collision)?;
+        if !imported.paths.is_empty() {
+            self.undo.push(Change::Imported { paths: imported.paths.clone(), replaced: imported.replaced });
         }
+        self.open_view(None, None, ScanReason::Open { highlight: imported.paths });
+        Ok(())
     }
 
-    pub fn move_right(&mut self) {
-        if self.selected + 1 < self.filtered_indices.len() {
-            self.selected += 1;
+    /// Move the selected wallpaper to the XDG trash, where `:trash restore` can recover it
+    fn trash_selected_wallpaper(&mut self) -> Result<()> {
+        let Some(path) = self.selected_wallpaper().map(|w| w.path.clone()) else {
+            return Ok(());
+        };
+        trashcan::move_to_trash(&path)?;
+        if self.compare_mark.as_ref() == Some(&path) {
+            self.compare_mark = None;
+        }
+        self.undo.push(Change::Trashed(path));
+        self.clear_preview();
+        self.mode = Mode::Grid;
+        // A directory view's watcher sees the file leave, but a collection view spans
+        // directories and has no watcher, so it re-lists itself
+        if self.active_collection.is_some() {
+            self.refresh();
         }
+        Ok(())
     }
 
-    pub fn toggle_preview(&mut self) {
-        match self.mode {
-            Mode::Grid => {
-                self.preview_state = None; // Reset preview state for new image
-                self.mode = Mode::Preview;
-            }
-            Mode::Preview => self.mode = Mode::Grid,
-            Mode::Help | Mode::Search | Mode::Command => {}
+    /// `u`: take back the latest trashing, collection change or import
+    pub fn undo(&mut self) -> Result<()> {
+        let change = self.undo.pop().ok_or_else(|| eyre!("Nothing to undo"))?;
+        let mut collections = Collections::load()?;
+        let result = undo::revert(&change, &mut collections);
+        collections.save()?;
+        self.collections = collections;
+        self.refresh();
+        if self.undo.is_empty() && matches!(self.mode, Mode::UndoList) {
+            self.mode = Mode::Grid;
+        }
+        // Kept on the stack, a change that can't be undone would block every one before it
+        if let Err(e) = result {
+            return Err(eyre!("Couldn't fully undo {}: {}", change.describe(), e));
         }
+        self.show_notice(&format!("Undid {}", change.describe()));
+        Ok(())
     }
 
-    pub fn toggle_help(&mut self) {
-        match self.mode {
-            Mode::Help => self.mode = Mode::Grid,
-            _ => self.mode = Mode::Help,
+    fn open_trash(&mut self) -> Result<()> {
+        self.trash_items = trashcan::list_images()?;
+        if self.trash_items.is_empty() {
+            return Err(eyre!("No wallpapers in the trash"));
         }
+        self.trash_selected = 0;
+        self.mode = Mode::Trash;
+        Ok(())
     }
 
-    pub fn apply_wallpaper(&mut self) -> Result<()> {
-        if let Some(&idx) = self.filtered_indices.get(self.selected) {
-            if let Some(wallpaper) = self.wallpapers.get(idx) {
-                // Install to omarchy backgrounds dir and get the path
-                let installed_path = wallpaper::install_wallpaper(wallpaper)?;
+    pub fn move_trash_selection(&mut self, delta: isize) {
+        let last = self.trash_items.len().saturating_sub(1);
+        self.trash_selected = self.trash_selected.saturating_add_signed(delta).min(last);
+    }
 
-                // Set as current wallpaper (symlink)
-                wallpaper::set_wallpaper(&installed_path)?;
-                self.current_wallpaper = Some(installed_path);
-            }
+    /// Restore the highlighted trash entry, staying in the list while more remain
+    pub fn restore_trashed(&mut self) -> Result<()> {
+        if self.trash_selected >= self.trash_items.len() {
+            return Ok(());
+        }
+        let item = self.trash_items.remove(self.trash_selected);
+        trashcan::restore(item)?;
+        if self.trash_items.is_empty() {
+            self.close_trash();
+        } else {
+            self.move_trash_selection(0);
         }
+        self.refresh();
         Ok(())
     }
 
-    pub fn escape(&mut self) {
-        match self.mode {
-            Mode::Preview | Mode::Help => self.mode = Mode::Grid,
-            Mode::Search => self.cancel_search(),
-            Mode::Command => self.cancel_command(),
-            Mode::Grid => self.should_quit = true,
+    pub fn close_trash(&mut self) {
+        self.trash_items.clear();
+        self.mode = Mode::Grid;
+    }
+
+    /// Re-list the current view from scratch
+    pub fn reload_wallpapers(&mut self) {
+        let reason = ScanReason::Open { highlight: Vec::new() };
+        self.open_view(self.current_view_dir.clone(), self.active_collection.clone(), reason);
+    }
+
+    /// Switch to browsing `dir` or `collection`; the grid changes once the background scan finishes
+    fn open_view(&mut self, dir: Option<PathBuf>, collection: Option<String>, reason: ScanReason) {
+        // Cancelling goes back to where the view was before the first of several pending scans
+        let (previous_dir, previous_collection) = match self.scan.take() {
+            Some(scan) if !matches!(scan.reason, ScanReason::Refresh) => {
+                (scan.previous_dir, scan.previous_collection)
+            }
+            _ => (self.current_view_dir.clone(), self.active_collection.clone()),
+        };
+        self.current_view_dir = dir.clone();
+        self.active_collection = collection.clone();
+        self.scan = Some(Scan::start(
+            dir,
+            collection,
+            self.sort,
+            reason,
+            previous_dir,
+            previous_collection,
+            self.min_size(),
+        ));
+    }
+
+    /// Re-list the current view in the background, keeping thumbnails and the selection
+    fn refresh(&mut self) {
+        // A pending scan is about to deliver a fresh listing anyway
+        if self.scan.is_some() {
+            return;
         }
+        self.scan = Some(Scan::start(
+            self.current_view_dir.clone(),
+            self.active_collection.clone(),
+            self.sort,
+            ScanReason::Refresh,
+            self.current_view_dir.clone(),
+            self.active_collection.clone(),
+            self.min_size(),
+        ));
     }
 
-    pub fn selected_wallpaper(&self) -> Option<&Wallpaper> {
-        self.filtered_indices
-            .get(self.selected)
-            .and_then(|&idx| self.wallpapers.get(idx))
+    /// Limits for directory listings, unless `:filter all` lifted them
+    fn min_size(&self) -> Option<MinSize> {
+        if self.show_all { None } else { self.config.min_size() }
     }
 
-    pub fn is_current(&self, index: usize) -> bool {
-        self.current_wallpaper
+    /// A user-started scan is running, as opposed to a background refresh
+    pub fn is_scanning(&self) -> bool {
+        self.scan
             .as_ref()
-            .map(|current| {
-                self.wallpapers
-                    .get(index)
-                    .map(|w| w.path == *current)
-                    .unwrap_or(false)
-            })
-            .unwrap_or(false)
+            .is_some_and(|scan| !matches!(scan.reason, ScanReason::Refresh))
     }
+
+    /// Stop waiting on a slow listing and go back to the previous view
+    pub fn cancel_scan(&mut self) {
+        if let Some(scan) = self.scan.take() {
+            self.abandon_scan(scan);
+        }
+    }
+
+    /// Return to the view from before `scan`, which failed or was cancelled
+    fn abandon_scan(&mut self, scan: Scan) {
+        let unchanged = scan.previous_dir == self.current_view_dir
+            && scan.previous_collection == self.active_collection;
+        self.current_view_dir = scan.previous_dir;
+        self.active_collection = scan.previous_collection;
+        // At startup there is no earlier listing on screen to fall back to
+        if let ScanReason::Startup { .. } = scan.reason
+            && !unchanged
+        {
+            self.open_view(self.current_view_dir.clone(), self.active_collection.clone(), scan.reason);
+        }
+    }
+
+    /// Replace the grid with a finished scan; returns true if anything changed
+    pub fn poll_scan(&mut self) -> bool {
+        let Some(result) = self.scan.as_ref().and_then(Scan::poll) else {
+            return false;
+        };
+        let Some(scan) = self.scan.take() else {
+            return false;
+        };
+        match result {
+            Ok(wallpapers) => {
+                let order = scan.order;
+                self.show_listing(wallpapers, scan.reason);
+                // The sort changed while scanning
+                if order != self.sort {
+                    self.set_sort(self.sort);
+                }
+            }
+            Err(e) => {
+                match scan.reason {
+                    ScanReason::Refresh => self.push_error(format!("Refresh failed: {}", e)),
+                    // Falling back from a vanished saved dir needs no message
+                    ScanReason::Startup { .. } => {}
+                    ScanReason::Open { .. } => self.push_error(e.to_string()),
+                }
+                self.abandon_scan(scan);
+            }
+        }
+        true
+    }
+
+    /// Rebuild `wallpaper_index` after `wallpapers` was replaced or re-sorted
+    fn index_wallpapers(&mut self) {
+        self.wallpaper_index = self.wallpapers.iter().enumerate().map(|(i, w)| (w.path.clone(), i)).collect();
+    }
+
+    fn show_listing(&mut self, mut wallpapers: Vec<Wallpaper>, reason: ScanReason) {
+        match reason {
+            ScanReason::Startup { selected, query } => {
+                self.wallpapers = wallpapers;
+                self.index_wallpapers();
+                self.encoder.clear_cache();
+                self.apply_filter();
+                // The last selection, or else the current wallpaper
+                self.browser.selected = selected
+                    .as_ref()
+                    .or(self.current_wallpaper.as_ref())
+                    .and_then(|path| self.wallpapers.iter().position(|w| w.path == *path))
+                    .unwrap_or(0);
+                self.restore_filter(query, selected);
+                self.watch_view_dir();
+            }
+            ScanReason::Open { highlight } => {
+                self.wallpapers = wallpapers;
+                self.index_wallpapers();
+                self.encoder.clear_cache();
+                self.clear_preview();
+                self.apply_filter();
+                self.browser.selected = self.browser
+                    .filtered_indices
+                    .iter()
+                    .position(|&i| highlight.contains(&self.wallpapers[i].path))
+                    .unwrap_or(0);
+                self.highlighted = highlight.into_iter().collect();
+                self.watch_view_dir();
+            }
+            ScanReason::Refresh => {
+                self.remember_selection();
+                let mut old: HashMap<PathBuf, Wallpaper> =
+                    std::mem::take(&mut self.wallpapers).into_iter().map(|w| (w.path.clone(), w)).collect();
+                for wallpaper in &mut wallpapers {
+                    if let Some(previous) = old.remove(&wallpaper.path) {
+                        wallpaper.thumbnail = previous.thumbnail;
+                        wallpaper.dimensions = wallpaper.dimensions.or(previous.dimensions);
+                        wallpaper.colors = previous.colors;
+                        wallpaper.rating = previous.rating;
+                        wallpaper.file_info = previous.file_info;
+                    }
+                }
+                self.wallpapers = wallpapers;
+                self.index_wallpapers();
+
+                // Cached encodings are keyed by index, which may have shifted
+                self.encoder.clear_cache();
+                self.apply_filter();
+            }
+        }
+        let unread_paths = self
+            .wallpapers
+            .iter()
+            .filter(|w| w.dimensions.is_none() || w.file_info.is_none())
+            .map(|w| w.path.clone())
+            .collect();
+        self.header_reader = Some(HeaderReader::start(unread_paths));
+        let unanalyzed_paths = self
+            .wallpapers
+            .iter()
+            .filter(|w| w.colors.is_none() && !w.unsupported)
+            .map(|w| w.path.clone())
+            .collect();
+        self.color_reader = Some(ColorReader::start(unanalyzed_paths));
+        self.emit_directory_loaded();
+    }
+
+    /// The directory currently being browsed
+    pub fn view_dir(&self) -> PathBuf {
+        self.current_view_dir
+            .clone()
+            .unwrap_or_else(wallpaper::get_backgrounds_dir)
+    }
+
+    /// (Re)start watching the directory currently being browsed
+    fn watch_view_dir(&mut self) {
+        // Watching is best-effort; the grid still works without live updates.
+        // Collections span directories, so they aren't watched.
+        self.watchers = match (&self.active_collection, &self.current_view_dir) {
+            (Some(_), _) => Vec::new(),
+            (None, None) if !wallpaper::library_roots().is_empty() => wallpaper::library_roots()
+                .into_iter()
+                .filter_map(|root| DirWatcher::recursive(&root).ok())
+                .collect(),
+            (None, _) => DirWatcher::new(&self.view_dir()).ok().into_iter().collect(),
+        };
+    }
+
+    fn emit_directory_loaded(&mut self) {
+        let event = AppEvent::DirectoryLoaded {
+            dir: self.view_dir(),
+            count: self.wallpapers.len(),
+        };
+        self.events.emit(event);
+    }
+
+    /// Emit `SelectionChanged` if the highlighted wallpaper differs from the last report.
+    /// Called once per main-loop iteration so every way of moving the cursor is covered.
+    pub fn emit_selection_changed(&mut self) {
+        let selection = self.selected_wallpaper().map(|w| w.path.clone());
+        if selection != self.last_selection {
+            self.last_selection = selection.clone();
+            self.selection_changed_at = Instant::now();
+            self.events.emit(AppEvent::SelectionChanged(selection));
+        }
+    }
+
+    /// Start a background re-scan if the view dir changed on disk
+    pub fn poll_watcher(&mut self) {
+        // Every watcher is drained, so a burst across roots still means one refresh
+        let changed = self.watchers.iter().fold(false, |changed, w| w.poll_changed() | changed);
+        if changed {
+            self.refresh();
+        }
+    }
+
+    /// Adopt a wallpaper another tool applied, so the ✓ marker follows it.
+    /// Returns true if the current wallpaper changed.
+    pub fn poll_current_wallpaper(&mut self) -> bool {
+        if !self.current_watcher.as_ref().is_some_and(|w| w.poll_changed()) {
+            return false;
+        }
+        let current = wallpaper::get_current_wallpaper();
+        if current == self.current_wallpaper {
+            return false;
+        }
+        self.current_wallpaper = current;
+        self.applied_at = wallpaper::get_current_wallpaper_applied_at();
+        self.clear_before();
+        true
+    }
+
+    /// Close the prompt, going back to the preview if that's where it was opened
+    fn leave_command(&mut self) {
+        self.mode = if self.command_from_preview { Mode::Preview } else { Mode::Grid };
+        self.command_from_preview = false;
+    }
+
+    pub fn reset_view_dir(&mut self) {
+        self.open_view(None, None, ScanReason::Open { highlight: Vec::new() });
+    }
+
+    /// Sort the visible wallpapers into `group_by`'s sections
+    fn regroup(&mut self) {
+        let Some(group_by) = self.group_by else {
+            self.browser.groups.clear();
+            return;
+        };
+        let labels: Vec<String> = self.browser
+            .filtered_indices
+            .iter()
+            .map(|&i| {
+                let path = &self.wallpapers[i].path;
+                match group_by {
+                    GroupBy::Tag => self
+                        .collections
+                        .containing(path)
+                        .into_iter()
+                        .next()
+                        .unwrap_or_else(|| groups::UNTAGGED.to_string()),
+                    GroupBy::Month => self
+                        .month_labels
+                        .entry(path.clone())
+                        .or_insert_with(|| {
+                            std::fs::metadata(path)
+                                .and_then(|m| m.modified())
+                                .map(groups::month_label)
+                                .unwrap_or_else(|_| "Unknown date".to_string())
+                        })
+                        .clone(),
+                    GroupBy::Dir => path
+                        .parent()
+                        .map(|dir| dir.display().to_string())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+        self.browser.groups = groups::group(&mut self.browser.filtered_indices, &labels, group_by == GroupBy::Month);
+    }
+
+    /// Browse a fetched subreddit's previews once they're cached. Returns true when done.
+    pub fn poll_reddit(&mut self) -> bool {
+        let Some(result) = self.reddit_fetch.as_ref().and_then(RedditFetch::poll) else {
+            return false;
+        };
+        self.reddit_fetch = None;
+        match result {
+            Ok(dir) => self.open_view(Some(dir), None, ScanReason::Open { highlight: Vec::new() }),
+            Err(e) => self.push_error(format!("Reddit: {}", e)),
+        }
+        true
+    }
+
+    /// Apply a downloaded picture of the day if asked to. Returns true when the download is done.
+    pub fn poll_potd(&mut self) -> bool {
+        let Some(result) = self.potd_fetch.as_ref().and_then(PotdFetch::poll) else {
+            return false;
+        };
+        let Some(fetch) = self.potd_fetch.take() else {
+            return false;
+        };
+        let result = result.and_then(|planned| {
+            let follow_up = FollowUp { note: Some(planned.title), source: planned.page_url, apply: fetch.apply };
+            // Re-running on the same day only applies the picture again, if asked to
+            if planned.exists {
+                return if fetch.apply { self.apply_downloaded(planned.path) } else { Ok(()) };
+            }
+            self.queue_download(planned.image_url, planned.path, follow_up)
+        });
+        self.report(result);
+        true
+    }
+
+    /// Queue a post's full image for the download dir, remembering where it came from
+    fn download_post(&mut self, post: &Post, apply: bool) -> Result<()> {
+        let dest = reddit::download_path(post, &self.config.download_dir());
+        let follow_up = FollowUp { note: None, source: Some(post.post_url()), apply };
+        self.queue_download(post.url.clone(), dest, follow_up)
+    }
+
+    /// Download `url` to `dest` in the background, or follow up straight away if it's already there
+    fn queue_download(&mut self, url: String, dest: PathBuf, follow_up: FollowUp) -> Result<()> {
+        if dest.exists() {
+            return self.finish_download(dest, follow_up);
+        }
+        net::ensure_online()?;
+        let id = self.downloads.enqueue(url, dest, None);
+        self.follow_ups.insert(id, follow_up);
+        Ok(())
+    }
+
+    /// Track queued downloads, recording and applying them as they finish. Returns true on progress.
+    pub fn poll_downloads(&mut self) -> bool {
+        let (changed, finished) = self.downloads.poll();
+        for (id, result) in finished {
+            let result = result.and_then(|path| match self.follow_ups.remove(&id) {
+                Some(follow_up) => self.finish_download(path, follow_up),
+                None => Ok(()),
+            });
+            self.report(result);
+        }
+        changed
+    }
+
+    fn finish_download(&mut self, path: PathBuf, follow_up: FollowUp) -> Result<()> {
+        if let Some(note) = &follow_up.note {
+            self.metadata.set_note(&path, note);
+        }
+        if let Some(source) = &follow_up.source {
+            self.metadata.set_source(&path, source);
+        }
+        self.metadata.save()?;
+        if follow_up.apply {
+            self.apply_downloaded(path)?;
+        }
+        Ok(())
+    }
+
+    fn apply_downloaded(&mut self, path: PathBuf) -> Result<()> {
+        let installed_path = wallpaper::install_wallpaper(&Wallpaper::new(path))?;
+        self.set_current(installed_path)
+    }
+
+    /// Forget finished and failed downloads in the queue modal
+    pub fn clear_downloads(&mut self) {
+        self.downloads.clear_finished();
+        let downloads = &self.downloads;
+        self.follow_ups.retain(|id, _| downloads.items().iter().any(|d| d.id == *id));
+    }
+
+    fn selected_path(&self) -> Result<PathBuf> {
+        self.selected_wallpaper()
+            .map(|w| w.path.clone())
+            .ok_or_else(|| eyre!("No wallpaper selected"))
+    }
+
+    /// `y` / `Y`: copy the selection's absolute path, or the file itself as a URI for file managers and chats
+    pub fn yank(&mut self, as_file: bool) -> Result<()> {
+        let path = self.selected_path()?;
+        let path = std::fs::canonicalize(&path).unwrap_or(path);
+        if as_file {
+            clipboard::copy(&format!("{}\r\n", clipboard::file_uri(&path)), "text/uri-list")?;
+        } else {
+            clipboard::copy(&path.to_string_lossy(), "text/plain;charset=utf-8")?;
+        }
+        let what = if as_file { "Copied file" } else { "Copied path of" };
+        self.show_notice(&format!("{} {}", what, path.file_name().unwrap_or_default().to_string_lossy()));
+        Ok(())
+    }
+
+    /// Move the cursor to the nth (1-based) wallpaper in the view
+    fn goto(&mut self, n: usize) -> Result<()> {
+        if n > self.browser.filtered_indices.len() {
+            return Err(eyre!("Only {} wallpapers in view", self.browser.filtered_indices.len()));
+        }
+        self.browser.selected = n - 1;
+        Ok(())
+    }
+
+    /// Move the cursor to the wallpaper with this file name, clearing a filter that hides it
+    fn select_by_name(&mut self, name: &str) -> Result<()> {
+        let matches = |w: &Wallpaper| w.path.file_name().is_some_and(|n| n == name) || w.name == name;
+        if !self.browser.filtered_indices.iter().any(|&i| matches(&self.wallpapers[i])) {
+            self.browser.search_query.clear();
+            self.update_filter();
+        }
+        let pos = self.browser
+            .filtered_indices
+            .iter()
+            .position(|&i| matches(&self.wallpapers[i]))
+            .ok_or_else(|| eyre!("No wallpaper named {}", name))?;
+        self.browser.selected = pos;
+        Ok(())
+    }
+
+    fn apply_setting(&mut self, setting: Setting) {
+        match setting {
+            Setting::Fill(mode) => {
+                self.fill_mode = mode;
+                self.clear_monitor_previews();
+            }
+            Setting::Sort(order) => self.set_sort(order),
+            Setting::CellWidth(width) => self.set_cell_width(width),
+            Setting::Crossfade(ms) => self.config.crossfade_ms = (ms > 0).then_some(ms),
+            Setting::SmoothScroll(on) => self.config.instant_scroll = !on,
+            Setting::HoverApply(on) => {
+                self.config.hover_apply = on;
+                if !on {
+                    let result = self.end_hover();
+                    self.report(result);
+                }
+            }
+            Setting::Transition(spec) => self.config.transition = Some(spec),
+            Setting::Backend(name) => self.config.backend = name,
+        }
+    }
+
+    /// Each `:set` option with its current value, as the settings list shows them
+    pub fn setting_values(&self) -> Vec<(&'static str, String)> {
+        let switch = |on: bool| if on { "on" } else { "off" }.to_string();
+        commands::SETTINGS
+            .iter()
+            .map(|&(option, _)| {
+                let value = match option {
+                    "fill" => self.fill_mode.as_str().to_string(),
+                    "sort" => self.sort.as_str().to_string(),
+                    "cell-width" => self.cell_width.to_string(),
+                    "crossfade" => self.config.crossfade_ms.unwrap_or(0).to_string(),
+                    "smooth-scroll" => switch(!self.config.instant_scroll),
+                    "hover-apply" => switch(self.config.hover_apply),
+                    "transition" => self.config.transition.clone().unwrap_or_default(),
+                    "backend" => self.config.backend.clone().unwrap_or_else(|| "auto".to_string()),
+                    _ => String::new(),
+                };
+                (option, value)
+            })
+            .collect()
+    }
+
+    pub fn move_settings_selection(&mut self, delta: isize) {
+        let last = commands::SETTINGS.len().saturating_sub(1);
+        self.settings_selected = self.settings_selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Open the prompt on `set option=value` for the highlighted setting, ready to edit
+    pub fn edit_setting(&mut self) {
+        let Some((option, value)) = self.setting_values().into_iter().nth(self.settings_selected) else {
+            return;
+        };
+        self.start_command();
+        self.browser.command_query = format!("set {}={}", option, value);
+    }
+
+    /// Re-order the grid, keeping the selected wallpaper selected
+    fn set_sort(&mut self, order: SortOrder) {
+        self.sort = order;
+        // Sorting by date reads every file's mtime, which may be slow on network shares
+        if order.uses_mtime() {
+            self.refresh();
+            return;
+        }
+        // Read the selection while indices still point where they did
+        self.remember_selection();
+        let metadata = &self.metadata;
+        loader::sort_wallpapers(&mut self.wallpapers, order, |w| metadata.stars(&w.path, w.rating));
+        self.index_wallpapers();
+        // Cached encodings are keyed by index
+        self.encoder.clear_cache();
+        self.apply_filter();
+    }
+
+    /// Bytes held by decoded thumbnails and the preview, and how many thumbnails that is
+    pub fn decoded_memory(&self) -> (usize, usize) {
+        let thumbnails: Vec<usize> = self
+            .wallpapers
+            .iter()
+            .filter_map(|w| w.thumbnail.as_deref().map(memory::image_bytes))
+            .collect();
+        let previews = [self.preview_image.as_ref(), self.crop_source.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(memory::image_bytes)
+            .sum::<usize>();
+        (thumbnails.iter().sum::<usize>() + previews, thumbnails.len())
+    }
+
+    /// Past `memory_budget_mb`, drop decoded thumbnails and encoded cells outside `keep`,
+    /// farthest from the selection first. They load again from the thumbnail cache when
+    /// scrolled back into view.
+    pub fn enforce_memory_budget(&mut self, keep: &HashSet<usize>) {
+        let Some(budget) = self.config.memory_budget_mb.map(|mb| mb as usize * 1024 * 1024) else {
+            return;
+        };
+        let encoded = self.encoder.cached_bytes();
+        let (decoded, _) = self.decoded_memory();
+        let used = decoded + encoded.values().sum::<usize>();
+        if used <= budget {
+            return;
+        }
+        let positions: HashMap<usize, usize> =
+            self.browser.filtered_indices.iter().enumerate().map(|(pos, &index)| (index, pos)).collect();
+        let candidates = self
+            .wallpapers
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !keep.contains(index))
+            .filter_map(|(index, w)| {
+                let thumbnail = w.thumbnail.as_deref().map(memory::image_bytes);
+                let bytes = thumbnail.unwrap_or(0) + encoded.get(&index).copied().unwrap_or(0);
+                // Wallpapers hidden by the filter are the first to go
+                let distance = positions.get(&index).map_or(usize::MAX, |pos| pos.abs_diff(self.browser.selected));
+                (bytes > 0).then_some((index, bytes, distance))
+            })
+            .collect();
+        let evicted: HashSet<usize> = memory::evictions(candidates, used, budget).into_iter().collect();
+        for &index in &evicted {
+            self.wallpapers[index].thumbnail = None;
+        }
+        self.encoder.evict(&evicted);
+    }
+
+    pub fn toggle_badges(&mut self) {
+        self.show_badges = !self.show_badges;
+    }
+
+    pub fn grow_cells(&mut self) {
+        self.set_cell_width(self.cell_width.saturating_add(CELL_WIDTH_STEP));
+    }
+
+    pub fn shrink_cells(&mut self) {
+        self.set_cell_width(self.cell_width.saturating_sub(CELL_WIDTH_STEP));
+    }
+
+    /// Match thumbnail resolution to a grid cell image area of `columns` × `rows` terminal cells,
+    /// using the font's pixel size, so HiDPI terminals get sharp thumbnails
+    pub fn fit_thumbnails_to_cells(&mut self, columns: u16, rows: u16) {
+        let (font_width, font_height) = self.picker.font_size();
+        let pixels = (columns as u32 * font_width as u32).max(rows as u32 * font_height as u32);
+        if self.thumbnail_loader.set_cell_pixels(pixels) {
+            for wallpaper in &mut self.wallpapers {
+                wallpaper.thumbnail = None;
+            }
+            self.encoder.clear_cache();
+            self.preloading = false;
+        }
+    }
+
+    fn set_cell_width(&mut self, width: u16) {
+        let width = width.clamp(MIN_CELL_WIDTH, MAX_CELL_WIDTH);
+        if width != self.cell_width {
+            self.cell_width = width;
+            // Encodings are per cell size; drop them so thumbnails are re-requested at the new size
+            self.encoder.clear_cache();
+        }
+    }
+
+    pub fn toggle_preview(&mut self) {
+        match self.mode {
+            Mode::Grid => {
+                self.clear_preview(); // Reset preview state for new image
+                self.mode = Mode::Preview;
+            }
+            Mode::Preview => {
+                self.clear_preview();
+                self.mode = Mode::Grid;
+            }
+            Mode::Help
+            | Mode::Search
+            | Mode::Command
+            | Mode::Crop
+            | Mode::ImportConflict
+            | Mode::Compare
+            | Mode::Trash
+            | Mode::ConfirmApply
+            | Mode::Downloads
+            | Mode::Settings
+            | Mode::UndoList
+            | Mode::TagEditor
+            | Mode::ApplyFailed
+            | Mode::Trying => {}
+        }
+    }
+
+    /// Show the next (`step` 1) or previous (-1) wallpaper in the preview, stopping at the ends
+    pub fn preview_step(&mut self, step: isize) {
+        let last = self.browser.filtered_indices.len().saturating_sub(1);
+        let selected = self.browser.selected.saturating_add_signed(step).min(last);
+        if selected != self.browser.selected {
+            self.browser.selected = selected;
+            self.clear_preview();
+        }
+    }
+
+    /// Drop preview images, deleting them from the terminal too
+    pub fn clear_preview(&mut self) {
+        self.preview_load = None;
+        self.preview_image = None;
+        self.clear_preview_placeholder();
+        if let Some(state) = self.preview_state.take() {
+            terminal::release_image(&state);
+        }
+        self.clear_monitor_previews();
+    }
+
+    fn clear_monitor_previews(&mut self) {
+        for state in self.monitor_preview_states.drain(..) {
+            terminal::release_image(&state);
+        }
+    }
+
+    pub fn toggle_monitor_preview(&mut self) {
+        self.monitor_preview = !self.monitor_preview;
+        if self.monitor_preview {
+            // Re-query on every toggle so hotplugged outputs show up
+            self.monitors = monitor::detect_monitors();
+            self.clear_monitor_previews();
+        }
+    }
+
+    pub fn toggle_before_after(&mut self) {
+        self.before_after = !self.before_after;
+    }
+
+    /// The wallpaper on the desktop, or what another tool left at the current-background path
+    pub fn before_path(&self) -> Option<PathBuf> {
+        self.current_wallpaper
+            .clone()
+            .or_else(|| Some(wallpaper::get_current_background_path()).filter(|p| p.exists()))
+    }
+
+    /// Start decoding the current wallpaper for the before/after preview unless it's loaded
+    pub fn request_before(&mut self) {
+        if self.before_state.is_some() || self.before_load.is_some() {
+            return;
+        }
+        if let Some(path) = self.before_path() {
+            self.before_load = Some(PreviewLoader::start(path, Adjustments::default(), self.preview_bounds()));
+        }
+    }
+
+    /// Drop the before image once the current wallpaper changes
+    fn clear_before(&mut self) {
+        self.before_load = None;
+        if let Some(state) = self.before_state.take() {
+            terminal::release_image(&state);
+        }
+    }
+
+    pub fn cycle_fill_mode(&mut self) {
+        self.fill_mode = self.fill_mode.next();
+        self.clear_monitor_previews();
+    }
+
+    /// Enter crop mode for the previewed wallpaper, starting from the largest
+    /// rectangle matching the primary monitor's aspect ratio
+    pub fn start_crop(&mut self) {
+        let Some(path) = self.selected_wallpaper().map(|w| w.path.clone()) else {
+            return;
+        };
+        let img = match wallpaper::open_image(&path) {
+            Ok(img) => img,
+            Err(e) => {
+                self.push_error(format!("Can't crop: {}", e));
+                return;
+            }
+        };
+
+        let aspect = monitor::detect_monitors()
+            .first()
+            .map(|m| m.width as f64 / m.height.max(1) as f64)
+            .unwrap_or(16.0 / 9.0);
+        self.crop = Some(CropRect::centered(img.width(), img.height(), aspect));
+        self.crop_source = Some(img.thumbnail(1024, 1024));
+        self.clear_crop_state();
+        self.mode = Mode::Crop;
+    }
+
+    pub fn cancel_crop(&mut self) {
+        self.crop = None;
+        self.crop_source = None;
+        self.clear_crop_state();
+        self.mode = Mode::Preview;
+    }
+
+    fn clear_crop_state(&mut self) {
+        if let Some(state) = self.crop_state.take() {
+            terminal::release_image(&state);
+        }
+    }
+
+    pub fn crop_move(&mut self, dx: f64, dy: f64) {
+        if let Some(crop) = self.crop.as_mut() {
+            crop.move_by(dx, dy);
+            self.clear_crop_state();
+        }
+    }
+
+    pub fn crop_grow(&mut self) {
+        if let Some(crop) = self.crop.as_mut() {
+            crop.grow();
+            self.clear_crop_state();
+        }
+    }
+
+    pub fn crop_shrink(&mut self) {
+        if let Some(crop) = self.crop.as_mut() {
+            crop.shrink();
+            self.clear_crop_state();
+        }
+    }
+
+    /// Write the cropped image to the backgrounds dir and apply it
+    pub fn apply_crop(&mut self) -> Result<()> {
+        let Some(crop) = self.crop else {
+            return Ok(());
+        };
+        let adjustments = self.adjustments;
+        if let Some(wallpaper) = self.selected_wallpaper() {
+            let settings = format!("{:?} {:?}", crop, adjustments);
+            let installed_path = wallpaper::install_processed(wallpaper, "cropped", &settings, |img| {
+                adjustments.apply(crop.apply(&img))
+            })?;
+            self.set_current(installed_path)?;
+        }
+        self.cancel_crop();
+        Ok(())
+    }
+
+    /// Mark the selection for comparison, or compare it against the earlier mark
+    pub fn mark_compare(&mut self) {
+        let Some(path) = self.selected_wallpaper().map(|w| w.path.clone()) else {
+            return;
+        };
+        match self.compare_mark.take() {
+            Some(mark) if mark != path => match Compare::open(mark, path) {
+                Ok(compare) => {
+                    self.clear_preview();
+                    self.compare = Some(compare);
+                    self.mode = Mode::Compare;
+                }
+                Err(e) => self.push_error(format!("Can't compare: {}", e)),
+            },
+            // Marking the same wallpaper again unmarks it
+            Some(_) => {}
+            None => self.compare_mark = Some(path),
+        }
+    }
+
+    pub fn close_compare(&mut self) {
+        self.compare = None;
+        self.clear_compare_states();
+        self.mode = Mode::Grid;
+    }
+
+    fn clear_compare_states(&mut self) {
+        for state in self.compare_states.drain(..) {
+            terminal::release_image(&state);
+        }
+    }
+
+    pub fn compare_zoom_in(&mut self) {
+        if let Some(compare) = self.compare.as_mut() {
+            compare.zoom_in();
+            self.clear_compare_states();
+        }
+    }
+
+    pub fn compare_zoom_out(&mut self) {
+        if let Some(compare) = self.compare.as_mut() {
+            compare.zoom_out();
+            self.clear_compare_states();
+        }
+    }
+
+    pub fn compare_pan(&mut self, dx: f64, dy: f64) {
+        if let Some(compare) = self.compare.as_mut() {
+            compare.pan(dx, dy);
+            self.clear_compare_states();
+        }
+    }
+
+    pub fn compare_reset(&mut self) {
+        if let Some(compare) = self.compare.as_mut() {
+            compare.reset();
+            self.clear_compare_states();
+        }
+    }
+
+    /// Apply the left (`false`) or right (`true`) side of the comparison and close it
+    pub fn apply_compared(&mut self, right: bool) -> Result<()> {
+        let Some(compare) = self.compare.as_ref() else {
+            return Ok(());
+        };
+        let path = if right { compare.right.clone() } else { compare.left.clone() };
+        self.close_compare();
+        let installed_path = wallpaper::install_wallpaper(&Wallpaper::new(path))?;
+        self.set_current(installed_path)
+    }
+
+    pub fn toggle_blur(&mut self) {
+        self.adjustments.toggle_blur();
+        self.clear_preview();
+    }
+
+    pub fn toggle_dim(&mut self) {
+        self.adjustments.toggle_dim();
+        self.clear_preview();
+    }
+
+    pub fn toggle_grayscale(&mut self) {
+        self.adjustments.toggle_grayscale();
+        self.clear_preview();
+    }
+
+    /// Start decoding the selected image for the preview modal, with adjustments applied
+    pub fn request_preview(&mut self) {
+        if self.preview_state.is_some() || self.preview_load.is_some() {
+            return;
+        }
+        let bounds = self.preview_bounds();
+        let Some((path, thumbnail)) = self.selected_wallpaper().map(|w| (w.path.clone(), w.thumbnail.clone())) else {
+            return;
+        };
+        self.preview_load = Some(PreviewLoader::start(path, self.adjustments, bounds));
+
+        // Adjusted previews would visibly change when the real one arrives, so only plain ones get a stand-in
+        if let Some(thumbnail) = thumbnail
+            && self.adjustments.is_identity()
+        {
+            let (width, height) = bounds;
+            let scaled = thumbnail.resize(width, height, image::imageops::FilterType::Triangle);
+            let protocol = self.picker.new_resize_protocol(scaled);
+            terminal::track_image(&protocol);
+            self.clear_preview_placeholder();
+            self.preview_placeholder = Some(protocol);
+        }
+    }
+
+    fn clear_preview_placeholder(&mut self) {
+        if let Some(state) = self.preview_placeholder.take() {
+            terminal::release_image(&state);
+        }
+    }
+
+    /// The terminal's size in pixels, which no preview needs to exceed
+    fn preview_bounds(&self) -> (u32, u32) {
+        let (font_width, font_height) = self.picker.font_size();
+        let (columns, rows) = crossterm::terminal::size().unwrap_or((240, 68));
+        (columns as u32 * font_width as u32, rows as u32 * font_height as u32)
+    }
+
+    /// Encode finished preview decodes; returns true once one is ready to draw
+    pub fn poll_preview(&mut self) -> bool {
+        self.poll_before() | self.poll_selected_preview()
+    }
+
+    fn poll_before(&mut self) -> bool {
+        let Some(result) = self.before_load.as_ref().and_then(PreviewLoader::poll) else {
+            return false;
+        };
+        self.before_load = None;
+        match result {
+            Ok(img) => {
+                let protocol = self.picker.new_resize_protocol(img);
+                terminal::track_image(&protocol);
+                self.before_state = Some(protocol);
+            }
+            Err(e) => {
+                self.push_error(format!("Can't show the current wallpaper: {}", e));
+                self.before_after = false;
+            }
+        }
+        true
+    }
+
+    fn poll_selected_preview(&mut self) -> bool {
+        let Some(result) = self.preview_load.as_ref().and_then(PreviewLoader::poll) else {
+            return false;
+        };
+        let Some(load) = self.preview_load.take() else {
+            return false;
+        };
+        // Paged past it before it finished
+        if self.selected_wallpaper().is_none_or(|w| w.path != load.path) {
+            return false;
+        }
+        match result {
+            Ok(img) => {
+                self.clear_preview_placeholder();
+                self.preview_image = Some(img.clone());
+                let protocol = self.picker.new_resize_protocol(img);
+                terminal::track_image(&protocol);
+                self.preview_state = Some(protocol);
+            }
+            Err(e) => {
+                // Close the modal rather than retrying (and re-reporting) every frame
+                self.push_error(format!("Preview failed: {}", e));
+                if matches!(self.mode, Mode::Preview) {
+                    self.mode = Mode::Grid;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn toggle_help(&mut self) {
+        match self.mode {
+            Mode::Help => self.mode = Mode::Grid,
+            _ => {
+                self.help_scroll = 0;
+                self.help_query.clear();
+                self.help_searching = false;
+                self.mode = Mode::Help;
+            }
+        }
+    }
+
+    pub fn scroll_help(&mut self, lines: isize) {
+        self.help_scroll = self.help_scroll.saturating_add_signed(lines);
+    }
+
+    pub fn help_search_input(&mut self, c: char) {
+        self.help_query.push(c);
+        self.help_scroll = 0;
+    }
+
+    pub fn help_search_backspace(&mut self) {
+        self.help_query.pop();
+        self.help_scroll = 0;
+    }
+
+    pub fn cancel_help_search(&mut self) {
+        self.help_query.clear();
+        self.help_searching = false;
+        self.help_scroll = 0;
+    }
+
+    /// Apply the selection, first asking for confirmation if it doesn't suit the monitors
+    pub fn request_apply(&mut self) -> Result<()> {
+        if !self.skip_resolution_warning
+            && let Some(wallpaper) = self.selected_wallpaper()
+            && let Some(size) = wallpaper.dimensions.or_else(|| formats::dimensions(&wallpaper.path))
+            && let Some(warning) = monitor::resolution_mismatch(size, &self.monitors)
+        {
+            self.apply_warning = Some(warning);
+            self.confirm_from_preview = matches!(self.mode, Mode::Preview);
+            self.mode = Mode::ConfirmApply;
+            return Ok(());
+        }
+        self.apply_wallpaper()
+    }
+
+    /// Answer the resolution warning; `remember` stops it being shown again
+    pub fn confirm_apply(&mut self, remember: bool) -> Result<()> {
+        self.leave_confirm();
+        if remember {
+            self.skip_resolution_warning = true;
+        }
+        self.apply_wallpaper()
+    }
+
+    pub fn cancel_apply(&mut self) {
+        self.leave_confirm();
+    }
+
+    /// Close the apply failure modal, along with its copy on the notification line
+    pub fn close_apply_failure(&mut self) {
+        if let Some(failure) = self.apply_failure.take() {
+            self.errors.retain(|error| *error != failure);
+        }
+        self.mode = Mode::Grid;
+    }
+
+    fn leave_confirm(&mut self) {
+        self.apply_warning = None;
+        self.mode = if self.confirm_from_preview { Mode::Preview } else { Mode::Grid };
+        self.confirm_from_preview = false;
+    }
+
+    /// Put the selection on the desktop without installing it or moving the current-background
+    /// symlink; it's reverted unless kept before the countdown runs out
+    pub fn start_trial(&mut self) -> Result<()> {
+        let Some(wallpaper) = self.selected_wallpaper() else {
+            return Ok(());
+        };
+        let name = wallpaper.name.clone();
+        let image = desktop_image(&wallpaper.path, self.adjustments, &wallpaper::get_trial_path())?;
+        backend::for_config(&self.config).show(&image, self.fill_mode)?;
+        self.hover_shown = None;
+
+        let seconds = self.config.try_seconds.unwrap_or(DEFAULT_TRY_SECONDS);
+        self.trial = Some(Trial {
+            name,
+            deadline: Instant::now() + Duration::from_secs(seconds),
+            shown_secs: seconds,
+            from_preview: matches!(self.mode, Mode::Preview),
+        });
+        self.mode = Mode::Trying;
+        Ok(())
+    }
+
+    /// Keep the wallpaper on trial by applying it for real
+    pub fn keep_trial(&mut self) -> Result<()> {
+        self.leave_trial();
+        self.apply_wallpaper()
+    }
+
+    /// Put the current wallpaper back
+    pub fn end_trial(&mut self) -> Result<()> {
+        self.leave_trial();
+        self.show_current()
+    }
+
+    fn show_current(&self) -> Result<()> {
+        let backend = backend::for_config(&self.config);
+        backend::show_with_pins(&*backend, &wallpaper::get_current_background_path(), self.fill_mode)
+    }
+
+    /// With hover-apply on, show the selection on the desktop once the cursor has rested on it,
+    /// and the current wallpaper again when it's selected. Returns true if the desktop changed.
+    pub fn poll_hover(&mut self) -> bool {
+        // One at a time, so a slow conversion can't land after a newer selection
+        if let Some(job) = &self.hover_job {
+            let result = match job.rx.try_recv() {
+                Err(TryRecvError::Empty) => return false,
+                Ok(result) => result,
+                Err(TryRecvError::Disconnected) => Err(eyre!("Hover-apply thread exited")),
+            };
+            self.hover_job = None;
+            // Hover ended while it was being shown: put the current wallpaper back over it
+            if self.hover_shown.is_none() {
+                let restored = self.show_current();
+                self.report(restored);
+            }
+            self.report(result);
+        }
+        if !self.config.hover_apply
+            || !matches!(self.mode, Mode::Grid | Mode::Preview)
+            || self.selection_changed_at.elapsed() < HOVER_DELAY
+        {
+            return false;
+        }
+        let Some(wallpaper) = self.selected_wallpaper() else {
+            return false;
+        };
+        if wallpaper.unsupported {
+            return false;
+        }
+        let path = wallpaper.path.clone();
+        if self.current_wallpaper.as_ref() == Some(&path) {
+            let result = self.end_hover();
+            self.report(result);
+            return false;
+        }
+        if self.hover_shown.as_ref() == Some(&path) {
+            return false;
+        }
+        // Converting and starting the backend can take a while, so it happens off the UI thread
+        let (tx, rx) = mpsc::channel();
+        let backend = backend::for_config(&self.config);
+        let (source, adjustments, mode) = (path.clone(), self.adjustments, self.fill_mode);
+        thread::spawn(move || {
+            let scratch = wallpaper::get_trial_path().with_file_name("hover.png");
+            let _ = tx.send(desktop_image(&source, adjustments, &scratch).and_then(|image| backend.show(&image, mode)));
+        });
+        self.hover_job = Some(HoverJob { rx });
+        // Not retried on failure until the selection moves
+        self.hover_shown = Some(path);
+        true
+    }
+
+    /// Put the current wallpaper back if hover-apply replaced it
+    pub fn end_hover(&mut self) -> Result<()> {
+        if self.hover_shown.take().is_none() {
+            return Ok(());
+        }
+        self.show_current()
+    }
+
+    fn leave_trial(&mut self) {
+        if let Some(trial) = self.trial.take() {
+            self.mode = if trial.from_preview { Mode::Preview } else { Mode::Grid };
+        }
+    }
+
+    /// Tick the trial countdown, reverting once it runs out. Returns true when a redraw is due.
+    pub fn poll_trial(&mut self) -> bool {
+        let Some(trial) = self.trial.as_mut() else {
+            return false;
+        };
+        let remaining = trial.remaining_secs();
+        if remaining == 0 {
+            let result = self.end_trial();
+            self.report(result);
+            return true;
+        }
+        let changed = remaining != trial.shown_secs;
+        trial.shown_secs = remaining;
+        changed
+    }
+
+    pub fn apply_wallpaper(&mut self) -> Result<()> {
+        self.apply_with(self.adjustments)
+    }
+
+    /// Apply the selected wallpaper, processing it first unless `adjustments` is a no-op
+    fn apply_with(&mut self, adjustments: Adjustments) -> Result<()> {
+        // A reddit preview is only thumbnail-sized; the full image is applied once downloaded
+        if let Some(post) = self.selected_wallpaper().and_then(|w| reddit::post_for(&w.path)) {
+            return self.download_post(&post, true);
+        }
+        if let Some(&idx) = self.browser.filtered_indices.get(self.browser.selected)
+            && let Some(wallpaper) = self.wallpapers.get(idx)
+        {
+            // Install to omarchy backgrounds dir and get the path
+            let installed_path = if adjustments.is_identity() {
+                wallpaper::install_wallpaper(wallpaper)?
+            } else {
+                let settings = format!("{:?}", adjustments);
+                wallpaper::install_processed(wallpaper, "adjusted", &settings, |img| adjustments.apply(img))?
+            };
+
+            self.set_current(installed_path)?;
+        }
+        Ok(())
+    }
+
+    /// Point the current-background symlink at an installed file and reload the backend
+    fn set_current(&mut self, installed_path: PathBuf) -> Result<()> {
+        self.clear_before();
+        let hovering = self.hover_shown.take().is_some();
+        let unchanged = self.current_wallpaper.as_ref() == Some(&installed_path);
+        if let Err(e) = wallpaper::set_wallpaper(&installed_path, self.fill_mode, &self.config) {
+            self.apply_failure = Some(e.to_string());
+            self.mode = Mode::ApplyFailed;
+            return Err(e);
+        }
+        // Re-applying the current wallpaper is skipped, but a hovered one may be on screen
+        if hovering && unchanged {
+            self.show_current()?;
+        }
+        self.current_wallpaper = Some(installed_path.clone());
+        self.applied_at = Some(SystemTime::now());
+        self.announce(format!(
+            "Applied {} to the desktop, {} mode",
+            installed_path.file_name().unwrap_or_default().to_string_lossy(),
+            self.fill_mode.as_str()
+        ));
+        self.events.emit(AppEvent::WallpaperApplied(installed_path));
+        Ok(())
+    }
+
+    /// Append the selection to the wallpaper queue
+    pub fn enqueue_selected(&mut self) -> Result<()> {
+        let path = self.selected_path()?;
+        self.edit_queue(|queue| {
+            queue.push(&path);
+            Ok(())
+        })
+    }
+
+    /// Change the stored queue, which the daemon may have advanced since it was last read
+    fn edit_queue(&mut self, change: impl FnOnce(&mut Queue) -> Result<()>) -> Result<()> {
+        self.queue = queue::edit(change)?.0;
+        Ok(())
+    }
+
+    /// Apply the next queued wallpaper once it is due. The daemon plays the queue too,
+    /// so the stored one is re-read rather than trusting `self.queue`.
+    /// Returns true if the queue changed.
+    pub fn poll_queue(&mut self) -> bool {
+        if self.queue_checked.elapsed() < QUEUE_CHECK_INTERVAL {
+            return false;
+        }
+        self.queue_checked = Instant::now();
+        let (queue, due) = match queue::edit(|queue| Ok(queue.take_due())) {
+            Ok(edited) => edited,
+            Err(e) => {
+                // Checked every second; a damaged queue file is reported once, not each time
+                let message = format!("Queue: {}", e);
+                let new = self.errors.back() != Some(&message);
+                if new {
+                    self.push_error(message);
+                }
+                return new;
+            }
+        };
+        let changed = due.is_some() || queue != self.queue;
+        self.queue = queue;
+        if let Some(path) = due {
+            let result = wallpaper::install_wallpaper(&Wallpaper::new(path)).and_then(|installed| self.set_current(installed));
+            self.report(result);
+        }
+        changed
+    }
+
+    /// Handle pending control-socket commands. Returns true if any were processed.
+    pub fn poll_ipc(&mut self) -> bool {
+        let mut handled = false;
+        while let Some(request) = self.ipc.as_ref().and_then(|ipc| ipc.try_recv()) {
+            let result = self.handle_ipc(&request.command);
+            request.respond(result);
+            handled = true;
+        }
+        handled
+    }
+
+    /// Move the cursor to a smart random pick from the grid
+    fn select_random(&mut self) -> Result<()> {
+        let candidates: Vec<PathBuf> = self.browser
+            .filtered_indices
+            .iter()
+            .map(|&i| self.wallpapers[i].path.clone())
+            .collect();
+        let pick = history::smart_random(&candidates, &self.config).ok_or_else(|| eyre!("no wallpapers"))?;
+        self.browser.selected = candidates.iter().position(|p| *p == pick).unwrap_or(0);
+        Ok(())
+    }
+
+    fn handle_ipc(&mut self, command: &IpcCommand) -> Result<()> {
+        let len = self.browser.filtered_indices.len();
+        match command {
+            IpcCommand::Next | IpcCommand::Prev | IpcCommand::Random if len == 0 => {
+                return Err(eyre!("no wallpapers"));
+            }
+            IpcCommand::Next => self.browser.selected = (self.browser.selected + 1) % len,
+            IpcCommand::Prev => self.browser.selected = (self.browser.selected + len - 1) % len,
+            IpcCommand::Random => self.select_random()?,
+            IpcCommand::Reload => return self.show_current(),
+            IpcCommand::Set(path) => {
+                let path = config::expand_home(path);
+                if let Some(pos) = self.browser
+                    .filtered_indices
+                    .iter()
+                    .position(|&i| self.wallpapers[i].path == path)
+                {
+                    self.browser.selected = pos;
+                } else {
+                    let installed_path = wallpaper::install_wallpaper(&Wallpaper::new(path))?;
+                    return self.set_current(installed_path);
+                }
+            }
+        }
+        self.apply_wallpaper()
+    }
+
+    pub fn escape(&mut self) {
+        match self.mode {
+            Mode::Preview => {
+                self.clear_preview();
+                self.mode = Mode::Grid;
+            }
+            Mode::Help => self.mode = Mode::Grid,
+            Mode::Search => self.handle(Input::SearchCancel),
+            Mode::Command => self.handle(Input::CommandCancel),
+            Mode::Crop => self.cancel_crop(),
+            Mode::ImportConflict => self.cancel_import(),
+            Mode::Compare => self.close_compare(),
+            Mode::Trash => self.close_trash(),
+            Mode::ConfirmApply => self.cancel_apply(),
+            Mode::ApplyFailed => self.close_apply_failure(),
+            Mode::TagEditor => self.close_tag_editor(),
+            Mode::Downloads | Mode::Settings | Mode::UndoList => self.mode = Mode::Grid,
+            Mode::Trying => {
+                let result = self.end_trial();
+                self.report(result);
+            }
+            // Esc abandons a slow listing or preload, then clears notifications, before it quits
+            Mode::Grid if self.is_scanning() => self.cancel_scan(),
+            Mode::Grid if self.preloading => self.cancel_preload(),
+            Mode::Grid if !self.marked.is_empty() => self.marked.clear(),
+            Mode::Grid if !self.errors.is_empty() => self.dismiss_error(),
+            Mode::Grid => self.should_quit = true,
+        }
+    }
+
+    /// Days the current wallpaper has been applied, once past the configured reminder threshold
+    pub fn rotation_reminder(&self) -> Option<u64> {
+        let threshold = self.config.rotation_reminder_days?;
+        let age = self.applied_at?.elapsed().ok()?;
+        let days = age.as_secs() / 86_400;
+        (days >= threshold).then_some(days)
+    }
+
+    /// Queue an error for the notification line instead of failing silently
+    pub fn push_error(&mut self, message: impl Into<String>) {
+        const MAX_ERRORS: usize = 20;
+        if self.errors.len() == MAX_ERRORS {
+            self.errors.pop_front();
+        }
+        let message = message.into();
+        self.announce(format!("Error: {}", message));
+        self.errors.push_back(message);
+    }
+
+    /// Whether thumbnails and previews are drawn; screen-reader mode leaves them out
+    pub fn shows_images(&self) -> bool {
+        self.announcer.is_none()
+    }
+
+    /// Say `message` in screen-reader mode
+    pub fn announce(&mut self, message: impl Into<String>) {
+        if self.announcer.is_none() {
+            return;
+        }
+        let state = self.spoken_state();
+        if let Some(announcer) = self.announcer.as_mut() {
+            announcer.say(message, state);
+        }
+    }
+
+    /// In screen-reader mode, describe the mode and selection once either changes.
+    /// Returns true if there is something new to read.
+    pub fn announce_state(&mut self) -> bool {
+        if self.announcer.is_none() {
+            return false;
+        }
+        let state = self.spoken_state();
+        self.announcer.as_mut().is_some_and(|announcer| announcer.update_state(state))
+    }
+
+    /// The mode and selection in words, e.g. `Preview: sea, 3 of 120, 3840 by 2160, current wallpaper`
+    fn spoken_state(&self) -> String {
+        let selection = match self.selected_wallpaper() {
+            Some(wallpaper) => {
+                let mut words = format!("{}, {} of {}", wallpaper.name, self.browser.selected + 1, self.browser.filtered_indices.len());
+                if let Some((width, height)) = wallpaper.dimensions {
+                    words.push_str(&format!(", {} by {}", width, height));
+                }
+                if self.current_wallpaper.as_ref() == Some(&wallpaper.path) {
+                    words.push_str(", current wallpaper");
+                }
+                words
+            }
+            None => "no wallpapers".to_string(),
+        };
+        match self.mode {
+            Mode::Grid => format!("Grid: {}", selection),
+            Mode::Preview => format!("Preview: {}", selection),
+            Mode::Search => format!("Search {}: {} matches", self.browser.search_query, self.browser.filtered_indices.len()),
+            Mode::Command => "Command line".to_string(),
+            Mode::Help => "Help".to_string(),
+            Mode::Crop => match &self.crop {
+                Some(crop) => format!(
+                    "Crop: {:.0} by {:.0} pixels at {:.0}, {:.0}",
+                    crop.width,
+                    crop.height(),
+                    crop.x,
+                    crop.y
+                ),
+                None => "Crop".to_string(),
+            },
+            Mode::ImportConflict => "Import: files with these names exist. r rename, s skip, o overwrite".to_string(),
+            Mode::Compare => match &self.compare {
+                Some(compare) => format!(
+                    "Compare: 1 {}, 2 {}",
+                    compare.left.file_stem().unwrap_or_default().to_string_lossy(),
+                    compare.right.file_stem().unwrap_or_default().to_string_lossy()
+                ),
+                None => "Compare".to_string(),
+            },
+            Mode::Trash => "Trash: choose a wallpaper to restore".to_string(),
+            Mode::ConfirmApply => format!(
+                "Apply anyway? {}",
+                self.apply_warning.as_deref().unwrap_or("y apply, n cancel")
+            ),
+            Mode::ApplyFailed => format!(
+                "Couldn't apply the wallpaper. {}",
+                self.apply_failure.as_deref().unwrap_or_default()
+            ),
+            Mode::Downloads => "Downloads".to_string(),
+            Mode::TagEditor => match self.tag_editor.as_ref().and_then(|e| e.rows.get(e.selected)) {
+                Some(row) => format!("Tags: {} {}", row.state.marker(), row.name),
+                None => "Tags".to_string(),
+            },
+            Mode::UndoList => match self.undo.iter().next() {
+                Some(change) => format!("Undo list: u undoes {}", change.describe()),
+                None => "Undo list: nothing to undo".to_string(),
+            },
+            Mode::Settings => match self.setting_values().get(self.settings_selected) {
+                Some((option, value)) => format!("Settings: {} is {}", option, value),
+                None => "Settings".to_string(),
+            },
+            Mode::Trying => match &self.trial {
+                Some(trial) => format!("Trying {} on the desktop. y keep, n undo", trial.name),
+                None => "Trying".to_string(),
+            },
+        }
+    }
+
+    /// Surface the error from a fallible action, if any
+    pub fn report(&mut self, result: Result<()>) {
+        if let Err(e) = result {
+            self.push_error(e.to_string());
+        }
+    }
+
+    pub fn dismiss_error(&mut self) {
+        self.errors.pop_front();
+    }
+
+    pub fn selected_wallpaper(&self) -> Option<&Wallpaper> {
+        self.browser.filtered_indices
+            .get(self.browser.selected)
+            .and_then(|&idx| self.wallpapers.get(idx))
+    }
+
+    pub fn is_highlighted(&self, index: usize) -> bool {
+        self.wallpapers
+            .get(index)
+            .is_some_and(|w| self.highlighted.contains(&w.path))
+    }
+
+    pub fn is_compare_mark(&self, index: usize) -> bool {
+        self.wallpapers
+            .get(index)
+            .is_some_and(|w| self.compare_mark.as_ref() == Some(&w.path))
+    }
+
+    pub fn is_current(&self, index: usize) -> bool {
+        self.current_wallpaper
+            .as_ref()
+            .map(|current| {
+                self.wallpapers
+                    .get(index)
+                    .map(|w| w.path == *current)
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// `path` as something the backend can draw: the file itself, or a PNG written to `scratch`
+/// for formats backends can't read (SVG, HEIC, AVIF, JXL) and for preview adjustments
+fn desktop_image(path: &Path, adjustments: Adjustments, scratch: &Path) -> Result<PathBuf> {
+    if adjustments.is_identity() && !svg::is_svg(path) && !formats::is_optional_format(path) {
+        return Ok(path.to_path_buf());
+    }
+    if let Some(parent) = scratch.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    adjustments
+        .apply(wallpaper::open_image(path)?)
+        .save_with_format(scratch, image::ImageFormat::Png)?;
+    Ok(scratch.to_path_buf())
+}
+
+/// Completion lookups against the app's state, the filesystem and the connected outputs
+struct AppCompletions<'a> {
+    wallpapers: &'a [Wallpaper],
+    collections: &'a Collections,
+}
+
+impl DirSource for AppCompletions<'_> {
+    fn entries(&self, dir: &Path) -> Vec<(String, bool)> {
+        browse::Fs.entries(dir)
+    }
+}
+
+impl CompletionSource for AppCompletions<'_> {
+    fn home(&self) -> Option<PathBuf> {
+        dirs::home_dir()
+    }
+
+    fn bookmarks(&self) -> Vec<String> {
+        Bookmarks::load().names().map(String::from).collect()
+    }
+
+    fn outputs(&self) -> Vec<String> {
+        monitor::detect_monitors().into_iter().map(|m| m.name).collect()
+    }
+
+    fn images(&self) -> Vec<String> {
+        self.wallpapers
+            .iter()
+            .filter_map(|w| w.path.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn collections(&self) -> Vec<String> {
+        self.collections.names().map(String::from).collect()
+    }
+}
+
+/// The terminal's image protocol, falling back to half-block mosaics (average colors, two per
+/// cell) when it doesn't answer the query or supports no graphics protocol
+fn image_picker() -> Picker {
+    Picker::from_query_stdio().unwrap_or_else(|_| {
+        let mut picker = Picker::from_fontsize(FALLBACK_FONT_SIZE);
+        // `from_fontsize` guesses from the environment, which is what just failed
+        picker.set_protocol_type(ProtocolType::Halfblocks);
+        picker
+    })
 }
@@ -0,0 +1,612 @@
+use crate::config::Config;
+use crate::monitor;
+use crate::wallpaper::{self, FillMode};
+use crate::pins::Pins;
+use color_eyre::{eyre::eyre, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// How long an old swaybg keeps running after its replacement is spawned. A replacement
+/// that exits within this time failed to start, and the old one is left running.
+const SWAYBG_OVERLAP: Duration = Duration::from_millis(150);
+
+/// Size past which the swaybg log is started over
+const SWAYBG_LOG_LIMIT: u64 = 1024 * 1024;
+
+/// Something that can put an image on the desktop background
+pub trait WallpaperBackend: Send {
+    /// Short identifier used in messages
+    fn name(&self) -> &'static str;
+
+    /// Show `image` on every output
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()>;
+
+    /// Show `image` on every output except those in `pinned`, which each get their own
+    fn show_pinned(&self, image: &Path, _pinned: &BTreeMap<String, PathBuf>, mode: FillMode) -> Result<()> {
+        self.show(image, mode)
+    }
+
+    /// Whether `show_pinned` can give outputs different wallpapers
+    fn shows_per_output(&self) -> bool {
+        false
+    }
+
+    /// Whether the process keeping the background up is alive. Backends that
+    /// set the background once and exit always report true.
+    fn is_alive(&self) -> bool {
+        true
+    }
+
+    /// Whether the backend animates changes itself, making the crossfade emulation redundant
+    fn has_transitions(&self) -> bool {
+        false
+    }
+}
+
+/// swww transition types (`swww img --transition-type`)
+pub const TRANSITION_TYPES: &[&str] = &[
+    "none", "simple", "fade", "left", "right", "top", "bottom", "wipe", "wave", "grow", "center",
+    "any", "outer", "random",
+];
+
+/// Named positions for `grow`/`outer` transitions (`swww img --transition-pos`)
+const TRANSITION_POSITIONS: &[&str] = &[
+    "center", "top", "left", "right", "bottom", "top-left", "top-right", "bottom-left", "bottom-right",
+];
+
+/// How swww animates to a new wallpaper, written `type[,duration][,position]` (e.g. `wipe,1s`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    pub kind: String,
+    /// Seconds
+    pub duration: Option<f32>,
+    pub position: Option<String>,
+}
+
+impl Transition {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut parts = spec.split(',').map(str::trim);
+        let kind = parts.next().unwrap_or_default().to_lowercase();
+        if !TRANSITION_TYPES.contains(&kind.as_str()) {
+            return Err(eyre!("Unknown transition: {} (one of {})", kind, TRANSITION_TYPES.join(", ")));
+        }
+        let mut transition = Transition { kind, duration: None, position: None };
+        for part in parts {
+            if TRANSITION_POSITIONS.contains(&part) {
+                transition.position = Some(part.to_string());
+            } else {
+                transition.duration = Some(parse_seconds(part).ok_or_else(|| {
+                    eyre!("Expected a duration (1s, 500ms) or position, got: {}", part)
+                })?);
+            }
+        }
+        Ok(transition)
+    }
+}
+
+/// `1s`, `1.5`, or `500ms` as seconds
+fn parse_seconds(text: &str) -> Option<f32> {
+    let seconds = if let Some(ms) = text.strip_suffix("ms") {
+        ms.parse::<f32>().ok()? / 1000.0
+    } else {
+        text.strip_suffix('s').unwrap_or(text).parse().ok()?
+    };
+    (seconds >= 0.0).then_some(seconds)
+}
+
+/// Wayland: the swww daemon, which animates between wallpapers itself
+#[derive(Default)]
+pub struct Swww {
+    pub transition: Option<Transition>,
+}
+
+impl WallpaperBackend for Swww {
+    fn name(&self) -> &'static str {
+        "swww"
+    }
+
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()> {
+        run(&mut self.img(image, mode))
+    }
+
+    fn show_pinned(&self, image: &Path, pinned: &BTreeMap<String, PathBuf>, mode: FillMode) -> Result<()> {
+        // swww rejects outputs that aren't connected; their pins apply once they are
+        let connected: Vec<String> = monitor::detect_monitors().into_iter().map(|m| m.name).collect();
+        run(&mut self.img(image, mode))?;
+        for (output, pin) in pinned {
+            if connected.is_empty() || connected.contains(output) {
+                run(self.img(pin, mode).args(["--outputs", output]))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn shows_per_output(&self) -> bool {
+        true
+    }
+
+    fn is_alive(&self) -> bool {
+        !pids("swww-daemon").is_empty()
+    }
+
+    fn has_transitions(&self) -> bool {
+        true
+    }
+}
+
+impl Swww {
+    /// `swww img` for `image`, with the configured transition
+    fn img(&self, image: &Path, mode: FillMode) -> Command {
+        // swww can't stretch or tile; the closest it has
+        let resize = match mode {
+            FillMode::Fill | FillMode::Stretch => "crop",
+            FillMode::Fit => "fit",
+            FillMode::Center | FillMode::Tile => "no",
+        };
+        let mut command = Command::new("swww");
+        command.arg("img").arg(resolve(image)).args(["--resize", resize]);
+        if let Some(transition) = &self.transition {
+            command.args(["--transition-type", &transition.kind]);
+            if let Some(duration) = transition.duration {
+                command.args(["--transition-duration", &duration.to_string()]);
+            }
+            if let Some(position) = &transition.position {
+                command.args(["--transition-pos", position]);
+            }
+        }
+        command
+    }
+}
+
+/// Wayland: long-running swaybg instances, one for all outputs or one per output
+#[derive(Default)]
+pub struct Swaybg {
+    pub per_output: bool,
+}
+
+impl WallpaperBackend for Swaybg {
+    fn name(&self) -> &'static str {
+        "swaybg"
+    }
+
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()> {
+        let mut outputs: Vec<Option<String>> = Vec::new();
+        if self.per_output {
+            outputs.extend(monitor::detect_monitors().into_iter().map(|m| Some(m.name)));
+        }
+        if outputs.is_empty() {
+            outputs.push(None);
+        }
+        let commands = outputs
+            .into_iter()
+            .map(|output| {
+                let mut command = Command::new("swaybg");
+                if let Some(output) = &output {
+                    command.args(["-o", output]);
+                }
+                command.arg("-i").arg(image).args(["-m", mode.as_str()]);
+                command
+            })
+            .collect();
+        replace_swaybg(commands)
+    }
+
+    /// One swaybg for everything: `*` covers the outputs without a pin, including
+    /// ones plugged in later, and swaybg prefers an output's own entry over it
+    fn show_pinned(&self, image: &Path, pinned: &BTreeMap<String, PathBuf>, mode: FillMode) -> Result<()> {
+        let mut command = Command::new("swaybg");
+        command.args(["-o", "*"]).arg("-i").arg(image).args(["-m", mode.as_str()]);
+        for (output, pin) in pinned {
+            command.args(["-o", output]).arg("-i").arg(pin).args(["-m", mode.as_str()]);
+        }
+        replace_swaybg(vec![command])
+    }
+
+    fn shows_per_output(&self) -> bool {
+        true
+    }
+
+    fn is_alive(&self) -> bool {
+        !running_swaybg().is_empty()
+    }
+}
+
+/// Start the swaybg `commands` before stopping the instances that were already running,
+/// so the desktop is never left without a background in between. If a new instance exits
+/// straight away, the rest are stopped and the old ones keep the desktop as it was.
+fn replace_swaybg(commands: Vec<Command>) -> Result<()> {
+    let old_pids = running_swaybg();
+
+    // stderr goes to a log file rather than a pipe nobody drains once swaybg is up
+    let log = open_swaybg_log()?;
+    let offset = log.metadata()?.len();
+    let mut spawned = Vec::new();
+    for mut command in commands {
+        spawned.push(command.stderr(Stdio::from(log.try_clone()?)).spawn()?);
+    }
+    let new_pids: Vec<u32> = spawned.iter().map(Child::id).collect();
+    let _ = save_swaybg_pids(&new_pids);
+
+    // Give the new instance time to draw (or fail) before the old one disappears
+    thread::sleep(SWAYBG_OVERLAP);
+    if let Some(failed) = spawned.iter_mut().position(|child| !matches!(child.try_wait(), Ok(None))) {
+        let message = startup_error(&mut spawned[failed], offset);
+        for child in &mut spawned {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let _ = save_swaybg_pids(&old_pids);
+        return Err(eyre!("swaybg didn't start: {}", message));
+    }
+    if !old_pids.is_empty() {
+        stop_swaybg(&old_pids);
+    }
+    SWAYBG_CHILDREN.lock().unwrap_or_else(|e| e.into_inner()).extend(spawned);
+    reap_swaybg();
+    Ok(())
+}
+
+/// What a swaybg that exited at startup printed after `offset` in the log, or its exit
+/// status if nothing
+fn startup_error(child: &mut Child, offset: u64) -> String {
+    let status = child.wait().map(|s| s.to_string()).unwrap_or_default();
+    let mut stderr = String::new();
+    if let Ok(mut log) = fs::File::open(swaybg_log_path()) {
+        let _ = log.seek(SeekFrom::Start(offset));
+        let _ = log.read_to_string(&mut stderr);
+    }
+    match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => line.trim().to_string(),
+        None => status,
+    }
+}
+
+/// Where spawned swaybg instances write their stderr
+fn swaybg_log_path() -> PathBuf {
+    swaybg_pids_path().with_file_name("swaybg.log")
+}
+
+/// The log, opened for appending; emptied first once it passes `SWAYBG_LOG_LIMIT`
+fn open_swaybg_log() -> Result<fs::File> {
+    let path = swaybg_log_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.len() > SWAYBG_LOG_LIMIT) {
+        let _ = fs::remove_file(&path);
+    }
+    Ok(fs::OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+/// swaybg instances this process spawned, waited on once they exit so they don't linger as zombies
+static SWAYBG_CHILDREN: Mutex<Vec<Child>> = Mutex::new(Vec::new());
+
+/// PIDs of the swaybg instances last started by any picker process (TUI, daemon, `--next`)
+fn swaybg_pids_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("omarchy-wallpaper-picker")
+        .join("swaybg.pids")
+}
+
+fn save_swaybg_pids(pids: &[u32]) -> Result<()> {
+    let path = swaybg_pids_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lines: Vec<String> = pids.iter().map(u32::to_string).collect();
+    fs::write(path, lines.join("\n"))?;
+    Ok(())
+}
+
+/// Wait on spawned instances that have exited
+fn reap_swaybg() {
+    SWAYBG_CHILDREN
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+}
+
+/// The swaybg instances we started that are still up, or, when there are none (e.g. the
+/// session's autostart launched it), this user's swaybg processes. Either way only those
+/// drawing on this session's Wayland display count; other users' and sessions' instances
+/// are left alone.
+fn running_swaybg() -> Vec<u32> {
+    reap_swaybg();
+    // The runtime dir, and so the PID file, is shared by all of the user's sessions
+    let tracked: Vec<u32> = fs::read_to_string(swaybg_pids_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .filter(|&pid| is_running("swaybg", pid) && on_this_display(pid))
+        .collect();
+    if !tracked.is_empty() {
+        return tracked;
+    }
+    let uid = fs::metadata("/proc/self").map(|m| m.uid().to_string()).unwrap_or_default();
+    pids_for_user("swaybg", &uid)
+        .iter()
+        .filter_map(|pid| pid.parse().ok())
+        .filter(|&pid| on_this_display(pid))
+        .collect()
+}
+
+/// Whether `pid` runs on the same Wayland display as we do. Processes whose environment
+/// can't be read aren't ours.
+fn on_this_display(pid: u32) -> bool {
+    let ours = std::env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| DEFAULT_WAYLAND_DISPLAY.to_string());
+    fs::read(format!("/proc/{}/environ", pid)).is_ok_and(|environ| display_in_environ(&environ) == ours)
+}
+
+/// Clients connect here when `WAYLAND_DISPLAY` is unset
+const DEFAULT_WAYLAND_DISPLAY: &str = "wayland-0";
+
+/// `WAYLAND_DISPLAY` from a NUL-separated `/proc/<pid>/environ`
+fn display_in_environ(environ: &[u8]) -> String {
+    environ
+        .split(|&b| b == 0)
+        .find_map(|var| var.strip_prefix(b"WAYLAND_DISPLAY="))
+        .map_or_else(|| DEFAULT_WAYLAND_DISPLAY.to_string(), |value| String::from_utf8_lossy(value).into_owned())
+}
+
+/// SIGTERM `pids` and reap the ones we spawned; `killall` this user's swaybg only if
+/// `kill` itself fails
+fn stop_swaybg(pids: &[u32]) {
+    let args: Vec<String> = pids.iter().map(u32::to_string).collect();
+    let killed = Command::new("kill").args(&args).status().is_ok_and(|s| s.success());
+    if !killed {
+        let _ = Command::new("killall").args(["-q", "-u", &whoami(), "swaybg"]).status();
+    }
+    let mut children = SWAYBG_CHILDREN.lock().unwrap_or_else(|e| e.into_inner());
+    for child in children.iter_mut().filter(|c| pids.contains(&c.id())) {
+        let _ = child.wait();
+    }
+    children.retain(|c| !pids.contains(&c.id()));
+}
+
+/// Whether `pid` is a live (not zombie) `process`
+fn is_running(process: &str, pid: u32) -> bool {
+    let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+        return false;
+    };
+    // `pid (comm) state ...`; comm may itself contain spaces or parentheses
+    let Some((head, rest)) = stat.rsplit_once(')') else {
+        return false;
+    };
+    head.split_once('(').is_some_and(|(_, comm)| comm == process) && !rest.trim_start().starts_with('Z')
+}
+
+fn whoami() -> String {
+    std::env::var("USER").unwrap_or_default()
+}
+
+/// X11: feh sets the root window pixmap and exits
+pub struct Feh;
+
+impl WallpaperBackend for Feh {
+    fn name(&self) -> &'static str {
+        "feh"
+    }
+
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()> {
+        let flag = match mode {
+            FillMode::Fill => "--bg-fill",
+            FillMode::Fit => "--bg-max",
+            FillMode::Stretch => "--bg-scale",
+            FillMode::Center => "--bg-center",
+            FillMode::Tile => "--bg-tile",
+        };
+        run(Command::new("feh").args(["--no-fehbg", flag]).arg(image))
+    }
+}
+
+/// X11: xwallpaper, for setups without feh
+pub struct Xwallpaper;
+
+impl WallpaperBackend for Xwallpaper {
+    fn name(&self) -> &'static str {
+        "xwallpaper"
+    }
+
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()> {
+        let flag = match mode {
+            FillMode::Fill => "--zoom",
+            FillMode::Fit => "--maximize",
+            FillMode::Stretch => "--stretch",
+            FillMode::Center => "--center",
+            FillMode::Tile => "--tile",
+        };
+        run(Command::new("xwallpaper").arg(flag).arg(image))
+    }
+}
+
+/// GNOME: point the desktop background settings at the image
+pub struct Gnome;
+
+impl WallpaperBackend for Gnome {
+    fn name(&self) -> &'static str {
+        "gnome"
+    }
+
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()> {
+        let options = match mode {
+            FillMode::Fill => "zoom",
+            FillMode::Fit => "scaled",
+            FillMode::Stretch => "stretched",
+            FillMode::Center => "centered",
+            FillMode::Tile => "wallpaper",
+        };
+        let uri = file_uri(image);
+        for (key, value) in [
+            ("picture-uri", uri.as_str()),
+            ("picture-uri-dark", uri.as_str()),
+            ("picture-options", options),
+        ] {
+            run(Command::new("gsettings").args(["set", "org.gnome.desktop.background", key, value]))?;
+        }
+        Ok(())
+    }
+}
+
+/// KDE Plasma: hand the image to the plasmashell desktop containments
+pub struct Plasma;
+
+impl WallpaperBackend for Plasma {
+    fn name(&self) -> &'static str {
+        "kde"
+    }
+
+    fn show(&self, image: &Path, mode: FillMode) -> Result<()> {
+        let fill = match mode {
+            FillMode::Fill => "preserveAspectCrop",
+            FillMode::Fit => "preserveAspectFit",
+            FillMode::Stretch => "stretch",
+            FillMode::Center => "pad",
+            FillMode::Tile => "tile",
+        };
+        run(Command::new("plasma-apply-wallpaperimage")
+            .args(["--fill-mode", fill])
+            .arg(resolve(image)))
+    }
+}
+
+/// Values `:set backend=` offers: `auto` to detect one, then every name
+pub const CHOICES: &[&str] = &["auto", "swaybg", "swww", "feh", "xwallpaper", "gnome", "kde"];
+
+/// Names `backend` accepts
+pub const NAMES: &[&str] = CHOICES.split_at(1).1;
+
+/// Backend for a config name, if it is one we know
+pub fn by_name(name: &str) -> Option<Box<dyn WallpaperBackend>> {
+    match name.to_lowercase().as_str() {
+        "swaybg" => Some(Box::new(Swaybg::default())),
+        "swww" => Some(Box::new(Swww::default())),
+        "feh" => Some(Box::new(Feh)),
+        "xwallpaper" => Some(Box::new(Xwallpaper)),
+        "gnome" => Some(Box::new(Gnome)),
+        "kde" | "plasma" => Some(Box::new(Plasma)),
+        _ => None,
+    }
+}
+
+/// The backend named in the config, or one picked for the current session
+pub fn for_config(config: &Config) -> Box<dyn WallpaperBackend> {
+    let backend = config.backend.as_deref().and_then(by_name).unwrap_or_else(auto_detect);
+    if backend.name() == "swww" {
+        // Checked when the config was loaded; a bad spec just means swww's default
+        let transition = config.transition.as_deref().and_then(|spec| Transition::parse(spec).ok());
+        return Box::new(Swww { transition });
+    }
+    if backend.name() == "swaybg" {
+        return Box::new(Swaybg { per_output: config.swaybg_per_output });
+    }
+    backend
+}
+
+/// GNOME or Plasma by desktop name, feh or xwallpaper on X11, swww if its daemon is up, swaybg otherwise
+fn auto_detect() -> Box<dyn WallpaperBackend> {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default().to_uppercase();
+    // Colon-separated list, e.g. "ubuntu:GNOME"
+    if desktop.split(':').any(|d| d == "GNOME") {
+        return Box::new(Gnome);
+    }
+    if desktop.split(':').any(|d| d == "KDE") {
+        return Box::new(Plasma);
+    }
+
+    let session = std::env::var("XDG_SESSION_TYPE").unwrap_or_default();
+    if session.eq_ignore_ascii_case("x11") {
+        if !in_path("feh") && in_path("xwallpaper") {
+            return Box::new(Xwallpaper);
+        }
+        return Box::new(Feh);
+    }
+    if !pids("swww-daemon").is_empty() {
+        return Box::new(Swww::default());
+    }
+    Box::new(Swaybg::default())
+}
+
+/// Show the current-background symlink through `config`'s backend
+pub fn reload(config: &Config, mode: FillMode) -> Result<()> {
+    show_with_pins(&*for_config(config), &wallpaper::get_current_background_path(), mode)
+}
+
+/// Show `image` through `backend`, keeping outputs pinned with `:pin` on their own wallpaper
+pub fn show_with_pins(backend: &dyn WallpaperBackend, image: &Path, mode: FillMode) -> Result<()> {
+    let pinned = Pins::load().existing();
+    if pinned.is_empty() || !backend.shows_per_output() {
+        return backend.show(image, mode);
+    }
+    backend.show_pinned(image, &pinned, mode)
+}
+
+/// Follow the current-background symlink. Desktops that cache by path would
+/// otherwise never notice the wallpaper changed.
+fn resolve(image: &Path) -> PathBuf {
+    fs::canonicalize(image).unwrap_or_else(|_| image.to_path_buf())
+}
+
+fn file_uri(image: &Path) -> String {
+    format!("file://{}", resolve(image).display())
+}
+
+/// Run a one-shot setter, turning a non-zero exit into an error
+fn run(command: &mut Command) -> Result<()> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "{} failed: {}",
+            command.get_program().to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn pids(process: &str) -> Vec<String> {
+    pgrep(&["-x", process])
+}
+
+/// `process` instances owned by the user with `uid`
+fn pids_for_user(process: &str, uid: &str) -> Vec<String> {
+    pgrep(&["-x", "-u", uid, process])
+}
+
+fn pgrep(args: &[&str]) -> Vec<String> {
+    Command::new("pgrep")
+        .args(args)
+        .output()
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .split_whitespace()
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether `program` is an executable somewhere on PATH
+pub fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_display_from_an_environment() {
+        assert_eq!(display_in_environ(b"HOME=/home/me\0WAYLAND_DISPLAY=wayland-1\0TERM=foot\0"), "wayland-1");
+        assert_eq!(display_in_environ(b"XWAYLAND_DISPLAY=wayland-2\0HOME=/home/me\0"), DEFAULT_WAYLAND_DISPLAY);
+    }
+}
@@ -0,0 +1,73 @@
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Named directories for `:cd @name`, stored under `$XDG_STATE_HOME`
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(transparent)]
+pub struct Bookmarks {
+    dirs: BTreeMap<String, PathBuf>,
+}
+
+pub fn get_bookmarks_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("bookmarks.json")
+}
+
+impl Bookmarks {
+    /// Load saved bookmarks; a missing or unreadable file means there are none
+    pub fn load() -> Self {
+        fs::read_to_string(get_bookmarks_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_bookmarks_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Point `name` at `dir`, replacing any earlier bookmark of that name
+    pub fn add(&mut self, name: &str, dir: &Path) {
+        self.dirs.insert(name.to_string(), dir.to_path_buf());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        self.dirs
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| eyre!("No bookmark named {}", name))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Path> {
+        self.dirs
+            .get(name)
+            .map(PathBuf::as_path)
+            .ok_or_else(|| eyre!("No bookmark named {}", name))
+    }
+
+    /// Take in another library's bookmarks, replacing ours of the same name
+    pub fn merge(&mut self, other: Bookmarks) {
+        self.dirs.extend(other.dirs);
+    }
+
+    /// Rewrite every bookmarked directory with `map`
+    pub fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        for dir in self.dirs.values_mut() {
+            *dir = map(dir);
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.dirs.keys().map(String::as_str)
+    }
+}
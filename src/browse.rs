@@ -0,0 +1,601 @@
+//! Browsing logic with no terminal or filesystem access of its own, so it can be tested directly
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// A cursor movement in the grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nav {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Where `nav` moves the cursor from `selected` in a grid of `len` items laid out `columns` wide.
+/// Moving down into a partial last row lands on its last item rather than doing nothing.
+pub fn navigate(selected: usize, len: usize, columns: usize, nav: Nav) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let columns = columns.max(1);
+    let selected = selected.min(len - 1);
+    match nav {
+        Nav::Up => selected.checked_sub(columns).unwrap_or(selected),
+        Nav::Down => {
+            let last_row = (len - 1) / columns;
+            if selected / columns == last_row {
+                selected
+            } else {
+                (selected + columns).min(len - 1)
+            }
+        }
+        Nav::Left => selected.saturating_sub(1),
+        Nav::Right => (selected + 1).min(len - 1),
+    }
+}
+
+/// Indices of the texts matching `query` case-insensitively, best matches first and in their
+/// original order otherwise; all of them for an empty query
+pub fn filter(texts: impl Iterator<Item = impl AsRef<str>>, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return texts.enumerate().map(|(i, _)| i).collect();
+    }
+    let mut matches: Vec<(usize, u8)> = texts
+        .enumerate()
+        .filter_map(|(i, text)| Some((i, match_quality(text.as_ref(), query)?)))
+        .collect();
+    // Stable, so equally good matches keep the grid's sort order
+    matches.sort_by_key(|&(_, quality)| std::cmp::Reverse(quality));
+    matches.into_iter().map(|(i, _)| i).collect()
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// How a line matches, best first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Quality {
+    /// Letters in order with gaps between them
+    Fuzzy,
+    Substring,
+    /// Substring starting a word
+    WordStart,
+    Prefix,
+}
+
+/// Quality and char positions of the best match of `query` in one line
+fn match_line(line: &[char], query: &[char]) -> Option<(Quality, Vec<usize>)> {
+    if query.is_empty() || query.len() > line.len() {
+        return None;
+    }
+    let mut best: Option<(Quality, usize)> = None;
+    for start in 0..=line.len() - query.len() {
+        if line[start..start + query.len()] != *query {
+            continue;
+        }
+        let quality = if start == 0 {
+            Quality::Prefix
+        } else if !line[start - 1].is_alphanumeric() {
+            Quality::WordStart
+        } else {
+            Quality::Substring
+        };
+        if best.is_none_or(|(q, _)| quality > q) {
+            best = Some((quality, start));
+        }
+    }
+    if let Some((quality, start)) = best {
+        return Some((quality, (start..start + query.len()).collect()));
+    }
+    let mut positions = Vec::with_capacity(query.len());
+    let mut wanted = query.iter().peekable();
+    for (i, c) in line.iter().enumerate() {
+        if wanted.peek() == Some(&c) {
+            positions.push(i);
+            wanted.next();
+        }
+    }
+    wanted.peek().is_none().then_some((Quality::Fuzzy, positions))
+}
+
+/// How well `text` matches `query`, higher is better, or None if it doesn't.
+/// Each line is matched on its own so a fuzzy match can't span a name and a note.
+pub fn match_quality(text: &str, query: &str) -> Option<u8> {
+    let query: Vec<char> = query.chars().map(fold).collect();
+    text.lines()
+        .filter_map(|line| match_line(&line.chars().map(fold).collect::<Vec<_>>(), &query))
+        .map(|(quality, _)| quality as u8)
+        .max()
+}
+
+/// Char positions in `text` that `query` matched, for highlighting: the substring if it occurs,
+/// otherwise the fuzzily matched letters
+pub fn match_positions(text: &str, query: &str) -> Option<Vec<usize>> {
+    let query: Vec<char> = query.chars().map(fold).collect();
+    match_line(&text.chars().map(fold).collect::<Vec<_>>(), &query).map(|(_, positions)| positions)
+}
+
+/// Order of the grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    #[default]
+    Name,
+    NameDesc,
+    Newest,
+    Oldest,
+    Random,
+    /// Closest to the current theme's palette first
+    Match,
+    /// Most stars first
+    Rating,
+}
+
+/// What a wallpaper is sorted by
+pub struct SortKey<'a> {
+    pub name: &'a str,
+    /// Only read for orders where `uses_mtime` is true
+    pub modified: Option<SystemTime>,
+    /// 0-100 score against the theme palette, once analyzed
+    pub theme_match: Option<f32>,
+    pub stars: Option<u8>,
+}
+
+impl SortOrder {
+    pub const NAMES: &'static [&'static str] = &["name", "name-desc", "newest", "oldest", "random", "match", "rating"];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "name" => Some(SortOrder::Name),
+            "name-desc" => Some(SortOrder::NameDesc),
+            "newest" => Some(SortOrder::Newest),
+            "oldest" => Some(SortOrder::Oldest),
+            "random" => Some(SortOrder::Random),
+            "match" => Some(SortOrder::Match),
+            "rating" => Some(SortOrder::Rating),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::NameDesc => "name-desc",
+            SortOrder::Newest => "newest",
+            SortOrder::Oldest => "oldest",
+            SortOrder::Random => "random",
+            SortOrder::Match => "match",
+            SortOrder::Rating => "rating",
+        }
+    }
+
+    /// Whether sorting needs modification times
+    pub fn uses_mtime(&self) -> bool {
+        matches!(self, SortOrder::Newest | SortOrder::Oldest)
+    }
+}
+
+/// Indices of `entries` (name, modification time) in `order`.
+/// Ties and missing times fall back to name order.
+pub fn sort_order(entries: &[SortKey], order: SortOrder) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..entries.len()).collect();
+    let by_name = |a: &usize, b: &usize| entries[*a].name.cmp(entries[*b].name);
+    let modified = |i: &usize| entries[*i].modified;
+    match order {
+        SortOrder::Name => indices.sort_by(by_name),
+        SortOrder::NameDesc => indices.sort_by(|a, b| by_name(b, a)),
+        SortOrder::Newest => indices.sort_by(|a, b| modified(b).cmp(&modified(a)).then(by_name(a, b))),
+        SortOrder::Oldest => indices.sort_by(|a, b| modified(a).cmp(&modified(b)).then(by_name(a, b))),
+        SortOrder::Random => fastrand::shuffle(&mut indices),
+        // Best match first; wallpapers not analyzed yet go last
+        SortOrder::Match => indices.sort_by(|a, b| {
+            let score = |i: &usize| entries[*i].theme_match.unwrap_or(f32::NEG_INFINITY);
+            score(b).total_cmp(&score(a)).then(by_name(a, b))
+        }),
+        // Unrated wallpapers go last
+        SortOrder::Rating => indices.sort_by(|a, b| entries[*b].stars.cmp(&entries[*a].stars).then(by_name(a, b))),
+    }
+    indices
+}
+
+/// How long the grid takes to glide to a new scroll position
+pub const SCROLL_DURATION: Duration = Duration::from_millis(150);
+
+/// A grid scroll between two row offsets, eased out so it slows as it arrives
+pub struct ScrollAnimation {
+    pub from: usize,
+    pub to: usize,
+    pub started: Instant,
+}
+
+impl ScrollAnimation {
+    /// Row offset to draw now, or None once the animation has finished
+    pub fn current(&self) -> Option<usize> {
+        let t = self.started.elapsed().as_secs_f32() / SCROLL_DURATION.as_secs_f32();
+        (t < 1.0).then(|| eased_offset(self.from, self.to, t))
+    }
+}
+
+/// `text` cut to fit `max_width` terminal columns, ending in `...` when shortened. Measured in
+/// display width, so wide CJK characters and emoji count double and are never split.
+pub fn truncate_label(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let (budget, ellipsis) = if max_width > 3 { (max_width - 3, "...") } else { (max_width, "") };
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > budget {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push_str(ellipsis);
+    out
+}
+
+/// Positions of the cells on screen when the grid is scrolled to `offset_row`
+pub fn visible_range(len: usize, columns: usize, offset_row: usize, visible_rows: usize) -> Range<usize> {
+    let columns = columns.max(1);
+    let start = (offset_row * columns).min(len);
+    start..((offset_row + visible_rows) * columns).min(len)
+}
+
+/// Wallpaper indices whose encoded cells are worth keeping: those on screen plus
+/// `margin_rows` either side, so short scrolls don't re-encode. Everything else is recycled.
+pub fn retained_indices(filtered: &[usize], visible: Range<usize>, columns: usize, margin_rows: usize) -> HashSet<usize> {
+    let margin = margin_rows * columns.max(1);
+    let start = visible.start.saturating_sub(margin);
+    let end = (visible.end + margin).min(filtered.len());
+    filtered.get(start..end).unwrap_or_default().iter().copied().collect()
+}
+
+/// Offset a fraction `t` (0-1) of the way from `from` to `to`, with cubic ease-out
+pub fn eased_offset(from: usize, to: usize, t: f32) -> usize {
+    let t = t.clamp(0.0, 1.0);
+    let eased = 1.0 - (1.0 - t).powi(3);
+    let (from, to) = (from as f32, to as f32);
+    (from + (to - from) * eased).round() as usize
+}
+
+/// Directory listing used by completion, so it can be faked in tests
+pub trait DirSource {
+    /// `(name, is_dir)` for each entry of `dir`; empty if it can't be read
+    fn entries(&self, dir: &Path) -> Vec<(String, bool)>;
+}
+
+/// The real filesystem
+pub struct Fs;
+
+impl DirSource for Fs {
+    fn entries(&self, dir: &Path) -> Vec<(String, bool)> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                // Follow symlinks so linked directories complete like real ones
+                let is_dir = entry.path().is_dir();
+                entry.file_name().to_str().map(|name| (name.to_string(), is_dir))
+            })
+            .collect()
+    }
+}
+
+/// Split a partially typed path into the text of its directory part and the name prefix
+/// being completed: `~/Pic` is (`~/`, `Pic`), `~/Pictures/` is (`~/Pictures/`, ``).
+pub fn split_partial(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(slash) => (&path[..=slash], &path[slash + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Directory to list for a typed directory part, expanding `~` with `home`
+pub fn resolve_dir(dir_part: &str, home: Option<&Path>) -> PathBuf {
+    if dir_part.is_empty() {
+        return PathBuf::from(".");
+    }
+    match (dir_part.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) => PathBuf::from(format!("{}{}", home.to_string_lossy(), rest)),
+        _ => PathBuf::from(dir_part),
+    }
+}
+
+/// Sorted `<prefix_text><dir_part><name>` completions for the subdirectories of `dir` starting
+/// with `prefix`, plus the files `include_file` accepts. Directories keep their trailing `/` so they sort apart and can be descended into.
+pub fn path_completions(
+    source: &impl DirSource,
+    dir: &Path,
+    prefix_text: &str,
+    dir_part: &str,
+    prefix: &str,
+    include_file: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut matches: Vec<String> = source
+        .entries(dir)
+        .into_iter()
+        .filter(|(name, is_dir)| name.starts_with(prefix) && (*is_dir || include_file(name)))
+        .map(|(name, is_dir)| {
+            let slash = if is_dir { "/" } else { "" };
+            format!("{}{}{}{}", prefix_text, dir_part, name, slash)
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Sorted `<prefix_text><name>` completions from a list of names
+pub fn name_completions<'a>(names: impl Iterator<Item = &'a str>, prefix_text: &str, prefix: &str) -> Vec<String> {
+    let mut matches: Vec<String> = names
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| format!("{}{}", prefix_text, name))
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn eased_scroll_reaches_both_ends() {
+        assert_eq!(eased_offset(0, 40, 0.0), 0);
+        assert_eq!(eased_offset(0, 40, 1.0), 40);
+        assert_eq!(eased_offset(40, 0, 1.0), 0);
+        // Ease-out covers most of the distance early
+        assert!(eased_offset(0, 40, 0.5) > 20);
+        assert!(eased_offset(40, 0, 0.5) < 20);
+    }
+
+    #[test]
+    fn truncate_label_keeps_short_names() {
+        assert_eq!(truncate_label("sea", 10), "sea");
+        assert_eq!(truncate_label("mountains", 6), "mou...");
+        assert_eq!(truncate_label("mountains", 2), "mo");
+    }
+
+    #[test]
+    fn truncate_label_measures_wide_characters() {
+        // Each of these takes two columns
+        assert_eq!(truncate_label("東京の夜景", 10), "東京の夜景");
+        assert_eq!(truncate_label("東京の夜景", 8), "東京...");
+        // A wide character that would straddle the limit is left out, not split
+        assert_eq!(truncate_label("東京の夜景", 9), "東京の...");
+        assert_eq!(truncate_label("🌄🌄🌄🌄", 6), "🌄...");
+        assert!(truncate_label("夜景夜景夜景", 7).width() <= 7);
+    }
+
+    #[test]
+    fn visible_range_clips_to_the_list() {
+        assert_eq!(visible_range(10, 4, 0, 2), 0..8);
+        assert_eq!(visible_range(10, 4, 2, 2), 8..10);
+        assert_eq!(visible_range(0, 4, 3, 2), 0..0);
+    }
+
+    #[test]
+    fn retained_indices_follow_the_window() {
+        let filtered: Vec<usize> = (0..100).rev().collect();
+        let keep = retained_indices(&filtered, 40..48, 4, 1);
+        assert_eq!(keep.len(), 16);
+        assert!(keep.contains(&(99 - 36)) && keep.contains(&(99 - 51)));
+        assert!(!keep.contains(&(99 - 35)));
+    }
+
+    #[test]
+    fn scrolling_a_large_library_keeps_a_bounded_cache() {
+        // Per-frame grid bookkeeping at 10k items: however far it scrolls, only the visible
+        // rows and a screen's margin either side are retained
+        let filtered: Vec<usize> = (0..10_000).collect();
+        let (columns, rows) = (8, 6);
+        let bound = columns * rows * 3;
+        let mut cache: HashMap<usize, [u8; 64]> = HashMap::new();
+        for offset in 0..filtered.len().div_ceil(columns) {
+            let visible = visible_range(filtered.len(), columns, offset, rows);
+            let keep = retained_indices(&filtered, visible.clone(), columns, rows);
+            assert!(keep.len() <= bound, "{} retained at row {}", keep.len(), offset);
+            assert!(filtered[visible.clone()].iter().all(|index| keep.contains(index)));
+            cache.retain(|index, _| keep.contains(index));
+            for &index in &filtered[visible] {
+                cache.entry(index).or_insert([0; 64]);
+            }
+            assert!(cache.len() <= bound);
+        }
+    }
+
+    #[test]
+    fn navigate_empty_grid_stays_at_zero() {
+        for nav in [Nav::Up, Nav::Down, Nav::Left, Nav::Right] {
+            assert_eq!(navigate(0, 0, 4, nav), 0);
+        }
+    }
+
+    #[test]
+    fn navigate_stops_at_edges() {
+        assert_eq!(navigate(0, 10, 4, Nav::Up), 0);
+        assert_eq!(navigate(0, 10, 4, Nav::Left), 0);
+        assert_eq!(navigate(9, 10, 4, Nav::Right), 9);
+        assert_eq!(navigate(9, 10, 4, Nav::Down), 9);
+    }
+
+    #[test]
+    fn navigate_down_into_partial_last_row_lands_on_last_item() {
+        // Rows: 0-3, 4-7, 8-9
+        assert_eq!(navigate(7, 10, 4, Nav::Down), 9);
+        assert_eq!(navigate(5, 10, 4, Nav::Down), 9);
+        assert_eq!(navigate(4, 10, 4, Nav::Down), 8);
+    }
+
+    #[test]
+    fn navigate_up_from_partial_last_row() {
+        assert_eq!(navigate(9, 10, 4, Nav::Up), 5);
+    }
+
+    #[test]
+    fn navigate_right_wraps_to_next_row() {
+        assert_eq!(navigate(3, 10, 4, Nav::Right), 4);
+    }
+
+    #[test]
+    fn navigate_clamps_stale_selection() {
+        // Selection left over from a longer list
+        assert_eq!(navigate(20, 10, 4, Nav::Left), 8);
+    }
+
+    #[test]
+    fn navigate_single_column() {
+        assert_eq!(navigate(0, 3, 1, Nav::Down), 1);
+        assert_eq!(navigate(2, 3, 0, Nav::Up), 1);
+    }
+
+    #[test]
+    fn filter_empty_query_keeps_everything() {
+        let names = ["a", "b", "c"];
+        assert_eq!(filter(names.into_iter(), ""), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn filter_is_case_insensitive() {
+        let names = ["Mountain", "sea", "MOUNT"];
+        assert_eq!(filter(names.into_iter(), "mount"), vec![0, 2]);
+    }
+
+    #[test]
+    fn filter_ranks_by_match_quality() {
+        let names = ["nightsky", "desk", "sky", "forest-sky", "seaky"];
+        // Prefix, then word start, then substring, then fuzzy
+        assert_eq!(filter(names.into_iter(), "sky"), vec![2, 3, 0, 4]);
+    }
+
+    #[test]
+    fn fuzzy_matches_stay_within_a_line() {
+        assert!(match_quality("sea\nky", "sky").is_none());
+        assert_eq!(match_positions("Starry_Key", "sky"), Some(vec![0, 7, 9]));
+        assert_eq!(match_positions("Misty", "ST"), Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn filter_with_no_matches_is_empty() {
+        let names = ["a", "b"];
+        assert!(filter(names.into_iter(), "zzz").is_empty());
+    }
+
+    #[test]
+    fn sort_by_name_and_time() {
+        let key = |name, secs| SortKey {
+            name,
+            modified: Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs)),
+            theme_match: None,
+            stars: None,
+        };
+        let entries = [key("b", 1), key("a", 3), key("c", 2)];
+        assert_eq!(sort_order(&entries, SortOrder::Name), vec![1, 0, 2]);
+        assert_eq!(sort_order(&entries, SortOrder::NameDesc), vec![2, 0, 1]);
+        assert_eq!(sort_order(&entries, SortOrder::Newest), vec![1, 2, 0]);
+        assert_eq!(sort_order(&entries, SortOrder::Oldest), vec![0, 2, 1]);
+
+        let mut random = sort_order(&entries, SortOrder::Random);
+        random.sort();
+        assert_eq!(random, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn sort_by_match_puts_unscored_last() {
+        let key = |name, theme_match| SortKey { name, modified: None, theme_match, stars: None };
+        let entries = [key("a", None), key("b", Some(40.0)), key("c", Some(90.0))];
+        assert_eq!(sort_order(&entries, SortOrder::Match), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn sort_by_rating_puts_unrated_last() {
+        let key = |name, stars| SortKey { name, modified: None, theme_match: None, stars };
+        let entries = [key("a", None), key("b", Some(3)), key("c", Some(5)), key("d", Some(3))];
+        assert_eq!(sort_order(&entries, SortOrder::Rating), vec![2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn sort_order_names_round_trip() {
+        for name in SortOrder::NAMES {
+            assert_eq!(SortOrder::parse(name).unwrap().as_str(), *name);
+        }
+    }
+
+    struct FakeFs(HashMap<PathBuf, Vec<(&'static str, bool)>>);
+
+    impl DirSource for FakeFs {
+        fn entries(&self, dir: &Path) -> Vec<(String, bool)> {
+            self.0
+                .get(dir)
+                .map(|entries| entries.iter().map(|(n, d)| (n.to_string(), *d)).collect())
+                .unwrap_or_default()
+        }
+    }
+
+    fn fake_fs() -> FakeFs {
+        FakeFs(HashMap::from([(
+            PathBuf::from("/w/"),
+            vec![
+                ("space", true),
+                ("nature", true),
+                ("sea", true),
+                ("sunset.png", false),
+                ("notes.txt", false),
+            ],
+        )]))
+    }
+
+    #[test]
+    fn split_partial_paths() {
+        assert_eq!(split_partial("~/Pic"), ("~/", "Pic"));
+        assert_eq!(split_partial("~/Pictures/"), ("~/Pictures/", ""));
+        assert_eq!(split_partial("Pic"), ("", "Pic"));
+    }
+
+    #[test]
+    fn resolve_dir_expands_home() {
+        let home = Path::new("/home/me");
+        assert_eq!(resolve_dir("~/a/", Some(home)), PathBuf::from("/home/me/a/"));
+        assert_eq!(resolve_dir("", Some(home)), PathBuf::from("."));
+        assert_eq!(resolve_dir("/tmp/", None), PathBuf::from("/tmp/"));
+    }
+
+    #[test]
+    fn dir_completions_filter_and_sort() {
+        let fs = fake_fs();
+        let matches = path_completions(&fs, Path::new("/w/"), "cd ", "/w/", "s", |_| false);
+        assert_eq!(matches, vec!["cd /w/sea/", "cd /w/space/"]);
+        assert!(path_completions(&fs, Path::new("/missing/"), "cd ", "/missing/", "", |_| false).is_empty());
+    }
+
+    #[test]
+    fn path_completions_include_accepted_files() {
+        let fs = fake_fs();
+        let matches = path_completions(&fs, Path::new("/w/"), "import ", "/w/", "s", |name| name.ends_with(".png"));
+        assert_eq!(matches, vec!["import /w/sea/", "import /w/space/", "import /w/sunset.png"]);
+        let matches = path_completions(&fs, Path::new("/w/"), "", "/w/", "n", |name| name.ends_with(".png"));
+        assert_eq!(matches, vec!["/w/nature/"]);
+    }
+
+    #[test]
+    fn name_completions_filter_sort_and_dedup() {
+        let names = ["b.png", "a.png", "b.png", "c.jpg"];
+        assert_eq!(
+            name_completions(names.into_iter(), "select ", "b"),
+            vec!["select b.png"]
+        );
+        assert_eq!(name_completions(names.into_iter(), "", "").len(), 3);
+    }
+}
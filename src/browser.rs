@@ -0,0 +1,434 @@
+//! The grid cursor, search query and command prompt as a state machine: `Browser::update`
+//! takes an input and returns what its owner has to do next, touching nothing else, so key
+//! handling can be tested without a terminal or filesystem
+
+use crate::browse::{self, DirSource, Nav};
+use crate::commands::{self, ArgCompletion};
+use crate::groups::{self, Group};
+use crate::{tags, wallpaper};
+use std::path::{Path, PathBuf};
+
+/// Something the user did to the grid, the search bar or the command prompt
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Move(Nav),
+    /// `[` / `]`: the start of the previous (-1) or next (1) group
+    JumpGroup(isize),
+    SearchChar(char),
+    SearchBackspace,
+    SearchConfirm,
+    SearchCancel,
+    /// The prompt was opened; it starts out empty
+    CommandOpen,
+    CommandChar(char),
+    CommandBackspace,
+    /// Tab: complete the command name or argument, or cycle a list already showing
+    CommandComplete,
+    CompletionNext,
+    CompletionPrev,
+    CommandConfirm,
+    CommandCancel,
+}
+
+/// What the owner of a `Browser` has to carry out after an update, in order
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    /// The search query changed, so `filtered_indices` and `groups` need recomputing
+    Refilter,
+    CloseSearch,
+    CloseCommand,
+    /// A command line submitted from the prompt
+    RunCommand(String),
+}
+
+/// Everything command completion looks up besides the command table, so it can be faked
+pub trait CompletionSource: DirSource {
+    /// What a leading `~` stands for
+    fn home(&self) -> Option<PathBuf>;
+    /// Bookmark names, completed after `:cd @`
+    fn bookmarks(&self) -> Vec<String>;
+    /// Names of the connected outputs
+    fn outputs(&self) -> Vec<String>;
+    /// File names of the images in the current view
+    fn images(&self) -> Vec<String>;
+    /// Collection names, completed as `#tag`
+    fn collections(&self) -> Vec<String>;
+}
+
+/// Cursor, search and prompt state. The owner fills in `filtered_indices` and `groups`
+/// when asked to refilter, and `columns` as the grid is laid out.
+#[derive(Default)]
+pub struct Browser {
+    /// Positions in the owner's wallpaper list that pass the filters, in grid order
+    pub filtered_indices: Vec<usize>,
+    /// Sections of `filtered_indices`, empty when not grouping
+    pub groups: Vec<Group>,
+    /// Cursor, as a position in `filtered_indices`
+    pub selected: usize,
+    pub columns: usize,
+    pub search_query: String,
+    pub command_query: String,
+    pub completions: Vec<String>,
+    pub completion_index: usize,
+}
+
+impl Browser {
+    pub fn new(columns: usize) -> Self {
+        Self { columns, ..Self::default() }
+    }
+
+    pub fn update(&mut self, input: Input, source: &impl CompletionSource) -> Vec<Effect> {
+        match input {
+            Input::Move(nav) => {
+                self.selected = if self.groups.is_empty() {
+                    browse::navigate(self.selected, self.filtered_indices.len(), self.columns, nav)
+                } else {
+                    groups::navigate(self.selected, &self.groups, self.columns, nav)
+                };
+            }
+            Input::JumpGroup(step) => self.selected = groups::jump(self.selected, &self.groups, step),
+            Input::SearchChar(c) => {
+                self.search_query.push(c);
+                return vec![Effect::Refilter];
+            }
+            Input::SearchBackspace => {
+                self.search_query.pop();
+                return vec![Effect::Refilter];
+            }
+            Input::SearchConfirm => return vec![Effect::CloseSearch],
+            Input::SearchCancel => {
+                self.search_query.clear();
+                return vec![Effect::Refilter, Effect::CloseSearch];
+            }
+            Input::CommandOpen => {
+                self.command_query.clear();
+                self.completions.clear();
+            }
+            Input::CommandChar(c) => {
+                self.command_query.push(c);
+                self.completions.clear();
+            }
+            Input::CommandBackspace => {
+                self.command_query.pop();
+                self.completions.clear();
+            }
+            Input::CommandComplete => self.complete(source),
+            Input::CompletionNext => self.step_completion(1),
+            Input::CompletionPrev => self.step_completion(-1),
+            Input::CommandConfirm => {
+                let command = std::mem::take(&mut self.command_query);
+                return vec![Effect::CloseCommand, Effect::RunCommand(command.trim().to_string())];
+            }
+            Input::CommandCancel => {
+                self.command_query.clear();
+                return vec![Effect::CloseCommand];
+            }
+        }
+        Vec::new()
+    }
+
+    /// Complete the command name, or its argument as its spec describes. Completing again
+    /// while a list is showing cycles through it.
+    fn complete(&mut self, source: &impl CompletionSource) {
+        if self.completions.len() > 1 && self.completions.get(self.completion_index) == Some(&self.command_query) {
+            self.step_completion(1);
+            return;
+        }
+
+        let query = self.command_query.clone();
+        let Some((name, arg)) = query.split_once(' ') else {
+            let names = commands::complete_name(&query).into_iter().map(|n| format!("{} ", n)).collect();
+            self.offer_completions(names);
+            return;
+        };
+        let Some(spec) = commands::find(name) else {
+            return;
+        };
+        let prefix_text = format!("{} ", name);
+
+        match spec.completion {
+            // `:cd @` completes bookmark names
+            ArgCompletion::Dirs if arg.starts_with('@') => {
+                let bookmarks = source.bookmarks();
+                let prefix_text = format!("{} @", name);
+                let matches = browse::name_completions(bookmarks.iter().map(String::as_str), &prefix_text, &arg[1..]);
+                self.offer_completions(matches);
+            }
+            ArgCompletion::Dirs | ArgCompletion::Files => {
+                let with_files = matches!(spec.completion, ArgCompletion::Files);
+                let home = source.home();
+                let list = |dir: &Path, dir_part: &str, prefix: &str| {
+                    browse::path_completions(source, dir, &prefix_text, dir_part, prefix, |file| {
+                        with_files && wallpaper::is_image(Path::new(file))
+                    })
+                };
+
+                let (dir_part, prefix) = browse::split_partial(arg);
+                let search_dir = browse::resolve_dir(dir_part, home.as_deref());
+                let matches = list(&search_dir, dir_part, prefix);
+                if let [only] = matches.as_slice()
+                    && only.ends_with('/')
+                {
+                    // Unique directory: complete it, then immediately list what's inside.
+                    // The query stays at the completed dir but the sub-options are shown.
+                    let next_path = only[prefix_text.len()..].to_string();
+                    let next_dir = browse::resolve_dir(&next_path, home.as_deref());
+                    self.completions = list(&next_dir, &next_path, "");
+                    self.completion_index = 0;
+                    self.command_query = only.clone();
+                } else {
+                    self.offer_completions(matches);
+                }
+            }
+            ArgCompletion::Settings => {
+                let matches = commands::setting_completions(arg)
+                    .into_iter()
+                    .map(|setting| format!("{}{}", prefix_text, setting))
+                    .collect();
+                self.offer_completions(matches);
+            }
+            ArgCompletion::Outputs => {
+                let outputs = source.outputs();
+                let matches = browse::name_completions(outputs.iter().map(String::as_str), &prefix_text, arg);
+                self.offer_completions(matches);
+            }
+            ArgCompletion::ViewImages => {
+                let images = source.images();
+                let matches = browse::name_completions(images.iter().map(String::as_str), &prefix_text, arg);
+                self.offer_completions(matches);
+            }
+            ArgCompletion::Tags(_) if arg.contains('#') => {
+                let collections = source.collections();
+                let matches = tags::tag_completions(arg, collections.iter().map(String::as_str))
+                    .into_iter()
+                    .map(|arg| format!("{}{}", prefix_text, arg))
+                    .collect();
+                self.offer_completions(matches);
+            }
+            ArgCompletion::Values(values) | ArgCompletion::Tags(values) => {
+                // Complete the last word, keeping earlier arguments
+                let (before, word) = match query.rfind(' ') {
+                    Some(space) => query.split_at(space + 1),
+                    None => (query.as_str(), ""),
+                };
+                let matches = values
+                    .iter()
+                    .filter(|v| v.starts_with(word))
+                    .map(|v| format!("{}{}", before, v))
+                    .collect();
+                self.offer_completions(matches);
+            }
+            ArgCompletion::None => {}
+        }
+    }
+
+    /// Fill in the first candidate and list them all, or just complete a unique one
+    fn offer_completions(&mut self, matches: Vec<String>) {
+        match matches.as_slice() {
+            [] => {}
+            [only] => {
+                self.command_query = only.clone();
+                self.completions.clear();
+            }
+            _ => {
+                self.completions = matches;
+                self.completion_index = 0;
+                self.command_query = self.completions[0].clone();
+            }
+        }
+    }
+
+    /// Move through the completion list by `step`, wrapping at either end
+    fn step_completion(&mut self, step: isize) {
+        if self.completions.is_empty() {
+            return;
+        }
+        let len = self.completions.len() as isize;
+        self.completion_index = (self.completion_index as isize + step).rem_euclid(len) as usize;
+        self.command_query = self.completions[self.completion_index].clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct FakeSource {
+        dirs: HashMap<PathBuf, Vec<(&'static str, bool)>>,
+        bookmarks: Vec<String>,
+        outputs: Vec<String>,
+    }
+
+    impl DirSource for FakeSource {
+        fn entries(&self, dir: &Path) -> Vec<(String, bool)> {
+            self.dirs
+                .get(dir)
+                .map(|entries| entries.iter().map(|(n, d)| (n.to_string(), *d)).collect())
+                .unwrap_or_default()
+        }
+    }
+
+    impl CompletionSource for FakeSource {
+        fn home(&self) -> Option<PathBuf> {
+            Some(PathBuf::from("/home/u"))
+        }
+        fn bookmarks(&self) -> Vec<String> {
+            self.bookmarks.clone()
+        }
+        fn outputs(&self) -> Vec<String> {
+            self.outputs.clone()
+        }
+        fn images(&self) -> Vec<String> {
+            vec!["dune.png".to_string(), "forest.jpg".to_string()]
+        }
+        fn collections(&self) -> Vec<String> {
+            vec!["favs".to_string(), "forest".to_string()]
+        }
+    }
+
+    fn grid(len: usize, columns: usize) -> Browser {
+        Browser { filtered_indices: (0..len).collect(), ..Browser::new(columns) }
+    }
+
+    fn typed(browser: &mut Browser, text: &str) {
+        for c in text.chars() {
+            browser.update(Input::CommandChar(c), &FakeSource::default());
+        }
+    }
+
+    #[test]
+    fn moving_down_into_a_partial_last_row_lands_on_its_last_item() {
+        let mut browser = grid(10, 4);
+        browser.selected = 6;
+        assert!(browser.update(Input::Move(Nav::Down), &FakeSource::default()).is_empty());
+        assert_eq!(browser.selected, 9);
+        browser.update(Input::Move(Nav::Down), &FakeSource::default());
+        assert_eq!(browser.selected, 9);
+    }
+
+    #[test]
+    fn moving_in_an_empty_filter_stays_put() {
+        let mut browser = grid(0, 4);
+        for nav in [Nav::Up, Nav::Down, Nav::Left, Nav::Right] {
+            browser.update(Input::Move(nav), &FakeSource::default());
+            assert_eq!(browser.selected, 0);
+        }
+    }
+
+    #[test]
+    fn moving_follows_groups() {
+        let mut browser = grid(6, 4);
+        browser.groups = vec![
+            Group { label: "a".to_string(), range: 0..2 },
+            Group { label: "b".to_string(), range: 2..6 },
+        ];
+        browser.update(Input::Move(Nav::Down), &FakeSource::default());
+        assert_eq!(browser.selected, 2);
+        browser.update(Input::JumpGroup(-1), &FakeSource::default());
+        assert_eq!(browser.selected, 0);
+    }
+
+    #[test]
+    fn search_edits_ask_for_a_refilter() {
+        let mut browser = grid(3, 4);
+        let source = FakeSource::default();
+        assert_eq!(browser.update(Input::SearchChar('s'), &source), [Effect::Refilter]);
+        assert_eq!(browser.update(Input::SearchChar('e'), &source), [Effect::Refilter]);
+        assert_eq!(browser.update(Input::SearchBackspace, &source), [Effect::Refilter]);
+        assert_eq!(browser.search_query, "s");
+        assert_eq!(browser.update(Input::SearchConfirm, &source), [Effect::CloseSearch]);
+        assert_eq!(browser.search_query, "s");
+        assert_eq!(browser.update(Input::SearchCancel, &source), [Effect::Refilter, Effect::CloseSearch]);
+        assert!(browser.search_query.is_empty());
+    }
+
+    #[test]
+    fn confirming_runs_the_trimmed_command_and_empties_the_prompt() {
+        let mut browser = Browser::default();
+        typed(&mut browser, " sort name ");
+        let effects = browser.update(Input::CommandConfirm, &FakeSource::default());
+        assert_eq!(effects, [Effect::CloseCommand, Effect::RunCommand("sort name".to_string())]);
+        assert!(browser.command_query.is_empty());
+    }
+
+    #[test]
+    fn cancelling_drops_the_command() {
+        let mut browser = Browser::default();
+        typed(&mut browser, "sort");
+        assert_eq!(browser.update(Input::CommandCancel, &FakeSource::default()), [Effect::CloseCommand]);
+        assert!(browser.command_query.is_empty());
+    }
+
+    #[test]
+    fn unique_command_names_complete_with_a_space() {
+        let mut browser = Browser::default();
+        typed(&mut browser, "bookm");
+        browser.update(Input::CommandComplete, &FakeSource::default());
+        assert_eq!(browser.command_query, "bookmark ");
+        assert!(browser.completions.is_empty());
+    }
+
+    #[test]
+    fn completing_again_cycles_the_list() {
+        let source = FakeSource { outputs: vec!["DP-1".to_string(), "DP-2".to_string()], ..FakeSource::default() };
+        let mut browser = Browser::default();
+        typed(&mut browser, "pin D");
+        browser.update(Input::CommandComplete, &source);
+        assert_eq!(browser.completions, ["pin DP-1", "pin DP-2"]);
+        assert_eq!(browser.command_query, "pin DP-1");
+        browser.update(Input::CommandComplete, &source);
+        assert_eq!(browser.command_query, "pin DP-2");
+        browser.update(Input::CompletionNext, &source);
+        assert_eq!(browser.command_query, "pin DP-1");
+        browser.update(Input::CompletionPrev, &source);
+        assert_eq!(browser.command_query, "pin DP-2");
+    }
+
+    #[test]
+    fn typing_clears_the_completion_list() {
+        let source = FakeSource { outputs: vec!["DP-1".to_string(), "DP-2".to_string()], ..FakeSource::default() };
+        let mut browser = Browser::default();
+        typed(&mut browser, "pin ");
+        browser.update(Input::CommandComplete, &source);
+        assert_eq!(browser.completions.len(), 2);
+        browser.update(Input::CommandChar('x'), &source);
+        assert!(browser.completions.is_empty());
+    }
+
+    #[test]
+    fn a_unique_directory_completes_and_lists_its_contents() {
+        let source = FakeSource {
+            dirs: HashMap::from([
+                (PathBuf::from("/home/u/"), vec![("walls", true), ("notes.txt", false)]),
+                (PathBuf::from("/home/u/walls/"), vec![("space", true), ("sea", true)]),
+            ]),
+            ..FakeSource::default()
+        };
+        let mut browser = Browser::default();
+        typed(&mut browser, "cd ~/w");
+        browser.update(Input::CommandComplete, &source);
+        assert_eq!(browser.command_query, "cd ~/walls/");
+        assert_eq!(browser.completions, ["cd ~/walls/sea/", "cd ~/walls/space/"]);
+    }
+
+    #[test]
+    fn cd_at_completes_bookmarks() {
+        let source = FakeSource { bookmarks: vec!["work".to_string(), "walls".to_string()], ..FakeSource::default() };
+        let mut browser = Browser::default();
+        typed(&mut browser, "cd @wo");
+        browser.update(Input::CommandComplete, &source);
+        assert_eq!(browser.command_query, "cd @work");
+    }
+
+    #[test]
+    fn opening_the_prompt_starts_empty() {
+        let mut browser = Browser::default();
+        typed(&mut browser, "pin ");
+        browser.completions = vec!["a".to_string(), "b".to_string()];
+        browser.update(Input::CommandOpen, &FakeSource::default());
+        assert!(browser.command_query.is_empty());
+        assert!(browser.completions.is_empty());
+    }
+}
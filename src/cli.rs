@@ -0,0 +1,147 @@
+use clap::{value_parser, Arg, ArgAction, ArgGroup, Command, ValueHint};
+use clap_complete::Shell;
+use color_eyre::Result;
+use std::io;
+
+/// Command-line interface; also the source for `--completions` and `--man`
+pub fn command() -> Command {
+    Command::new("omarchy-wallpaper-picker")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Browse and apply wallpapers for omarchy from the terminal")
+        .arg(flag("offline", "Disable every network source"))
+        .arg(flag("screen-reader", "Leave out images and describe each change on one line for screen readers"))
+        .arg(flag("no-color", "Draw without colors, marking states with symbols and bold or reversed text"))
+        .arg(
+            Arg::new("backgrounds-dir")
+                .long("backgrounds-dir")
+                .value_name("DIR")
+                .value_hint(ValueHint::DirPath)
+                .help("Browse DIR instead of the current theme's backgrounds"),
+        )
+        .arg(
+            Arg::new("current-link")
+                .long("current-link")
+                .value_name("PATH")
+                .value_hint(ValueHint::FilePath)
+                .help("Point PATH at applied wallpapers instead of omarchy's current background"),
+        )
+        .arg(flag("daemon", "Run in the background, keeping the wallpaper set and answering --next/--prev"))
+        .arg(
+            flag(
+                "apply-current",
+                "Show the current wallpaper again, with monitors pinned by :pin on their own, and exit; for login and compositor restarts. A running instance does it instead",
+            )
+            .visible_alias("restore"),
+        )
+        .arg(flag("install-service", "Write and enable a systemd user unit that runs --apply-current at login, and exit"))
+        .arg(flag("next", "Switch to the next wallpaper without opening the picker"))
+        .arg(flag("prev", "Switch to the previous wallpaper without opening the picker"))
+        .arg(flag("random", "Switch to a random wallpaper without opening the picker"))
+        .arg(
+            Arg::new("set")
+                .long("set")
+                .value_name("PATH")
+                .value_hint(ValueHint::FilePath)
+                .help("Apply PATH without opening the picker, through the running instance if there is one"),
+        )
+        .arg(
+            Arg::new("generate-thumbs")
+                .long("generate-thumbs")
+                .value_name("DIR")
+                .num_args(0..=1)
+                .value_hint(ValueHint::DirPath)
+                .help("Write thumbnails for the library (or DIR) and exit, e.g. from cron"),
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .value_name("DIR")
+                .num_args(0..=1)
+                .value_hint(ValueHint::DirPath)
+                .help("Print the library's (or DIR's) wallpapers and exit, one path per line"),
+        )
+        .arg(flag("json", "With --list, print path, name, size, current flag and tags as JSON").requires("list"))
+        .arg(
+            Arg::new("min-size")
+                .long("min-size")
+                .value_name("WxH")
+                .requires("list")
+                .help("With --list, only wallpapers at least WxH, e.g. 2560x1440"),
+        )
+        .arg(
+            Arg::new("tag")
+                .long("tag")
+                .value_name("NAME")
+                .requires("list")
+                .help("With --list, only wallpapers in the collection NAME"),
+        )
+        .arg(
+            Arg::new("min-stars")
+                .long("min-stars")
+                .value_name("N")
+                .value_parser(value_parser!(u8).range(1..=5))
+                .requires("list")
+                .help("With --list, only wallpapers rated N stars or more"),
+        )
+        .arg(flag("by-theme", "With --list, order by how well the colors match the current theme").requires("list"))
+        .arg(
+            Arg::new("dmenu")
+                .long("dmenu")
+                .value_name("COMMAND")
+                .num_args(0..=1)
+                .value_hint(ValueHint::CommandString)
+                .help("Pick by name in a text menu: print names and apply the line read back, or run COMMAND (e.g. 'fuzzel --dmenu') as the menu"),
+        )
+        .arg(flag("profile", "Print discovery, decode, encode and frame timings on exit"))
+        .arg(
+            Arg::new("completions")
+                .long("completions")
+                .value_name("SHELL")
+                .value_parser(value_parser!(Shell))
+                .help("Print a completion script for SHELL and exit"),
+        )
+        .arg(flag("man", "Print the man page and exit"))
+        .group(
+            ArgGroup::new("mode")
+                .args(["daemon", "apply-current", "install-service", "next", "prev", "random", "set", "generate-thumbs", "list", "dmenu", "profile", "completions", "man"])
+                .multiple(false),
+        )
+}
+
+fn flag(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name).long(name).action(ArgAction::SetTrue).help(help)
+}
+
+/// `--completions <shell>`
+pub fn print_completions(shell: Shell) {
+    clap_complete::generate(shell, &mut command(), "omarchy-wallpaper-picker", &mut io::stdout());
+}
+
+/// `--man`: roff source for omarchy-wallpaper-picker(1)
+pub fn print_man() -> Result<()> {
+    clap_mangen::Man::new(command()).render(&mut io::stdout())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_is_consistent() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn modes_are_exclusive() {
+        assert!(command().try_get_matches_from(["picker", "--next", "--offline"]).is_ok());
+        assert!(command().try_get_matches_from(["picker", "--next", "--daemon"]).is_err());
+        let matches = command().try_get_matches_from(["picker", "--generate-thumbs"]).unwrap();
+        assert!(matches.contains_id("generate-thumbs"));
+        assert_eq!(matches.get_one::<String>("generate-thumbs"), None);
+        assert!(command().try_get_matches_from(["picker", "--tag", "dark"]).is_err());
+        assert!(command().try_get_matches_from(["picker", "--list", "--min-size", "1920x1080", "--by-theme"]).is_ok());
+        let matches = command().try_get_matches_from(["picker", "--restore"]).unwrap();
+        assert!(matches.get_flag("apply-current"));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::backend;
+use color_eyre::{eyre::eyre, Result};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Put `text` on the clipboard as `mime` through wl-copy on Wayland, or xclip on X11
+pub fn copy(text: &str, mime: &str) -> Result<()> {
+    let mut command = if std::env::var_os("WAYLAND_DISPLAY").is_some() && backend::in_path("wl-copy") {
+        let mut command = Command::new("wl-copy");
+        command.args(["--type", mime]);
+        command
+    } else if backend::in_path("xclip") {
+        let mut command = Command::new("xclip");
+        command.args(["-selection", "clipboard", "-t", mime]);
+        command
+    } else {
+        return Err(eyre!("Copying needs wl-copy (wl-clipboard) or xclip"));
+    };
+    // Both fork to serve the selection once stdin closes, so waiting doesn't block
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    if !child.wait()?.success() {
+        return Err(eyre!("{} failed", command.get_program().to_string_lossy()));
+    }
+    Ok(())
+}
+
+/// `file://` URI for `path`, percent-encoding everything but unreserved characters and `/`
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_are_percent_encoded() {
+        assert_eq!(file_uri(Path::new("/home/me/a b/ñ#1.png")), "file:///home/me/a%20b/%C3%B1%231.png");
+    }
+}
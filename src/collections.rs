@@ -0,0 +1,150 @@
+use crate::index::Index;
+use crate::ospath;
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Named sets of wallpapers from any directory, stored under `$XDG_DATA_HOME`
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(transparent)]
+pub struct Collections {
+    #[serde(with = "ospath::seq_values")]
+    sets: BTreeMap<String, Vec<PathBuf>>,
+}
+
+pub fn get_collections_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/share"))
+        .join("omarchy-wallpaper-picker")
+        .join("collections.json")
+}
+
+impl Collections {
+    /// Load saved collections; a missing file means there are none yet
+    pub fn load() -> Result<Self> {
+        let path = get_collections_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)?;
+        serde_json::from_str(&contents).map_err(|e| eyre!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_collections_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        // The index only mirrors the file; it catches up at the next `--list` query if this fails
+        if let Ok(mut index) = Index::open() {
+            let _ = index.sync_tags(self);
+        }
+        Ok(())
+    }
+
+    /// Add `path` to `name`, creating the collection if needed
+    pub fn add(&mut self, name: &str, path: &Path) {
+        let set = self.sets.entry(name.to_string()).or_default();
+        if !set.iter().any(|p| p == path) {
+            set.push(path.to_path_buf());
+        }
+    }
+
+    /// Remove `path` from `name`, dropping the collection once it is empty
+    pub fn remove(&mut self, name: &str, path: &Path) -> Result<()> {
+        let set = self
+            .sets
+            .get_mut(name)
+            .ok_or_else(|| eyre!("No collection named {}", name))?;
+        set.retain(|p| p != path);
+        if set.is_empty() {
+            self.sets.remove(name);
+        }
+        Ok(())
+    }
+
+    /// Point every collection holding `old` at `new` instead. Returns true if any did.
+    pub fn rename(&mut self, old: &Path, new: &Path) -> bool {
+        let mut renamed = false;
+        for set in self.sets.values_mut() {
+            if let Some(pos) = set.iter().position(|p| p == old) {
+                if set.iter().any(|p| p == new) {
+                    set.remove(pos);
+                } else {
+                    set[pos] = new.to_path_buf();
+                }
+                renamed = true;
+            }
+        }
+        renamed
+    }
+
+    /// Add every member of another library's collections
+    pub fn merge(&mut self, other: Collections) {
+        for (name, paths) in other.sets {
+            for path in paths {
+                self.add(&name, &path);
+            }
+        }
+    }
+
+    /// Rewrite every member path with `map`
+    pub fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        for paths in self.sets.values_mut() {
+            for path in paths.iter_mut() {
+                *path = map(path);
+            }
+        }
+    }
+
+    /// Every collection with its members
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[PathBuf])> {
+        self.sets.iter().map(|(name, paths)| (name.as_str(), paths.as_slice()))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sets.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, name: &str, path: &Path) -> bool {
+        self.sets.get(name).is_some_and(|paths| paths.iter().any(|p| p == path))
+    }
+
+    /// Names of the collections `path` belongs to
+    pub fn containing(&self, path: &Path) -> Vec<String> {
+        self.sets
+            .iter()
+            .filter(|(_, paths)| paths.iter().any(|p| p == path))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Members of `name` that still exist on disk
+    pub fn paths(&self, name: &str) -> Result<Vec<PathBuf>> {
+        let set = self
+            .sets
+            .get(name)
+            .ok_or_else(|| eyre!("No collection named {}", name))?;
+        Ok(set.iter().filter(|p| p.is_file()).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn non_utf8_paths_survive_a_save() {
+        let odd = Path::new(OsStr::from_bytes(b"/w/caf\xe9.jpg"));
+        let mut collections = Collections::default();
+        collections.add("dark", odd);
+        let json = serde_json::to_string(&collections).unwrap();
+        let loaded: Collections = serde_json::from_str(&json).unwrap();
+        assert!(loaded.contains("dark", odd));
+    }
+}
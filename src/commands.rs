@@ -0,0 +1,743 @@
+use crate::browse::SortOrder;
+use crate::backend::{self, Transition};
+use crate::groups::GroupBy;
+use crate::palette::Tone;
+use crate::potd::Source;
+use crate::tags::{self, TagExpr};
+use crate::{library, queue, rating, reddit};
+use crate::wallpaper::FillMode;
+use color_eyre::{eyre::eyre, Result};
+use std::time::Duration;
+
+/// What Tab completes for a command's arguments
+#[derive(Clone, Copy)]
+pub enum ArgCompletion {
+    None,
+    /// Directories on disk
+    Dirs,
+    /// Directories and image files on disk
+    Files,
+    /// Names of the connected outputs
+    Outputs,
+    /// `option=value` for `:set`
+    Settings,
+    /// File names of the images in the current view
+    ViewImages,
+    /// A fixed set of words
+    Values(&'static [&'static str]),
+    /// Collection names after `#`, otherwise the words
+    Tags(&'static [&'static str]),
+}
+
+/// A `:` command as listed in help and used for parsing and completion
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// Argument synopsis, e.g. `<path>`
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub min_args: usize,
+    /// None for any number
+    pub max_args: Option<usize>,
+    pub completion: ArgCompletion,
+}
+
+/// Every command, in the order help lists them
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "cd",
+        usage: "[path|@bookmark]",
+        help: "Browse a directory (default dir without a path)",
+        min_args: 0,
+        max_args: None,
+        completion: ArgCompletion::Dirs,
+    },
+    CommandSpec {
+        name: "bookmark",
+        usage: "add|remove <name>",
+        help: "Bookmark the current directory for :cd @name",
+        min_args: 2,
+        max_args: Some(2),
+        completion: ArgCompletion::Values(&["add", "remove"]),
+    },
+    CommandSpec {
+        name: "apply",
+        usage: "[--blur N] [--dim F] [--grayscale]",
+        help: "Apply with adjustments",
+        min_args: 0,
+        max_args: None,
+        completion: ArgCompletion::Values(&["--blur", "--dim", "--grayscale", "--rotate", "--flip"]),
+    },
+    CommandSpec {
+        name: "lockscreen",
+        usage: "[--blur N] [--dim F] [--grayscale]",
+        help: "Use the selection as the hyprlock/swaylock background",
+        min_args: 0,
+        max_args: None,
+        completion: ArgCompletion::Values(&["--blur", "--dim", "--grayscale"]),
+    },
+    CommandSpec {
+        name: "sync",
+        usage: "login",
+        help: "Copy the applied wallpaper to the login screen (via pkexec)",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["login"]),
+    },
+    CommandSpec {
+        name: "rotate",
+        usage: "<90|180|270>",
+        help: "Rotate before applying",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["90", "180", "270"]),
+    },
+    CommandSpec {
+        name: "flip",
+        usage: "<h|v>",
+        help: "Mirror before applying",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["h", "v"]),
+    },
+    CommandSpec {
+        name: "export-scaled",
+        usage: "<file> [monitor] [mode]",
+        help: "Save as shown on a monitor",
+        min_args: 1,
+        max_args: Some(3),
+        completion: ArgCompletion::Files,
+    },
+    CommandSpec {
+        name: "pin",
+        usage: "<output>",
+        help: "Keep the selection on one monitor while others follow the applied wallpaper",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Outputs,
+    },
+    CommandSpec {
+        name: "unpin",
+        usage: "[output]",
+        help: "Let a monitor (or all without one) show the applied wallpaper again",
+        min_args: 0,
+        max_args: Some(1),
+        completion: ArgCompletion::Outputs,
+    },
+    CommandSpec {
+        name: "collection",
+        usage: "add|remove|view <name>",
+        help: "Group wallpapers across directories",
+        min_args: 2,
+        max_args: Some(2),
+        completion: ArgCompletion::Values(&["add", "remove", "view"]),
+    },
+    CommandSpec {
+        name: "tag",
+        usage: "[add|remove <name>]",
+        help: "Add or remove the marked wallpapers (x) in a collection; without arguments, edit their collections",
+        min_args: 0,
+        max_args: Some(2),
+        completion: ArgCompletion::Values(&["add", "remove"]),
+    },
+    CommandSpec {
+        name: "import",
+        usage: "<path|glob> [--link] | <file.json>",
+        help: "Add images to the library, or merge in an :export file",
+        min_args: 1,
+        max_args: None,
+        completion: ArgCompletion::Files,
+    },
+    CommandSpec {
+        name: "export",
+        usage: "<file.json>",
+        help: "Save notes, ratings, collections, bookmarks and history to a file",
+        min_args: 1,
+        max_args: None,
+        completion: ArgCompletion::Files,
+    },
+    CommandSpec {
+        name: "trash",
+        usage: "[restore]",
+        help: "Move the selection to the trash, or pick one to restore",
+        min_args: 0,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["restore"]),
+    },
+    CommandSpec {
+        name: "select",
+        usage: "<file name>",
+        help: "Jump to a wallpaper in the current view",
+        min_args: 1,
+        max_args: None,
+        completion: ArgCompletion::ViewImages,
+    },
+    CommandSpec {
+        name: "note",
+        usage: "[text]",
+        help: "Attach a note to the selection, searchable with / (clears without text)",
+        min_args: 0,
+        max_args: None,
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "source",
+        usage: "[url]",
+        help: "Record where the selection came from (clears without a URL)",
+        min_args: 0,
+        max_args: Some(1),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "reddit",
+        usage: "<subreddit>",
+        help: "Browse the month's top images of a subreddit; applying downloads the full image",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["wallpapers", "WidescreenWallpaper", "wallpaper", "EarthPorn"]),
+    },
+    CommandSpec {
+        name: "potd",
+        usage: "bing|nasa [--apply]",
+        help: "Download today's picture of the day into the download dir",
+        min_args: 1,
+        max_args: Some(2),
+        completion: ArgCompletion::Values(Source::NAMES),
+    },
+    CommandSpec {
+        name: "download",
+        usage: "",
+        help: "Save the full image of a :reddit preview without applying it",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "undolist",
+        usage: "",
+        help: "Show the changes u can undo, newest first",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "downloads",
+        usage: "",
+        help: "Show the download queue",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "queue",
+        usage: "play <interval>|stop|clear",
+        help: "Apply the queued wallpapers (a adds one) in order, e.g. play 2h",
+        min_args: 1,
+        max_args: Some(2),
+        completion: ArgCompletion::Values(&["play", "stop", "clear"]),
+    },
+    CommandSpec {
+        name: "goto",
+        usage: "<n>",
+        help: "Jump to the nth wallpaper in the view",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "group-by",
+        usage: "<tag|month|dir|off>",
+        help: "Split the grid into sections by collection, month or folder; [ and ] jump between them",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(GroupBy::NAMES),
+    },
+    CommandSpec {
+        name: "sort",
+        usage: "<name|name-desc|newest|oldest|random|match|rating>",
+        help: "Change the grid order",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(SortOrder::NAMES),
+    },
+    CommandSpec {
+        name: "rate",
+        usage: "<0-5|clear>",
+        help: "Star the selection (clear goes back to the file's own rating)",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["0", "1", "2", "3", "4", "5", "clear"]),
+    },
+    CommandSpec {
+        name: "stars",
+        usage: "<1-5|off>",
+        help: "Only show wallpapers with at least this many stars",
+        min_args: 1,
+        max_args: Some(1),
+        completion: ArgCompletion::Values(&["1", "2", "3", "4", "5", "off"]),
+    },
+    CommandSpec {
+        name: "filter",
+        usage: "[text|dark|light|all|#tag expression]",
+        help: "Filter by name, brightness or collections like #nature & !#people | #minimal (clears without text); all lists images below min_size",
+        min_args: 0,
+        max_args: None,
+        completion: ArgCompletion::Tags(&["dark", "light", "all"]),
+    },
+    CommandSpec {
+        name: "random",
+        usage: "",
+        help: "Apply a random wallpaper from the grid",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "preload",
+        usage: "",
+        help: "Load every thumbnail in the view now (Esc cancels)",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "reload",
+        usage: "",
+        help: "Re-scan the current directory",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+    CommandSpec {
+        name: "set",
+        usage: "[<option>=<value>]",
+        help: "Change a setting, or list them all with their values",
+        min_args: 0,
+        max_args: Some(1),
+        completion: ArgCompletion::Settings,
+    },
+    CommandSpec {
+        name: "q",
+        usage: "",
+        help: "Quit",
+        min_args: 0,
+        max_args: Some(0),
+        completion: ArgCompletion::None,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|spec| spec.name == name)
+}
+
+/// A setting changed with `:set`
+#[derive(Debug, PartialEq)]
+pub enum Setting {
+    Fill(FillMode),
+    Sort(SortOrder),
+    CellWidth(u16),
+    /// Milliseconds; 0 turns crossfading off
+    Crossfade(u64),
+    SmoothScroll(bool),
+    HoverApply(bool),
+    /// A checked swww transition spec
+    Transition(String),
+    /// A known backend name, or None for `auto`
+    Backend(Option<String>),
+}
+
+impl Setting {
+    pub fn parse(assignment: &str) -> Result<Self> {
+        let (option, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected <option>=<value>"))?;
+        let value = value.trim();
+        match option.trim() {
+            "fill" => FillMode::parse(value)
+                .map(Setting::Fill)
+                .ok_or_else(|| eyre!("Unknown fill mode: {}", value)),
+            "sort" => SortOrder::parse(value)
+                .map(Setting::Sort)
+                .ok_or_else(|| eyre!("Unknown sort order: {}", value)),
+            "cell-width" => Ok(Setting::CellWidth(value.parse()?)),
+            "crossfade" => Ok(Setting::Crossfade(value.parse()?)),
+            // The spec itself contains commas, e.g. `transition=wipe,1s`
+            "transition" => {
+                Transition::parse(value)?;
+                Ok(Setting::Transition(value.to_string()))
+            }
+            "smooth-scroll" => parse_switch(value).map(Setting::SmoothScroll),
+            "hover-apply" => parse_switch(value).map(Setting::HoverApply),
+            "backend" if value == "auto" => Ok(Setting::Backend(None)),
+            "backend" => backend::by_name(value)
+                .map(|_| Setting::Backend(Some(value.to_string())))
+                .ok_or_else(|| eyre!("Unknown backend: {} (auto, {})", value, backend::NAMES.join(", "))),
+            other => Err(eyre!("Unknown option: {}", other)),
+        }
+    }
+}
+
+fn parse_switch(value: &str) -> Result<bool> {
+    match value {
+        "on" | "true" => Ok(true),
+        "off" | "false" => Ok(false),
+        _ => Err(eyre!("Expected on or off: {}", value)),
+    }
+}
+
+/// Options `:set` takes, in the order the settings list shows them, with the values Tab offers
+pub const SETTINGS: &[(&str, &[&str])] = &[
+    ("fill", &["fill", "fit", "stretch", "center", "tile"]),
+    ("sort", SortOrder::NAMES),
+    // The narrowest, the default and the widest, with a few between
+    ("cell-width", &["12", "20", "30", "40", "60", "80"]),
+    ("crossfade", &["0", "300", "500", "1000"]),
+    ("smooth-scroll", &["on", "off"]),
+    ("hover-apply", &["on", "off"]),
+    ("transition", backend::TRANSITION_TYPES),
+    ("backend", backend::CHOICES),
+];
+
+/// Completions for a partly typed `:set` argument: option names, then the option's values
+pub fn setting_completions(arg: &str) -> Vec<String> {
+    match arg.split_once('=') {
+        Some((option, value)) => SETTINGS
+            .iter()
+            .filter(|(name, _)| *name == option)
+            .flat_map(|(_, values)| values.iter())
+            .filter(|v| v.starts_with(value))
+            .map(|v| format!("{}={}", option, v))
+            .collect(),
+        None => SETTINGS
+            .iter()
+            .filter(|(name, _)| name.starts_with(arg))
+            .map(|(name, _)| format!("{}=", name))
+            .collect(),
+    }
+}
+
+/// A parsed `:` command
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `cd <path>`, or plain `cd` (None) for the default directory
+    Cd(Option<String>),
+    Apply(Vec<String>),
+    Lockscreen(Vec<String>),
+    SyncLogin,
+    /// `bookmark add|remove <name>`
+    Bookmark(String, String),
+    ExportScaled(Vec<String>),
+    Pin(String),
+    /// `unpin [output]`; None unpins every output
+    Unpin(Option<String>),
+    Import(Vec<String>),
+    /// `import <file.json>`: merge in an export
+    ImportLibrary(String),
+    Export(String),
+    Collection(Vec<String>),
+    /// `tag add|remove <name>` on the marked wallpapers
+    Tag { name: String, add: bool },
+    /// `tag` alone: the batch tag editor
+    TagEditor,
+    Rotate(String),
+    Flip(String),
+    Select(String),
+    /// `note [text]`; empty clears
+    Note(String),
+    /// `source [url]`; empty clears
+    Source(String),
+    Reddit(String),
+    /// `potd <source> [--apply]`
+    Potd(Source, bool),
+    Download,
+    Downloads,
+    UndoList,
+    /// `queue play <interval>`
+    QueuePlay(Duration),
+    QueueStop,
+    QueueClear,
+    /// `goto <n>`, 1-based
+    Goto(usize),
+    Trash,
+    TrashRestore,
+    Sort(SortOrder),
+    /// None for `group-by off`
+    GroupBy(Option<GroupBy>),
+    Filter(String),
+    /// `filter all`: include images below the configured minimum size
+    FilterAll,
+    /// `filter dark` / `filter light`
+    FilterTone(Tone),
+    /// `filter #a & !#b`: only wallpapers whose collections satisfy the expression
+    FilterTags(TagExpr),
+    /// `rate <0-5>`, or None for `rate clear`
+    Rate(Option<u8>),
+    /// `stars <n>`, or None for `stars off`
+    MinStars(Option<u8>),
+    Random,
+    Preload,
+    Reload,
+    Set(Setting),
+    /// Bare `set`: list the settings
+    Settings,
+    Quit,
+}
+
+impl Command {
+    /// Parse a command line, checking it against its spec; an empty line is Ok(None)
+    pub fn parse(input: &str) -> Result<Option<Self>> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        let (name, rest) = input.split_once(' ').unwrap_or((input, ""));
+        let rest = rest.trim();
+        let spec = find(name).ok_or_else(|| eyre!("Unknown command: {}", name))?;
+
+        let mut args: Vec<String> = rest.split_whitespace().map(String::from).collect();
+        if args.len() < spec.min_args || spec.max_args.is_some_and(|max| args.len() > max) {
+            return Err(eyre!("Usage: {} {}", spec.name, spec.usage));
+        }
+
+        let command = match spec.name {
+            "cd" if rest.is_empty() => Command::Cd(None),
+            // Paths may contain spaces
+            "cd" => Command::Cd(Some(rest.to_string())),
+            "apply" => Command::Apply(args),
+            "lockscreen" => Command::Lockscreen(args),
+            "sync" => match args[0].as_str() {
+                "login" => Command::SyncLogin,
+                other => return Err(eyre!("Unknown sync target: {}", other)),
+            },
+            "bookmark" => Command::Bookmark(args.remove(0), args.remove(0)),
+            "export-scaled" => Command::ExportScaled(args),
+            "pin" => Command::Pin(args.remove(0)),
+            "unpin" => Command::Unpin(args.pop()),
+            "import" if args.len() == 1 && library::is_export_file(&args[0]) => {
+                Command::ImportLibrary(args.remove(0))
+            }
+            "import" => Command::Import(args),
+            // Paths may contain spaces
+            "export" => Command::Export(rest.to_string()),
+            "collection" => Command::Collection(args),
+            "tag" => match args.as_slice() {
+                [] => Command::TagEditor,
+                [action, name] if action == "add" || action == "remove" => {
+                    Command::Tag { name: name.clone(), add: action == "add" }
+                }
+                _ => return Err(eyre!("Usage: {} {}", spec.name, spec.usage)),
+            },
+            "rotate" => Command::Rotate(args.remove(0)),
+            "flip" => Command::Flip(args.remove(0)),
+            // File names may contain spaces
+            "select" => Command::Select(rest.to_string()),
+            // Notes are free text
+            "note" => Command::Note(rest.to_string()),
+            "source" => Command::Source(rest.to_string()),
+            "reddit" => Command::Reddit(reddit::parse_subreddit(&args[0])?),
+            "download" => Command::Download,
+            "downloads" => Command::Downloads,
+            "undolist" => Command::UndoList,
+            "potd" => Command::Potd(
+                Source::parse(&args[0]).ok_or_else(|| eyre!("Unknown picture of the day: {}", args[0]))?,
+                match args.get(1).map(String::as_str) {
+                    None => false,
+                    Some("--apply") => true,
+                    Some(other) => return Err(eyre!("Unknown option: {}", other)),
+                },
+            ),
+            "queue" => match (args[0].as_str(), args.get(1)) {
+                ("play", Some(interval)) => Command::QueuePlay(queue::parse_interval(interval)?),
+                ("play", None) => return Err(eyre!("Usage: queue play <interval>")),
+                ("stop", None) => Command::QueueStop,
+                ("clear", None) => Command::QueueClear,
+                _ => return Err(eyre!("Usage: {} {}", spec.name, spec.usage)),
+            },
+            "goto" => Command::Goto(
+                args[0]
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| eyre!("Not a position: {}", args[0]))?,
+            ),
+            "trash" => match args.first().map(String::as_str) {
+                None => Command::Trash,
+                Some("restore") => Command::TrashRestore,
+                Some(other) => return Err(eyre!("Unknown trash action: {}", other)),
+            },
+            "group-by" if args[0] == "off" => Command::GroupBy(None),
+            "group-by" => Command::GroupBy(Some(
+                GroupBy::parse(&args[0]).ok_or_else(|| eyre!("Unknown grouping: {}", args[0]))?,
+            )),
+            "sort" => Command::Sort(
+                SortOrder::parse(&args[0]).ok_or_else(|| eyre!("Unknown sort order: {}", args[0]))?,
+            ),
+            "filter" if rest == "all" => Command::FilterAll,
+            "filter" if tags::is_expression(rest) => Command::FilterTags(TagExpr::parse(rest)?),
+            "filter" => match Tone::parse(rest) {
+                Some(tone) => Command::FilterTone(tone),
+                None => Command::Filter(rest.to_string()),
+            },
+            "rate" => Command::Rate(match args[0].as_str() {
+                "clear" => None,
+                n => Some(parse_stars(n, 0)?),
+            }),
+            "stars" => Command::MinStars(match args[0].as_str() {
+                "off" => None,
+                n => Some(parse_stars(n, 1)?),
+            }),
+            "random" => Command::Random,
+            "preload" => Command::Preload,
+            "reload" => Command::Reload,
+            "set" if args.is_empty() => Command::Settings,
+            "set" => Command::Set(Setting::parse(&args[0])?),
+            "q" => Command::Quit,
+            _ => unreachable!("command {} has a spec but no parser", spec.name),
+        };
+        Ok(Some(command))
+    }
+}
+
+fn parse_stars(text: &str, min: u8) -> Result<u8> {
+    text.parse()
+        .ok()
+        .filter(|n| (min..=rating::MAX_STARS).contains(n))
+        .ok_or_else(|| eyre!("Stars must be {}-{}: {}", min, rating::MAX_STARS, text))
+}
+
+/// Sorted command names starting with `prefix`
+pub fn complete_name(prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = COMMANDS
+        .iter()
+        .filter(|spec| spec.name.starts_with(prefix))
+        .map(|spec| spec.name.to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::parse("").unwrap(), None);
+        assert_eq!(Command::parse("cd").unwrap(), Some(Command::Cd(None)));
+        assert_eq!(
+            Command::parse("cd ~/My Pictures ").unwrap(),
+            Some(Command::Cd(Some("~/My Pictures".to_string())))
+        );
+        assert_eq!(
+            Command::parse("apply --blur 4").unwrap(),
+            Some(Command::Apply(vec!["--blur".to_string(), "4".to_string()]))
+        );
+        assert_eq!(Command::parse("apply").unwrap(), Some(Command::Apply(Vec::new())));
+        assert_eq!(Command::parse("rotate 90").unwrap(), Some(Command::Rotate("90".to_string())));
+        assert_eq!(Command::parse("sort newest").unwrap(), Some(Command::Sort(SortOrder::Newest)));
+        assert_eq!(Command::parse("rate 4").unwrap(), Some(Command::Rate(Some(4))));
+        assert_eq!(Command::parse("stars off").unwrap(), Some(Command::MinStars(None)));
+        assert!(Command::parse("stars 0").is_err());
+        assert!(Command::parse("rate 6").is_err());
+        assert_eq!(Command::parse("filter").unwrap(), Some(Command::Filter(String::new())));
+        assert_eq!(Command::parse("filter dark").unwrap(), Some(Command::FilterTone(Tone::Dark)));
+        assert_eq!(Command::parse("filter all").unwrap(), Some(Command::FilterAll));
+        assert_eq!(
+            Command::parse("filter #dark !#people").unwrap(),
+            Some(Command::FilterTags(TagExpr::parse("#dark & !#people").unwrap()))
+        );
+        assert!(Command::parse("filter #dark &").is_err());
+        assert_eq!(Command::parse("q").unwrap(), Some(Command::Quit));
+        assert_eq!(Command::parse("pin DP-1").unwrap(), Some(Command::Pin("DP-1".to_string())));
+        assert_eq!(Command::parse("unpin").unwrap(), Some(Command::Unpin(None)));
+        assert_eq!(Command::parse("set").unwrap(), Some(Command::Settings));
+        assert_eq!(
+            Command::parse("set backend=auto").unwrap(),
+            Some(Command::Set(Setting::Backend(None)))
+        );
+        assert!(Command::parse("set backend=xsetroot").is_err());
+        assert_eq!(Command::parse("goto 12").unwrap(), Some(Command::Goto(12)));
+        assert_eq!(
+            Command::parse("queue play 2h").unwrap(),
+            Some(Command::QueuePlay(Duration::from_secs(7200)))
+        );
+        assert_eq!(Command::parse("queue stop").unwrap(), Some(Command::QueueStop));
+        assert_eq!(
+            Command::parse("import ~/backup/library.json").unwrap(),
+            Some(Command::ImportLibrary("~/backup/library.json".to_string()))
+        );
+        assert_eq!(
+            Command::parse("import ~/Downloads/*.jpg").unwrap(),
+            Some(Command::Import(vec!["~/Downloads/*.jpg".to_string()]))
+        );
+        assert_eq!(
+            Command::parse("note from that  reddit post").unwrap(),
+            Some(Command::Note("from that  reddit post".to_string()))
+        );
+        assert_eq!(Command::parse("source").unwrap(), Some(Command::Source(String::new())));
+        assert_eq!(Command::parse("potd nasa --apply").unwrap(), Some(Command::Potd(Source::Nasa, true)));
+        assert_eq!(
+            Command::parse("bookmark add nas").unwrap(),
+            Some(Command::Bookmark("add".to_string(), "nas".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_validates_arguments() {
+        assert!(Command::parse("frobnicate").is_err());
+        assert!(Command::parse("import").is_err());
+        assert!(Command::parse("flip").is_err());
+        assert!(Command::parse("flip h v").is_err());
+        assert!(Command::parse("reload now").is_err());
+        assert!(Command::parse("sort sideways").is_err());
+        assert!(Command::parse("trash empty").is_err());
+        assert!(Command::parse("goto 0").is_err());
+        assert!(Command::parse("goto top").is_err());
+        assert!(Command::parse("queue play").is_err());
+        assert!(Command::parse("queue stop now").is_err());
+    }
+
+    #[test]
+    fn parse_settings() {
+        assert_eq!(Setting::parse("fill=fit").unwrap(), Setting::Fill(FillMode::Fit));
+        assert_eq!(Setting::parse("cell-width=40").unwrap(), Setting::CellWidth(40));
+        assert_eq!(Setting::parse("crossfade=0").unwrap(), Setting::Crossfade(0));
+        assert_eq!(Setting::parse("smooth-scroll=off").unwrap(), Setting::SmoothScroll(false));
+        assert_eq!(Setting::parse("hover-apply=on").unwrap(), Setting::HoverApply(true));
+        assert_eq!(
+            Setting::parse("transition=grow,800ms,top-right").unwrap(),
+            Setting::Transition("grow,800ms,top-right".to_string())
+        );
+        assert!(Setting::parse("transition=spin").is_err());
+        assert!(Setting::parse("transition=wipe,fast").is_err());
+        assert!(Setting::parse("fill").is_err());
+        assert!(Setting::parse("volume=11").is_err());
+        assert!(Setting::parse("cell-width=wide").is_err());
+    }
+
+    #[test]
+    fn every_spec_parses() {
+        for spec in COMMANDS {
+            let args = vec!["x"; spec.min_args].join(" ");
+            let line = format!("{} {}", spec.name, args);
+            // Values may be rejected, but never as an unknown command
+            if let Err(e) = Command::parse(&line) {
+                assert!(!e.to_string().starts_with("Unknown command"), "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn completes_settings_then_values() {
+        assert_eq!(setting_completions("h"), vec!["hover-apply="]);
+        assert_eq!(setting_completions("fill=f"), vec!["fill=fill", "fill=fit"]);
+        assert!(setting_completions("nope=").is_empty());
+        assert_eq!(setting_completions("backend=s"), vec!["backend=swaybg", "backend=swww"]);
+        assert_eq!(setting_completions("cell-width=1"), vec!["cell-width=12"]);
+    }
+
+    #[test]
+    fn complete_names_by_prefix() {
+        assert_eq!(complete_name("r"), vec!["random", "rate", "reddit", "reload", "rotate"]);
+        assert!(complete_name("zz").is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::wallpaper;
+use color_eyre::Result;
+use image::{DynamicImage, GenericImageView};
+use std::path::PathBuf;
+
+/// Zoom factor change per key press
+const ZOOM_STEP: f64 = 1.25;
+const MAX_ZOOM: f64 = 8.0;
+/// Fraction of the visible area panned per key press
+const PAN_STEP: f64 = 0.1;
+/// Longest edge of each encoded half; the terminal can't show more detail than this
+const VIEW_MAX_EDGE: u32 = 1600;
+
+/// Two wallpapers shown side by side, sharing one zoom level and viewport
+pub struct Compare {
+    pub left: PathBuf,
+    pub right: PathBuf,
+    left_image: DynamicImage,
+    right_image: DynamicImage,
+    pub zoom: f64,
+    /// Viewport center as a fraction of each image's width and height
+    center: (f64, f64),
+}
+
+impl Compare {
+    pub fn open(left: PathBuf, right: PathBuf) -> Result<Self> {
+        let left_image = wallpaper::open_image(&left)?;
+        let right_image = wallpaper::open_image(&right)?;
+        Ok(Self {
+            left,
+            right,
+            left_image,
+            right_image,
+            zoom: 1.0,
+            center: (0.5, 0.5),
+        })
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom * ZOOM_STEP).min(MAX_ZOOM);
+        self.clamp();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = (self.zoom / ZOOM_STEP).max(1.0);
+        self.clamp();
+    }
+
+    pub fn reset(&mut self) {
+        self.zoom = 1.0;
+        self.center = (0.5, 0.5);
+    }
+
+    /// Move the viewport by `dx`/`dy` steps, in both images at once
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        let step = PAN_STEP / self.zoom;
+        self.center.0 += dx * step;
+        self.center.1 += dy * step;
+        self.clamp();
+    }
+
+    /// Keep the viewport inside the image
+    fn clamp(&mut self) {
+        let half = 0.5 / self.zoom;
+        self.center.0 = self.center.0.clamp(half, 1.0 - half);
+        self.center.1 = self.center.1.clamp(half, 1.0 - half);
+    }
+
+    pub fn left_view(&self) -> DynamicImage {
+        self.view(&self.left_image)
+    }
+
+    pub fn right_view(&self) -> DynamicImage {
+        self.view(&self.right_image)
+    }
+
+    /// The part of `img` inside the shared viewport, downscaled for encoding
+    fn view(&self, img: &DynamicImage) -> DynamicImage {
+        let (w, h) = img.dimensions();
+        let (vw, vh) = (w as f64 / self.zoom, h as f64 / self.zoom);
+        let x = (self.center.0 * w as f64 - vw / 2.0).max(0.0);
+        let y = (self.center.1 * h as f64 - vh / 2.0).max(0.0);
+        img.crop_imm(
+            x.round() as u32,
+            y.round() as u32,
+            (vw.round() as u32).max(1),
+            (vh.round() as u32).max(1),
+        )
+        .thumbnail(VIEW_MAX_EDGE, VIEW_MAX_EDGE)
+    }
+}
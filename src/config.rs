@@ -0,0 +1,226 @@
+use crate::backend;
+use crate::potd::Source;
+use crate::wallpaper::MinSize;
+use color_eyre::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// User settings read from `~/.config/omarchy-wallpaper-picker/config.toml`.
+/// Every field is optional; a missing file yields the defaults.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// Show a status-bar nudge once the current wallpaper is older than this many days
+    pub rotation_reminder_days: Option<u64>,
+    /// Blend from the old wallpaper to the new one over this many milliseconds when applying
+    pub crossfade_ms: Option<u64>,
+    /// Shell commands run on app events, keyed by event name (e.g. `wallpaper_applied`)
+    pub hooks: HashMap<String, String>,
+    /// Wallpaper setter to use (`swaybg`, `swww`, `feh`, `xwallpaper`, `gnome`, `kde`); detected when unset
+    pub backend: Option<String>,
+    /// Run a separate swaybg for each output instead of one for all of them
+    pub swaybg_per_output: bool,
+    /// Rotate through this collection instead of the backgrounds dir for `--next`/`--prev`/`--random`
+    pub rotation_collection: Option<String>,
+    /// Re-encode images larger than the biggest monitor down to its resolution when installing
+    pub downscale_on_install: bool,
+    /// Format for downscaled copies (`jpg`, `png` or `webp`); the original's when unset
+    pub install_format: Option<String>,
+    /// JPEG quality (1-100) for downscaled copies
+    pub install_quality: Option<u8>,
+    /// Blur sigma for the lock screen image when `:lockscreen` is given no options
+    pub lockscreen_blur: Option<f32>,
+    /// Write `<name>-blur.png` and `<name>-dim.png` to the cache dir whenever a wallpaper is applied
+    pub generate_variants: bool,
+    /// Blur sigma for the blurred variant
+    pub variant_blur: Option<f32>,
+    /// Dim amount (0-1) for the dimmed variant
+    pub variant_dim: Option<f32>,
+    /// Display manager background that `:sync login` overwrites (SDDM theme, greetd greeter)
+    pub login_background: Option<PathBuf>,
+    /// Show a desktop notification with the new wallpaper after each apply
+    pub notify_on_apply: bool,
+    /// Start with the grid filtered to wallpapers matching the desktop's dark/light mode
+    pub match_system_tone: bool,
+    /// Random picks skip this many of the most recently applied wallpapers (default 10)
+    pub random_avoid_recent: Option<usize>,
+    /// Collection whose wallpapers random picks favor (default `favorites`)
+    pub favorites_collection: Option<String>,
+    /// How many times likelier a favorite is to be picked (default 3)
+    pub favorite_weight: Option<u32>,
+    /// Jump straight to the new grid position on big moves (G, paging) instead of gliding there
+    pub instant_scroll: bool,
+    /// swww transition as `type[,duration][,position]`, e.g. `wipe,1s` or `grow,0.8s,top-right`;
+    /// `random` picks a different one each time
+    pub transition: Option<String>,
+    /// Put the selected wallpaper on the desktop once the cursor rests on it, restoring the
+    /// applied one on exit unless Enter made the selection stick
+    pub hover_apply: bool,
+    /// Seconds a wallpaper tried with `t` stays up before the previous one comes back (default 10)
+    pub try_seconds: Option<u64>,
+    /// Where downloads (`:reddit`, `:potd`) are saved (default `~/Pictures/Wallpapers`)
+    pub download_dir: Option<PathBuf>,
+    /// Picture-of-the-day feed (`bing` or `nasa`) the daemon downloads each day
+    pub potd: Option<String>,
+    /// Apply each new picture of the day the daemon downloads
+    pub potd_apply: bool,
+    /// api.nasa.gov key for `nasa` pictures; the rate-limited demo key otherwise
+    pub nasa_api_key: Option<String>,
+    /// Downloads transferring at once (default 3)
+    pub download_concurrency: Option<usize>,
+    /// Proxy for network features, e.g. `http://proxy:8080` or `socks5://localhost:1080`;
+    /// `HTTPS_PROXY`/`HTTP_PROXY` are used when unset
+    pub proxy: Option<String>,
+    /// Disable network sources (`:reddit`, `:potd`, downloads), as `--offline` does
+    pub offline: bool,
+    /// Directory wallpapers are installed to and listed from (default
+    /// `~/.config/omarchy/current/theme/backgrounds`); `--backgrounds-dir` overrides it
+    pub backgrounds_dir: Option<PathBuf>,
+    /// Directories merged into one grid, subdirectories included, in place of the backgrounds
+    /// dir; each wallpaper is badged with the root it came from
+    pub library_roots: Vec<PathBuf>,
+    /// Leave images smaller than this (e.g. `1280x720`, either orientation) out of directory listings;
+    /// `:filter all` shows them anyway
+    pub min_size: Option<String>,
+    /// Leave files smaller than this many KiB out of directory listings
+    pub min_file_kb: Option<u64>,
+    /// Symlink pointing at the applied wallpaper (default `~/.config/omarchy/current/background`);
+    /// `--current-link` overrides it
+    pub current_link: Option<PathBuf>,
+    /// TUI colors by role (`accent`, `highlight`, `current`, `marked`, `muted`, `error`, `text`),
+    /// replacing those taken from the omarchy theme, e.g. `accent = "#89b4fa"`
+    pub tui_colors: HashMap<String, String>,
+    /// Draw without colors, marking states with `[*]`/`[✓]` and bold or reversed text
+    /// (also `--no-color` or `$NO_COLOR`)
+    pub no_color: bool,
+    /// Leave out images and describe the selection, mode changes and apply results in words on
+    /// one line, for terminal screen readers (also `--screen-reader`)
+    pub screen_reader: bool,
+    /// Grid cell width in columns, replacing the size last picked with +/-
+    pub cell_width: Option<u16>,
+    /// Keys by action, replacing its default keys, e.g. `toggle-preview = "p Space"`; actions
+    /// are named in kebab-case (`move-up`, `apply`, `search`, `quit`, ...)
+    pub keys: HashMap<String, String>,
+    /// Megabytes of decoded thumbnails and encoded grid cells to keep; past it, those farthest from the
+    /// selection are dropped and read back from the thumbnail cache when scrolled to
+    pub memory_budget_mb: Option<u64>,
+}
+
+/// Formats `install_format` accepts
+pub const INSTALL_FORMATS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// `path` with a leading `~` component replaced by the home directory
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) if rest.as_os_str().is_empty() => home,
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+pub fn get_config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
+        .join("omarchy-wallpaper-picker")
+        .join("config.toml")
+}
+
+impl Config {
+    pub fn crossfade(&self) -> Option<Duration> {
+        self.crossfade_ms.map(Duration::from_millis)
+    }
+
+    /// Limits for directory listings, if any are set
+    pub fn min_size(&self) -> Option<MinSize> {
+        let min = MinSize {
+            dimensions: self.min_size.as_deref().and_then(MinSize::parse_dimensions),
+            bytes: self.min_file_kb.map(|kb| kb * 1024),
+        };
+        (min != MinSize::default()).then_some(min)
+    }
+
+    /// Resolve `~` in the configured paths, as the shell would for the same flags
+    fn expand_paths(&mut self) {
+        let dirs = [&mut self.backgrounds_dir, &mut self.current_link, &mut self.download_dir, &mut self.login_background];
+        for path in dirs.into_iter().flatten().chain(&mut self.library_roots) {
+            *path = expand_home(path);
+        }
+    }
+
+    pub fn download_dir(&self) -> PathBuf {
+        self.download_dir.clone().unwrap_or_else(|| {
+            dirs::picture_dir()
+                .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join("Pictures"))
+                .join("Wallpapers")
+        })
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = get_config_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)?;
+        let mut config: Self = toml::from_str(&contents)
+            .map_err(|e| color_eyre::eyre::eyre!("{}: {}", path.display(), e))?;
+        config.expand_paths();
+        if let Some(name) = &config.backend
+            && backend::by_name(name).is_none()
+        {
+            return Err(color_eyre::eyre::eyre!("{}: unknown backend '{}'", path.display(), name));
+        }
+        if let Some(format) = &config.install_format
+            && !INSTALL_FORMATS.contains(&format.as_str())
+        {
+            return Err(color_eyre::eyre::eyre!("{}: unknown install_format '{}'", path.display(), format));
+        }
+        if let Some(name) = &config.potd
+            && Source::parse(name).is_none()
+        {
+            return Err(color_eyre::eyre::eyre!("{}: unknown potd '{}' (bing or nasa)", path.display(), name));
+        }
+        if let Some(spec) = &config.transition {
+            backend::Transition::parse(spec)
+                .map_err(|e| color_eyre::eyre::eyre!("{}: transition: {}", path.display(), e))?;
+        }
+        if let Some(proxy) = &config.proxy {
+            ureq::Proxy::new(proxy)
+                .map_err(|e| color_eyre::eyre::eyre!("{}: proxy: {}", path.display(), e))?;
+        }
+        if let Some(spec) = &config.min_size
+            && MinSize::parse_dimensions(spec).is_none()
+        {
+            return Err(color_eyre::eyre::eyre!("{}: min_size must look like 1280x720", path.display()));
+        }
+        if config.install_quality.is_some_and(|q| !(1..=100).contains(&q)) {
+            return Err(color_eyre::eyre::eyre!("{}: install_quality must be 1-100", path.display()));
+        }
+        crate::theme::Theme::default()
+            .with_overrides(&config.tui_colors)
+            .map_err(|e| color_eyre::eyre::eyre!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_expand_home() {
+        let home = dirs::home_dir().unwrap();
+        let mut config = Config {
+            backgrounds_dir: Some(PathBuf::from("~/Pictures/walls")),
+            current_link: Some(PathBuf::from("/tmp/bg")),
+            library_roots: vec![PathBuf::from("~"), PathBuf::from("~user/x")],
+            ..Config::default()
+        };
+        config.expand_paths();
+        assert_eq!(config.backgrounds_dir, Some(home.join("Pictures/walls")));
+        assert_eq!(config.current_link, Some(PathBuf::from("/tmp/bg")));
+        assert_eq!(config.library_roots, [home, PathBuf::from("~user/x")]);
+    }
+}
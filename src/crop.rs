@@ -0,0 +1,113 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+/// Fraction of the image's shorter side moved per key press
+const MOVE_STEP: f64 = 0.02;
+/// Factor the crop grows/shrinks by per key press
+const RESIZE_STEP: f64 = 1.05;
+/// Smallest crop width as a fraction of the image width
+const MIN_WIDTH: f64 = 0.1;
+
+/// A crop rectangle with a fixed aspect ratio, in source image pixels
+#[derive(Clone, Copy, Debug)]
+pub struct CropRect {
+    image_width: f64,
+    image_height: f64,
+    aspect: f64,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+}
+
+impl CropRect {
+    /// Largest rectangle of `aspect` (width / height) that fits the image, centered
+    pub fn centered(image_width: u32, image_height: u32, aspect: f64) -> Self {
+        let (iw, ih) = (image_width.max(1) as f64, image_height.max(1) as f64);
+        let aspect = if aspect > 0.0 { aspect } else { iw / ih };
+        let width = iw.min(ih * aspect);
+        let mut rect = Self {
+            image_width: iw,
+            image_height: ih,
+            aspect,
+            x: 0.0,
+            y: 0.0,
+            width,
+        };
+        rect.x = (iw - width) / 2.0;
+        rect.y = (ih - rect.height()) / 2.0;
+        rect
+    }
+
+    pub fn height(&self) -> f64 {
+        self.width / self.aspect
+    }
+
+    pub fn move_by(&mut self, dx: f64, dy: f64) {
+        let step = self.image_width.min(self.image_height) * MOVE_STEP;
+        self.x += dx * step;
+        self.y += dy * step;
+        self.clamp();
+    }
+
+    pub fn grow(&mut self) {
+        self.resize_by(RESIZE_STEP);
+    }
+
+    pub fn shrink(&mut self) {
+        self.resize_by(1.0 / RESIZE_STEP);
+    }
+
+    /// Resize around the center, never exceeding the image
+    fn resize_by(&mut self, factor: f64) {
+        let (cx, cy) = (self.x + self.width / 2.0, self.y + self.height() / 2.0);
+        let max_width = self.image_width.min(self.image_height * self.aspect);
+        let min_width = (self.image_width * MIN_WIDTH).min(max_width);
+        self.width = (self.width * factor).clamp(min_width, max_width);
+        self.x = cx - self.width / 2.0;
+        self.y = cy - self.height() / 2.0;
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.x = self.x.clamp(0.0, (self.image_width - self.width).max(0.0));
+        self.y = self.y.clamp(0.0, (self.image_height - self.height()).max(0.0));
+    }
+
+    /// Integer pixel bounds `(x, y, width, height)` for an image scaled by `scale`
+    pub fn pixel_bounds(&self, scale: f64) -> (u32, u32, u32, u32) {
+        (
+            (self.x * scale).round() as u32,
+            (self.y * scale).round() as u32,
+            ((self.width * scale).round() as u32).max(1),
+            ((self.height() * scale).round() as u32).max(1),
+        )
+    }
+
+    /// Crop a full-resolution copy of the source image
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        let scale = img.width() as f64 / self.image_width;
+        let (x, y, w, h) = self.pixel_bounds(scale);
+        img.crop_imm(x, y, w, h)
+    }
+}
+
+/// Dim everything outside `rect` and outline it, for showing the crop in a preview.
+/// `preview` may be a downscaled copy of the image `rect` was made for.
+pub fn draw_overlay(preview: &DynamicImage, rect: &CropRect) -> DynamicImage {
+    let scale = preview.width() as f64 / rect.image_width;
+    let (x0, y0, w, h) = rect.pixel_bounds(scale);
+    let (x1, y1) = (x0 + w, y0 + h);
+
+    let mut out: RgbaImage = preview.to_rgba8();
+    let (pw, ph) = preview.dimensions();
+    for (x, y, pixel) in out.enumerate_pixels_mut() {
+        let inside = x >= x0 && x < x1 && y >= y0 && y < y1;
+        let on_border = inside && (x < x0 + 2 || x + 2 >= x1.min(pw) || y < y0 + 2 || y + 2 >= y1.min(ph));
+        if on_border {
+            *pixel = Rgba([255, 215, 0, 255]);
+        } else if !inside {
+            let [r, g, b, a] = pixel.0;
+            *pixel = Rgba([r / 3, g / 3, b / 3, a]);
+        }
+    }
+    DynamicImage::ImageRgba8(out)
+}
@@ -0,0 +1,176 @@
+use crate::collections::Collections;
+use crate::config::Config;
+use crate::events::{self, AppEvent, EventBus};
+use crate::{backend, history, hooks, monitor, net, notifications, queue, variants};
+use crate::ipc::{IpcCommand, IpcServer};
+use crate::instance::{InstanceLock, Role};
+use crate::pins::{self, Pins};
+use crate::potd::{self, Source};
+use crate::wallpaper;
+use color_eyre::{eyre::eyre, Result};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How often the watchdog checks that the backend is alive
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Consecutive failed checks before respawning. Applying a wallpaper briefly
+/// kills swaybg, so a single miss is not enough to act on.
+const MISSES_BEFORE_RESPAWN: u32 = 2;
+
+/// How often the daemon looks for a new picture of the day; feeds change once a day,
+/// at times that vary by source and timezone
+const POTD_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Run headless, keeping the backend alive with the current wallpaper and serving
+/// the control socket. If the compositor or the OOM killer takes swaybg down,
+/// it is restarted. Monitors pinned with `:pin` get their wallpaper back when plugged in again.
+pub fn run() -> Result<()> {
+    let _lock = InstanceLock::acquire(Role::Daemon)?;
+    let config = Config::load()?;
+    net::configure(&config);
+    wallpaper::configure(&config);
+    let mut events = EventBus::default();
+    hooks::subscribe(&mut events, &config.hooks);
+    history::subscribe(&mut events);
+    variants::subscribe(&mut events, &config);
+    notifications::subscribe(&mut events, &config);
+    let ipc = IpcServer::start()?;
+    if ipc.is_none() {
+        eprintln!("another instance owns the control socket; running watchdog only");
+    }
+    if let Some(ipc) = ipc.as_ref() {
+        events.subscribe(ipc.event_forwarder());
+    }
+
+    let backend = backend::for_config(&config);
+    if !Pins::load().existing().is_empty() && wallpaper::get_current_wallpaper().is_some() {
+        restore(&config, "pinned wallpapers");
+    }
+    let hotplug = monitor::watch_hotplug();
+    let potd_source = config.potd.as_deref().and_then(Source::parse).filter(|_| !net::is_offline());
+    let mut last_potd: Option<Instant> = None;
+    let mut misses = 0;
+    let mut last_check = Instant::now();
+    loop {
+        if let Some(source) = potd_source
+            && last_potd.is_none_or(|t| t.elapsed() >= POTD_INTERVAL)
+        {
+            last_potd = Some(Instant::now());
+            if let Err(e) = daily_picture(source, &config, &mut events) {
+                eprintln!("picture of the day: {}", e);
+            }
+        }
+
+        let timeout = CHECK_INTERVAL.saturating_sub(last_check.elapsed());
+        match ipc.as_ref() {
+            Some(ipc) => {
+                if let Some(request) = ipc.recv_timeout(timeout) {
+                    let result = apply_command(&request.command, &config, &mut events);
+                    request.respond(result);
+                    continue;
+                }
+            }
+            None => std::thread::sleep(timeout),
+        }
+        last_check = Instant::now();
+
+        if let Some(hotplug) = hotplug.as_ref() {
+            let pins = Pins::load();
+            if hotplug.try_iter().any(|output| pins.get(&output).is_some_and(|path| path.exists())) {
+                restore(&config, "pinned wallpaper");
+            }
+        }
+
+        match queue::take_due() {
+            Ok(Some(path)) => {
+                if let Err(e) = apply_command(&IpcCommand::Set(path), &config, &mut events) {
+                    eprintln!("queue: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("queue: {}", e),
+        }
+
+        if backend.is_alive() {
+            misses = 0;
+        } else {
+            misses += 1;
+            if misses >= MISSES_BEFORE_RESPAWN && wallpaper::get_current_wallpaper().is_some() {
+                eprintln!("{} is not running, respawning", backend.name());
+                if let Err(e) = backend::reload(&config, wallpaper::get_current_fill_mode().unwrap_or_default()) {
+                    eprintln!("failed to respawn {}: {}", backend.name(), e);
+                }
+                misses = 0;
+            }
+        }
+    }
+}
+
+/// Show the current wallpaper again, logging failures to bring back `what`
+fn restore(config: &Config, what: &str) {
+    if let Err(e) = backend::reload(config, wallpaper::get_current_fill_mode().unwrap_or_default()) {
+        eprintln!("failed to restore {}: {}", what, e);
+    }
+}
+
+/// Download today's picture if it's new, applying it when `potd_apply` is set
+fn daily_picture(source: Source, config: &Config, events: &mut EventBus) -> Result<()> {
+    let (path, new) = potd::fetch(source, config)?;
+    if new && config.potd_apply {
+        let installed = wallpaper::apply_path(&path, wallpaper::get_current_fill_mode().unwrap_or_default(), config)?;
+        events.emit(AppEvent::WallpaperApplied(installed));
+    }
+    Ok(())
+}
+
+/// Apply a wallpaper chosen by `command` without a running TUI
+pub fn apply_command(command: &IpcCommand, config: &Config, events: &mut EventBus) -> Result<()> {
+    let target = match command {
+        IpcCommand::Next => wallpaper::relative_wallpaper(&rotation_pool(config)?, 1),
+        IpcCommand::Prev => wallpaper::relative_wallpaper(&rotation_pool(config)?, -1),
+        IpcCommand::Random => history::smart_random(&rotation_pool(config)?, config),
+        IpcCommand::Set(path) => Some(path.clone()),
+        IpcCommand::Reload => return pins::restore(),
+    };
+    let target = target.ok_or_else(|| eyre!("no wallpapers"))?;
+    let installed = wallpaper::apply_path(&target, wallpaper::get_current_fill_mode().unwrap_or_default(), config)?;
+    events.emit(AppEvent::WallpaperApplied(installed));
+    Ok(())
+}
+
+/// Wallpapers to rotate through: the configured collection, or the whole backgrounds dir
+fn rotation_pool(config: &Config) -> Result<Vec<PathBuf>> {
+    match &config.rotation_collection {
+        Some(name) => Collections::load()?.paths(name),
+        None => Ok(wallpaper::discover_wallpapers(None)?
+            .into_iter()
+            .map(|w| w.path)
+            .collect()),
+    }
+}
+
+/// `--next` / `--prev` / `--random` / `--set` / `--apply-current`: hand the command to a running
+/// instance if there is one, so its grid and subscribers stay in sync, otherwise apply it directly
+pub fn quick_switch(command: IpcCommand) -> Result<()> {
+    if let Some(reply) = crate::ipc::send(&command)? {
+        return match reply.strip_prefix("error: ") {
+            Some(e) => Err(eyre!("{}", e)),
+            None => Ok(()),
+        };
+    }
+    // Also keeps a second command started at the same moment from racing this one
+    let _lock = InstanceLock::acquire(Role::OneShot)?;
+
+    let config = Config::load()?;
+    wallpaper::configure(&config);
+    let mut events = EventBus::default();
+    hooks::subscribe(&mut events, &config.hooks);
+    history::subscribe(&mut events);
+    variants::subscribe(&mut events, &config);
+    notifications::subscribe(&mut events, &config);
+    let result = apply_command(&command, &config, &mut events);
+    // Let variant generation and notifications finish before exiting
+    events::wait_for_background();
+    result
+}
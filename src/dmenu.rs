@@ -0,0 +1,71 @@
+use crate::config::Config;
+use crate::daemon;
+use crate::ipc::IpcCommand;
+use crate::wallpaper::{self, Wallpaper};
+use color_eyre::{eyre::eyre, Result};
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// `--dmenu [command]`: a text-only picker. Prints wallpaper names, reads the chosen line back
+/// and applies it. Without a command the names go to stdout and the choice comes from stdin;
+/// with one, e.g. `fuzzel --dmenu` or `rofi -dmenu`, it is run with the names on its stdin.
+pub fn run(launcher: Option<&str>) -> Result<()> {
+    let config = Config::load()?;
+    wallpaper::configure(&config);
+    let wallpapers = wallpaper::discover_wallpapers(None)?;
+    let menu: String = wallpapers.iter().map(|w| label(w) + "\n").collect();
+
+    let choice = match launcher {
+        Some(launcher) => choose_with(launcher, &menu)?,
+        None => {
+            let mut out = io::stdout().lock();
+            out.write_all(menu.as_bytes())?;
+            out.flush()?;
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line
+        }
+    };
+    let choice = choice.trim_end_matches(['\n', '\r']);
+    // Dismissing the menu picks nothing
+    if choice.is_empty() {
+        return Ok(());
+    }
+    let path = wallpapers
+        .into_iter()
+        .find(|w| label(w) == choice)
+        .map(|w| w.path)
+        .or_else(|| Some(PathBuf::from(choice)).filter(|p| p.is_file()))
+        .ok_or_else(|| eyre!("No wallpaper named {}", choice))?;
+    daemon::quick_switch(IpcCommand::Set(path))
+}
+
+/// The menu line for a wallpaper; names from different library roots can collide
+fn label(wallpaper: &Wallpaper) -> String {
+    match &wallpaper.origin {
+        Some(origin) => format!("{} ({})", wallpaper.name, origin),
+        None => wallpaper.name.clone(),
+    }
+}
+
+/// Run `launcher` through the shell with `menu` on its stdin and return what it printed
+fn choose_with(launcher: &str, menu: &str) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(launcher)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("{}: {}", launcher, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A launcher that exits early closes the pipe; its choice still counts
+        let _ = stdin.write_all(menu.as_bytes());
+    }
+    let mut choice = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout.read_to_string(&mut choice)?;
+    }
+    child.wait()?;
+    Ok(choice)
+}
@@ -0,0 +1,155 @@
+use crate::net;
+use color_eyre::{eyre::eyre, Result};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Transfers running at once unless configured
+pub const DEFAULT_CONCURRENCY: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DownloadState {
+    Queued,
+    Active,
+    Done,
+    Failed(String),
+}
+
+/// One file in the download queue
+pub struct Download {
+    pub id: u64,
+    pub url: String,
+    pub dest: PathBuf,
+    /// Hex MD5 the source published for the file, checked before it's moved into place
+    pub expected_md5: Option<String>,
+    pub received: u64,
+    pub total: Option<u64>,
+    pub state: DownloadState,
+}
+
+impl Download {
+    /// Completed fraction, when the size is known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.filter(|&t| t > 0).map(|t| self.received as f64 / t as f64)
+    }
+}
+
+enum Update {
+    Progress { id: u64, received: u64, total: Option<u64> },
+    Finished { id: u64, result: Result<()> },
+}
+
+/// Queue of network downloads, run on background threads a few at a time
+pub struct DownloadManager {
+    items: Vec<Download>,
+    tx: Sender<Update>,
+    rx: Receiver<Update>,
+    concurrency: usize,
+    next_id: u64,
+}
+
+impl DownloadManager {
+    pub fn new(concurrency: usize) -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self { items: Vec::new(), tx, rx, concurrency: concurrency.max(1), next_id: 0 }
+    }
+
+    /// Queue `url` to be saved as `dest`, verified against `expected_md5` when the source
+    /// gives one; the id identifies it in `poll` results
+    pub fn enqueue(&mut self, url: String, dest: PathBuf, expected_md5: Option<String>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.items.push(Download {
+            id,
+            url,
+            dest,
+            expected_md5,
+            received: 0,
+            total: None,
+            state: DownloadState::Queued,
+        });
+        self.start_queued();
+        id
+    }
+
+    fn start_queued(&mut self) {
+        let active = self.items.iter().filter(|d| d.state == DownloadState::Active).count();
+        let free = self.concurrency.saturating_sub(active);
+        for item in self.items.iter_mut().filter(|d| d.state == DownloadState::Queued).take(free) {
+            item.state = DownloadState::Active;
+            let (id, url, dest, md5) = (item.id, item.url.clone(), item.dest.clone(), item.expected_md5.clone());
+            let tx = self.tx.clone();
+            thread::spawn(move || {
+                let progress_tx = tx.clone();
+                let result = net::download_with_progress(&url, &dest, md5.as_deref(), |received, total| {
+                    let _ = progress_tx.send(Update::Progress { id, received, total });
+                });
+                let _ = tx.send(Update::Finished { id, result });
+            });
+        }
+    }
+
+    /// Apply progress from the workers and start queued downloads as slots free up.
+    /// Returns whether anything changed, and each download that finished with its outcome.
+    pub fn poll(&mut self) -> (bool, Vec<(u64, Result<PathBuf>)>) {
+        let mut changed = false;
+        let mut finished = Vec::new();
+        for update in self.rx.try_iter() {
+            changed = true;
+            match update {
+                Update::Progress { id, received, total } => {
+                    if let Some(item) = self.items.iter_mut().find(|d| d.id == id) {
+                        item.received = received;
+                        item.total = total;
+                    }
+                }
+                Update::Finished { id, result } => {
+                    let Some(item) = self.items.iter_mut().find(|d| d.id == id) else {
+                        continue;
+                    };
+                    match result {
+                        Ok(()) => {
+                            item.state = DownloadState::Done;
+                            finished.push((id, Ok(item.dest.clone())));
+                        }
+                        Err(e) => {
+                            item.state = DownloadState::Failed(e.to_string());
+                            finished.push((id, Err(eyre!("Download failed: {}", e))));
+                        }
+                    }
+                }
+            }
+        }
+        if !finished.is_empty() {
+            self.start_queued();
+        }
+        (changed, finished)
+    }
+
+    pub fn items(&self) -> &[Download] {
+        &self.items
+    }
+
+    /// Whether anything is queued or transferring
+    pub fn is_busy(&self) -> bool {
+        self.items
+            .iter()
+            .any(|d| matches!(d.state, DownloadState::Queued | DownloadState::Active))
+    }
+
+    /// Drop finished and failed entries from the list
+    pub fn clear_finished(&mut self) {
+        self.items
+            .retain(|d| matches!(d.state, DownloadState::Queued | DownloadState::Active));
+    }
+
+    /// Queue failed downloads again; partial data is resumed
+    pub fn retry_failed(&mut self) {
+        for item in &mut self.items {
+            if matches!(item.state, DownloadState::Failed(_)) {
+                item.state = DownloadState::Queued;
+            }
+        }
+        self.start_queued();
+    }
+}
@@ -1,14 +1,18 @@
+use crate::memory;
+use crate::profile::{self, Phase};
+use crate::terminal;
 use image::DynamicImage;
 use ratatui_image::picker::Picker;
 use ratatui_image::protocol::StatefulProtocol;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 
 /// Request to encode an image for a specific cell size
 pub struct EncodeRequest {
     pub index: usize,
-    pub image: DynamicImage,
+    pub image: Arc<DynamicImage>,
     pub width: u16,
     pub height: u16,
 }
@@ -19,6 +23,8 @@ pub struct EncodeResult {
     pub width: u16,
     pub height: u16,
     pub protocol: StatefulProtocol,
+    /// Estimated size of the protocol state, see `memory::protocol_bytes`
+    pub bytes: usize,
 }
 
 /// Cache key for encoded protocols
@@ -34,10 +40,10 @@ pub struct ImageEncoder {
     tx: Sender<EncodeRequest>,
     rx: Receiver<EncodeResult>,
     _handle: JoinHandle<()>,
-    /// Cache of encoded protocols by (index, width, height)
-    cache: HashMap<CacheKey, StatefulProtocol>,
-    /// Track pending requests to avoid duplicates
-    pending: HashMap<CacheKey, bool>,
+    /// Cache of encoded protocols and their estimated sizes by (index, width, height)
+    cache: HashMap<CacheKey, (StatefulProtocol, usize)>,
+    /// Requests still wanted; shared so the worker skips cells that scrolled away before their turn
+    pending: Arc<Mutex<HashSet<CacheKey>>>,
 }
 
 impl ImageEncoder {
@@ -45,15 +51,29 @@ impl ImageEncoder {
         let (req_tx, req_rx) = mpsc::channel::<EncodeRequest>();
         let (res_tx, res_rx) = mpsc::channel::<EncodeResult>();
 
+        let pending: Arc<Mutex<HashSet<CacheKey>>> = Arc::default();
+        let wanted = Arc::clone(&pending);
+
         let handle = thread::spawn(move || {
             let mut picker = picker;
             while let Ok(request) = req_rx.recv() {
-                let protocol = picker.new_resize_protocol(request.image);
+                let key = CacheKey { index: request.index, width: request.width, height: request.height };
+                if !wanted.lock().unwrap_or_else(|e| e.into_inner()).contains(&key) {
+                    continue;
+                }
+                let (font_width, font_height) = picker.font_size();
+                let cell_pixels = (request.width as u32 * font_width as u32, request.height as u32 * font_height as u32);
+                let bytes = memory::protocol_bytes(&request.image, cell_pixels);
+                let protocol = profile::time(Phase::Encode, || {
+                    // The only copy made, one image at a time, rather than one per queued request
+                    picker.new_resize_protocol(Arc::unwrap_or_clone(request.image))
+                });
                 let _ = res_tx.send(EncodeResult {
                     index: request.index,
                     width: request.width,
                     height: request.height,
                     protocol,
+                    bytes,
                 });
             }
         });
@@ -63,7 +83,7 @@ impl ImageEncoder {
             rx: res_rx,
             _handle: handle,
             cache: HashMap::new(),
-            pending: HashMap::new(),
+            pending,
         }
     }
 
@@ -71,18 +91,17 @@ impl ImageEncoder {
     pub fn request_encode(
         &mut self,
         index: usize,
-        image: DynamicImage,
+        image: Arc<DynamicImage>,
         width: u16,
         height: u16,
     ) {
         let key = CacheKey { index, width, height };
 
         // Skip if already cached or pending
-        if self.cache.contains_key(&key) || self.pending.contains_key(&key) {
+        if self.cache.contains_key(&key) || !self.pending().insert(key) {
             return;
         }
 
-        self.pending.insert(key, true);
         let _ = self.tx.send(EncodeRequest {
             index,
             image,
@@ -91,33 +110,91 @@ impl ImageEncoder {
         });
     }
 
-    /// Poll for completed encodings and update cache
-    pub fn poll_results(&mut self) {
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashSet<CacheKey>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Poll for completed encodings and update cache; returns true if any arrived
+    pub fn poll_results(&mut self) -> bool {
+        let mut arrived = false;
         while let Ok(result) = self.rx.try_recv() {
             let key = CacheKey {
                 index: result.index,
                 width: result.width,
                 height: result.height,
             };
-            self.pending.remove(&key);
-            self.cache.insert(key, result.protocol);
+            // Recycled or cleared while it was encoding
+            if !self.pending().remove(&key) {
+                continue;
+            }
+            arrived = true;
+            terminal::track_image(&result.protocol);
+            if let Some((old, _)) = self.cache.insert(key, (result.protocol, result.bytes)) {
+                terminal::release_image(&old);
+            }
         }
+        arrived
+    }
+
+    /// Recycle protocols for images outside `keep` and drop their queued encodes,
+    /// so memory and encoding work follow the visible window rather than the library size
+    pub fn retain(&mut self, keep: &HashSet<usize>) {
+        self.cache.retain(|key, (protocol, _)| {
+            let kept = keep.contains(&key.index);
+            if !kept {
+                terminal::release_image(protocol);
+            }
+            kept
+        });
+        self.pending().retain(|key| keep.contains(&key.index));
+    }
+
+    /// Recycle the protocols of the images in `indices`, e.g. to stay within the memory budget
+    pub fn evict(&mut self, indices: &HashSet<usize>) {
+        self.cache.retain(|key, (protocol, _)| {
+            let evicted = indices.contains(&key.index);
+            if evicted {
+                terminal::release_image(protocol);
+            }
+            !evicted
+        });
     }
 
     /// Get a cached protocol if available
     pub fn get_cached(&mut self, index: usize, width: u16, height: u16) -> Option<&mut StatefulProtocol> {
         let key = CacheKey { index, width, height };
-        self.cache.get_mut(&key)
+        self.cache.get_mut(&key).map(|(protocol, _)| protocol)
     }
 
     /// Clear cache (e.g., when wallpapers are reloaded)
     pub fn clear_cache(&mut self) {
+        for (protocol, _) in self.cache.values() {
+            terminal::release_image(protocol);
+        }
         self.cache.clear();
-        self.pending.clear();
+        self.pending().clear();
+    }
+
+    /// Whether any encodings are still in flight
+    pub fn has_pending(&self) -> bool {
+        !self.pending().is_empty()
     }
 
-    /// Get the number of cached protocols
-    pub fn cache_len(&self) -> usize {
+    pub fn pending_count(&self) -> usize {
+        self.pending().len()
+    }
+
+    /// Number of encoded images held
+    pub fn cached_count(&self) -> usize {
         self.cache.len()
     }
+
+    /// Estimated bytes held by encoded images, by wallpaper index
+    pub fn cached_bytes(&self) -> HashMap<usize, usize> {
+        let mut bytes = HashMap::new();
+        for (key, (_, size)) in &self.cache {
+            *bytes.entry(key.index).or_default() += size;
+        }
+        bytes
+    }
 }
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+/// Something that happened in the app that other components may want to react to
+#[derive(Clone, Debug)]
+pub enum AppEvent {
+    /// The highlighted wallpaper changed (None when the grid is empty)
+    SelectionChanged(Option<PathBuf>),
+    /// A wallpaper was installed and set as the current background
+    WallpaperApplied(PathBuf),
+    /// The grid was (re)populated from a directory
+    DirectoryLoaded { dir: PathBuf, count: usize },
+}
+
+impl AppEvent {
+    /// Stable identifier used for hook names and IPC messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppEvent::SelectionChanged(_) => "selection_changed",
+            AppEvent::WallpaperApplied(_) => "wallpaper_applied",
+            AppEvent::DirectoryLoaded { .. } => "directory_loaded",
+        }
+    }
+}
+
+/// Threads started by subscribers, so short-lived processes can wait for them
+static BACKGROUND: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// Run a subscriber's slow work (image processing, D-Bus) without blocking the emitter
+pub fn spawn_background<F>(work: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let handle = thread::spawn(work);
+    let mut background = BACKGROUND.lock().unwrap_or_else(|e| e.into_inner());
+    background.retain(|h| !h.is_finished());
+    background.push(handle);
+}
+
+/// Block until work from `spawn_background` is done; exiting first would cut it short
+pub fn wait_for_background() {
+    let handles = std::mem::take(&mut *BACKGROUND.lock().unwrap_or_else(|e| e.into_inner()));
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
+
+type Subscriber = Box<dyn FnMut(&AppEvent) + Send>;
+
+/// Fan-out of app events to every registered subscriber, in registration order
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Vec<Subscriber>,
+}
+
+impl EventBus {
+    pub fn subscribe<F>(&mut self, subscriber: F)
+    where
+        F: FnMut(&AppEvent) + Send + 'static,
+    {
+        self.subscribers.push(Box::new(subscriber));
+    }
+
+    pub fn emit(&mut self, event: AppEvent) {
+        for subscriber in &mut self.subscribers {
+            subscriber(&event);
+        }
+    }
+}
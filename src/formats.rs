@@ -0,0 +1,260 @@
+use color_eyre::{eyre::eyre, Result};
+use image::DynamicImage;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+}
+
+/// Formats with feature-gated decoders, and whether this build has each one
+const OPTIONAL_DECODERS: &[(&str, bool)] = &[
+    ("avif", cfg!(feature = "avif")),
+    ("heic", cfg!(feature = "heic")),
+    ("heif", cfg!(feature = "heic")),
+    ("jxl", cfg!(feature = "jxl")),
+];
+
+fn optional_decoder(path: &Path) -> Option<bool> {
+    let ext = extension(path)?;
+    OPTIONAL_DECODERS
+        .iter()
+        .find(|(name, _)| *name == ext)
+        .map(|(_, enabled)| *enabled)
+}
+
+/// Formats that are listed in the grid even when no decoder is compiled in,
+/// so they show up with an "unsupported" badge rather than silently vanishing
+pub fn is_optional_format(path: &Path) -> bool {
+    optional_decoder(path).is_some()
+}
+
+/// Whether this build can decode the file (decoders for newer formats are cargo features)
+pub fn is_supported(path: &Path) -> bool {
+    optional_decoder(path).unwrap_or(true)
+}
+
+/// Pixel size from the file header, without decoding; None for vector or unreadable images
+pub fn dimensions(path: &Path) -> Option<(u32, u32)> {
+    if crate::svg::is_svg(path) || !is_supported(path) {
+        return None;
+    }
+    image::image_dimensions(path).ok()
+}
+
+/// Whether the file holds more than one frame (animated GIF, APNG or WebP), from its headers
+pub fn is_animated(path: &Path) -> bool {
+    let Some(ext) = extension(path) else {
+        return false;
+    };
+    File::open(path)
+        .and_then(|file| animated(&ext, BufReader::new(file)))
+        .unwrap_or(false)
+}
+
+fn animated(ext: &str, mut reader: impl Read + Seek) -> io::Result<bool> {
+    let mut byte = [0u8; 1];
+    let mut read_byte = |reader: &mut dyn Read| reader.read_exact(&mut byte).map(|_| byte[0]);
+    match ext {
+        "gif" => {
+            // Header and screen descriptor, then the global color table if there is one
+            let mut header = [0u8; 13];
+            reader.read_exact(&mut header)?;
+            if header[10] & 0x80 != 0 {
+                reader.seek(SeekFrom::Current(3 << ((header[10] & 7) + 1)))?;
+            }
+            let mut frames = 0;
+            loop {
+                match read_byte(&mut reader)? {
+                    // Extension: label, then data sub-blocks
+                    0x21 => {
+                        read_byte(&mut reader)?;
+                    }
+                    // Image: descriptor, local color table, LZW code size, then data sub-blocks
+                    0x2C => {
+                        frames += 1;
+                        if frames > 1 {
+                            return Ok(true);
+                        }
+                        let mut descriptor = [0u8; 9];
+                        reader.read_exact(&mut descriptor)?;
+                        if descriptor[8] & 0x80 != 0 {
+                            reader.seek(SeekFrom::Current(3 << ((descriptor[8] & 7) + 1)))?;
+                        }
+                        read_byte(&mut reader)?;
+                    }
+                    _ => return Ok(false),
+                }
+                loop {
+                    let len = read_byte(&mut reader)?;
+                    if len == 0 {
+                        break;
+                    }
+                    reader.seek(SeekFrom::Current(len as i64))?;
+                }
+            }
+        }
+        // APNG declares its frames in an acTL chunk ahead of the image data
+        "png" | "apng" => {
+            reader.seek(SeekFrom::Start(8))?;
+            loop {
+                let mut chunk = [0u8; 8];
+                reader.read_exact(&mut chunk)?;
+                match &chunk[4..] {
+                    b"acTL" => return Ok(true),
+                    b"IDAT" | b"IEND" => return Ok(false),
+                    _ => {}
+                }
+                let len = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+                reader.seek(SeekFrom::Current(len as i64 + 4))?;
+            }
+        }
+        // Extended WebP sets an animation flag in its VP8X chunk
+        "webp" => {
+            let mut header = [0u8; 21];
+            reader.read_exact(&mut header)?;
+            Ok(&header[12..16] == b"VP8X" && header[20] & 0x02 != 0)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Decode a raster image, routing formats the `image` crate can't read to their own decoders
+pub fn decode(path: &Path) -> Result<DynamicImage> {
+    decode_until(path, &AtomicBool::new(false))
+}
+
+/// Reads `inner` until `stop` is set, then fails every read
+struct Stoppable<'a, R> {
+    inner: R,
+    stop: &'a AtomicBool,
+}
+
+impl<R: Read> Read for Stoppable<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.stop.load(Ordering::Relaxed) {
+            // Not `Interrupted`, which `read_exact` would retry
+            return Err(io::Error::other("decode cancelled"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for Stoppable<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// `decode`, giving up once `stop` is set. Decoders that read as they go (PNG, GIF, WebP, JXL)
+/// stop partway through; the rest stop once their input is read.
+pub fn decode_until(path: &Path, stop: &AtomicBool) -> Result<DynamicImage> {
+    if !is_supported(path) {
+        return Err(eyre!(
+            "{}: unsupported format (rebuild with the matching cargo feature)",
+            path.display()
+        ));
+    }
+    match extension(path).as_deref() {
+        #[cfg(feature = "heic")]
+        Some("heic" | "heif") => decode_heic(path),
+        #[cfg(feature = "jxl")]
+        Some("jxl") => decode_jxl(path, stop),
+        // AVIF goes through image's `avif-native` decoder when the feature is on
+        _ => {
+            let file = BufReader::new(Stoppable { inner: File::open(path)?, stop });
+            let reader = image::ImageReader::with_format(file, image::ImageFormat::from_path(path)?);
+            Ok(reader.decode()?)
+        }
+    }
+}
+
+#[cfg(feature = "jxl")]
+fn decode_jxl(path: &Path, stop: &AtomicBool) -> Result<DynamicImage> {
+    let file = BufReader::new(Stoppable { inner: File::open(path)?, stop });
+    let decoder = jxl_oxide::integration::JxlDecoder::new(file)?;
+    Ok(DynamicImage::from_decoder(decoder)?)
+}
+
+#[cfg(feature = "heic")]
+fn decode_heic(path: &Path) -> Result<DynamicImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let path_str = path.to_str().ok_or_else(|| eyre!("Non-UTF-8 path"))?;
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file(path_str)?;
+    let handle = ctx.primary_image_handle()?;
+    let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+
+    let planes = image.planes();
+    let plane = planes.interleaved.ok_or_else(|| eyre!("HEIC has no interleaved plane"))?;
+    let (width, height) = (plane.width, plane.height);
+
+    // Rows may be padded; copy only the pixel bytes of each
+    let row_len = width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    let buffer = image::RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| eyre!("Failed to decode {}", path.display()))?;
+    Ok(DynamicImage::ImageRgba8(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn gif(frames: usize) -> Vec<u8> {
+        let mut bytes = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        // Looping extension, as animations carry
+        bytes.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+        for _ in 0..frames {
+            bytes.extend_from_slice(&[0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0, 2, 2, 0x44, 0x01, 0]);
+        }
+        bytes.push(0x3B);
+        bytes
+    }
+
+    fn png(chunks: &[&[u8; 4]]) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        for chunk in chunks {
+            bytes.extend_from_slice(&[0, 0, 0, 1]);
+            bytes.extend_from_slice(*chunk);
+            bytes.extend_from_slice(&[0, 0, 0, 0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn stopped_decodes_give_up() {
+        let dir = std::env::temp_dir().join(format!("owp-{}-{}", "decode-stop", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plain.png");
+        image::RgbImage::new(64, 64).save(&path).unwrap();
+
+        assert_eq!(decode_until(&path, &AtomicBool::new(false)).unwrap().width(), 64);
+        assert!(decode_until(&path, &AtomicBool::new(true)).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn detects_animation_from_headers() {
+        let check = |ext, bytes: Vec<u8>| animated(ext, Cursor::new(bytes)).unwrap_or(false);
+        assert!(check("gif", gif(2)));
+        assert!(!check("gif", gif(1)));
+        assert!(check("png", png(&[b"IHDR", b"acTL", b"IDAT"])));
+        assert!(!check("png", png(&[b"IHDR", b"IDAT", b"acTL"])));
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+        webp.extend_from_slice(&[0x02, 0, 0, 0]);
+        assert!(check("webp", webp.clone()));
+        webp[20] = 0x10;
+        assert!(!check("webp", webp));
+    }
+}
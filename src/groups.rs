@@ -0,0 +1,245 @@
+//! `:group-by`: sections in the grid, each starting on a new row under a header
+
+use crate::browse::Nav;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The first collection a wallpaper is in
+    Tag,
+    /// Month the file was last modified, newest first
+    Month,
+    /// Containing directory
+    Dir,
+}
+
+impl GroupBy {
+    pub const NAMES: &'static [&'static str] = &["tag", "month", "dir", "off"];
+
+    /// None for `off`, which is handled by the caller
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "tag" => Some(GroupBy::Tag),
+            "month" => Some(GroupBy::Month),
+            "dir" => Some(GroupBy::Dir),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupBy::Tag => "tag",
+            GroupBy::Month => "month",
+            GroupBy::Dir => "dir",
+        }
+    }
+}
+
+/// Header shown for wallpapers in no collection; sorts after every real one
+pub const UNTAGGED: &str = "Untagged";
+
+/// A run of grid positions sharing a header
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub label: String,
+    pub range: Range<usize>,
+}
+
+/// One line of the grouped grid: a header or up to `columns` cells
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayoutRow {
+    Header(usize),
+    Cells(Range<usize>),
+}
+
+/// Stable-sort `positions` by their label and return the groups they form. `newest_first`
+/// reverses the label order, for months.
+pub fn group(positions: &mut Vec<usize>, labels: &[String], newest_first: bool) -> Vec<Group> {
+    let mut keyed: Vec<(usize, &String)> = positions.iter().copied().zip(labels).collect();
+    keyed.sort_by(|(_, a), (_, b)| {
+        let order = (a.as_str() == UNTAGGED).cmp(&(b.as_str() == UNTAGGED));
+        order.then_with(|| if newest_first { b.cmp(a) } else { a.cmp(b) })
+    });
+    *positions = keyed.iter().map(|&(i, _)| i).collect();
+
+    let mut groups: Vec<Group> = Vec::new();
+    for (pos, (_, label)) in keyed.iter().enumerate() {
+        match groups.last_mut() {
+            Some(group) if group.label == **label => group.range.end = pos + 1,
+            _ => groups.push(Group { label: label.to_string(), range: pos..pos + 1 }),
+        }
+    }
+    groups
+}
+
+/// Header and cell rows for `groups` in a grid `columns` wide
+pub fn layout(groups: &[Group], columns: usize) -> Vec<LayoutRow> {
+    let columns = columns.max(1);
+    let mut rows = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        rows.push(LayoutRow::Header(i));
+        for start in group.range.clone().step_by(columns) {
+            rows.push(LayoutRow::Cells(start..(start + columns).min(group.range.end)));
+        }
+    }
+    rows
+}
+
+impl LayoutRow {
+    /// Lines the row takes: one for a header, a full cell for cells
+    pub fn height(&self, cell_height: u16) -> u16 {
+        match self {
+            LayoutRow::Header(_) => 1,
+            LayoutRow::Cells(_) => cell_height,
+        }
+    }
+}
+
+/// First row to draw so `selected` sits near the middle of `height` lines, without leaving
+/// space below the last row
+pub fn scroll_target(heights: &[u16], selected: usize, height: u16) -> usize {
+    let Some(&own) = heights.get(selected) else {
+        return 0;
+    };
+    let budget = u32::from(height.saturating_add(own) / 2);
+    let (mut start, mut used) = (selected, u32::from(own));
+    while start > 0 && used + u32::from(heights[start - 1]) <= budget {
+        start -= 1;
+        used += u32::from(heights[start]);
+    }
+    let (mut last_start, mut tail) = (heights.len(), 0u32);
+    while last_start > 0 && tail + u32::from(heights[last_start - 1]) <= u32::from(height) {
+        last_start -= 1;
+        tail += u32::from(heights[last_start]);
+    }
+    start.min(last_start)
+}
+
+/// Like `browse::navigate`, with up and down crossing into the neighbouring group at the same
+/// column, or its nearest cell
+pub fn navigate(selected: usize, groups: &[Group], columns: usize, nav: Nav) -> usize {
+    let columns = columns.max(1);
+    let Some(g) = groups.iter().position(|g| g.range.contains(&selected)) else {
+        return selected;
+    };
+    let range = &groups[g].range;
+    let local = selected - range.start;
+    let column = local % columns;
+    let last = groups.last().map_or(0, |g| g.range.end) - 1;
+    match nav {
+        Nav::Left => selected.saturating_sub(1),
+        Nav::Right => (selected + 1).min(last),
+        Nav::Up if local >= columns => selected - columns,
+        Nav::Up => match g.checked_sub(1).map(|g| &groups[g].range) {
+            Some(prev) => {
+                let last_row = (prev.len() - 1) / columns * columns;
+                (prev.start + last_row + column).min(prev.end - 1)
+            }
+            None => selected,
+        },
+        Nav::Down if local / columns < (range.len() - 1) / columns => (selected + columns).min(range.end - 1),
+        Nav::Down => match groups.get(g + 1) {
+            Some(next) => (next.range.start + column).min(next.range.end - 1),
+            None => selected,
+        },
+    }
+}
+
+/// First position of the group `step` groups away from the one holding `selected`
+pub fn jump(selected: usize, groups: &[Group], step: isize) -> usize {
+    let Some(g) = groups.iter().position(|g| g.range.contains(&selected)) else {
+        return selected;
+    };
+    // Going back from inside a group first returns to its start
+    let target = if step < 0 && selected > groups[g].range.start {
+        g as isize + step + 1
+    } else {
+        g as isize + step
+    };
+    groups[target.clamp(0, groups.len() as isize - 1) as usize].range.start
+}
+
+/// `YYYY-MM` of `time` in UTC
+pub fn month_label(time: SystemTime) -> String {
+    let days = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / 86_400) as i64;
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}", year, month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn labels(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn groups_are_contiguous_and_ordered() {
+        let mut positions = vec![10, 11, 12, 13, 14];
+        let groups = group(&mut positions, &labels(&["b", UNTAGGED, "a", "b", "a"]), false);
+        assert_eq!(positions, [12, 14, 10, 13, 11]);
+        let names: Vec<&str> = groups.iter().map(|g| g.label.as_str()).collect();
+        assert_eq!(names, ["a", "b", UNTAGGED]);
+        assert_eq!(groups[1].range, 2..4);
+
+        let mut positions = vec![0, 1, 2];
+        let groups = group(&mut positions, &labels(&["2023-01", "2024-05", "2023-01"]), true);
+        assert_eq!(positions, [1, 0, 2]);
+        assert_eq!(groups[0].label, "2024-05");
+    }
+
+    #[test]
+    fn layout_breaks_rows_at_groups() {
+        let groups = [Group { label: "a".into(), range: 0..5 }, Group { label: "b".into(), range: 5..6 }];
+        assert_eq!(
+            layout(&groups, 4),
+            [LayoutRow::Header(0), LayoutRow::Cells(0..4), LayoutRow::Cells(4..5), LayoutRow::Header(1), LayoutRow::Cells(5..6)]
+        );
+    }
+
+    #[test]
+    fn scrolling_centres_the_selection() {
+        // Header, three rows of 10 lines, header, two rows
+        let heights = [1, 10, 10, 10, 1, 10, 10];
+        assert_eq!(scroll_target(&heights, 1, 30), 0);
+        assert_eq!(scroll_target(&heights, 3, 30), 2);
+        // Near the end the last rows fill the screen instead
+        assert_eq!(scroll_target(&heights, 6, 30), 4);
+        assert_eq!(scroll_target(&heights, 0, 100), 0);
+    }
+
+    #[test]
+    fn navigation_crosses_groups() {
+        let groups = [Group { label: "a".into(), range: 0..5 }, Group { label: "b".into(), range: 5..11 }];
+        // Down from the first row of "a" stays in it, clamped to its partial last row
+        assert_eq!(navigate(2, &groups, 4, Nav::Down), 4);
+        assert_eq!(navigate(4, &groups, 4, Nav::Down), 5);
+        assert_eq!(navigate(7, &groups, 4, Nav::Up), 4);
+        assert_eq!(navigate(5, &groups, 4, Nav::Up), 4);
+        assert_eq!(navigate(9, &groups, 4, Nav::Down), 9);
+        assert_eq!(navigate(10, &groups, 4, Nav::Right), 10);
+
+        assert_eq!(jump(2, &groups, 1), 5);
+        assert_eq!(jump(7, &groups, -1), 5);
+        assert_eq!(jump(5, &groups, -1), 0);
+        assert_eq!(jump(7, &groups, 1), 5);
+    }
+
+    #[test]
+    fn months() {
+        assert_eq!(month_label(UNIX_EPOCH), "1970-01");
+        // 2024-02-29
+        assert_eq!(month_label(UNIX_EPOCH + Duration::from_secs(19_782 * 86_400)), "2024-02");
+    }
+}
@@ -0,0 +1,186 @@
+use crate::collections::Collections;
+use crate::config::Config;
+use crate::events::{AppEvent, EventBus};
+use crate::ospath;
+use crate::wallpaper::same_wallpaper;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Applies remembered, enough to cover any sensible `random_avoid_recent`
+const MAX_HISTORY: usize = 100;
+/// Recent wallpapers random picks skip unless configured
+const DEFAULT_AVOID_RECENT: usize = 10;
+/// How much likelier favorites are to be picked unless configured
+const DEFAULT_FAVORITE_WEIGHT: u32 = 3;
+/// Collection whose members count as favorites unless configured
+const DEFAULT_FAVORITES: &str = "favorites";
+
+/// Recently applied wallpapers, newest first, stored under `$XDG_STATE_HOME`
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(transparent)]
+pub struct History {
+    #[serde(with = "ospath::seq")]
+    recent: VecDeque<PathBuf>,
+}
+
+pub fn get_history_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("history.json")
+}
+
+impl History {
+    /// Load the history; a missing or unreadable file means nothing was applied yet
+    pub fn load() -> Self {
+        fs::read_to_string(get_history_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_history_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, path: &Path) {
+        self.recent.retain(|p| !same_wallpaper(p, path));
+        self.recent.push_front(path.to_path_buf());
+        self.recent.truncate(MAX_HISTORY);
+    }
+
+    /// Append another library's history after ours, skipping wallpapers already in it
+    pub fn merge(&mut self, other: History) {
+        for path in other.recent {
+            if !self.recent.iter().any(|p| same_wallpaper(p, &path)) {
+                self.recent.push_back(path);
+            }
+        }
+        self.recent.truncate(MAX_HISTORY);
+    }
+
+    /// Rewrite every path with `map`
+    pub fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        for path in self.recent.iter_mut() {
+            *path = map(path);
+        }
+    }
+
+    /// Whether `path` was among the last `count` applies
+    pub fn is_recent(&self, path: &Path, count: usize) -> bool {
+        self.recent.iter().take(count).any(|p| same_wallpaper(p, path))
+    }
+}
+
+/// Remember every applied wallpaper for smart random picks
+pub fn subscribe(bus: &mut EventBus) {
+    bus.subscribe(|event| {
+        if let AppEvent::WallpaperApplied(path) = event {
+            let mut history = History::load();
+            history.record(path);
+            // Losing history only makes random picks less varied
+            let _ = history.save();
+        }
+    });
+}
+
+/// Pick a random wallpaper, skipping the configured number of recent applies and weighting
+/// favorites higher. With too few candidates to avoid them all, only the current one is skipped.
+pub fn smart_random(candidates: &[PathBuf], config: &Config) -> Option<PathBuf> {
+    let history = History::load();
+    let favorites = Collections::load()
+        .ok()
+        .and_then(|c| c.paths(config.favorites_collection.as_deref().unwrap_or(DEFAULT_FAVORITES)).ok())
+        .unwrap_or_default();
+    let avoid = config.random_avoid_recent.unwrap_or(DEFAULT_AVOID_RECENT);
+    let favorite_weight = config.favorite_weight.unwrap_or(DEFAULT_FAVORITE_WEIGHT);
+    pick(candidates, &history, avoid, |path| {
+        if is_favorite(path, &favorites) { favorite_weight } else { 1 }
+    })
+}
+
+/// Candidates may be installed copies of the favorited source files, or the other way round
+fn is_favorite(path: &Path, favorites: &[PathBuf]) -> bool {
+    favorites.iter().any(|f| same_wallpaper(f, path))
+}
+
+fn pick(
+    candidates: &[PathBuf],
+    history: &History,
+    avoid: usize,
+    weight: impl Fn(&Path) -> u32,
+) -> Option<PathBuf> {
+    let pool = [avoid, 1, 0]
+        .into_iter()
+        .map(|count| {
+            candidates
+                .iter()
+                .filter(|p| !history.is_recent(p, count))
+                .collect::<Vec<_>>()
+        })
+        .find(|pool| !pool.is_empty())?;
+    let weights: Vec<u32> = pool.iter().map(|p| weight(p).max(1)).collect();
+    let mut roll = fastrand::u32(..weights.iter().sum::<u32>());
+    for (path, w) in pool.iter().zip(&weights) {
+        if roll < *w {
+            return Some((*path).clone());
+        }
+        roll -= w;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favorites_match_installed_copies() {
+        let favorites = [PathBuf::from("/pics/sea.svg")];
+        assert!(is_favorite(Path::new("/backgrounds/sea.png"), &favorites));
+        assert!(!is_favorite(Path::new("/backgrounds/seal.png"), &favorites));
+    }
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(|n| PathBuf::from(format!("/w/{}.jpg", n))).collect()
+    }
+
+    #[test]
+    fn never_repeats_recent() {
+        let candidates = paths(&["a", "b", "c", "d"]);
+        let mut history = History::default();
+        for p in &candidates[..3] {
+            history.record(p);
+        }
+        for _ in 0..50 {
+            assert_eq!(pick(&candidates, &history, 3, |_| 1), Some(candidates[3].clone()));
+        }
+    }
+
+    #[test]
+    fn falls_back_when_everything_is_recent() {
+        let candidates = paths(&["a", "b"]);
+        let mut history = History::default();
+        history.record(&candidates[0]);
+        history.record(&candidates[1]);
+        // Only the current wallpaper (b) is skipped
+        assert_eq!(pick(&candidates, &history, 10, |_| 1), Some(candidates[0].clone()));
+        assert_eq!(pick(&paths(&["b"]), &history, 10, |_| 1), Some(candidates[1].clone()));
+    }
+
+    #[test]
+    fn installed_copies_match_sources() {
+        let mut history = History::default();
+        history.record(Path::new("/backgrounds/sunset.jpg"));
+        assert!(history.is_recent(Path::new("/pictures/sunset.jpg"), 1));
+        assert!(!history.is_recent(Path::new("/pictures/sunrise.jpg"), 1));
+    }
+}
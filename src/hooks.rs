@@ -0,0 +1,50 @@
+use crate::events::{AppEvent, EventBus};
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Subscribe user hook commands (`[hooks]` in the config, keyed by event name).
+/// Each command runs via `sh -c` with the event details in the environment:
+/// `WALLPAPER_EVENT`, plus `WALLPAPER_PATH` or `WALLPAPER_DIR`/`WALLPAPER_COUNT`.
+pub fn subscribe(bus: &mut EventBus, hooks: &HashMap<String, String>) {
+    if hooks.is_empty() {
+        return;
+    }
+    let hooks = hooks.clone();
+    bus.subscribe(move |event| {
+        if let Some(command) = hooks.get(event.name()) {
+            run_hook(command, event);
+        }
+    });
+}
+
+fn run_hook(command: &str, event: &AppEvent) {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("WALLPAPER_EVENT", event.name())
+        // Hooks must never scribble over the TUI
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    match event {
+        AppEvent::SelectionChanged(path) => {
+            if let Some(path) = path {
+                cmd.env("WALLPAPER_PATH", path);
+            }
+        }
+        AppEvent::WallpaperApplied(path) => {
+            cmd.env("WALLPAPER_PATH", path);
+        }
+        AppEvent::DirectoryLoaded { dir, count } => {
+            cmd.env("WALLPAPER_DIR", dir).env("WALLPAPER_COUNT", count.to_string());
+        }
+    }
+
+    // A slow hook shouldn't block the UI; it is waited on from its own thread so it
+    // doesn't linger as a zombie once it exits
+    if let Ok(mut child) = cmd.spawn() {
+        thread::spawn(move || child.wait());
+    }
+}
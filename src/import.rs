@@ -0,0 +1,134 @@
+use crate::{trashcan, wallpaper};
+use color_eyre::{eyre::eyre, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What to do when an imported file's name is already taken in the library
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    /// Import as `name-1.ext`, `name-2.ext`, ...
+    Rename,
+    Skip,
+    Overwrite,
+}
+
+/// What an import wrote
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub paths: Vec<PathBuf>,
+    /// Of those, the ones whose earlier file `--overwrite` moved to the trash
+    pub replaced: Vec<PathBuf>,
+}
+
+/// Images matched by an `:import` pattern, ready to be copied or linked into a directory
+pub struct ImportPlan {
+    pub sources: Vec<PathBuf>,
+    pub dest_dir: PathBuf,
+    /// Symlink instead of copying
+    pub link: bool,
+    /// Set by `--rename` / `--skip` / `--overwrite`; asked for when unset and names collide
+    pub collision: Option<Collision>,
+}
+
+impl ImportPlan {
+    /// Parse `<path-or-glob> [--link] [--rename|--skip|--overwrite]`.
+    /// A directory imports every image directly inside it.
+    pub fn parse(args: &[String], dest_dir: PathBuf) -> Result<Self> {
+        let mut pattern = None;
+        let mut link = false;
+        let mut collision = None;
+        for arg in args {
+            match arg.as_str() {
+                "--link" => link = true,
+                "--rename" => collision = Some(Collision::Rename),
+                "--skip" => collision = Some(Collision::Skip),
+                "--overwrite" => collision = Some(Collision::Overwrite),
+                other if other.starts_with("--") => return Err(eyre!("Unknown option: {}", other)),
+                other => pattern = Some(other),
+            }
+        }
+        let pattern = pattern.ok_or_else(|| eyre!("import needs a path or glob"))?;
+        let sources = expand(pattern)?;
+        if sources.is_empty() {
+            return Err(eyre!("No images match {}", pattern));
+        }
+        Ok(Self { sources, dest_dir, link, collision })
+    }
+
+    /// Number of sources whose file name already exists in the destination
+    pub fn conflicts(&self) -> usize {
+        self.sources
+            .iter()
+            .filter(|source| self.dest_for(source).exists())
+            .count()
+    }
+
+    fn dest_for(&self, source: &Path) -> PathBuf {
+        self.dest_dir.join(source.file_name().unwrap_or_default())
+    }
+
+    /// Copy or link every source, returning the paths that were written
+    pub fn run(&self, collision: Collision) -> Result<Imported> {
+        fs::create_dir_all(&self.dest_dir)?;
+        let mut imported = Imported::default();
+        for source in &self.sources {
+            let mut dest = self.dest_for(source);
+            // Importing a file onto itself would truncate it
+            if fs::canonicalize(source).ok() == fs::canonicalize(&dest).ok() {
+                continue;
+            }
+            if dest.exists() || dest.is_symlink() {
+                match collision {
+                    Collision::Skip => continue,
+                    Collision::Rename => dest = free_name(&dest),
+                    // Recoverable from the trash if overwriting was a mistake
+                    Collision::Overwrite => {
+                        trashcan::move_to_trash(&dest)?;
+                        imported.replaced.push(dest.clone());
+                    }
+                }
+            }
+            if self.link {
+                let source = fs::canonicalize(source)?;
+                std::os::unix::fs::symlink(&source, &dest)?;
+            } else {
+                fs::copy(source, &dest)?;
+            }
+            imported.paths.push(dest);
+        }
+        Ok(imported)
+    }
+}
+
+/// Resolve a path or glob to the images it names
+fn expand(pattern: &str) -> Result<Vec<PathBuf>> {
+    let path = crate::config::expand_home(Path::new(pattern));
+    let mut sources: Vec<PathBuf> = if path.is_dir() {
+        fs::read_dir(&path)?
+            .flatten()
+            .map(|entry| entry.path())
+            .collect()
+    } else {
+        glob::glob(&path.to_string_lossy())?
+            .flatten()
+            .collect()
+    };
+    sources.retain(|p| p.is_file() && wallpaper::is_image(p));
+    sources.sort();
+    Ok(sources)
+}
+
+/// First `stem-N.ext` next to `dest` that doesn't exist yet
+fn free_name(dest: &Path) -> PathBuf {
+    (1..)
+        .map(|n| {
+            let mut name = wallpaper::stem_with(dest, &format!("-{}", n));
+            if let Some(ext) = dest.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            dest.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists() && !candidate.is_symlink())
+        .unwrap_or_else(|| dest.to_path_buf())
+}
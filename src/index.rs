@@ -0,0 +1,410 @@
+//! SQLite index of what's been learned about each image file (size, embedded rating,
+//! dominant colors, content hash), so reopening a large library doesn't re-read every file.
+//! Rows are keyed by path and only trusted while the file's size and mtime still match;
+//! the hash recognizes files that were renamed or moved since. Collections and `:rate` stars
+//! are mirrored in too, so the library can be queried by resolution, tag, rating and palette
+//! without touching the files.
+
+use crate::collections::Collections;
+use crate::loader::Header;
+use crate::metadata::Metadata;
+use crate::ospath;
+use crate::palette::{self, Rgb};
+use color_eyre::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Bump when the schema changes; older databases are rebuilt from scratch
+const SCHEMA_VERSION: i32 = 3;
+
+/// Bytes hashed from each end of a file; enough to tell images apart without reading them whole
+const HASH_SAMPLE: u64 = 64 * 1024;
+
+/// Another picker process (the daemon, a second window) may be writing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn get_index_path() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".cache"))
+        .join("omarchy-wallpaper-picker")
+        .join("library.db")
+}
+
+/// Identifies one version of a file's contents: its size and modification time in nanoseconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stamp(i64, i64);
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp(meta.len() as i64, modified.as_nanos() as i64))
+    }
+}
+
+/// Filters and order for `Index::query`; unset fields don't filter
+#[derive(Debug, Default)]
+pub struct Query {
+    /// Smallest width and height, e.g. from `--min-size 2560x1440`
+    pub min_dimensions: Option<(u32, u32)>,
+    /// Collection the file must be in, case-insensitively
+    pub tag: Option<String>,
+    /// Fewest stars, from `:rate` or else embedded in the file
+    pub min_stars: Option<u8>,
+    /// Best match with these colors first; files whose colors aren't known yet go last
+    pub palette: Vec<Rgb>,
+}
+
+/// What the index knows about an unchanged file
+#[derive(Debug, Default, PartialEq)]
+pub struct Record {
+    /// The header pass has run; None dimensions then mean the format has no readable size
+    pub header: Option<Header>,
+    pub dominant: Option<Vec<(Rgb, f32)>>,
+    pub luminance: Option<f32>,
+    pub hash: Option<String>,
+}
+
+pub struct Index {
+    conn: Connection,
+}
+
+impl Index {
+    pub fn open() -> Result<Self> {
+        let path = get_index_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // A cache: losing the last few writes in a crash is fine, waiting on fsync isn't
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            conn.execute_batch("DROP TABLE IF EXISTS files; DROP TABLE IF EXISTS tags; DROP TABLE IF EXISTS stars;")?;
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS files (
+                path BLOB PRIMARY KEY,
+                size INTEGER NOT NULL,
+                mtime INTEGER NOT NULL,
+                header_read INTEGER NOT NULL DEFAULT 0,
+                width INTEGER,
+                height INTEGER,
+                rating INTEGER,
+                dominant TEXT,
+                luminance REAL,
+                hash TEXT
+            );
+            CREATE INDEX IF NOT EXISTS files_hash ON files (hash);
+            CREATE TABLE IF NOT EXISTS tags (
+                path BLOB NOT NULL,
+                tag TEXT NOT NULL COLLATE NOCASE,
+                PRIMARY KEY (path, tag)
+            );
+            CREATE TABLE IF NOT EXISTS stars (
+                path BLOB PRIMARY KEY,
+                stars INTEGER NOT NULL
+            );",
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// The stored record for `path`, if any and the file hasn't changed since
+    pub fn lookup(&self, path: &Path) -> Option<Record> {
+        self.lookup_stamped(path, Stamp::of(path)?)
+    }
+
+    fn lookup_stamped(&self, path: &Path, stamp: Stamp) -> Option<Record> {
+        self.conn
+            .query_row(
+                "SELECT header_read, width, height, rating, dominant, luminance, hash
+                 FROM files WHERE path = ?1 AND size = ?2 AND mtime = ?3",
+                params![ospath::to_bytes(path), stamp.0, stamp.1],
+                |row| {
+                    let header_read: bool = row.get(0)?;
+                    let width: Option<u32> = row.get(1)?;
+                    let height: Option<u32> = row.get(2)?;
+                    let rating: Option<u8> = row.get(3)?;
+                    let dominant: Option<String> = row.get(4)?;
+                    Ok(Record {
+                        header: header_read.then(|| Header { dimensions: width.zip(height), rating }),
+                        dominant: dominant.and_then(|json| serde_json::from_str(&json).ok()),
+                        luminance: row.get(5)?,
+                        hash: row.get(6)?,
+                    })
+                },
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    pub fn store_header(&self, path: &Path, header: &Header) -> Result<()> {
+        let Some(stamp) = Stamp::of(path) else {
+            return Ok(());
+        };
+        self.store_header_stamped(path, stamp, header)
+    }
+
+    fn store_header_stamped(&self, path: &Path, stamp: Stamp, header: &Header) -> Result<()> {
+        self.claim(path, stamp)?;
+        self.conn.execute(
+            "UPDATE files SET header_read = 1, width = ?2, height = ?3, rating = ?4 WHERE path = ?1",
+            params![
+                ospath::to_bytes(path),
+                header.dimensions.map(|d| d.0),
+                header.dimensions.map(|d| d.1),
+                header.rating
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Remember a file's dominant colors and brightness; the theme match is derived from
+    /// them, so changing themes doesn't invalidate anything
+    pub fn store_colors(&self, path: &Path, dominant: &[(Rgb, f32)], luminance: f32) -> Result<()> {
+        let Some(stamp) = Stamp::of(path) else {
+            return Ok(());
+        };
+        self.claim(path, stamp)?;
+        self.conn.execute(
+            "UPDATE files SET dominant = ?2, luminance = ?3 WHERE path = ?1",
+            params![ospath::to_bytes(path), serde_json::to_string(dominant)?, luminance],
+        )?;
+        Ok(())
+    }
+
+    pub fn store_hash(&self, path: &Path, hash: &str) -> Result<()> {
+        let Some(stamp) = Stamp::of(path) else {
+            return Ok(());
+        };
+        self.store_hash_stamped(path, stamp, hash)
+    }
+
+    fn store_hash_stamped(&self, path: &Path, stamp: Stamp, hash: &str) -> Result<()> {
+        self.claim(path, stamp)?;
+        self.conn.execute(
+            "UPDATE files SET hash = ?2 WHERE path = ?1",
+            params![ospath::to_bytes(path), hash],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `path` has a row at all, even one for an older version of the file
+    pub fn contains(&self, path: &Path) -> bool {
+        self.conn
+            .query_row("SELECT 1 FROM files WHERE path = ?1", params![ospath::to_bytes(path)], |_| Ok(()))
+            .optional()
+            .is_ok_and(|row| row.is_some())
+    }
+
+    /// Files in `paths` that an indexed file with the same contents was renamed or moved to,
+    /// as `(old, new)` pairs. The old rows are dropped, so each move is reported once.
+    pub fn find_moves(&self, paths: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>> {
+        self.find_moves_where(paths, |old| !old.exists())
+    }
+
+    fn find_moves_where(&self, paths: &[PathBuf], gone: impl Fn(&Path) -> bool) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let mut moves = Vec::new();
+        let mut same_hash = self.conn.prepare("SELECT path FROM files WHERE hash = ?1 AND path != ?2")?;
+        for path in paths {
+            let key = ospath::to_bytes(path);
+            let hash: Option<String> = self
+                .conn
+                .query_row("SELECT hash FROM files WHERE path = ?1", params![key], |row| row.get(0))
+                .optional()?
+                .flatten();
+            let Some(hash) = hash else {
+                continue;
+            };
+            let candidates: Vec<Vec<u8>> =
+                same_hash.query_map(params![hash, key], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+            if let Some(old) = candidates.into_iter().map(ospath::from_bytes).find(|old| gone(old)) {
+                self.conn.execute("DELETE FROM files WHERE path = ?1", params![ospath::to_bytes(&old)])?;
+                moves.push((old, path.clone()));
+            }
+        }
+        Ok(moves)
+    }
+
+    /// Replace the mirrored collections with `collections`
+    pub fn sync_tags(&mut self, collections: &Collections) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM tags", [])?;
+        {
+            let mut insert = tx.prepare("INSERT OR IGNORE INTO tags (path, tag) VALUES (?1, ?2)")?;
+            for (name, paths) in collections.iter() {
+                for path in paths {
+                    insert.execute(params![ospath::to_bytes(path), name])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replace the mirrored `:rate` stars with those in `metadata`
+    pub fn sync_stars(&mut self, metadata: &Metadata) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM stars", [])?;
+        {
+            let mut insert = tx.prepare("INSERT INTO stars (path, stars) VALUES (?1, ?2)")?;
+            for (path, stars) in metadata.rated() {
+                insert.execute(params![ospath::to_bytes(path), stars])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Indexed files passing `query`, in path order or by palette match. Only what the index
+    /// already holds is searched; files are neither read nor checked for changes.
+    pub fn query(&self, query: &Query) -> Result<Vec<PathBuf>> {
+        let mut statement = self.conn.prepare(
+            "SELECT f.path, f.dominant FROM files f LEFT JOIN stars s ON s.path = f.path
+             WHERE (?1 IS NULL OR f.width >= ?1)
+               AND (?2 IS NULL OR f.height >= ?2)
+               AND (?3 IS NULL OR EXISTS (SELECT 1 FROM tags t WHERE t.path = f.path AND t.tag = ?3))
+               AND (?4 IS NULL OR COALESCE(s.stars, f.rating) >= ?4)
+             ORDER BY f.path",
+        )?;
+        let rows = statement.query_map(
+            params![
+                query.min_dimensions.map(|d| d.0),
+                query.min_dimensions.map(|d| d.1),
+                query.tag,
+                query.min_stars
+            ],
+            |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Option<String>>(1)?)),
+        )?;
+        let mut found: Vec<(PathBuf, Option<f32>)> = Vec::new();
+        for row in rows {
+            let (path, dominant) = row?;
+            let score = dominant
+                .and_then(|json| serde_json::from_str::<Vec<(Rgb, f32)>>(&json).ok())
+                .filter(|_| !query.palette.is_empty())
+                .map(|dominant| palette::match_score(&dominant, &query.palette));
+            found.push((ospath::from_bytes(path), score));
+        }
+        if !query.palette.is_empty() {
+            found.sort_by(|(_, a), (_, b)| b.unwrap_or(-1.0).total_cmp(&a.unwrap_or(-1.0)));
+        }
+        Ok(found.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Make sure `path` has a row for this version of the file, dropping what was
+    /// known about an older one
+    fn claim(&self, path: &Path, stamp: Stamp) -> Result<()> {
+        let key = ospath::to_bytes(path);
+        self.conn.execute(
+            "DELETE FROM files WHERE path = ?1 AND (size != ?2 OR mtime != ?3)",
+            params![key, stamp.0, stamp.1],
+        )?;
+        self.conn.execute(
+            "INSERT OR IGNORE INTO files (path, size, mtime) VALUES (?1, ?2, ?3)",
+            params![key, stamp.0, stamp.1],
+        )?;
+        Ok(())
+    }
+}
+
+/// Hash of a file's size and the start and end of its contents
+pub fn content_hash(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    let mut context = md5::Context::new();
+    context.consume(len.to_le_bytes());
+    let mut sample = Vec::new();
+    (&mut file).take(HASH_SAMPLE).read_to_end(&mut sample).ok()?;
+    if len > HASH_SAMPLE * 2 {
+        file.seek(SeekFrom::End(-(HASH_SAMPLE as i64))).ok()?;
+        file.take(HASH_SAMPLE).read_to_end(&mut sample).ok()?;
+    } else {
+        file.read_to_end(&mut sample).ok()?;
+    }
+    context.consume(&sample);
+    Some(format!("{:x}", context.compute()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_are_dropped_when_the_file_changes() {
+        let index = Index::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let path = Path::new("/w/sea.png");
+        let stamp = Stamp(1000, 42);
+        let header = Header { dimensions: Some((3840, 2160)), rating: Some(4) };
+
+        assert_eq!(index.lookup_stamped(path, stamp), None);
+        index.store_header_stamped(path, stamp, &header).unwrap();
+        let record = index.lookup_stamped(path, stamp).unwrap();
+        assert_eq!(record.header, Some(header));
+        assert_eq!(record.dominant, None);
+
+        let edited = Stamp(1200, 43);
+        assert_eq!(index.lookup_stamped(path, edited), None);
+        index.store_header_stamped(path, edited, &Header { dimensions: None, rating: None }).unwrap();
+        assert_eq!(index.lookup_stamped(path, stamp), None);
+        assert_eq!(
+            index.lookup_stamped(path, edited).unwrap().header,
+            Some(Header { dimensions: None, rating: None })
+        );
+    }
+
+    #[test]
+    fn moved_files_are_matched_by_hash() {
+        let index = Index::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let (old, new, copy) = (PathBuf::from("/w/a.jpg"), PathBuf::from("/w/sub/a.jpg"), PathBuf::from("/w/b.jpg"));
+        index.store_hash_stamped(&old, Stamp(10, 1), "abc").unwrap();
+        index.store_hash_stamped(&new, Stamp(10, 2), "abc").unwrap();
+        index.store_hash_stamped(&copy, Stamp(10, 3), "def").unwrap();
+
+        // A duplicate that still exists is not a move
+        assert!(index.find_moves_where(std::slice::from_ref(&new), |_| false).unwrap().is_empty());
+        let moves = index.find_moves_where(&[new.clone(), copy], |p| p == old).unwrap();
+        assert_eq!(moves, vec![(old.clone(), new.clone())]);
+        assert!(!index.contains(&old));
+        assert!(index.contains(&new));
+        assert!(index.find_moves_where(&[new], |p| p == old).unwrap().is_empty());
+    }
+
+    #[test]
+    fn queries_by_size_tag_rating_and_palette() {
+        let mut index = Index::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let (red, blue, small) = (PathBuf::from("/w/red.png"), PathBuf::from("/w/blue.png"), PathBuf::from("/w/small.png"));
+        let header = |w, h, rating| Header { dimensions: Some((w, h)), rating };
+        index.store_header_stamped(&red, Stamp(1, 1), &header(3840, 2160, Some(2))).unwrap();
+        index.store_header_stamped(&blue, Stamp(1, 2), &header(2560, 1440, None)).unwrap();
+        index.store_header_stamped(&small, Stamp(1, 3), &header(800, 600, Some(5))).unwrap();
+        index.conn.execute("UPDATE files SET dominant = '[[[250,0,0],1.0]]' WHERE path = ?1", params![ospath::to_bytes(&red)]).unwrap();
+        index.conn.execute("UPDATE files SET dominant = '[[[0,0,250],1.0]]' WHERE path = ?1", params![ospath::to_bytes(&blue)]).unwrap();
+
+        let mut collections = Collections::default();
+        collections.add("Dark", &blue);
+        index.sync_tags(&collections).unwrap();
+        let mut metadata = Metadata::default();
+        metadata.set_stars(&blue, Some(4));
+        index.sync_stars(&metadata).unwrap();
+
+        let big = Query { min_dimensions: Some((2560, 1440)), ..Query::default() };
+        assert_eq!(index.query(&big).unwrap(), [blue.clone(), red.clone()]);
+        let tagged = Query { tag: Some("dark".to_string()), ..Query::default() };
+        assert_eq!(index.query(&tagged).unwrap(), std::slice::from_ref(&blue));
+        // :rate stars win over the embedded rating
+        let rated = Query { min_stars: Some(3), ..Query::default() };
+        assert_eq!(index.query(&rated).unwrap(), [blue.clone(), small.clone()]);
+        let reddish = Query { palette: vec![[255, 10, 10]], ..Query::default() };
+        assert_eq!(index.query(&reddish).unwrap(), [red, blue, small]);
+    }
+}
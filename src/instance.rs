@@ -0,0 +1,83 @@
+use color_eyre::{eyre::eyre, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Which kind of process holds a lock. The picker and the daemon run side by side, so each
+/// kind has its own lock: only a second picker, a second daemon or two one-shot commands at
+/// once are turned away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Picker,
+    Daemon,
+    /// `--next`, `--set` and friends when nothing is serving the control socket
+    OneShot,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Picker => "picker",
+            Role::Daemon => "daemon",
+            Role::OneShot => "oneshot",
+        }
+    }
+
+    fn busy_message(self) -> &'static str {
+        match self {
+            Role::Picker => "the picker is already open",
+            Role::Daemon => "the daemon is already running",
+            Role::OneShot => "another wallpaper command is running",
+        }
+    }
+}
+
+/// Held for as long as the process runs, so two processes of the same role never restart
+/// the backend or move the current-background link at the same time. The OS drops the lock
+/// when the process exits, however it exits.
+pub struct InstanceLock {
+    _file: File,
+}
+
+pub fn get_lock_path(role: Role) -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("omarchy-wallpaper-picker-{}.lock", role.name()))
+}
+
+impl InstanceLock {
+    /// Take the lock for `role`, failing with the holder's pid if another process has it
+    pub fn acquire(role: Role) -> Result<Self> {
+        let path = get_lock_path(role);
+        let mut file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = fs::read_to_string(&path).unwrap_or_default();
+                let holder = holder.trim();
+                return Err(eyre!(
+                    "{}{}; control it with --next, --prev, --random or --set",
+                    role.busy_message(),
+                    if holder.is_empty() { String::new() } else { format!(" (pid {})", holder) }
+                ));
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_lock_separately() {
+        let names: Vec<PathBuf> = [Role::Picker, Role::Daemon, Role::OneShot].into_iter().map(get_lock_path).collect();
+        assert_ne!(names[0], names[1]);
+        assert_ne!(names[1], names[2]);
+        assert_ne!(names[0], names[2]);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::events::AppEvent;
+use color_eyre::{eyre::eyre, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long a broadcast waits on a subscriber that has stopped reading before dropping it
+const SUBSCRIBER_WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A command received over the control socket
+#[derive(Debug)]
+pub enum IpcCommand {
+    Next,
+    Prev,
+    Random,
+    Set(PathBuf),
+    /// Show the current wallpaper again
+    Reload,
+}
+
+impl IpcCommand {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match name {
+            "next" => Ok(IpcCommand::Next),
+            "prev" => Ok(IpcCommand::Prev),
+            "random" => Ok(IpcCommand::Random),
+            "reload" => Ok(IpcCommand::Reload),
+            "set" if !arg.trim().is_empty() => Ok(IpcCommand::Set(PathBuf::from(arg.trim()))),
+            "set" => Err(eyre!("set needs a path")),
+            _ => Err(eyre!("unknown command: {}", name)),
+        }
+    }
+}
+
+impl std::fmt::Display for IpcCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpcCommand::Next => write!(f, "next"),
+            IpcCommand::Prev => write!(f, "prev"),
+            IpcCommand::Random => write!(f, "random"),
+            IpcCommand::Set(path) => write!(f, "set {}", path.display()),
+            IpcCommand::Reload => write!(f, "reload"),
+        }
+    }
+}
+
+/// Send a command to a running instance. Returns None if nothing is listening.
+pub fn send(command: &IpcCommand) -> Result<Option<String>> {
+    let Ok(mut stream) = UnixStream::connect(get_socket_path()) else {
+        return Ok(None);
+    };
+    writeln!(stream, "{}", command)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(Some(reply.trim().to_string()))
+}
+
+/// A command plus the channel its reply line goes back on
+pub struct IpcRequest {
+    pub command: IpcCommand,
+    reply: Sender<String>,
+}
+
+impl IpcRequest {
+    pub fn respond(self, result: Result<()>) {
+        let line = match result {
+            Ok(()) => "ok".to_string(),
+            Err(e) => format!("error: {}", e),
+        };
+        let _ = self.reply.send(line);
+    }
+}
+
+pub fn get_socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("omarchy-wallpaper-picker.sock")
+}
+
+/// Line-based control server on a unix socket.
+///
+/// Clients send one command per line (`next`, `prev`, `random`, `set <path>`, `reload`) and get
+/// `ok` or `error: ...` back. `subscribe` keeps the connection open and streams
+/// `wallpaper_applied <path>` lines.
+pub struct IpcServer {
+    rx: Receiver<IpcRequest>,
+    subscribers: Arc<Mutex<Vec<UnixStream>>>,
+    path: PathBuf,
+}
+
+impl IpcServer {
+    /// Bind the socket, or return None if another instance is already serving it
+    pub fn start() -> Result<Option<Self>> {
+        let path = get_socket_path();
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                return Ok(None);
+            }
+            // Stale socket left behind by a crashed instance
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        let (tx, rx) = mpsc::channel();
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+
+        let accept_subscribers = Arc::clone(&subscribers);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                let subscribers = Arc::clone(&accept_subscribers);
+                thread::spawn(move || handle_client(stream, tx, subscribers));
+            }
+        });
+
+        Ok(Some(Self { rx, subscribers, path }))
+    }
+
+    /// Next pending request, if any
+    pub fn try_recv(&self) -> Option<IpcRequest> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Wait up to `timeout` for a request
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<IpcRequest> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// A callback for the event bus that forwards applied wallpapers to subscribers
+    pub fn event_forwarder(&self) -> impl FnMut(&AppEvent) + Send + 'static {
+        let subscribers = Arc::clone(&self.subscribers);
+        move |event| {
+            if let AppEvent::WallpaperApplied(path) = event {
+                broadcast(&subscribers, &format!("{} {}", event.name(), path.display()));
+            }
+        }
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Send a line to every subscriber, forgetting the ones that hung up or whose buffer stayed
+/// full past `SUBSCRIBER_WRITE_TIMEOUT`
+fn broadcast(subscribers: &Mutex<Vec<UnixStream>>, line: &str) {
+    let mut subscribers = subscribers.lock().unwrap_or_else(|e| e.into_inner());
+    subscribers.retain_mut(|stream| writeln!(stream, "{}", line).is_ok());
+}
+
+fn handle_client(stream: UnixStream, tx: Sender<IpcRequest>, subscribers: Arc<Mutex<Vec<UnixStream>>>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if line.trim() == "subscribe" {
+            // A subscriber that stops reading must not stall the daemon's event loop
+            if writer.set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT)).is_ok() && writeln!(writer, "ok").is_ok() {
+                subscribers.lock().unwrap_or_else(|e| e.into_inner()).push(writer);
+            }
+            return;
+        }
+
+        let reply = match IpcCommand::parse(&line) {
+            Ok(command) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if tx.send(IpcRequest { command, reply: reply_tx }).is_err() {
+                    return;
+                }
+                reply_rx.recv().unwrap_or_else(|_| "error: shutting down".to_string())
+            }
+            Err(e) => format!("error: {}", e),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn stalled_subscribers_are_dropped() {
+        let (writer, _reader) = UnixStream::pair().unwrap();
+        writer.set_write_timeout(Some(SUBSCRIBER_WRITE_TIMEOUT)).unwrap();
+        let subscribers = Mutex::new(vec![writer]);
+        let line = "x".repeat(64 * 1024);
+        let started = Instant::now();
+        // Nothing reads `_reader`, so the socket buffer fills and a write times out
+        for _ in 0..1000 {
+            if subscribers.lock().unwrap().is_empty() {
+                break;
+            }
+            broadcast(&subscribers, &line);
+        }
+        assert!(subscribers.lock().unwrap().is_empty());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
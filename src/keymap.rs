@@ -0,0 +1,302 @@
+use color_eyre::{eyre::eyre, Result};
+use crossterm::event::KeyCode;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Something a key does in the grid or the preview
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    PreviewPrev,
+    PreviewNext,
+    Apply,
+    TogglePreview,
+    Try,
+    MonitorPreview,
+    BeforeAfter,
+    Crop,
+    ToggleBlur,
+    ToggleDim,
+    ToggleGrayscale,
+    CycleFillMode,
+    ToggleBadges,
+    GrowCells,
+    ShrinkCells,
+    PrevGroup,
+    NextGroup,
+    MarkCompare,
+    ToggleMark,
+    Enqueue,
+    YankPath,
+    YankFile,
+    Undo,
+    Search,
+    Command,
+    ResetViewDir,
+    Help,
+    ToggleDiagnostics,
+    Escape,
+    Quit,
+}
+
+/// Where a binding applies
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Grid,
+    Preview,
+    /// Grid and preview
+    Both,
+}
+
+impl Scope {
+    fn applies(self, in_preview: bool) -> bool {
+        match self {
+            Scope::Grid => !in_preview,
+            Scope::Preview => in_preview,
+            Scope::Both => true,
+        }
+    }
+
+    fn overlaps(self, other: Scope) -> bool {
+        [false, true].into_iter().any(|in_preview| self.applies(in_preview) && other.applies(in_preview))
+    }
+}
+
+/// A key binding as listed in help and used for dispatch
+pub struct Binding {
+    pub keys: &'static [KeyCode],
+    pub action: Action,
+    pub scope: Scope,
+    pub help: &'static str,
+}
+
+const fn bind(keys: &'static [KeyCode], action: Action, scope: Scope, help: &'static str) -> Binding {
+    Binding { keys, action, scope, help }
+}
+
+/// Every binding, in the order help lists them; the first match for a key wins
+pub const BINDINGS: &[Binding] = &[
+    bind(&[KeyCode::Up, KeyCode::Char('k')], Action::MoveUp, Scope::Grid, "Move up"),
+    bind(&[KeyCode::Down, KeyCode::Char('j')], Action::MoveDown, Scope::Grid, "Move down"),
+    bind(&[KeyCode::Left, KeyCode::Char('h')], Action::MoveLeft, Scope::Grid, "Move left"),
+    bind(&[KeyCode::Right, KeyCode::Char('l')], Action::MoveRight, Scope::Grid, "Move right"),
+    bind(&[KeyCode::Left, KeyCode::Char('h')], Action::PreviewPrev, Scope::Preview, "Previous wallpaper"),
+    bind(&[KeyCode::Right, KeyCode::Char('l')], Action::PreviewNext, Scope::Preview, "Next wallpaper"),
+    bind(&[KeyCode::Enter], Action::Apply, Scope::Both, "Apply wallpaper"),
+    bind(&[KeyCode::Char(' ')], Action::TogglePreview, Scope::Both, "Preview wallpaper"),
+    bind(&[KeyCode::Char('t')], Action::Try, Scope::Both, "Try on the desktop for a few seconds"),
+    bind(&[KeyCode::Char('m')], Action::MonitorPreview, Scope::Preview, "Monitor layout preview"),
+    bind(&[KeyCode::Char('o')], Action::BeforeAfter, Scope::Preview, "Side by side with the current wallpaper"),
+    bind(&[KeyCode::Char('c')], Action::Crop, Scope::Preview, "Crop to monitor aspect"),
+    bind(&[KeyCode::Char('b')], Action::ToggleBlur, Scope::Preview, "Toggle blur"),
+    bind(&[KeyCode::Char('d')], Action::ToggleDim, Scope::Preview, "Toggle dim"),
+    bind(&[KeyCode::Char('g')], Action::ToggleGrayscale, Scope::Preview, "Toggle grayscale"),
+    bind(&[KeyCode::Char('f')], Action::CycleFillMode, Scope::Both, "Cycle fill mode"),
+    bind(&[KeyCode::Char('i')], Action::ToggleBadges, Scope::Grid, "Toggle format and file size badges"),
+    bind(&[KeyCode::Char('+'), KeyCode::Char('=')], Action::GrowCells, Scope::Grid, "Larger grid cells"),
+    bind(&[KeyCode::Char('-')], Action::ShrinkCells, Scope::Grid, "Smaller grid cells"),
+    bind(&[KeyCode::Char('[')], Action::PrevGroup, Scope::Grid, "Previous group (:group-by)"),
+    bind(&[KeyCode::Char(']')], Action::NextGroup, Scope::Grid, "Next group (:group-by)"),
+    bind(
+        &[KeyCode::Char('v')],
+        Action::MarkCompare,
+        Scope::Both,
+        "Mark for A/B compare (again on another to compare)",
+    ),
+    bind(&[KeyCode::Char('x')], Action::ToggleMark, Scope::Grid, "Mark for :tag (Esc clears marks)"),
+    bind(&[KeyCode::Char('a')], Action::Enqueue, Scope::Both, "Add to the wallpaper queue (:queue play)"),
+    bind(&[KeyCode::Char('y')], Action::YankPath, Scope::Both, "Copy the wallpaper's path to the clipboard"),
+    bind(&[KeyCode::Char('Y')], Action::YankFile, Scope::Both, "Copy the wallpaper file to the clipboard (as a file URI)"),
+    bind(&[KeyCode::Char('u')], Action::Undo, Scope::Both, "Undo the last trash, collection change or import"),
+    bind(&[KeyCode::Char('/')], Action::Search, Scope::Both, "Search/filter"),
+    bind(&[KeyCode::Char(':')], Action::Command, Scope::Both, "Open command mode"),
+    bind(&[KeyCode::Char('H')], Action::ResetViewDir, Scope::Both, "Reset view dir"),
+    bind(&[KeyCode::Char('?')], Action::Help, Scope::Both, "Toggle help"),
+    bind(&[KeyCode::F(12)], Action::ToggleDiagnostics, Scope::Both, "Memory and frame time overlay"),
+    bind(&[KeyCode::Esc], Action::Escape, Scope::Both, "Close modal / Exit"),
+    bind(&[KeyCode::Char('q')], Action::Quit, Scope::Both, "Quit"),
+];
+
+/// Keys replacing an action's defaults
+type Overrides = Vec<(Action, Vec<KeyCode>)>;
+
+/// Overrides from the config's `[keys]`, replaced on reload
+static OVERRIDES: RwLock<Overrides> = RwLock::new(Vec::new());
+
+/// Take key overrides from the config's `[keys]`, e.g. `apply = "Enter o"`.
+/// Entries that can't be read are skipped and reported together.
+pub fn configure(keys: &HashMap<String, String>) -> Result<()> {
+    let (overrides, errors) = parse_overrides(keys);
+    *OVERRIDES.write().unwrap_or_else(|e| e.into_inner()) = overrides;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!("[keys]: {}", errors.join("; ")))
+    }
+}
+
+fn parse_overrides(keys: &HashMap<String, String>) -> (Overrides, Vec<String>) {
+    let mut names: Vec<&String> = keys.keys().collect();
+    names.sort();
+    let (mut overrides, mut errors) = (Vec::new(), Vec::new());
+    for name in names {
+        let spec = &keys[name];
+        let Some(binding) = BINDINGS.iter().find(|b| b.name() == *name) else {
+            errors.push(format!("unknown action '{}'", name));
+            continue;
+        };
+        match spec.split_whitespace().map(parse_key).collect::<Option<Vec<_>>>() {
+            Some(parsed) if !parsed.is_empty() => overrides.push((binding.action, parsed)),
+            _ => errors.push(format!("{}: can't read keys '{}'", name, spec)),
+        }
+    }
+    (overrides, errors)
+}
+
+/// A key as written in `[keys]`: a single character, or `Up`, `Enter`, `Space`, `F12`, ...
+fn parse_key(spec: &str) -> Option<KeyCode> {
+    let mut chars = spec.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let key = match spec.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "delete" => KeyCode::Delete,
+        other => KeyCode::F(other.strip_prefix('f')?.parse().ok().filter(|n| (1..=24).contains(n))?),
+    };
+    Some(key)
+}
+
+/// What `key` does in the grid, or in the preview when `in_preview`
+pub fn action(key: KeyCode, in_preview: bool) -> Option<Action> {
+    action_with(key, in_preview, &OVERRIDES.read().unwrap_or_else(|e| e.into_inner()))
+}
+
+fn action_with(key: KeyCode, in_preview: bool, overrides: &Overrides) -> Option<Action> {
+    BINDINGS
+        .iter()
+        .find(|b| b.scope.applies(in_preview) && b.keys_with(overrides).contains(&key))
+        .map(|b| b.action)
+}
+
+fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        other => format!("{:?}", other),
+    }
+}
+
+impl Binding {
+    /// Name used in `[keys]`, e.g. `toggle-preview`
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for c in format!("{:?}", self.action).chars() {
+            if c.is_uppercase() && !name.is_empty() {
+                name.push('-');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    }
+
+    /// Keys bound to the action: the configured ones if set, otherwise the defaults that no
+    /// override in an overlapping scope has taken
+    pub fn keys(&self) -> Vec<KeyCode> {
+        self.keys_with(&OVERRIDES.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn keys_with(&self, overrides: &Overrides) -> Vec<KeyCode> {
+        if let Some((_, keys)) = overrides.iter().find(|(action, _)| *action == self.action) {
+            return keys.clone();
+        }
+        let taken = |key: &KeyCode| {
+            overrides.iter().any(|(action, keys)| {
+                keys.contains(key)
+                    && BINDINGS.iter().any(|b| b.action == *action && b.scope.overlaps(self.scope))
+            })
+        };
+        self.keys.iter().copied().filter(|key| !taken(key)).collect()
+    }
+
+    /// Keys as shown in help, e.g. `↑/k`
+    pub fn label(&self) -> String {
+        self.keys().into_iter().map(key_label).collect::<Vec<_>>().join("/")
+    }
+
+    /// Help text, noting bindings that only work in the preview
+    pub fn description(&self) -> String {
+        match self.scope {
+            Scope::Preview => format!("{} (in preview)", self.help),
+            Scope::Grid | Scope::Both => self.help.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_bindings_shadow_grid_ones() {
+        assert_eq!(action(KeyCode::Char('h'), false), Some(Action::MoveLeft));
+        assert_eq!(action(KeyCode::Char('h'), true), Some(Action::PreviewPrev));
+        assert_eq!(action(KeyCode::Char('j'), true), None);
+        assert_eq!(action(KeyCode::Char('c'), false), None);
+    }
+
+    #[test]
+    fn overrides_replace_and_take_keys() {
+        let keys = HashMap::from([
+            ("apply".to_string(), "o Enter".to_string()),
+            ("toggle-diagnostics".to_string(), "F2".to_string()),
+            ("frobnicate".to_string(), "z".to_string()),
+            ("quit".to_string(), "Ctrl-Q".to_string()),
+        ]);
+        let (overrides, errors) = parse_overrides(&keys);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(action_with(KeyCode::Char('o'), false, &overrides), Some(Action::Apply));
+        assert_eq!(action_with(KeyCode::Enter, true, &overrides), Some(Action::Apply));
+        assert_eq!(action_with(KeyCode::F(2), false, &overrides), Some(Action::ToggleDiagnostics));
+        assert_eq!(action_with(KeyCode::F(12), false, &overrides), None);
+        // `o` is taken from the preview's side-by-side view, which keeps none of its keys
+        assert_eq!(action_with(KeyCode::Char('o'), true, &overrides), Some(Action::Apply));
+        // Unreadable entries leave the defaults alone
+        assert_eq!(action_with(KeyCode::Char('q'), false, &overrides), Some(Action::Quit));
+    }
+
+    #[test]
+    fn no_key_is_bound_twice_in_a_scope() {
+        for in_preview in [false, true] {
+            let mut seen = Vec::new();
+            for binding in BINDINGS.iter().filter(|b| b.scope.applies(in_preview)) {
+                for key in binding.keys {
+                    assert!(!seen.contains(key), "{:?} bound twice", key);
+                    seen.push(*key);
+                }
+            }
+        }
+    }
+}
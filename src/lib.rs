@@ -0,0 +1,61 @@
+pub mod adjust;
+pub mod announce;
+pub mod app;
+pub mod backend;
+pub mod bookmarks;
+pub mod browse;
+pub mod browser;
+pub mod cli;
+pub mod clipboard;
+pub mod collections;
+pub mod commands;
+pub mod compare;
+pub mod config;
+pub mod crop;
+pub mod daemon;
+pub mod dmenu;
+pub mod downloads;
+pub mod encoder;
+pub mod events;
+pub mod formats;
+pub mod groups;
+pub mod history;
+pub mod hooks;
+pub mod import;
+pub mod index;
+pub mod instance;
+pub mod loader;
+pub mod lockscreen;
+pub mod login;
+pub mod memory;
+pub mod metadata;
+pub mod ipc;
+pub mod keymap;
+pub mod library;
+pub mod listing;
+pub mod monitor;
+pub mod net;
+pub mod notifications;
+pub mod ospath;
+pub mod palette;
+pub mod pins;
+pub mod potd;
+pub mod pregen;
+pub mod profile;
+pub mod queue;
+pub mod rating;
+pub mod reddit;
+pub mod service;
+pub mod session;
+pub mod svg;
+pub mod tags;
+pub mod terminal;
+pub mod theme;
+pub mod thumbnails;
+pub mod transition;
+pub mod variants;
+pub mod trashcan;
+pub mod ui;
+pub mod undo;
+pub mod wallpaper;
+pub mod watcher;
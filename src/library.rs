@@ -0,0 +1,211 @@
+use crate::bookmarks::Bookmarks;
+use crate::collections::Collections;
+use crate::history::History;
+use crate::metadata::Metadata;
+use crate::wallpaper;
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Export format version; newer files are refused rather than half-read.
+/// 2: paths under the home directory or a library root are stored relative to it.
+const VERSION: u32 = 2;
+
+/// Everything curated about the library in one file, for `:export` and `:import`:
+/// notes, sources and ratings, collections (favorites among them), bookmarks and history
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+struct Library {
+    version: u32,
+    metadata: Metadata,
+    collections: Collections,
+    bookmarks: Bookmarks,
+    history: History,
+}
+
+/// Where paths are anchored, so an export can be imported under another home or layout
+struct Anchors {
+    home: Option<PathBuf>,
+    /// Library roots and the backgrounds dir, most specific first
+    roots: Vec<PathBuf>,
+}
+
+impl Anchors {
+    fn current() -> Self {
+        let mut roots = wallpaper::library_roots();
+        roots.push(wallpaper::get_backgrounds_dir());
+        // A root inside another must win, so match the longest first
+        roots.sort_by_key(|root| std::cmp::Reverse(root.components().count()));
+        Self { home: dirs::home_dir(), roots }
+    }
+
+    /// `@<root name>/rest` under a library root, `~/rest` under home, else unchanged
+    fn portable(&self, path: &Path) -> PathBuf {
+        for root in &self.roots {
+            if let (Ok(rest), Some(name)) = (path.strip_prefix(root), root.file_name()) {
+                let mut anchor = OsString::from("@");
+                anchor.push(name);
+                return Path::new(&anchor).join(rest);
+            }
+        }
+        match self.home.as_deref().and_then(|home| path.strip_prefix(home).ok()) {
+            Some(rest) => Path::new("~").join(rest),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Undo `portable` here: `@name` goes to the root of that name, or else the first one
+    fn local(&self, path: &Path) -> PathBuf {
+        let mut components = path.components();
+        let Some(Component::Normal(first)) = components.next() else {
+            return path.to_path_buf();
+        };
+        let rest = components.as_path();
+        if first == "~" {
+            return match &self.home {
+                Some(home) => home.join(rest),
+                None => path.to_path_buf(),
+            };
+        }
+        let Some(name) = first.as_encoded_bytes().strip_prefix(b"@") else {
+            return path.to_path_buf();
+        };
+        let root = self
+            .roots
+            .iter()
+            .find(|root| root.file_name().is_some_and(|n| n.as_encoded_bytes() == name))
+            .or(self.roots.first());
+        match root {
+            Some(root) => root.join(rest),
+            None => path.to_path_buf(),
+        }
+    }
+}
+
+impl Library {
+    fn load() -> Result<Self> {
+        Ok(Self {
+            version: VERSION,
+            metadata: Metadata::load(),
+            collections: Collections::load()?,
+            bookmarks: Bookmarks::load(),
+            history: History::load(),
+        })
+    }
+
+    fn save(&self) -> Result<()> {
+        self.metadata.save()?;
+        self.collections.save()?;
+        self.bookmarks.save()?;
+        self.history.save()
+    }
+
+    fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        self.metadata.map_paths(&map);
+        self.collections.map_paths(&map);
+        self.bookmarks.map_paths(&map);
+        self.history.map_paths(&map);
+    }
+
+    /// Take in an imported library. Entries already here are kept, except notes, sources,
+    /// ratings and bookmarks the import also has, which it replaces.
+    fn merge(&mut self, other: Library) {
+        self.metadata.merge(other.metadata);
+        self.collections.merge(other.collections);
+        self.bookmarks.merge(other.bookmarks);
+        self.history.merge(other.history);
+    }
+}
+
+/// Whether an `:import` argument is an export file rather than images
+pub fn is_export_file(arg: &str) -> bool {
+    Path::new(arg).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Write the library's curation to `path`
+pub fn export(path: &Path) -> Result<()> {
+    let mut library = Library::load()?;
+    let anchors = Anchors::current();
+    library.map_paths(|p| anchors.portable(p));
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&library)?)?;
+    Ok(())
+}
+
+/// Merge an export from `path` into the library
+pub fn import(path: &Path) -> Result<()> {
+    let contents = fs::read_to_string(path).map_err(|e| eyre!("{}: {}", path.display(), e))?;
+    let mut imported: Library = serde_json::from_str(&contents).map_err(|e| eyre!("{}: {}", path.display(), e))?;
+    if imported.version > VERSION {
+        return Err(eyre!("{}: exported by a newer version (format {})", path.display(), imported.version));
+    }
+    // Version 1 files hold absolute paths, which `local` leaves alone
+    let anchors = Anchors::current();
+    imported.map_paths(|p| anchors.local(p));
+
+    let mut library = Library::load()?;
+    library.merge(imported);
+    library.save()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors(home: &str, roots: &[&str]) -> Anchors {
+        Anchors { home: Some(PathBuf::from(home)), roots: roots.iter().map(PathBuf::from).collect() }
+    }
+
+    #[test]
+    fn paths_move_with_home_and_roots() {
+        let here = anchors("/home/ana", &["/mnt/art/Walls", "/home/ana/Pictures/bg"]);
+        let there = anchors("/home/bo", &["/data/Walls", "/home/bo/bg"]);
+
+        let cases = [
+            ("/mnt/art/Walls/sea/a.jpg", "@Walls/sea/a.jpg", "/data/Walls/sea/a.jpg"),
+            ("/home/ana/Pictures/bg/b.png", "@bg/b.png", "/home/bo/bg/b.png"),
+            ("/home/ana/Downloads/c.png", "~/Downloads/c.png", "/home/bo/Downloads/c.png"),
+            ("/srv/d.png", "/srv/d.png", "/srv/d.png"),
+        ];
+        for (path, portable, local) in cases {
+            assert_eq!(here.portable(Path::new(path)), Path::new(portable));
+            assert_eq!(there.local(Path::new(portable)), Path::new(local));
+        }
+        // An unknown root lands in the first one
+        assert_eq!(there.local(Path::new("@Gone/e.png")), Path::new("/data/Walls/e.png"));
+    }
+
+    #[test]
+    fn export_round_trips_and_merges() {
+        let here = anchors("/home/ana", &["/home/ana/Walls"]);
+        let there = anchors("/home/bo", &["/home/bo/Walls"]);
+
+        let mut exported = Library { version: VERSION, ..Library::default() };
+        exported.collections.add("dark", Path::new("/home/ana/Walls/a.jpg"));
+        exported.metadata.set_note(Path::new("/home/ana/Walls/a.jpg"), "from the trip");
+        exported.metadata.set_stars(Path::new("/home/ana/Walls/b.jpg"), Some(5));
+        exported.map_paths(|p| here.portable(p));
+        let json = serde_json::to_string(&exported).unwrap();
+        assert!(!json.contains("/home/ana"));
+
+        let mut imported: Library = serde_json::from_str(&json).unwrap();
+        imported.map_paths(|p| there.local(p));
+        let mut library = Library::default();
+        library.collections.add("dark", Path::new("/home/bo/Walls/c.jpg"));
+        library.metadata.set_stars(Path::new("/home/bo/Walls/b.jpg"), Some(2));
+        library.metadata.set_note(Path::new("/home/bo/Walls/b.jpg"), "mine");
+        library.merge(imported);
+
+        assert!(library.collections.contains("dark", Path::new("/home/bo/Walls/a.jpg")));
+        assert!(library.collections.contains("dark", Path::new("/home/bo/Walls/c.jpg")));
+        let b = library.metadata.get(Path::new("/home/bo/Walls/b.jpg")).unwrap();
+        assert_eq!(b.stars, Some(5));
+        assert_eq!(b.note.as_deref(), Some("mine"));
+        let a = library.metadata.get(Path::new("/home/bo/Walls/a.jpg")).unwrap();
+        assert_eq!(a.note.as_deref(), Some("from the trip"));
+    }
+}
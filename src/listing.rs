@@ -0,0 +1,93 @@
+use crate::collections::Collections;
+use crate::config::Config;
+use crate::index::{Index, Query};
+use crate::loader::Header;
+use crate::metadata::Metadata;
+use crate::wallpaper::{self, Wallpaper};
+use crate::{formats, ospath, rating};
+use color_eyre::Result;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// One wallpaper as printed by `--list --json`
+#[derive(Serialize, Debug)]
+struct Listed {
+    #[serde(serialize_with = "ospath::serialize")]
+    path: PathBuf,
+    name: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    current: bool,
+    /// Collections the file belongs to
+    tags: Vec<String>,
+}
+
+/// `--list [dir] [--json]`: print the wallpapers the picker would show, one path per line or
+/// as a JSON array, for rofi/fuzzel menus and scripts. With a `query` (`--min-size`, `--tag`,
+/// `--min-stars`, `--by-theme`) only the matches are printed, in its order.
+pub fn run(dir: Option<PathBuf>, json: bool, query: Option<Query>) -> Result<()> {
+    let config = Config::load()?;
+    wallpaper::configure(&config);
+    let mut wallpapers = wallpaper::discover_wallpapers(dir)?;
+    if let Some(query) = query {
+        wallpapers = filter(wallpapers, &query)?;
+    }
+    let mut out = io::stdout().lock();
+    if !json {
+        for wallpaper in &wallpapers {
+            out.write_all(ospath::to_bytes(&wallpaper.path))?;
+            out.write_all(b"\n")?;
+        }
+        return Ok(());
+    }
+
+    let current = wallpaper::get_current_wallpaper();
+    let collections = Collections::load()?;
+    // Sizes come from the index when the picker has seen the file before
+    let index = Index::open().ok();
+    let listed: Vec<Listed> = wallpapers
+        .into_iter()
+        .map(|w| {
+            let dimensions = dimensions(index.as_ref(), &w.path);
+            Listed {
+                current: current.as_ref() == Some(&w.path),
+                tags: collections.containing(&w.path),
+                width: dimensions.map(|d| d.0),
+                height: dimensions.map(|d| d.1),
+                name: w.name,
+                path: w.path,
+            }
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut out, &listed)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// The wallpapers the index says pass `query`, headers of files it hasn't seen read first
+fn filter(wallpapers: Vec<Wallpaper>, query: &Query) -> Result<Vec<Wallpaper>> {
+    let mut index = Index::open()?;
+    for wallpaper in &wallpapers {
+        dimensions(Some(&index), &wallpaper.path);
+    }
+    index.sync_tags(&Collections::load()?)?;
+    index.sync_stars(&Metadata::load())?;
+    let order: HashMap<PathBuf, usize> = index.query(query)?.into_iter().enumerate().map(|(i, p)| (p, i)).collect();
+    let mut matching: Vec<(usize, Wallpaper)> =
+        wallpapers.into_iter().filter_map(|w| Some((*order.get(&w.path)?, w))).collect();
+    matching.sort_by_key(|(i, _)| *i);
+    Ok(matching.into_iter().map(|(_, w)| w).collect())
+}
+
+fn dimensions(index: Option<&Index>, path: &Path) -> Option<(u32, u32)> {
+    if let Some(header) = index.and_then(|index| index.lookup(path)).and_then(|record| record.header) {
+        return header.dimensions;
+    }
+    let header = Header { dimensions: formats::dimensions(path), rating: rating::read(path) };
+    if let Some(index) = index {
+        let _ = index.store_header(path, &header);
+    }
+    header.dimensions
+}
@@ -0,0 +1,477 @@
+use crate::adjust::{self, Adjustments};
+use crate::browse::{self, SortKey, SortOrder};
+use crate::collections::Collections;
+use crate::index::{self, Index, Record};
+use crate::{formats, rating, thumbnails};
+use crate::palette::{self, ColorStats};
+use crate::wallpaper::{self, FileInfo, MinSize, Wallpaper};
+use color_eyre::{eyre::eyre, Result};
+use image::DynamicImage;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Why a scan was started, which decides how its result replaces the grid
+pub enum ScanReason {
+    /// First listing at launch; restore the saved selection and filter
+    Startup { selected: Option<PathBuf>, query: String },
+    /// A different directory or collection; select the first of `highlight`, if any
+    Open { highlight: Vec<PathBuf> },
+    /// The same view changed on disk or needs re-sorting; keep thumbnails and the selection
+    Refresh,
+}
+
+/// A directory or collection listing running on a background thread,
+/// so slow or hung filesystems (NFS, SMB) never block drawing
+pub struct Scan {
+    rx: Receiver<Result<Vec<Wallpaper>>>,
+    pub reason: ScanReason,
+    /// Order the listing is sorted in
+    pub order: SortOrder,
+    /// Where the view was before, to go back to if the scan fails or is cancelled
+    pub previous_dir: Option<PathBuf>,
+    pub previous_collection: Option<String>,
+    pub started: Instant,
+}
+
+impl Scan {
+    pub fn start(
+        dir: Option<PathBuf>,
+        collection: Option<String>,
+        order: SortOrder,
+        reason: ScanReason,
+        previous_dir: Option<PathBuf>,
+        previous_collection: Option<String>,
+        min_size: Option<MinSize>,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        // A cancelled scan's thread finishes on its own; its send then fails harmlessly
+        thread::spawn(move || {
+            let result = load_wallpapers(dir, collection.as_deref(), min_size).map(|mut wallpapers| {
+                // Ratings arrive with the header pass, which re-sorts when it's done
+                sort_wallpapers(&mut wallpapers, order, |w| w.rating);
+                wallpapers
+            });
+            let _ = tx.send(result);
+        });
+        Self { rx, reason, order, previous_dir, previous_collection, started: Instant::now() }
+    }
+
+    /// The listing, once the thread is done
+    pub fn poll(&self) -> Option<Result<Vec<Wallpaper>>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(eyre!("Scan thread exited"))),
+        }
+    }
+}
+
+/// A loaded thumbnail, or None if the file couldn't be decoded
+pub struct ThumbnailResult {
+    pub path: PathBuf,
+    /// Edge it was loaded for
+    pub edge: u32,
+    pub thumbnail: Option<Arc<DynamicImage>>,
+}
+
+/// Counts for the thumbnails queued since the loader was last idle
+pub struct LoadProgress {
+    pub done: usize,
+    pub failed: usize,
+    pub total: usize,
+    started: Instant,
+}
+
+impl LoadProgress {
+    fn new() -> Self {
+        Self { done: 0, failed: 0, total: 0, started: Instant::now() }
+    }
+
+    /// Time left at the rate so far, once there's a rate to go by
+    pub fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let per_item = self.started.elapsed() / self.done as u32;
+        Some(per_item * (self.total - self.done) as u32)
+    }
+}
+
+/// Reads and decodes thumbnails on a background thread
+pub struct ThumbnailLoader {
+    tx: Sender<(u64, u32, PathBuf)>,
+    rx: Receiver<ThumbnailResult>,
+    /// Paths requested but not yet returned, to avoid queueing duplicates
+    pending: HashSet<PathBuf>,
+    /// Bumped by `cancel`; the worker skips requests queued under an older generation
+    generation: Arc<AtomicU64>,
+    progress: LoadProgress,
+    /// Thumbnail edge in pixels for the current grid cell size
+    edge: u32,
+}
+
+impl Default for ThumbnailLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThumbnailLoader {
+    pub fn new() -> Self {
+        let (req_tx, req_rx) = mpsc::channel::<(u64, u32, PathBuf)>();
+        let (res_tx, res_rx) = mpsc::channel();
+        let generation = Arc::new(AtomicU64::new(0));
+        let current = Arc::clone(&generation);
+        thread::spawn(move || {
+            while let Ok((queued_in, edge, path)) = req_rx.recv() {
+                if queued_in != current.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let thumbnail = wallpaper::load_thumbnail(&path, edge).map(Arc::new);
+                if res_tx.send(ThumbnailResult { path, edge, thumbnail }).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            tx: req_tx,
+            rx: res_rx,
+            pending: HashSet::new(),
+            generation,
+            progress: LoadProgress::new(),
+            edge: thumbnails::DEFAULT_EDGE,
+        }
+    }
+
+    pub fn request(&mut self, path: &Path) {
+        if self.pending.is_empty() {
+            self.progress = LoadProgress::new();
+        }
+        if self.pending.insert(path.to_path_buf()) {
+            self.progress.total += 1;
+            let _ = self.tx.send((self.generation.load(Ordering::Relaxed), self.edge, path.to_path_buf()));
+        }
+    }
+
+    /// Thumbnails finished since the last poll
+    pub fn poll_results(&mut self) -> Vec<ThumbnailResult> {
+        let mut results: Vec<ThumbnailResult> = self.rx.try_iter().collect();
+        for result in &results {
+            // Results of a cancelled batch that were already in flight don't count towards the next
+            if self.pending.remove(&result.path) {
+                self.progress.done += 1;
+                if result.thumbnail.is_none() {
+                    self.progress.failed += 1;
+                }
+            }
+        }
+        // Loaded for a cell size that has since changed
+        let edge = self.edge;
+        results.retain(|result| result.edge == edge);
+        results
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Progress of the current batch, while one is loading
+    pub fn progress(&self) -> Option<&LoadProgress> {
+        self.has_pending().then_some(&self.progress)
+    }
+
+    /// Load thumbnails for cells `pixels` across from now on. Returns true when that changes the
+    /// thumbnail size, dropping queued requests; loaded thumbnails should then be reloaded.
+    pub fn set_cell_pixels(&mut self, pixels: u32) -> bool {
+        let edge = thumbnails::edge_for(pixels);
+        if edge == self.edge {
+            return false;
+        }
+        self.edge = edge;
+        self.cancel();
+        true
+    }
+
+    /// Drop everything still queued; the thumbnail being decoded right now still arrives
+    pub fn cancel(&mut self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        self.pending.clear();
+    }
+}
+
+/// Full-size decodes run one at a time, so paging quickly through large images doesn't
+/// start a decode per page that was only glanced at
+static DECODE_SLOT: Mutex<()> = Mutex::new(());
+
+/// Decodes a full-size image for the preview modal on a background thread, scaled down to
+/// `bounds` there so the render thread only ever handles a screen-sized image.
+/// Dropping the loader cancels a decode that hasn't started and stops one in progress at the
+/// next read or stage, releasing the decode slot for the next preview.
+pub struct PreviewLoader {
+    rx: Receiver<Result<DynamicImage>>,
+    pub path: PathBuf,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PreviewLoader {
+    pub fn start(path: PathBuf, adjustments: Adjustments, bounds: (u32, u32)) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let image_path = path.clone();
+        let stop = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let decoded = {
+                let _slot = DECODE_SLOT.lock().unwrap_or_else(|e| e.into_inner());
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                wallpaper::open_image_until(&image_path, &stop)
+            };
+            // Each stage is checked, so an abandoned preview frees the thread as soon as it can
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let result = decoded.map(|img| adjust::preview(img, &adjustments));
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let _ = tx.send(result.map(|img| fit_within(img, bounds)));
+        });
+        Self { rx, path, cancelled }
+    }
+
+    /// The adjusted image, once it's decoded
+    pub fn poll(&self) -> Option<Result<DynamicImage>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(eyre!("Preview thread exited"))),
+        }
+    }
+}
+
+impl Drop for PreviewLoader {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Shrink `img` to fit `width` × `height`, keeping its aspect ratio; smaller images are kept as they are
+fn fit_within(img: DynamicImage, (width, height): (u32, u32)) -> DynamicImage {
+    if img.width() <= width && img.height() <= height {
+        return img;
+    }
+    img.resize(width.max(1), height.max(1), image::imageops::FilterType::Triangle)
+}
+
+/// What the header pass learns about a file without decoding it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub dimensions: Option<(u32, u32)>,
+    /// Stars from a photo manager
+    pub rating: Option<u8>,
+}
+
+/// Reads image sizes and ratings from file headers on a background thread, one file at a time,
+/// along with the format, size and animation shown in badges.
+/// Files are hashed along the way, and once the pass ends, the ones that turn out to be earlier
+/// indexed files under a new name are reported as moves.
+pub struct HeaderReader {
+    rx: Receiver<(PathBuf, Header, FileInfo)>,
+    moves_rx: Receiver<Vec<(PathBuf, PathBuf)>>,
+    done: bool,
+}
+
+impl HeaderReader {
+    /// Dropping the reader stops the pass at the next file
+    pub fn start(paths: Vec<PathBuf>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (moves_tx, moves_rx) = mpsc::channel();
+        thread::spawn(move || {
+            // Without the index every header is read again, which is only slower
+            let index = Index::open().ok();
+            // Only files the index has never seen can be where a vanished file went; an old
+            // copy with the same contents (e.g. an install's source) isn't a move
+            let mut arrived = Vec::new();
+            for path in &paths {
+                if index.as_ref().is_some_and(|index| !index.contains(path)) {
+                    arrived.push(path.clone());
+                }
+                let indexed = index.as_ref().and_then(|index| index.lookup(path)).unwrap_or_default();
+                let header = indexed.header.unwrap_or_else(|| read_header(index.as_ref(), path));
+                if indexed.hash.is_none()
+                    && let Some(index) = index.as_ref()
+                    && let Some(hash) = index::content_hash(path)
+                {
+                    let _ = index.store_hash(path, &hash);
+                }
+                if tx.send((path.clone(), header, FileInfo::read(path))).is_err() {
+                    return;
+                }
+            }
+            if let Some(moves) = index.and_then(|index| index.find_moves(&arrived).ok())
+                && !moves.is_empty()
+            {
+                let _ = moves_tx.send(moves);
+            }
+        });
+        Self { rx, moves_rx, done: false }
+    }
+
+    /// Files found to have been renamed or moved, as `(old, new)`, once the pass is done
+    pub fn moves(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.moves_rx.try_recv().unwrap_or_default()
+    }
+
+    /// Headers and badge info read since the last poll
+    pub fn poll_results(&mut self) -> Vec<(PathBuf, Header, FileInfo)> {
+        let mut results = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(result) => results.push(result),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        results
+    }
+
+    /// Every file has been read
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// Analyzes thumbnail colors against the theme palette on a background thread
+pub struct ColorReader {
+    rx: Receiver<(PathBuf, ColorStats)>,
+    done: bool,
+}
+
+impl ColorReader {
+    /// Dropping the reader stops the pass at the next file
+    pub fn start(paths: Vec<PathBuf>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let palette = palette::theme_palette();
+            let index = Index::open().ok();
+            for path in paths {
+                let indexed = index.as_ref().and_then(|index| index.lookup(&path));
+                let stats = match indexed {
+                    Some(Record { dominant: Some(dominant), luminance: Some(luminance), .. }) => {
+                        ColorStats::from_dominant(&dominant, luminance, &palette)
+                    }
+                    _ => {
+                        let Some(thumbnail) = wallpaper::load_thumbnail(&path, thumbnails::DEFAULT_EDGE) else {
+                            continue;
+                        };
+                        let dominant = palette::dominant_colors(&thumbnail);
+                        let luminance = palette::mean_luminance(&thumbnail);
+                        if let Some(index) = index.as_ref() {
+                            let _ = index.store_colors(&path, &dominant, luminance);
+                        }
+                        ColorStats::from_dominant(&dominant, luminance, &palette)
+                    }
+                };
+                if tx.send((path, stats)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { rx, done: false }
+    }
+
+    /// Stats computed since the last poll
+    pub fn poll_results(&mut self) -> Vec<(PathBuf, ColorStats)> {
+        let mut results = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(result) => results.push(result),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        results
+    }
+
+    /// Every file has been analyzed
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// Order `wallpapers`; `stars` gives each one's star rating for `SortOrder::Rating`
+pub fn sort_wallpapers(wallpapers: &mut Vec<Wallpaper>, order: SortOrder, stars: impl Fn(&Wallpaper) -> Option<u8>) {
+    let entries: Vec<SortKey> = wallpapers
+        .iter()
+        .map(|w| SortKey {
+            name: w.name.as_str(),
+            modified: if order.uses_mtime() {
+                std::fs::metadata(&w.path).and_then(|m| m.modified()).ok()
+            } else {
+                None
+            },
+            theme_match: w.colors.and_then(|c| c.theme_match),
+            stars: stars(w),
+        })
+        .collect();
+    let indices = browse::sort_order(&entries, order);
+    let mut slots: Vec<Option<Wallpaper>> = std::mem::take(wallpapers).into_iter().map(Some).collect();
+    *wallpapers = indices.into_iter().filter_map(|i| slots[i].take()).collect();
+}
+
+/// Read `path`'s header from the file itself and store it, so the next pass finds it indexed
+fn read_header(index: Option<&Index>, path: &Path) -> Header {
+    let header = Header { dimensions: formats::dimensions(path), rating: rating::read(path) };
+    if let Some(index) = index {
+        let _ = index.store_header(path, &header);
+    }
+    header
+}
+
+/// Wallpapers in a collection when one is given, otherwise in `dir`
+/// Collections are hand-picked, so only directory listings are held to `min_size`
+fn load_wallpapers(dir: Option<PathBuf>, collection: Option<&str>, min_size: Option<MinSize>) -> Result<Vec<Wallpaper>> {
+    match collection {
+        Some(name) => Ok(Collections::load()?
+            .paths(name)?
+            .into_iter()
+            .map(Wallpaper::new)
+            .collect()),
+        None => {
+            if let Some(dir) = dir.as_ref()
+                && !dir.is_dir()
+            {
+                return Err(eyre!("No such directory: {}", dir.display()));
+            }
+            let mut wallpapers = wallpaper::discover_wallpapers(dir)?;
+            if let Some(min) = min_size {
+                let index = min.dimensions.and_then(|_| Index::open().ok());
+                wallpapers.retain_mut(|w| {
+                    let bytes = std::fs::metadata(&w.path).map(|m| m.len()).unwrap_or_default();
+                    if min.dimensions.is_some() {
+                        let header = index
+                            .as_ref()
+                            .and_then(|index| index.lookup(&w.path)?.header)
+                            .unwrap_or_else(|| read_header(index.as_ref(), &w.path));
+                        w.dimensions = header.dimensions;
+                        w.rating = header.rating;
+                    }
+                    min.admits(bytes, w.dimensions)
+                });
+            }
+            Ok(wallpapers)
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::adjust::Adjustments;
+use crate::wallpaper;
+use color_eyre::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Stable path of the lock screen image; lockers are pointed here once and it's replaced in place
+pub fn get_lockscreen_image_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/share"))
+        .join("omarchy-wallpaper-picker")
+        .join("lockscreen.png")
+}
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir().unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
+}
+
+/// Include file for hyprlock; `source` it from hyprlock.conf and use
+/// `path = $lockscreen_wallpaper` in a `background` block
+pub fn get_hyprlock_include_path() -> PathBuf {
+    config_dir().join("hypr").join("hyprlock-wallpaper.conf")
+}
+
+/// Render `source` with `adjustments` as the lock screen image and point hyprlock and swaylock at it
+pub fn apply(source: &Path, adjustments: Adjustments) -> Result<PathBuf> {
+    let image_path = get_lockscreen_image_path();
+    if let Some(parent) = image_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let img = adjustments.apply(wallpaper::open_image(source)?);
+    // Write beside and rename so a locker starting now never reads half a file
+    let tmp_path = image_path.with_extension("png.tmp");
+    img.save_with_format(&tmp_path, image::ImageFormat::Png)?;
+    fs::rename(&tmp_path, &image_path)?;
+
+    write_hyprlock_include(&image_path)?;
+    update_swaylock_config(&image_path)?;
+    Ok(image_path)
+}
+
+/// Written only when hyprlock's config dir exists, i.e. Hyprland is set up
+fn write_hyprlock_include(image_path: &Path) -> Result<()> {
+    let include = get_hyprlock_include_path();
+    if include.parent().is_some_and(Path::is_dir) {
+        fs::write(
+            &include,
+            format!(
+                "# Managed by omarchy-wallpaper-picker (:lockscreen)\n$lockscreen_wallpaper = {}\n",
+                image_path.display()
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+/// swaylock has no includes, so its `image=` line is rewritten in place when a config exists
+fn update_swaylock_config(image_path: &Path) -> Result<()> {
+    let config = config_dir().join("swaylock").join("config");
+    let Ok(contents) = fs::read_to_string(&config) else {
+        return Ok(());
+    };
+    let image_line = format!("image={}", image_path.display());
+    let mut replaced = false;
+    let mut lines: Vec<String> = contents
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with("image=") {
+                replaced = true;
+                image_line.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !replaced {
+        lines.push(image_line);
+    }
+    fs::write(&config, lines.join("\n") + "\n")?;
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use crate::config::Config;
+use crate::wallpaper;
+use color_eyre::{eyre::eyre, Result};
+use std::fs;
+use std::process::{Command, Stdio};
+
+/// Copy the applied wallpaper to the display manager's background (`login_background` in the
+/// config, e.g. an SDDM theme's `background.jpg`). The destination is usually root-owned, so
+/// the copy runs through `pkexec`, which asks for authorization.
+pub fn sync(config: &Config) -> Result<()> {
+    let dest = config
+        .login_background
+        .as_ref()
+        .ok_or_else(|| eyre!("Set login_background in the config to enable :sync login"))?;
+    let current = wallpaper::get_current_background_path();
+    let source = fs::canonicalize(&current)
+        .map_err(|_| eyre!("No wallpaper applied yet"))?;
+
+    let status = Command::new("pkexec")
+        .arg("install")
+        .args(["-m", "644"])
+        .arg(&source)
+        .arg(dest)
+        // pkexec's agent prompt is graphical; keep its output off the TUI
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| eyre!("Failed to run pkexec: {}", e))?;
+    if !status.success() {
+        return Err(eyre!("Copying to {} was not authorized or failed", dest.display()));
+    }
+    Ok(())
+}
@@ -1,91 +1,239 @@
-mod app;
-mod encoder;
-mod ui;
-mod wallpaper;
-
+use omarchy_wallpaper_picker::{announce, app, browse, browser, cli, config, daemon, dmenu, events, import, index, instance, ipc, keymap, listing, net, palette, pregen, profile, service, terminal, theme, ui, wallpaper};
 use app::{App, Mode};
+use browse::Nav;
+use browser::Input;
 use color_eyre::Result;
-use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use import::Collision;
+use ipc::IpcCommand;
+use keymap::Action;
+use profile::Phase;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use std::path::Path;
 use std::time::{Duration, Instant};
-use ratatui::{prelude::*, widgets::{Block, Borders, Gauge}};
-use std::io::{self, stdout};
+use terminal::{Screen, TerminalGuard};
 
-fn main() -> Result<()> {
-    color_eyre::install()?;
+/// The index query given with `--list`, if any of its filters were
+fn list_query(args: &clap::ArgMatches) -> Result<Option<index::Query>> {
+    let min_dimensions = match args.get_one::<String>("min-size") {
+        Some(spec) => Some(
+            wallpaper::MinSize::parse_dimensions(spec)
+                .ok_or_else(|| color_eyre::eyre::eyre!("--min-size: expected WxH, got {}", spec))?,
+        ),
+        None => None,
+    };
+    let query = index::Query {
+        min_dimensions,
+        tag: args.get_one::<String>("tag").cloned(),
+        min_stars: args.get_one::<u8>("min-stars").copied(),
+        palette: if args.get_flag("by-theme") { palette::theme_palette() } else { Vec::new() },
+    };
+    let filtered = query.min_dimensions.is_some() || query.tag.is_some() || query.min_stars.is_some() || args.get_flag("by-theme");
+    Ok(filtered.then_some(query))
+}
 
-    // Setup terminal
-    enable_raw_mode()?;
-    execute!(stdout(), EnterAlternateScreen)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+fn main() -> Result<()> {
+    terminal::install_hooks()?;
 
-    // Run app
-    let result = run(&mut terminal);
+    // --offline and the path overrides combine with any mode
+    let args = cli::command().get_matches();
+    if args.get_flag("offline") {
+        net::set_offline();
+    }
+    if args.get_flag("no-color") {
+        theme::set_no_color();
+    }
+    if args.get_flag("screen-reader") {
+        announce::set_enabled();
+    }
+    if let Some(dir) = args.get_one::<String>("backgrounds-dir") {
+        wallpaper::set_backgrounds_dir(config::expand_home(Path::new(dir)));
+    }
+    if let Some(link) = args.get_one::<String>("current-link") {
+        wallpaper::set_current_link(config::expand_home(Path::new(link)));
+    }
+    if let Some(&shell) = args.get_one("completions") {
+        cli::print_completions(shell);
+        return Ok(());
+    }
+    if args.get_flag("man") {
+        return cli::print_man();
+    }
+    if args.get_flag("daemon") {
+        return daemon::run();
+    }
+    if args.get_flag("apply-current") {
+        return daemon::quick_switch(IpcCommand::Reload);
+    }
+    if args.get_flag("install-service") {
+        let link = args.get_one::<String>("current-link").map(|link| config::expand_home(Path::new(link)));
+        return service::install(link.as_deref());
+    }
+    for (flag, command) in [("next", IpcCommand::Next), ("prev", IpcCommand::Prev), ("random", IpcCommand::Random)] {
+        if args.get_flag(flag) {
+            return daemon::quick_switch(command);
+        }
+    }
+    if let Some(path) = args.get_one::<String>("set") {
+        return daemon::quick_switch(IpcCommand::Set(config::expand_home(Path::new(path))));
+    }
+    if args.contains_id("generate-thumbs") {
+        return pregen::run(args.get_one::<String>("generate-thumbs").map(|dir| config::expand_home(Path::new(dir))));
+    }
+    if args.contains_id("list") {
+        return listing::run(
+            args.get_one::<String>("list").map(|dir| config::expand_home(Path::new(dir))),
+            args.get_flag("json"),
+            list_query(&args)?,
+        );
+    }
+    if args.contains_id("dmenu") {
+        return dmenu::run(args.get_one::<String>("dmenu").map(String::as_str));
+    }
+    if args.get_flag("profile") {
+        profile::enable();
+    }
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(stdout(), LeaveAlternateScreen)?;
+    let _lock = instance::InstanceLock::acquire(instance::Role::Picker)?;
+    let result = {
+        // Setup terminal; the guard restores it on return or panic
+        let mut guard = TerminalGuard::new()?;
+        run(&mut guard.terminal)
+    };
+    // A crossfade or variant still being written would be cut off otherwise
+    events::wait_for_background();
 
+    // Printed after the terminal is restored so it stays on screen
+    if profile::is_enabled() {
+        eprint!("{}", profile::report());
+    }
     result
 }
 
-fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+fn run(screen: &mut impl Screen) -> Result<()> {
     let mut app = App::new()?;
 
-    // Preload all thumbnails with progress
-    app.preload_thumbnails(|current, total, name| {
-        let _ = terminal.draw(|frame| {
-            let area = frame.area();
-            let chunks = Layout::vertical([
-                Constraint::Percentage(40),
-                Constraint::Length(3),
-                Constraint::Length(1),
-                Constraint::Percentage(40),
-            ]).split(area);
-
-            let progress = if total > 0 { current as f64 / total as f64 } else { 0.0 };
-            let gauge = Gauge::default()
-                .block(Block::default().title(" Loading thumbnails ").borders(Borders::ALL))
-                .gauge_style(Style::default().fg(Color::Cyan))
-                .ratio(progress)
-                .label(format!("{}/{}", current + 1, total));
-            frame.render_widget(gauge, chunks[1]);
-
-            let name_text = ratatui::widgets::Paragraph::new(name.to_string())
-                .alignment(Alignment::Center)
-                .style(Style::default().fg(Color::DarkGray));
-            frame.render_widget(name_text, chunks[2]);
-        });
-    });
-
     let mut needs_redraw = true;
     let mut last_draw = Instant::now();
     let frame_duration = Duration::from_millis(16); // ~60fps max
+    let mut last_tick = Instant::now();
+    let spinner_interval = Duration::from_millis(100);
 
     loop {
         // Poll for completed image encodings
-        let had_new_images = {
-            let before = app.encoder.cache_len();
-            app.encoder.poll_results();
-            app.encoder.cache_len() > before
-        };
-        if had_new_images {
+        if app.encoder.poll_results() {
+            needs_redraw = true;
+        }
+
+        // Thumbnails read on the loader thread
+        if app.poll_thumbnails() {
+            needs_redraw = true;
+        }
+
+        // Full-size image for the preview modal
+        if app.poll_preview() {
+            needs_redraw = true;
+        }
+
+        // Image sizes for the grid badges
+        if app.poll_headers() {
+            needs_redraw = true;
+        }
+
+        // Theme-match scores for :sort match
+        if app.poll_colors() {
+            needs_redraw = true;
+        }
+
+        // Animate placeholders while thumbnails or a listing are still loading
+        if app.is_loading() && last_tick.elapsed() >= spinner_interval {
+            app.spinner_tick = app.spinner_tick.wrapping_add(1);
+            last_tick = Instant::now();
+            needs_redraw = true;
+        }
+
+        // Pick up files added/removed/renamed in the browsed directory
+        app.poll_watcher();
+
+        // Apply the next queued wallpaper when it is due
+        if app.poll_queue() {
+            needs_redraw = true;
+        }
+
+        // Apply edits to the config file, and clear the confirmation after a while
+        if app.poll_config() | app.expire_notice() {
+            needs_redraw = true;
+        }
+
+        // Pick up collection changes made by other instances or the CLI
+        if app.poll_collections() {
+            needs_redraw = true;
+        }
+
+        // Follow wallpapers applied by other tools
+        if app.poll_current_wallpaper() {
+            needs_redraw = true;
+        }
+
+        // Swap in a finished directory listing
+        if app.poll_scan() {
+            needs_redraw = true;
+        }
+
+        // Open a fetched subreddit
+        if app.poll_reddit() {
+            needs_redraw = true;
+        }
+
+        // Download progress, and applying finished downloads
+        if app.poll_downloads() {
+            needs_redraw = true;
+        }
+
+        // Apply a downloaded picture of the day
+        if app.poll_potd() {
+            needs_redraw = true;
+        }
+
+        // Count down a wallpaper on trial
+        if app.poll_trial() {
+            needs_redraw = true;
+        }
+
+        // Commands from the control socket
+        if app.poll_ipc() {
+            needs_redraw = true;
+        }
+
+        app.emit_selection_changed();
+
+        // Describe the new state in screen-reader mode
+        if app.announce_state() {
+            needs_redraw = true;
+        }
+
+        // Show the selection on the desktop with hover-apply on
+        if app.poll_hover() {
+            needs_redraw = true;
+        }
+
+        // Keep drawing while the grid glides to a new position
+        if app.is_scrolling() {
             needs_redraw = true;
         }
 
         // Only redraw if needed and enough time has passed
         if needs_redraw && last_draw.elapsed() >= frame_duration {
-            terminal.draw(|frame| ui::render(frame, &mut app))?;
+            let started = Instant::now();
+            profile::time(Phase::Frame, || screen.draw(|frame| ui::render(frame, &mut app)))?;
+            app.frame_time = started.elapsed();
             last_draw = Instant::now();
             needs_redraw = false;
         }
 
         // Poll for events with timeout instead of blocking
-        if event::poll(Duration::from_millis(50))? {
-            match event::read()? {
+        let poll_timeout = if app.is_scrolling() { frame_duration } else { Duration::from_millis(50) };
+        if let Some(event) = screen.next_event(poll_timeout)? {
+            match event {
                 Event::Resize(_, _) => {
                     needs_redraw = true;
                 }
@@ -95,49 +243,162 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
                     // Handle input modes separately
                     match app.mode {
                         Mode::Search => match key.code {
-                            KeyCode::Esc => app.cancel_search(),
-                            KeyCode::Enter => app.confirm_search(),
-                            KeyCode::Backspace => app.search_backspace(),
-                            KeyCode::Char(c) => app.search_input(c),
+                            KeyCode::Esc => app.handle(Input::SearchCancel),
+                            KeyCode::Enter => app.handle(Input::SearchConfirm),
+                            KeyCode::Backspace => app.handle(Input::SearchBackspace),
+                            KeyCode::Char(c) => app.handle(Input::SearchChar(c)),
                             _ => {}
                         },
                         Mode::Command => match key.code {
-                            KeyCode::Esc => app.cancel_command(),
-                            KeyCode::Enter => app.confirm_command()?,
-                            KeyCode::Backspace => app.command_backspace(),
-                            KeyCode::Tab => app.command_autocomplete(),
-                            KeyCode::Up => app.move_completion_up(),
-                            KeyCode::Down => app.move_completion_down(),
-                            KeyCode::Char(c) => app.command_input(c),
+                            KeyCode::Esc => app.handle(Input::CommandCancel),
+                            KeyCode::Enter => app.handle(Input::CommandConfirm),
+                            KeyCode::Backspace => app.handle(Input::CommandBackspace),
+                            KeyCode::Tab => app.handle(Input::CommandComplete),
+                            KeyCode::Up => app.handle(Input::CompletionPrev),
+                            KeyCode::Down => app.handle(Input::CompletionNext),
+                            KeyCode::Char(c) => app.handle(Input::CommandChar(c)),
                             _ => {}
                         },
-                        _ => match key.code {
-                            // Quit
-                            KeyCode::Char('q') => app.should_quit = true,
-
-                            // Navigation - vim bindings
-                            KeyCode::Char('h') | KeyCode::Left => app.move_left(),
-                            KeyCode::Char('j') | KeyCode::Down => app.move_down(),
-                            KeyCode::Char('k') | KeyCode::Up => app.move_up(),
-                            KeyCode::Char('l') | KeyCode::Right => app.move_right(),
-
-                            // Search and Command
-                            KeyCode::Char('/') => app.start_search(),
-                            KeyCode::Char(':') => app.start_command(),
-
-                            // Reset destination
-                            KeyCode::Char('H') => app.reset_view_dir()?,
-
-                            // Actions
+                        Mode::Crop => match key.code {
+                            KeyCode::Esc | KeyCode::Char('c') => app.cancel_crop(),
                             KeyCode::Enter => {
-                                app.apply_wallpaper()?;
+                                let result = app.apply_crop();
+                                app.report(result);
                             }
-                            KeyCode::Char(' ') => app.toggle_preview(),
-                            KeyCode::Char('?') => app.toggle_help(),
-                            KeyCode::Esc => app.escape(),
-
+                            KeyCode::Char('h') | KeyCode::Left => app.crop_move(-1.0, 0.0),
+                            KeyCode::Char('j') | KeyCode::Down => app.crop_move(0.0, 1.0),
+                            KeyCode::Char('k') | KeyCode::Up => app.crop_move(0.0, -1.0),
+                            KeyCode::Char('l') | KeyCode::Right => app.crop_move(1.0, 0.0),
+                            KeyCode::Char('+') | KeyCode::Char('=') => app.crop_grow(),
+                            KeyCode::Char('-') => app.crop_shrink(),
+                            _ => {}
+                        },
+                        Mode::Compare => match key.code {
+                            KeyCode::Esc | KeyCode::Char('v') => app.close_compare(),
+                            KeyCode::Char('1') => {
+                                let result = app.apply_compared(false);
+                                app.report(result);
+                            }
+                            KeyCode::Char('2') => {
+                                let result = app.apply_compared(true);
+                                app.report(result);
+                            }
+                            KeyCode::Char('h') | KeyCode::Left => app.compare_pan(-1.0, 0.0),
+                            KeyCode::Char('j') | KeyCode::Down => app.compare_pan(0.0, 1.0),
+                            KeyCode::Char('k') | KeyCode::Up => app.compare_pan(0.0, -1.0),
+                            KeyCode::Char('l') | KeyCode::Right => app.compare_pan(1.0, 0.0),
+                            KeyCode::Char('+') | KeyCode::Char('=') => app.compare_zoom_in(),
+                            KeyCode::Char('-') => app.compare_zoom_out(),
+                            KeyCode::Char('0') => app.compare_reset(),
+                            _ => {}
+                        },
+                        Mode::Trash => match key.code {
+                            KeyCode::Esc | KeyCode::Char('q') => app.close_trash(),
+                            KeyCode::Char('j') | KeyCode::Down => app.move_trash_selection(1),
+                            KeyCode::Char('k') | KeyCode::Up => app.move_trash_selection(-1),
+                            KeyCode::Enter => {
+                                let result = app.restore_trashed();
+                                app.report(result);
+                            }
+                            _ => {}
+                        },
+                        Mode::ConfirmApply => match key.code {
+                            KeyCode::Esc | KeyCode::Char('n') => app.cancel_apply(),
+                            KeyCode::Enter | KeyCode::Char('y') => {
+                                let result = app.confirm_apply(false);
+                                app.report(result);
+                            }
+                            KeyCode::Char('a') => {
+                                let result = app.confirm_apply(true);
+                                app.report(result);
+                            }
+                            _ => {}
+                        },
+                        Mode::ApplyFailed => match key.code {
+                            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => app.close_apply_failure(),
+                            _ => {}
+                        },
+                        Mode::Downloads => match key.code {
+                            KeyCode::Esc | KeyCode::Char('q') => app.mode = Mode::Grid,
+                            KeyCode::Char('c') => app.clear_downloads(),
+                            KeyCode::Char('r') => app.downloads.retry_failed(),
+                            _ => {}
+                        },
+                        Mode::TagEditor => match key.code {
+                            KeyCode::Esc | KeyCode::Char('q') => app.close_tag_editor(),
+                            KeyCode::Char('j') | KeyCode::Down => app.move_tag_selection(1),
+                            KeyCode::Char('k') | KeyCode::Up => app.move_tag_selection(-1),
+                            KeyCode::Char(' ') => app.toggle_tag(),
+                            KeyCode::Enter => {
+                                let result = app.apply_tag_editor();
+                                app.report(result);
+                            }
+                            _ => {}
+                        },
+                        Mode::UndoList => match key.code {
+                            KeyCode::Esc | KeyCode::Char('q') => app.mode = Mode::Grid,
+                            KeyCode::Char('u') => {
+                                let result = app.undo();
+                                app.report(result);
+                            }
+                            _ => {}
+                        },
+                        Mode::Settings => match key.code {
+                            KeyCode::Esc | KeyCode::Char('q') => app.mode = Mode::Grid,
+                            KeyCode::Char('j') | KeyCode::Down => app.move_settings_selection(1),
+                            KeyCode::Char('k') | KeyCode::Up => app.move_settings_selection(-1),
+                            KeyCode::Enter => app.edit_setting(),
+                            _ => {}
+                        },
+                        Mode::Trying => match key.code {
+                            KeyCode::Char('y') | KeyCode::Enter => {
+                                let result = app.keep_trial();
+                                app.report(result);
+                            }
+                            KeyCode::Char('n') | KeyCode::Esc => {
+                                let result = app.end_trial();
+                                app.report(result);
+                            }
+                            _ => {}
+                        },
+                        Mode::ImportConflict => match key.code {
+                            KeyCode::Esc => app.cancel_import(),
+                            KeyCode::Char('r') => {
+                                let result = app.resolve_import(Collision::Rename);
+                                app.report(result);
+                            }
+                            KeyCode::Char('s') => {
+                                let result = app.resolve_import(Collision::Skip);
+                                app.report(result);
+                            }
+                            KeyCode::Char('o') => {
+                                let result = app.resolve_import(Collision::Overwrite);
+                                app.report(result);
+                            }
+                            _ => {}
+                        },
+                        Mode::Help if app.help_searching => match key.code {
+                            KeyCode::Esc => app.cancel_help_search(),
+                            KeyCode::Enter => app.help_searching = false,
+                            KeyCode::Backspace => app.help_search_backspace(),
+                            KeyCode::Char(c) => app.help_search_input(c),
+                            _ => {}
+                        },
+                        Mode::Help => match key.code {
+                            KeyCode::Char('j') | KeyCode::Down => app.scroll_help(1),
+                            KeyCode::Char('k') | KeyCode::Up => app.scroll_help(-1),
+                            KeyCode::PageDown | KeyCode::Char(' ') => app.scroll_help(10),
+                            KeyCode::PageUp => app.scroll_help(-10),
+                            KeyCode::Char('/') => app.help_searching = true,
+                            KeyCode::Esc if !app.help_query.is_empty() => app.cancel_help_search(),
+                            KeyCode::Esc | KeyCode::Char('?') => app.toggle_help(),
+                            KeyCode::Char('q') => app.should_quit = true,
                             _ => needs_redraw = false,
                         },
+                        _ => match keymap::action(key.code, matches!(app.mode, Mode::Preview)) {
+                            Some(action) => run_action(&mut app, action),
+                            None => needs_redraw = false,
+                        },
                     }
                 }
                 _ => {}
@@ -149,5 +410,66 @@ fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
         }
     }
 
-    Ok(())
+    // A hovered wallpaper that wasn't applied goes away with the picker
+    let reverted = app.end_hover();
+    app.save_session()?;
+    reverted
+}
+
+/// Carry out a key binding in the grid or preview
+fn run_action(app: &mut App, action: Action) {
+    match action {
+        Action::Quit => app.should_quit = true,
+        Action::MoveUp => app.handle(Input::Move(Nav::Up)),
+        Action::MoveDown => app.handle(Input::Move(Nav::Down)),
+        Action::MoveLeft => app.handle(Input::Move(Nav::Left)),
+        Action::MoveRight => app.handle(Input::Move(Nav::Right)),
+        Action::PreviewPrev => app.preview_step(-1),
+        Action::PreviewNext => app.preview_step(1),
+        Action::Search => app.start_search(),
+        Action::Command => app.start_command(),
+        Action::ResetViewDir => app.reset_view_dir(),
+        Action::Apply => {
+            let result = app.request_apply();
+            app.report(result);
+        }
+        Action::TogglePreview => app.toggle_preview(),
+        Action::Try => {
+            let result = app.start_trial();
+            app.report(result);
+        }
+        Action::MonitorPreview => app.toggle_monitor_preview(),
+        Action::BeforeAfter => app.toggle_before_after(),
+        Action::Crop => app.start_crop(),
+        Action::ToggleBlur => app.toggle_blur(),
+        Action::ToggleDim => app.toggle_dim(),
+        Action::ToggleGrayscale => app.toggle_grayscale(),
+        Action::CycleFillMode => app.cycle_fill_mode(),
+        Action::MarkCompare => app.mark_compare(),
+        Action::ToggleMark => app.toggle_mark(),
+        Action::YankPath => {
+            let result = app.yank(false);
+            app.report(result);
+        }
+        Action::YankFile => {
+            let result = app.yank(true);
+            app.report(result);
+        }
+        Action::Enqueue => {
+            let result = app.enqueue_selected();
+            app.report(result);
+        }
+        Action::Undo => {
+            let result = app.undo();
+            app.report(result);
+        }
+        Action::ToggleBadges => app.toggle_badges(),
+        Action::ToggleDiagnostics => app.show_diagnostics = !app.show_diagnostics,
+        Action::GrowCells => app.grow_cells(),
+        Action::ShrinkCells => app.shrink_cells(),
+        Action::PrevGroup => app.handle(Input::JumpGroup(-1)),
+        Action::NextGroup => app.handle(Input::JumpGroup(1)),
+        Action::Help => app.toggle_help(),
+        Action::Escape => app.escape(),
+    }
 }
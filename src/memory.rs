@@ -0,0 +1,47 @@
+use image::DynamicImage;
+
+/// Bytes held by a decoded image's pixels
+pub fn image_bytes(img: &DynamicImage) -> usize {
+    img.as_bytes().len()
+}
+
+/// Rough size of an encoded protocol state: the protocol keeps its own copy of `source`, plus
+/// the escape sequence data, at most base64 RGBA for the `cell_pixels` it's drawn into
+pub fn protocol_bytes(source: &DynamicImage, (width, height): (u32, u32)) -> usize {
+    image_bytes(source) + (width as usize * height as usize * 4).div_ceil(3) * 4
+}
+
+/// Which `(index, bytes, distance)` candidates to drop, farthest from the selection first,
+/// to bring `used` bytes within `budget`
+pub fn evictions(mut candidates: Vec<(usize, usize, usize)>, used: usize, budget: usize) -> Vec<usize> {
+    candidates.sort_by_key(|&(_, _, distance)| std::cmp::Reverse(distance));
+    let mut used = used;
+    let mut evicted = Vec::new();
+    for (index, bytes, _) in candidates {
+        if used <= budget {
+            break;
+        }
+        used = used.saturating_sub(bytes);
+        evicted.push(index);
+    }
+    evicted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocols_count_their_copy_and_payload() {
+        let source = DynamicImage::new_rgba8(10, 10);
+        assert_eq!(protocol_bytes(&source, (0, 0)), 400);
+        assert_eq!(protocol_bytes(&source, (3, 1)), 400 + 16);
+    }
+
+    #[test]
+    fn evicts_farthest_until_within_budget() {
+        let candidates = vec![(0, 10, 1), (1, 10, 8), (2, 10, 4), (3, 10, 2)];
+        assert_eq!(evictions(candidates.clone(), 40, 25), vec![1, 2]);
+        assert!(evictions(candidates, 20, 25).is_empty());
+    }
+}
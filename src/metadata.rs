@@ -0,0 +1,170 @@
+use crate::index::Index;
+use crate::ospath;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What's known about one wallpaper beyond its pixels
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Entry {
+    pub note: Option<String>,
+    /// Where the image came from, e.g. the post it was found in
+    pub source: Option<String>,
+    /// Stars set with `:rate`, overriding any rating embedded in the file
+    pub stars: Option<u8>,
+}
+
+impl Entry {
+    fn is_empty(&self) -> bool {
+        self.note.is_none() && self.source.is_none() && self.stars.is_none()
+    }
+}
+
+/// Notes and source URLs keyed by wallpaper path, stored under `$XDG_DATA_HOME`
+/// so the image files themselves are never touched
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(transparent)]
+pub struct Metadata {
+    #[serde(with = "ospath::keys")]
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+pub fn get_metadata_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/share"))
+        .join("omarchy-wallpaper-picker")
+        .join("metadata.json")
+}
+
+impl Metadata {
+    /// Load saved metadata; a missing or unreadable file means there is none
+    pub fn load() -> Self {
+        fs::read_to_string(get_metadata_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_metadata_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        // The index only mirrors the file; it catches up at the next `--list` query if this fails
+        if let Ok(mut index) = Index::open() {
+            let _ = index.sync_stars(self);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&Entry> {
+        self.entries.get(path)
+    }
+
+    /// Set or, with an empty value, clear the note for `path`
+    pub fn set_note(&mut self, path: &Path, note: &str) {
+        self.update(path, |entry| entry.note = non_empty(note));
+    }
+
+    /// Set or, with an empty value, clear the source URL for `path`
+    pub fn set_source(&mut self, path: &Path, source: &str) {
+        self.update(path, |entry| entry.source = non_empty(source));
+    }
+
+    /// Set the star rating for `path`, or with None go back to the file's own rating
+    pub fn set_stars(&mut self, path: &Path, stars: Option<u8>) {
+        self.update(path, |entry| entry.stars = stars);
+    }
+
+    /// Stars for `path`: set here, or else `embedded` from the file
+    pub fn stars(&self, path: &Path, embedded: Option<u8>) -> Option<u8> {
+        self.get(path).and_then(|entry| entry.stars).or(embedded)
+    }
+
+    /// Wallpapers given stars with `:rate`
+    pub fn rated(&self) -> impl Iterator<Item = (&Path, u8)> {
+        self.entries.iter().filter_map(|(path, entry)| Some((path.as_path(), entry.stars?)))
+    }
+
+    /// Carry `old`'s entry over to `new` after the file was renamed or moved.
+    /// Returns true if there was one.
+    pub fn rename(&mut self, old: &Path, new: &Path) -> bool {
+        let Some(moved) = self.entries.remove(old) else {
+            return false;
+        };
+        // Anything already noted on the new path wins, but nothing of the old entry is lost
+        self.update(new, |entry| {
+            entry.note = entry.note.take().or(moved.note);
+            entry.source = entry.source.take().or(moved.source);
+            entry.stars = entry.stars.or(moved.stars);
+        });
+        true
+    }
+
+    /// Take in entries from another library; its fields win where both have one
+    pub fn merge(&mut self, other: Metadata) {
+        for (path, theirs) in other.entries {
+            self.update(&path, |entry| {
+                entry.note = theirs.note.or(entry.note.take());
+                entry.source = theirs.source.or(entry.source.take());
+                entry.stars = theirs.stars.or(entry.stars);
+            });
+        }
+    }
+
+    /// Rewrite every path with `map`
+    pub fn map_paths(&mut self, map: impl Fn(&Path) -> PathBuf) {
+        self.entries = std::mem::take(&mut self.entries).into_iter().map(|(path, entry)| (map(&path), entry)).collect();
+    }
+
+    fn update(&mut self, path: &Path, change: impl FnOnce(&mut Entry)) {
+        let entry = self.entries.entry(path.to_path_buf()).or_default();
+        change(entry);
+        if entry.is_empty() {
+            self.entries.remove(path);
+        }
+    }
+
+    /// Text search matches against: the name plus any note and source
+    pub fn search_text(&self, path: &Path, name: &str) -> String {
+        match self.get(path) {
+            Some(entry) => [Some(name), entry.note.as_deref(), entry.source.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => name.to_string(),
+        }
+    }
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renaming_onto_an_entry_merges() {
+        let (old, new) = (PathBuf::from("/w/a.jpg"), PathBuf::from("/w/b.jpg"));
+        let mut metadata = Metadata::default();
+        metadata.update(&old, |e| {
+            e.note = Some("old note".to_string());
+            e.stars = Some(4);
+        });
+        metadata.update(&new, |e| e.note = Some("new note".to_string()));
+
+        assert!(metadata.rename(&old, &new));
+        assert!(metadata.get(&old).is_none());
+        let entry = metadata.get(&new).unwrap();
+        assert_eq!(entry.note.as_deref(), Some("new note"));
+        assert_eq!(entry.stars, Some(4));
+    }
+}
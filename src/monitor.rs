@@ -0,0 +1,233 @@
+use serde::Deserialize;
+use std::env;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// A connected output and its place in the compositor layout
+#[derive(Clone, Debug)]
+pub struct Monitor {
+    pub name: String,
+    /// Physical resolution in pixels (what the backend renders into)
+    pub width: u32,
+    pub height: u32,
+    /// Position in the logical layout
+    pub x: i32,
+    pub y: i32,
+    pub scale: f64,
+}
+
+impl Monitor {
+    /// Size in layout coordinates, after scaling
+    pub fn logical_size(&self) -> (f64, f64) {
+        let scale = if self.scale > 0.0 { self.scale } else { 1.0 };
+        (self.width as f64 / scale, self.height as f64 / scale)
+    }
+}
+
+/// Images covering less than this fraction of an output's width or height get a warning
+const MIN_COVERAGE: f64 = 0.75;
+/// Aspect ratios further apart than this factor get a warning (16:9 vs 4:3 is 1.33)
+const MAX_ASPECT_FACTOR: f64 = 1.5;
+
+/// Why an image of this size would look poor on one of `monitors`, if it would
+pub fn resolution_mismatch((width, height): (u32, u32), monitors: &[Monitor]) -> Option<String> {
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let aspect = width as f64 / height as f64;
+    monitors.iter().find_map(|m| {
+        if m.width == 0 || m.height == 0 {
+            return None;
+        }
+        if (width as f64) < m.width as f64 * MIN_COVERAGE || (height as f64) < m.height as f64 * MIN_COVERAGE {
+            return Some(format!(
+                "{}×{} is much smaller than {} ({}×{}) and will look blurry",
+                width, height, m.name, m.width, m.height
+            ));
+        }
+        let monitor_aspect = m.width as f64 / m.height as f64;
+        let factor = (aspect / monitor_aspect).max(monitor_aspect / aspect);
+        (factor > MAX_ASPECT_FACTOR).then(|| {
+            format!(
+                "{}×{} has a very different shape from {} ({}×{}) and will be heavily cropped",
+                width, height, m.name, m.width, m.height
+            )
+        })
+    })
+}
+
+#[derive(Deserialize)]
+struct HyprMonitor {
+    name: String,
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    transform: u32,
+}
+
+#[derive(Deserialize)]
+struct WlrOutput {
+    name: String,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default)]
+    modes: Vec<WlrMode>,
+    position: Option<WlrPosition>,
+    scale: Option<f64>,
+    transform: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WlrMode {
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    current: bool,
+}
+
+#[derive(Deserialize)]
+struct WlrPosition {
+    x: i32,
+    y: i32,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Query the compositor for connected monitors.
+/// Tries `hyprctl` first, then `wlr-randr`; returns an empty list if neither works.
+pub fn detect_monitors() -> Vec<Monitor> {
+    if let Some(monitors) = query_hyprctl()
+        && !monitors.is_empty()
+    {
+        return monitors;
+    }
+    query_wlr_randr().unwrap_or_default()
+}
+
+fn run_json(program: &str, args: &[&str]) -> Option<Vec<u8>> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(output.stdout)
+}
+
+fn query_hyprctl() -> Option<Vec<Monitor>> {
+    let stdout = run_json("hyprctl", &["monitors", "-j"])?;
+    let parsed: Vec<HyprMonitor> = serde_json::from_slice(&stdout).ok()?;
+
+    Some(
+        parsed
+            .into_iter()
+            .map(|m| {
+                // Odd transforms are rotated by 90/270 degrees
+                let (width, height) = if m.transform % 2 == 1 {
+                    (m.height, m.width)
+                } else {
+                    (m.width, m.height)
+                };
+                Monitor {
+                    name: m.name,
+                    width,
+                    height,
+                    x: m.x,
+                    y: m.y,
+                    scale: m.scale,
+                }
+            })
+            .collect(),
+    )
+}
+
+fn query_wlr_randr() -> Option<Vec<Monitor>> {
+    let stdout = run_json("wlr-randr", &["--json"])?;
+    let parsed: Vec<WlrOutput> = serde_json::from_slice(&stdout).ok()?;
+
+    Some(
+        parsed
+            .into_iter()
+            .filter(|o| o.enabled)
+            .filter_map(|o| {
+                let mode = o.modes.iter().find(|m| m.current).or(o.modes.first())?;
+                let rotated = matches!(
+                    o.transform.as_deref(),
+                    Some("90" | "270" | "flipped-90" | "flipped-270")
+                );
+                let (width, height) = if rotated {
+                    (mode.height, mode.width)
+                } else {
+                    (mode.width, mode.height)
+                };
+                let (x, y) = o.position.as_ref().map(|p| (p.x, p.y)).unwrap_or((0, 0));
+                Some(Monitor {
+                    name: o.name,
+                    width,
+                    height,
+                    x,
+                    y,
+                    scale: o.scale.unwrap_or(1.0),
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Hyprland's event socket, under `$XDG_RUNTIME_DIR` on current versions and `/tmp` on older ones
+fn hyprland_event_socket() -> Option<PathBuf> {
+    let signature = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+    let runtime = env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("hypr"));
+    runtime
+        .into_iter()
+        .chain([PathBuf::from("/tmp/hypr")])
+        .map(|dir| dir.join(&signature).join(".socket2.sock"))
+        .find(|path| path.exists())
+}
+
+/// Names of outputs as Hyprland reports them being plugged in; None outside Hyprland
+pub fn watch_hotplug() -> Option<Receiver<String>> {
+    let stream = UnixStream::connect(hyprland_event_socket()?).ok()?;
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else { break };
+            if let Some(name) = added_output(&line)
+                && tx.send(name.to_string()).is_err()
+            {
+                break;
+            }
+        }
+    });
+    Some(rx)
+}
+
+/// The output named by a `monitoradded>>NAME` event; the `monitoraddedv2` Hyprland sends alongside it is skipped
+fn added_output(line: &str) -> Option<&str> {
+    line.strip_prefix("monitoradded>>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_monitor_added_events() {
+        assert_eq!(added_output("monitoradded>>DP-2"), Some("DP-2"));
+        assert_eq!(added_output("monitoraddedv2>>1,DP-2,Dell U2720Q"), None);
+        assert_eq!(added_output("monitorremoved>>DP-2"), None);
+        assert_eq!(added_output("workspace>>2"), None);
+    }
+}
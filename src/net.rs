@@ -0,0 +1,252 @@
+use crate::config::Config;
+use color_eyre::{eyre::eyre, Result};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Give up on unresponsive servers rather than leaving a fetch spinning forever
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Some APIs (reddit) refuse requests without a descriptive user agent
+const USER_AGENT: &str = concat!("omarchy-wallpaper-picker/", env!("CARGO_PKG_VERSION"));
+
+/// `--offline` holds for the whole run; the config setting follows reloads
+static OFFLINE_FLAG: AtomicBool = AtomicBool::new(false);
+static OFFLINE_CONFIG: AtomicBool = AtomicBool::new(false);
+/// Proxy from the config; `HTTPS_PROXY` and friends apply when unset
+static PROXY: RwLock<Option<String>> = RwLock::new(None);
+
+/// Disable every network source (`--offline`)
+pub fn set_offline() {
+    OFFLINE_FLAG.store(true, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE_FLAG.load(Ordering::Relaxed) || OFFLINE_CONFIG.load(Ordering::Relaxed)
+}
+
+/// Take the proxy and offline settings from the config, replacing the previous config's
+pub fn configure(config: &Config) {
+    OFFLINE_CONFIG.store(config.offline, Ordering::Relaxed);
+    *PROXY.write().unwrap_or_else(|e| e.into_inner()) = config.proxy.clone();
+}
+
+/// Fail fast instead of waiting on a connection that will never be made
+pub fn ensure_online() -> Result<()> {
+    if is_offline() {
+        return Err(eyre!("Offline mode: network sources are disabled"));
+    }
+    Ok(())
+}
+
+fn agent() -> Result<ureq::Agent> {
+    ensure_online()?;
+    let builder = ureq::AgentBuilder::new()
+        .timeout_connect(TIMEOUT)
+        .timeout_read(TIMEOUT)
+        .user_agent(USER_AGENT);
+    let proxy = PROXY.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let builder = match &proxy {
+        Some(proxy) => builder.proxy(ureq::Proxy::new(proxy).map_err(|e| eyre!("proxy {}: {}", proxy, e))?),
+        None => builder.try_proxy_from_env(true),
+    };
+    Ok(builder.build())
+}
+
+pub fn get_json(url: &str) -> Result<serde_json::Value> {
+    let body = agent()?
+        .get(url)
+        .call()
+        .map_err(|e| eyre!("{}: {}", url, e))?
+        .into_string()?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Fetch `url` into `dest`. See `download_with_progress`.
+pub fn download(url: &str, dest: &Path) -> Result<()> {
+    download_with_progress(url, dest, None, |_, _| {})
+}
+
+/// Fetch `url` into `dest`, reporting `(received, total)` bytes as it goes.
+///
+/// Data goes to `<dest>.part` first, and a `.part` left by an interrupted run is resumed with
+/// a range request. The result is checked against the announced length and, if given, an
+/// expected MD5 before it's renamed into place; a file that fails the checksum is deleted.
+pub fn download_with_progress(
+    url: &str,
+    dest: &Path,
+    expected_md5: Option<&str>,
+    progress: impl FnMut(u64, Option<u64>),
+) -> Result<()> {
+    fetch_resumable(&agent()?, url, dest, expected_md5, progress)
+}
+
+fn fetch_resumable(
+    agent: &ureq::Agent,
+    url: &str,
+    dest: &Path,
+    expected_md5: Option<&str>,
+    mut progress: impl FnMut(u64, Option<u64>),
+) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let part = part_path(dest);
+    let mut resume_from = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = agent.get(url);
+    if resume_from > 0 {
+        request = request.set("Range", &format!("bytes={}-", resume_from));
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        // The partial file is already complete or stale; start over
+        Err(ureq::Error::Status(416, _)) => {
+            fs::remove_file(&part)?;
+            resume_from = 0;
+            agent.get(url).call().map_err(|e| eyre!("{}: {}", url, e))?
+        }
+        Err(e) => return Err(eyre!("{}: {}", url, e)),
+    };
+
+    // Servers without range support send the whole file again
+    let resumed = resume_from > 0 && response.status() == 206;
+    let start = if resumed { resume_from } else { 0 };
+    let total = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok())
+        .map(|len| len + start);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part)?;
+
+    let mut reader = response.into_reader();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut received = start;
+    progress(received, total);
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| eyre!("{}: {}", url, e))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])?;
+        received += read as u64;
+        progress(received, total);
+    }
+    file.flush()?;
+    drop(file);
+
+    if let Some(total) = total
+        && received != total
+    {
+        // Keep the partial file; the next attempt resumes it
+        return Err(eyre!("{}: connection closed after {} of {} bytes", url, received, total));
+    }
+    if let Some(expected) = expected_md5 {
+        let actual = format!("{:x}", md5::compute(fs::read(&part)?));
+        if !actual.eq_ignore_ascii_case(expected) {
+            // Resuming a corrupt file would only corrupt it again
+            fs::remove_file(&part)?;
+            return Err(eyre!("{}: checksum mismatch (expected {}, got {})", url, expected, actual));
+        }
+    }
+    fs::rename(&part, dest)?;
+    Ok(())
+}
+
+fn part_path(dest: &Path) -> std::path::PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const BODY: &[u8] = b"0123456789abcdef";
+
+    /// Serve `BODY` twice: the first time cut off after 6 bytes, the second from the
+    /// requested range. Returns the URL and the Range headers received.
+    fn flaky_server() -> (String, thread::JoinHandle<Vec<Option<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/a.png", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut ranges = Vec::new();
+            for attempt in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        range = Some(value.trim().to_string());
+                    }
+                }
+                let from: usize = range.as_deref().and_then(|r| r.trim_end_matches('-').parse().ok()).unwrap_or(0);
+                let status = if from > 0 { "206 Partial Content" } else { "200 OK" };
+                let rest = &BODY[from..];
+                let head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, rest.len());
+                stream.write_all(head.as_bytes()).unwrap();
+                let sent = if attempt == 0 { &rest[..6] } else { rest };
+                stream.write_all(sent).unwrap();
+                ranges.push(range);
+            }
+            ranges
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn interrupted_downloads_resume() {
+        let dir = std::env::temp_dir().join(format!("owp-resume-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("a.png");
+        let (url, server) = flaky_server();
+        let agent = ureq::AgentBuilder::new().timeout_read(TIMEOUT).build();
+
+        assert!(fetch_resumable(&agent, &url, &dest, None, |_, _| {}).is_err());
+        assert!(!dest.exists());
+        assert_eq!(fs::read(part_path(&dest)).unwrap(), &BODY[..6]);
+
+        let mut last = (0, None);
+        let md5 = format!("{:x}", md5::compute(BODY));
+        fetch_resumable(&agent, &url, &dest, Some(&md5), |received, total| last = (received, total)).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), BODY);
+        assert!(!part_path(&dest).exists());
+        assert_eq!(last, (BODY.len() as u64, Some(BODY.len() as u64)));
+        assert_eq!(server.join().unwrap(), [None, Some("6-".to_string())]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn checksum_mismatches_discard_the_download() {
+        let dir = std::env::temp_dir().join(format!("owp-checksum-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dest = dir.join("a.png");
+        let (url, server) = flaky_server();
+        let agent = ureq::AgentBuilder::new().timeout_read(TIMEOUT).build();
+
+        assert!(fetch_resumable(&agent, &url, &dest, None, |_, _| {}).is_err());
+        let wrong = format!("{:x}", md5::compute(b"something else"));
+        let err = fetch_resumable(&agent, &url, &dest, Some(&wrong), |_, _| {}).unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"));
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+        server.join().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::config::Config;
+use crate::events::{self, AppEvent, EventBus};
+use color_eyre::Result;
+use notify_rust::Notification;
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Current wallpaper as waybar `return-type: json` output, rewritten on every apply.
+/// A `custom` module can show it with `exec: cat <path>` and `interval`/`signal`.
+pub fn get_status_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("status.json")
+}
+
+/// Keep the status file up to date, and pop a notification per apply when `notify_on_apply` is set
+pub fn subscribe(bus: &mut EventBus, config: &Config) {
+    let notify = config.notify_on_apply;
+    bus.subscribe(move |event| {
+        if let AppEvent::WallpaperApplied(path) = event {
+            // Status-bar integration is best-effort
+            let _ = write_status(path);
+            if notify {
+                let path = path.clone();
+                // Talking to the notification daemon over D-Bus can stall; don't block on it
+                events::spawn_background(move || {
+                    let _ = Notification::new()
+                        .summary("Wallpaper changed")
+                        .body(&display_name(&path))
+                        .image_path(&path.to_string_lossy())
+                        .appname("omarchy-wallpaper-picker")
+                        .show();
+                });
+            }
+        }
+    });
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn write_status(path: &Path) -> Result<()> {
+    let status_path = get_status_path();
+    if let Some(parent) = status_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let status = json!({
+        "text": display_name(path),
+        "tooltip": path.display().to_string(),
+        "class": "wallpaper",
+    });
+    // Replace atomically so waybar never reads a partial file
+    let tmp_path = status_path.with_extension("json.tmp");
+    fs::write(&tmp_path, status.to_string())?;
+    fs::rename(&tmp_path, &status_path)?;
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! Serde for stored paths that may not be UTF-8. Valid paths stay plain strings so the
+//! files remain readable; anything else is kept as its raw bytes instead of failing the save.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Stored {
+    fn from_path(path: &Path) -> Self {
+        match path.to_str() {
+            Some(text) => Stored::Text(text.to_string()),
+            None => Stored::Bytes(path.as_os_str().as_bytes().to_vec()),
+        }
+    }
+
+    fn into_path(self) -> PathBuf {
+        match self {
+            Stored::Text(text) => PathBuf::from(text),
+            Stored::Bytes(bytes) => PathBuf::from(OsString::from_vec(bytes)),
+        }
+    }
+}
+
+/// For output-only `PathBuf` fields, via `serialize_with`
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    Stored::from_path(path).serialize(serializer)
+}
+
+/// For `Option<PathBuf>` fields
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(Stored::from_path).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<Stored>::deserialize(deserializer)?.map(Stored::into_path))
+    }
+}
+
+/// For sequences of paths, e.g. `Vec<PathBuf>` or `VecDeque<PathBuf>`
+pub mod seq {
+    use super::*;
+
+    pub fn serialize<'a, T, S>(paths: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator<Item = &'a PathBuf>,
+        S: Serializer,
+    {
+        serializer.collect_seq(paths.into_iter().map(|p| Stored::from_path(p)))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromIterator<PathBuf>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<Stored>::deserialize(deserializer)?.into_iter().map(Stored::into_path).collect())
+    }
+}
+
+/// For maps from names to one path each, e.g. `BTreeMap<String, PathBuf>`
+pub mod values {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(map: &BTreeMap<String, PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (k, Stored::from_path(v))))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, PathBuf>, D::Error> {
+        let stored = BTreeMap::<String, Stored>::deserialize(deserializer)?;
+        Ok(stored.into_iter().map(|(k, v)| (k, v.into_path())).collect())
+    }
+}
+
+/// For maps from names to lists of paths, e.g. `BTreeMap<String, Vec<PathBuf>>`
+pub mod seq_values {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(map: &BTreeMap<String, Vec<PathBuf>>, serializer: S) -> Result<S::Ok, S::Error> {
+        let stored = map.iter().map(|(k, v)| (k, v.iter().map(|p| Stored::from_path(p)).collect::<Vec<_>>()));
+        serializer.collect_map(stored)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Vec<PathBuf>>, D::Error> {
+        let stored = BTreeMap::<String, Vec<Stored>>::deserialize(deserializer)?;
+        Ok(stored
+            .into_iter()
+            .map(|(k, v)| (k, v.into_iter().map(Stored::into_path).collect()))
+            .collect())
+    }
+}
+
+/// Prefix of map keys holding the hex bytes of a path that isn't UTF-8
+const BYTES_KEY: &str = "bytes:";
+
+fn key_from_path(path: &Path) -> String {
+    match path.to_str() {
+        // Paths that happen to start with the prefix are escaped too, so keys read back unambiguously
+        Some(text) if !text.starts_with(BYTES_KEY) => text.to_string(),
+        _ => path.as_os_str().as_bytes().iter().fold(BYTES_KEY.to_string(), |mut key, b| {
+            key.push_str(&format!("{:02x}", b));
+            key
+        }),
+    }
+}
+
+fn path_from_key(key: String) -> PathBuf {
+    let bytes = key.strip_prefix(BYTES_KEY).and_then(|hex| {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+    });
+    match bytes {
+        Some(bytes) => from_bytes(bytes),
+        None => PathBuf::from(key),
+    }
+}
+
+/// For maps keyed by path, e.g. `BTreeMap<PathBuf, T>`; JSON keys must be strings, so
+/// paths that aren't UTF-8 are written as `bytes:` and their hex
+pub mod keys {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    pub fn serialize<T: Serialize, S: Serializer>(map: &BTreeMap<PathBuf, T>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (key_from_path(k), v)))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<BTreeMap<PathBuf, T>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let stored = BTreeMap::<String, T>::deserialize(deserializer)?;
+        Ok(stored.into_iter().map(|(k, v)| (path_from_key(k), v)).collect())
+    }
+}
+
+/// A file name for display and search that keeps names which aren't UTF-8 distinct, writing
+/// invalid bytes as `\xNN` instead of collapsing them all into U+FFFD
+pub fn display_name(name: &OsStr) -> String {
+    let mut text = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            text.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    text
+}
+
+/// `path` as raw bytes, for state files that hold a single path
+pub fn to_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_bytes()
+}
+
+pub fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, VecDeque};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Paths {
+        #[serde(with = "option")]
+        one: Option<PathBuf>,
+        #[serde(with = "seq")]
+        many: VecDeque<PathBuf>,
+    }
+
+    #[test]
+    fn non_utf8_paths_round_trip() {
+        let odd = PathBuf::from(OsStr::from_bytes(b"/w/caf\xe9.jpg"));
+        let paths = Paths { one: Some(odd.clone()), many: VecDeque::from([PathBuf::from("/w/sea.png"), odd]) };
+        let json = serde_json::to_string(&paths).unwrap();
+        assert!(json.contains("\"/w/sea.png\""));
+        assert_eq!(serde_json::from_str::<Paths>(&json).unwrap(), paths);
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Maps {
+        #[serde(with = "keys")]
+        by_path: BTreeMap<PathBuf, u8>,
+        #[serde(with = "values")]
+        one: BTreeMap<String, PathBuf>,
+        #[serde(with = "seq_values")]
+        many: BTreeMap<String, Vec<PathBuf>>,
+    }
+
+    #[test]
+    fn non_utf8_map_entries_round_trip() {
+        let odd = PathBuf::from(OsStr::from_bytes(b"/w/caf\xe9.jpg"));
+        let tricky = PathBuf::from("bytes:2f");
+        let maps = Maps {
+            by_path: BTreeMap::from([(odd.clone(), 1), (tricky, 2), (PathBuf::from("/w/sea.png"), 3)]),
+            one: BTreeMap::from([("DP-1".to_string(), odd.clone())]),
+            many: BTreeMap::from([("dark".to_string(), vec![odd, PathBuf::from("/w/sea.png")])]),
+        };
+        let json = serde_json::to_string(&maps).unwrap();
+        assert!(json.contains("\"/w/sea.png\":3"));
+        assert_eq!(serde_json::from_str::<Maps>(&json).unwrap(), maps);
+    }
+
+    #[test]
+    fn display_names_stay_distinct() {
+        let a = display_name(OsStr::from_bytes(b"caf\xe9"));
+        let b = display_name(OsStr::from_bytes(b"caf\xe8"));
+        assert_eq!(a, "caf\\xe9");
+        assert_ne!(a, b);
+        assert_eq!(display_name(OsStr::new("café")), "café");
+    }
+}
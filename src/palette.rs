@@ -0,0 +1,205 @@
+use image::DynamicImage;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Distinct colors sampled when summarizing a wallpaper
+const DOMINANT_COLORS: usize = 5;
+/// Largest possible distance between two RGB colors
+const MAX_DISTANCE: f32 = 441.67;
+/// Mean luminance below which a wallpaper counts as dark
+const DARK_THRESHOLD: f32 = 0.5;
+
+pub type Rgb = [u8; 3];
+
+/// Color summary of a wallpaper, computed from its thumbnail in the background
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ColorStats {
+    /// 0-100 closeness of the dominant colors to the theme palette; None without a theme
+    pub theme_match: Option<f32>,
+    /// Mean relative luminance, 0 (black) to 1 (white)
+    pub luminance: f32,
+}
+
+impl ColorStats {
+    /// Stats from a wallpaper's dominant colors and brightness, e.g. as stored in the index
+    pub fn from_dominant(dominant: &[(Rgb, f32)], luminance: f32, palette: &[Rgb]) -> Self {
+        let theme_match = (!palette.is_empty()).then(|| match_score(dominant, palette));
+        Self { theme_match, luminance }
+    }
+
+    pub fn tone(&self) -> Tone {
+        if self.luminance < DARK_THRESHOLD { Tone::Dark } else { Tone::Light }
+    }
+}
+
+/// Whether a wallpaper (or the desktop) is mostly dark or light
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone {
+    Dark,
+    Light,
+}
+
+impl Tone {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dark" => Some(Tone::Dark),
+            "light" => Some(Tone::Light),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Tone::Dark => "dark",
+            Tone::Light => "light",
+        }
+    }
+}
+
+/// The desktop's light/dark mode: omarchy themes are dark unless they ship `light.mode`,
+/// otherwise GNOME's `color-scheme` preference is used
+pub fn system_tone() -> Option<Tone> {
+    let theme_dir = get_theme_dir();
+    if theme_dir.is_dir() {
+        return Some(if theme_dir.join("light.mode").exists() { Tone::Light } else { Tone::Dark });
+    }
+    let output = Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.interface", "color-scheme"])
+        .output()
+        .ok()?;
+    let scheme = String::from_utf8_lossy(&output.stdout);
+    Some(if scheme.contains("dark") { Tone::Dark } else { Tone::Light })
+}
+
+pub fn mean_luminance(img: &DynamicImage) -> f32 {
+    let small = img.thumbnail(64, 64).to_rgb8();
+    let count = small.pixels().len().max(1) as f32;
+    let total: f32 = small
+        .pixels()
+        .map(|p| {
+            let [r, g, b] = p.0;
+            (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0
+        })
+        .sum();
+    total / count
+}
+
+/// The active omarchy theme, whose terminal color files define the palette
+pub fn get_theme_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
+        .join("omarchy")
+        .join("current")
+        .join("theme")
+}
+
+/// Colors of the current theme, from the first terminal config found; empty if there is none
+pub fn theme_palette() -> Vec<Rgb> {
+    let dir = get_theme_dir();
+    ["alacritty.toml", "kitty.conf", "ghostty.conf"]
+        .iter()
+        .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+        .map(|contents| parse_colors(&contents))
+        .find(|colors| !colors.is_empty())
+        .unwrap_or_default()
+}
+
+/// Every distinct `#rrggbb` or `0xrrggbb` color in a config file
+pub fn parse_colors(contents: &str) -> Vec<Rgb> {
+    let bytes = contents.as_bytes();
+    let mut colors = Vec::new();
+    for i in 0..bytes.len() {
+        let start = match bytes[i] {
+            b'#' => i + 1,
+            b'0' if matches!(bytes.get(i + 1), Some(b'x' | b'X')) => i + 2,
+            _ => continue,
+        };
+        let Some(digits) = contents.get(start..start + 6) else {
+            continue;
+        };
+        let terminated = bytes.get(start + 6).is_none_or(|c| !c.is_ascii_hexdigit());
+        if !terminated || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        let channel = |at: usize| u8::from_str_radix(&digits[at..at + 2], 16).unwrap_or(0);
+        let color = [channel(0), channel(2), channel(4)];
+        if !colors.contains(&color) {
+            colors.push(color);
+        }
+    }
+    colors
+}
+
+/// The most common colors, each with the share of pixels it covers
+pub fn dominant_colors(img: &DynamicImage) -> Vec<(Rgb, f32)> {
+    let small = img.thumbnail(64, 64).to_rgb8();
+    // 3 bits per channel: 512 buckets, each remembering the sum of its pixels
+    let mut buckets = vec![([0u64; 3], 0u32); 512];
+    for pixel in small.pixels() {
+        let [r, g, b] = pixel.0;
+        let index = ((r >> 5) as usize) << 6 | ((g >> 5) as usize) << 3 | (b >> 5) as usize;
+        let bucket = &mut buckets[index];
+        bucket.0[0] += r as u64;
+        bucket.0[1] += g as u64;
+        bucket.0[2] += b as u64;
+        bucket.1 += 1;
+    }
+    let total = small.pixels().len().max(1) as f32;
+    buckets.sort_by_key(|bucket| std::cmp::Reverse(bucket.1));
+    buckets
+        .into_iter()
+        .take(DOMINANT_COLORS)
+        .filter(|(_, count)| *count > 0)
+        .map(|(sum, count)| {
+            let mean = |c: usize| (sum[c] / count as u64) as u8;
+            ([mean(0), mean(1), mean(2)], count as f32 / total)
+        })
+        .collect()
+}
+
+/// 0-100: how close each dominant color is to its nearest palette color, weighted by coverage
+pub fn match_score(dominant: &[(Rgb, f32)], palette: &[Rgb]) -> f32 {
+    let total_weight: f32 = dominant.iter().map(|(_, weight)| weight).sum();
+    if total_weight <= 0.0 || palette.is_empty() {
+        return 0.0;
+    }
+    let closeness: f32 = dominant
+        .iter()
+        .map(|(color, weight)| {
+            let nearest = palette
+                .iter()
+                .map(|p| distance(*color, *p))
+                .fold(f32::INFINITY, f32::min);
+            (1.0 - nearest / MAX_DISTANCE) * weight
+        })
+        .sum();
+    100.0 * closeness / total_weight
+}
+
+fn distance(a: Rgb, b: Rgb) -> f32 {
+    let d = |i: usize| a[i] as f32 - b[i] as f32;
+    (d(0).powi(2) + d(1).powi(2) + d(2).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_alacritty_and_kitty_colors() {
+        let alacritty = "[colors.primary]\nbackground = '#1a1b26'\nforeground = \"0xC0CAF5\"\n";
+        assert_eq!(parse_colors(alacritty), vec![[0x1a, 0x1b, 0x26], [0xc0, 0xca, 0xf5]]);
+        let kitty = "# comment\ncolor0 #15161e\nbackground #15161e\nbox 0x12\n";
+        assert_eq!(parse_colors(kitty), vec![[0x15, 0x16, 0x1e]]);
+    }
+
+    #[test]
+    fn closer_colors_score_higher() {
+        let palette = [[0, 0, 0], [255, 255, 255]];
+        let dark = match_score(&[([10, 10, 10], 1.0)], &palette);
+        let red = match_score(&[([255, 0, 0], 1.0)], &palette);
+        assert!(dark > red);
+        assert!(dark <= 100.0 && red >= 0.0);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::backend;
+use crate::config::Config;
+use crate::ospath;
+use crate::wallpaper;
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long `restore` keeps trying while the session comes up
+const RESTORE_WAIT: Duration = Duration::from_secs(15);
+const RESTORE_RETRY: Duration = Duration::from_millis(500);
+
+/// Wallpapers pinned to single outputs by name, stored under `$XDG_STATE_HOME`.
+/// Every other output shows the current wallpaper.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(transparent)]
+pub struct Pins {
+    #[serde(with = "ospath::values")]
+    outputs: BTreeMap<String, PathBuf>,
+}
+
+pub fn get_pins_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("pins.json")
+}
+
+impl Pins {
+    /// Load saved pins; a missing or unreadable file means there are none
+    pub fn load() -> Self {
+        fs::read_to_string(get_pins_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_pins_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn pin(&mut self, output: &str, path: &Path) {
+        self.outputs.insert(output.to_string(), path.to_path_buf());
+    }
+
+    pub fn unpin(&mut self, output: &str) -> Result<()> {
+        self.outputs
+            .remove(output)
+            .map(|_| ())
+            .ok_or_else(|| eyre!("Nothing is pinned to {}", output))
+    }
+
+    pub fn clear(&mut self) {
+        self.outputs.clear();
+    }
+
+    pub fn get(&self, output: &str) -> Option<&Path> {
+        self.outputs.get(output).map(PathBuf::as_path)
+    }
+
+    /// Pins whose wallpaper still exists; a deleted file falls back to the current wallpaper
+    pub fn existing(&self) -> BTreeMap<String, PathBuf> {
+        self.outputs
+            .iter()
+            .filter(|(_, path)| path.exists())
+            .map(|(output, path)| (output.clone(), path.clone()))
+            .collect()
+    }
+
+    /// Follow a pinned file that was renamed or moved. Returns true if it was pinned.
+    pub fn rename(&mut self, old: &Path, new: &Path) -> bool {
+        let mut renamed = false;
+        for path in self.outputs.values_mut().filter(|p| *p == old) {
+            *path = new.to_path_buf();
+            renamed = true;
+        }
+        renamed
+    }
+}
+
+/// `--apply-current`: show the current wallpaper again, with every pinned output on its own.
+/// At login the compositor or swww-daemon may still be starting, so failures are retried for a while.
+pub fn restore() -> Result<()> {
+    let config = Config::load()?;
+    wallpaper::configure(&config);
+    if wallpaper::get_current_wallpaper().is_none() {
+        return Err(eyre!("No wallpaper has been applied yet"));
+    }
+    let mode = wallpaper::get_current_fill_mode().unwrap_or_default();
+    let started = Instant::now();
+    loop {
+        let result = backend::show_with_pins(&*backend::for_config(&config), &wallpaper::get_current_background_path(), mode);
+        if result.is_ok() || started.elapsed() >= RESTORE_WAIT {
+            return result;
+        }
+        thread::sleep(RESTORE_RETRY);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::config::Config;
+use crate::metadata::Metadata;
+use crate::net;
+use color_eyre::{eyre::eyre, Result};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// NASA's shared demo key; rate limited, so a personal `nasa_api_key` is better for daily use
+const NASA_DEMO_KEY: &str = "DEMO_KEY";
+
+/// A picture-of-the-day feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Bing,
+    Nasa,
+}
+
+impl Source {
+    pub const NAMES: &[&str] = &["bing", "nasa"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bing" => Some(Source::Bing),
+            "nasa" | "apod" => Some(Source::Nasa),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Source::Bing => "bing",
+            Source::Nasa => "nasa",
+        }
+    }
+}
+
+/// Today's picture as announced by the feed
+struct Announcement {
+    /// As the feed gives it, e.g. `20261018` or `2026-10-18`; names the file for dedup
+    date: String,
+    title: String,
+    image_url: String,
+    /// Page describing the picture
+    page_url: Option<String>,
+}
+
+fn announcement(source: Source, config: &Config) -> Result<Announcement> {
+    match source {
+        Source::Bing => {
+            let json = net::get_json("https://www.bing.com/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=en-US")?;
+            let image = &json["images"][0];
+            let base = image["urlbase"].as_str().ok_or_else(|| eyre!("Unexpected Bing response"))?;
+            Ok(Announcement {
+                date: image["startdate"].as_str().unwrap_or_default().to_string(),
+                title: image["title"].as_str().unwrap_or_default().to_string(),
+                // The UHD rendition is the largest Bing offers
+                image_url: format!("https://www.bing.com{}_UHD.jpg", base),
+                page_url: image["copyrightlink"].as_str().map(String::from),
+            })
+        }
+        Source::Nasa => {
+            let key = config.nasa_api_key.as_deref().unwrap_or(NASA_DEMO_KEY);
+            let json = net::get_json(&format!("https://api.nasa.gov/planetary/apod?api_key={}", key))?;
+            if json["media_type"].as_str() != Some("image") {
+                return Err(eyre!("Today's NASA picture of the day is a video"));
+            }
+            let date = json["date"].as_str().unwrap_or_default().to_string();
+            Ok(Announcement {
+                page_url: Some(format!(
+                    "https://apod.nasa.gov/apod/ap{}.html",
+                    date.get(2..).unwrap_or_default().replace('-', "")
+                )),
+                date,
+                title: json["title"].as_str().unwrap_or_default().to_string(),
+                image_url: json["hdurl"]
+                    .as_str()
+                    .or(json["url"].as_str())
+                    .ok_or_else(|| eyre!("Unexpected NASA response"))?
+                    .to_string(),
+            })
+        }
+    }
+}
+
+/// Today's picture and where it goes on disk
+pub struct Planned {
+    pub path: PathBuf,
+    pub image_url: String,
+    pub title: String,
+    pub page_url: Option<String>,
+    /// Today's picture was already downloaded
+    pub exists: bool,
+}
+
+/// Look up today's picture and its file in `<download dir>/potd`
+pub fn plan(source: Source, config: &Config) -> Result<Planned> {
+    let today = announcement(source, config)?;
+    if today.date.is_empty() || today.date.contains(['/', '\\', '.']) {
+        return Err(eyre!("Unexpected date from {}: {}", source.as_str(), today.date));
+    }
+    let ext = today
+        .image_url
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .filter(|ext| ["jpg", "jpeg", "png", "webp"].contains(&ext.as_str()))
+        .unwrap_or_else(|| "jpg".to_string());
+    let path = config
+        .download_dir()
+        .join("potd")
+        .join(format!("{}-{}.{}", source.as_str(), today.date, ext));
+    Ok(Planned {
+        exists: path.exists(),
+        path,
+        image_url: today.image_url,
+        title: today.title,
+        page_url: today.page_url,
+    })
+}
+
+/// Download today's picture right away, doing nothing if it's already there.
+/// Returns the path and whether it's new.
+pub fn fetch(source: Source, config: &Config) -> Result<(PathBuf, bool)> {
+    let planned = plan(source, config)?;
+    if planned.exists {
+        return Ok((planned.path, false));
+    }
+    net::download(&planned.image_url, &planned.path)?;
+    let mut metadata = Metadata::load();
+    metadata.set_note(&planned.path, &planned.title);
+    if let Some(page) = &planned.page_url {
+        metadata.set_source(&planned.path, page);
+    }
+    metadata.save()?;
+    Ok((planned.path, true))
+}
+
+/// A `:potd` lookup running on a background thread; the image itself goes to the download queue
+pub struct PotdFetch {
+    rx: Receiver<Result<Planned>>,
+    pub source: Source,
+    /// Apply the picture once it's downloaded
+    pub apply: bool,
+}
+
+impl PotdFetch {
+    pub fn start(source: Source, apply: bool, config: Config) -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(plan(source, &config));
+        });
+        Self { rx, source, apply }
+    }
+
+    pub fn poll(&self) -> Option<Result<Planned>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(eyre!("Picture of the day thread exited"))),
+        }
+    }
+}
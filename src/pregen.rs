@@ -0,0 +1,51 @@
+use crate::config::Config;
+use crate::{thumbnails, wallpaper};
+use color_eyre::Result;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// `--generate-thumbs [dir]`: write freedesktop thumbnails for the library (or `dir`) without
+/// starting the TUI, e.g. from a cron job, so the grid fills in instantly later.
+/// Files already thumbnailed, or known to fail, are skipped.
+pub fn run(dir: Option<PathBuf>) -> Result<()> {
+    let config = Config::load()?;
+    wallpaper::configure(&config);
+    let pending: Vec<PathBuf> = wallpaper::discover_wallpapers(dir)?
+        .into_iter()
+        .filter(|w| !w.unsupported)
+        .map(|w| w.path)
+        .filter(|path| !thumbnails::is_cached(path, thumbnails::DEFAULT_EDGE) && !thumbnails::has_failed(path))
+        .collect();
+    let total = pending.len();
+    if total == 0 {
+        eprintln!("thumbnails: all up to date");
+        return Ok(());
+    }
+
+    let queue = Mutex::new(pending.into_iter());
+    let (done, failed) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let Some(path) = queue.lock().unwrap_or_else(|e| e.into_inner()).next() else {
+                        break;
+                    };
+                    if wallpaper::load_thumbnail(&path, thumbnails::DEFAULT_EDGE).is_none() {
+                        failed.fetch_add(1, Ordering::Relaxed);
+                        eprintln!("thumbnails: can't decode {}", path.display());
+                    }
+                    let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                    if done % 50 == 0 {
+                        eprintln!("thumbnails: {}/{}", done, total);
+                    }
+                }
+            });
+        }
+    });
+    eprintln!("thumbnails: {} generated, {} failed", total - failed.load(Ordering::Relaxed), failed.load(Ordering::Relaxed));
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A measurable stage of getting wallpapers onto the screen
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /// Listing the directory for images
+    Discovery,
+    /// Looking up and reading cached freedesktop thumbnails
+    ThumbnailLookup,
+    /// Decoding originals when no cached thumbnail exists
+    Decode,
+    /// Encoding thumbnails for the terminal's image protocol
+    Encode,
+    /// Drawing one frame of the UI; should stay under 16ms
+    Frame,
+}
+
+const PHASES: [(Phase, &str); 5] = [
+    (Phase::Discovery, "discovery"),
+    (Phase::ThumbnailLookup, "thumbnail lookup"),
+    (Phase::Decode, "decode"),
+    (Phase::Encode, "encode"),
+    (Phase::Frame, "frame"),
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Total time, call count and slowest call per phase, indexed by `Phase as usize`
+static TOTALS: Mutex<[(Duration, u32, Duration); 5]> = Mutex::new([(Duration::ZERO, 0, Duration::ZERO); 5]);
+
+/// Start recording timings (`--profile`)
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Run `f`, adding its duration to `phase` when profiling is on
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    if !is_enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    let mut totals = TOTALS.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = &mut totals[phase as usize];
+    entry.0 += elapsed;
+    entry.1 += 1;
+    entry.2 = entry.2.max(elapsed);
+    result
+}
+
+/// Timing breakdown, one line per phase
+pub fn report() -> String {
+    let totals = TOTALS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut out = String::from("phase              total      calls  avg      worst\n");
+    for (phase, name) in PHASES {
+        let (total, calls, worst) = totals[phase as usize];
+        let avg = if calls > 0 { total / calls } else { Duration::ZERO };
+        out.push_str(&format!(
+            "{:<18} {:>8.1}ms {:>6}  {:<8} {:.2}ms\n",
+            name,
+            total.as_secs_f64() * 1000.0,
+            calls,
+            format!("{:.2}ms", avg.as_secs_f64() * 1000.0),
+            worst.as_secs_f64() * 1000.0
+        ));
+    }
+    out
+}
@@ -0,0 +1,202 @@
+use crate::ospath;
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Wallpapers lined up to be applied one after another, stored under `$XDG_STATE_HOME`
+/// so the daemon keeps playing them after the picker is closed
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+#[serde(default)]
+pub struct Queue {
+    #[serde(with = "ospath::seq")]
+    pub paths: VecDeque<PathBuf>,
+    /// Seconds between applies; None while stopped
+    pub interval: Option<u64>,
+    /// When the next wallpaper is due, in Unix seconds
+    pub next_at: u64,
+}
+
+pub fn get_queue_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("queue.json")
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+impl Queue {
+    /// Load the queue; a missing file means nothing is queued. A damaged one is an error
+    /// rather than an empty queue, so it isn't saved over.
+    pub fn load() -> Result<Self> {
+        let path = get_queue_path();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(eyre!("{}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&contents).map_err(|e| eyre!("{}: {}", path.display(), e))
+    }
+
+    /// Write the queue under a temporary name and rename it into place, so a reader never
+    /// sees it half-written
+    pub fn save(&self) -> Result<()> {
+        let path = get_queue_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let staging = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&staging, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&staging, &path)?;
+        Ok(())
+    }
+
+    pub fn push(&mut self, path: &Path) {
+        self.paths.push_back(path.to_path_buf());
+    }
+
+    /// Follow a queued file that was renamed or moved. Returns true if it was queued.
+    pub fn rename(&mut self, old: &Path, new: &Path) -> bool {
+        let mut renamed = false;
+        for path in self.paths.iter_mut().filter(|p| *p == old) {
+            *path = new.to_path_buf();
+            renamed = true;
+        }
+        renamed
+    }
+
+    /// Start applying the queue, the first wallpaper right away and then one every `interval`
+    pub fn play(&mut self, interval: Duration) -> Result<()> {
+        if self.paths.is_empty() {
+            return Err(eyre!("The queue is empty (a adds the selection)"));
+        }
+        self.interval = Some(interval.as_secs().max(1));
+        self.next_at = now();
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.interval = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+        self.stop();
+    }
+
+    /// Time until the next apply while playing
+    pub fn next_in(&self) -> Option<Duration> {
+        self.interval?;
+        Some(Duration::from_secs(self.next_at.saturating_sub(now())))
+    }
+
+    /// The next wallpaper if it is due, removed from the queue. Playback stops with the last one.
+    pub fn take_due(&mut self) -> Option<PathBuf> {
+        self.take_due_at(now())
+    }
+
+    fn take_due_at(&mut self, now: u64) -> Option<PathBuf> {
+        let interval = self.interval?;
+        if now < self.next_at {
+            return None;
+        }
+        let path = self.paths.pop_front();
+        self.next_at = now + interval;
+        if self.paths.is_empty() {
+            self.stop();
+        }
+        path
+    }
+}
+
+/// Load, `change` and save the stored queue while holding its lock, so the picker and the
+/// daemon never apply the same entry or lose each other's edits. Returns the saved queue.
+pub fn edit<T>(change: impl FnOnce(&mut Queue) -> Result<T>) -> Result<(Queue, T)> {
+    let lock_path = get_queue_path().with_extension("lock");
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lock = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)?;
+    lock.lock()?;
+    let mut queue = Queue::load()?;
+    let before = serde_json::to_string(&queue)?;
+    let result = change(&mut queue)?;
+    if serde_json::to_string(&queue)? != before {
+        queue.save()?;
+    }
+    Ok((queue, result))
+}
+
+/// Pop the wallpaper due now from the stored queue, if any
+pub fn take_due() -> Result<Option<PathBuf>> {
+    Ok(edit(|queue| Ok(queue.take_due()))?.1)
+}
+
+/// `90`, `30s`, `10m`, `2h` or `1d`; bare numbers are seconds
+pub fn parse_interval(text: &str) -> Result<Duration> {
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => text.split_at(at),
+        None => (text, "s"),
+    };
+    let seconds_per = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(eyre!("Expected an interval like 30s, 10m, 2h or 1d: {}", text)),
+    };
+    let number: u64 = number
+        .parse()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| eyre!("Expected an interval like 30s, 10m, 2h or 1d: {}", text))?;
+    Ok(Duration::from_secs(number * seconds_per))
+}
+
+/// Short form of a duration for the status bar, e.g. `2h`, `12m` or `40s`
+pub fn format_interval(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        s if s >= 86_400 && s % 86_400 == 0 => format!("{}d", s / 86_400),
+        s if s >= 3600 => format!("{}h", s.div_ceil(3600)),
+        s if s >= 60 => format!("{}m", s.div_ceil(60)),
+        s => format!("{}s", s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals() {
+        assert_eq!(parse_interval("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_interval("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_interval("1d").unwrap(), Duration::from_secs(86_400));
+        assert!(parse_interval("0m").is_err());
+        assert!(parse_interval("soon").is_err());
+        assert!(parse_interval("5w").is_err());
+    }
+
+    #[test]
+    fn plays_in_order_then_stops() {
+        let mut queue = Queue::default();
+        queue.push(Path::new("/w/a.jpg"));
+        queue.push(Path::new("/w/b.jpg"));
+        queue.interval = Some(60);
+        queue.next_at = 1000;
+
+        assert_eq!(queue.take_due_at(999), None);
+        assert_eq!(queue.take_due_at(1000), Some(PathBuf::from("/w/a.jpg")));
+        assert_eq!(queue.take_due_at(1059), None);
+        assert_eq!(queue.take_due_at(1060), Some(PathBuf::from("/w/b.jpg")));
+        assert_eq!(queue.interval, None);
+        assert_eq!(queue.take_due_at(2000), None);
+    }
+}
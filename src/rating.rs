@@ -0,0 +1,119 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Embedded metadata sits near the start of a file; don't read whole images to find it
+const HEAD_BYTES: u64 = 256 * 1024;
+/// EXIF `Rating` tag, as written by Windows, digiKam and Lightroom
+const EXIF_RATING_TAG: u16 = 0x4746;
+/// Highest star rating
+pub const MAX_STARS: u8 = 5;
+
+/// Star rating a photo manager left on `path`: an XMP sidecar first (`photo.jpg.xmp` or
+/// `photo.xmp`), since that's where later edits go, then XMP or EXIF embedded in the file.
+/// Rejected images (-1) count as 0 stars.
+pub fn read(path: &Path) -> Option<u8> {
+    let sidecars = [path.as_os_str().to_owned(), path.with_extension("").into_os_string()];
+    for mut sidecar in sidecars {
+        sidecar.push(".xmp");
+        if let Ok(xmp) = fs::read_to_string(&sidecar)
+            && let Some(rating) = parse_xmp_rating(&xmp)
+        {
+            return Some(rating);
+        }
+    }
+
+    let mut head = Vec::new();
+    File::open(path).ok()?.take(HEAD_BYTES).read_to_end(&mut head).ok()?;
+    parse_xmp_rating(&String::from_utf8_lossy(&head)).or_else(|| parse_exif_rating(&head))
+}
+
+/// `xmp:Rating` from an XMP packet, written either as an attribute or an element
+fn parse_xmp_rating(xmp: &str) -> Option<u8> {
+    let start = xmp.find("xmp:Rating")? + "xmp:Rating".len();
+    let value = xmp[start..].trim_start_matches(['=', '"', '\'', '>', ' ']);
+    let end = value.find(|c: char| c != '-' && !c.is_ascii_digit()).unwrap_or(value.len());
+    let rating: i32 = value[..end].parse().ok()?;
+    Some(rating.clamp(0, MAX_STARS as i32) as u8)
+}
+
+/// The EXIF `Rating` tag from the first EXIF block in `data`, found by its JPEG (`Exif\0\0`),
+/// PNG (`eXIf`) or WebP (`EXIF`) marker
+fn parse_exif_rating(data: &[u8]) -> Option<u8> {
+    let tiff = [(&b"Exif\0\0"[..], 0), (&b"eXIf"[..], 0), (&b"EXIF"[..], 4)]
+        .iter()
+        .find_map(|(marker, skip)| {
+            let at = data.windows(marker.len()).position(|w| w == *marker)?;
+            let tiff = data.get(at + marker.len() + skip..)?;
+            // WebP writers sometimes keep the JPEG-style prefix too
+            Some(tiff.strip_prefix(b"Exif\0\0").unwrap_or(tiff))
+        })?;
+
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let bytes = [*tiff.get(at)?, *tiff.get(at + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |at: usize| {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries).find_map(|i| {
+        let entry = ifd + 2 + i * 12;
+        (u16_at(entry)? == EXIF_RATING_TAG).then_some(())?;
+        // A SHORT, stored in the first two bytes of the value field
+        let rating = u16_at(entry + 8)?;
+        Some(rating.min(MAX_STARS as u16) as u8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xmp_attribute_and_element_forms() {
+        assert_eq!(parse_xmp_rating(r#"<rdf:Description xmp:Rating="4" xmp:Label="Red"/>"#), Some(4));
+        assert_eq!(parse_xmp_rating("<xmp:Rating>3</xmp:Rating>"), Some(3));
+        assert_eq!(parse_xmp_rating(r#"xmp:Rating="-1""#), Some(0));
+        assert_eq!(parse_xmp_rating("<x:xmpmeta/>"), None);
+    }
+
+    fn exif_block(big_endian: bool, rating: u16) -> Vec<u8> {
+        let u16b = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let u32b = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if big_endian { b"MM\0*" } else { b"II*\0" });
+        tiff.extend_from_slice(&u32b(8));
+        tiff.extend_from_slice(&u16b(2));
+        // Orientation, then Rating
+        for (tag, value) in [(0x0112, 1), (EXIF_RATING_TAG, rating)] {
+            tiff.extend_from_slice(&u16b(tag));
+            tiff.extend_from_slice(&u16b(3));
+            tiff.extend_from_slice(&u32b(1));
+            tiff.extend_from_slice(&u16b(value));
+            tiff.extend_from_slice(&[0, 0]);
+        }
+        tiff
+    }
+
+    #[test]
+    fn exif_rating_in_jpeg_and_png() {
+        let mut jpeg = b"\xff\xd8\xff\xe1\x00\x40Exif\0\0".to_vec();
+        jpeg.extend(exif_block(true, 5));
+        assert_eq!(parse_exif_rating(&jpeg), Some(5));
+
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x26eXIf".to_vec();
+        png.extend(exif_block(false, 2));
+        assert_eq!(parse_exif_rating(&png), Some(2));
+
+        assert_eq!(parse_exif_rating(b"\xff\xd8\xff\xdb no metadata"), None);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::net;
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Posts fetched per listing; reddit's maximum
+const LIMIT: usize = 100;
+/// Index in each subreddit's preview dir mapping preview file names to their posts
+const INDEX_FILE: &str = "posts.json";
+
+/// An image post, with a small preview for the grid and the full image for applying
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Post {
+    pub id: String,
+    pub title: String,
+    /// Full-size image
+    pub url: String,
+    pub preview: String,
+    pub permalink: String,
+}
+
+impl Post {
+    /// File name shared by the preview and the download: readable title, then the id to stay unique
+    fn file_stem(&self) -> String {
+        let slug: String = self
+            .title
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let slug: Vec<&str> = slug.split('-').filter(|s| !s.is_empty()).take(8).collect();
+        format!("{}-{}", slug.join("-"), self.id)
+    }
+
+    fn extension(&self) -> &str {
+        url_extension(&self.url).unwrap_or("jpg")
+    }
+
+    pub fn post_url(&self) -> String {
+        format!("https://www.reddit.com{}", self.permalink)
+    }
+}
+
+fn url_extension(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    let ext = path.rsplit_once('.')?.1;
+    ["jpg", "jpeg", "png", "webp"]
+        .iter()
+        .find(|e| e.eq_ignore_ascii_case(ext))
+        .copied()
+}
+
+/// Where a subreddit's previews are cached; the grid browses this directory
+pub fn get_previews_dir(subreddit: &str) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".cache"))
+        .join("omarchy-wallpaper-picker")
+        .join("reddit")
+        .join(subreddit.to_lowercase())
+}
+
+/// Accept `wallpapers` or `r/wallpapers`
+pub fn parse_subreddit(name: &str) -> Result<String> {
+    let name = name.trim_start_matches('/').trim_start_matches("r/");
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(eyre!("Not a subreddit name: {}", name));
+    }
+    Ok(name.to_string())
+}
+
+/// Image posts in a listing from the JSON API, skipping NSFW, galleries and link posts
+fn parse_listing(listing: &Value) -> Vec<Post> {
+    let Some(children) = listing["data"]["children"].as_array() else {
+        return Vec::new();
+    };
+    children
+        .iter()
+        .filter_map(|child| {
+            let data = &child["data"];
+            if data["over_18"].as_bool() == Some(true) {
+                return None;
+            }
+            let url = data["url"].as_str()?;
+            url_extension(url)?;
+            // A mid-sized rendition is plenty for a grid cell
+            let resolutions = data["preview"]["images"][0]["resolutions"].as_array()?;
+            let preview = resolutions
+                .iter()
+                .find(|r| r["width"].as_u64().is_some_and(|w| w >= 640))
+                .or(resolutions.last())?["url"]
+                .as_str()?;
+            Some(Post {
+                id: data["id"].as_str()?.to_string(),
+                title: data["title"].as_str().unwrap_or_default().to_string(),
+                url: url.to_string(),
+                preview: preview.to_string(),
+                permalink: data["permalink"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Fetch the month's top image posts and cache their previews, returning the previews dir
+fn fetch(subreddit: &str) -> Result<PathBuf> {
+    // raw_json=1 keeps URLs from being HTML-escaped
+    let url = format!(
+        "https://www.reddit.com/r/{}/top.json?t=month&limit={}&raw_json=1",
+        subreddit, LIMIT
+    );
+    let posts = parse_listing(&net::get_json(&url)?);
+    if posts.is_empty() {
+        return Err(eyre!("No image posts in r/{}", subreddit));
+    }
+
+    let dir = get_previews_dir(subreddit);
+    fs::create_dir_all(&dir)?;
+    let mut index = load_index(&dir);
+    for post in posts {
+        let name = format!("{}.{}", post.file_stem(), post.extension());
+        let preview_path = dir.join(&name);
+        // A failed preview only drops that post from the grid
+        if preview_path.exists() || net::download(&post.preview, &preview_path).is_ok() {
+            index.insert(name, post);
+        }
+    }
+    fs::write(dir.join(INDEX_FILE), serde_json::to_string_pretty(&index)?)?;
+    Ok(dir)
+}
+
+fn load_index(dir: &Path) -> BTreeMap<String, Post> {
+    fs::read_to_string(dir.join(INDEX_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// The post a cached preview was made from, if `path` is one
+pub fn post_for(path: &Path) -> Option<Post> {
+    let name = path.file_name()?.to_str()?;
+    load_index(path.parent()?).remove(name)
+}
+
+/// Where a post's full image is saved in `dir`
+pub fn download_path(post: &Post, dir: &Path) -> PathBuf {
+    dir.join(format!("{}.{}", post.file_stem(), post.extension()))
+}
+
+/// A subreddit listing being fetched on a background thread
+pub struct RedditFetch {
+    rx: Receiver<Result<PathBuf>>,
+    pub subreddit: String,
+}
+
+impl RedditFetch {
+    pub fn start(subreddit: String) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let name = subreddit.clone();
+        thread::spawn(move || {
+            let _ = tx.send(fetch(&name));
+        });
+        Self { rx, subreddit }
+    }
+
+    /// The previews dir, once the fetch is done
+    pub fn poll(&self) -> Option<Result<PathBuf>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(eyre!("Reddit fetch thread exited"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn child(id: &str, url: &str, over_18: bool) -> Value {
+        json!({"data": {
+            "id": id,
+            "title": "Misty Mountains at Dawn [3840x2160]",
+            "url": url,
+            "over_18": over_18,
+            "permalink": format!("/r/wallpapers/comments/{}/", id),
+            "preview": {"images": [{"resolutions": [
+                {"url": "https://preview.redd.it/small.jpg", "width": 320},
+                {"url": "https://preview.redd.it/medium.jpg", "width": 640},
+            ]}]},
+        }})
+    }
+
+    #[test]
+    fn keeps_only_safe_image_posts() {
+        let listing = json!({"data": {"children": [
+            child("a1", "https://i.redd.it/a1.png", false),
+            child("b2", "https://i.redd.it/b2.jpg", true),
+            child("c3", "https://www.reddit.com/gallery/c3", false),
+        ]}});
+        let posts = parse_listing(&listing);
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].preview, "https://preview.redd.it/medium.jpg");
+        assert_eq!(posts[0].file_stem(), "misty-mountains-at-dawn-3840x2160-a1");
+        assert_eq!(posts[0].extension(), "png");
+    }
+
+    #[test]
+    fn subreddit_names() {
+        assert_eq!(parse_subreddit("r/WidescreenWallpaper").unwrap(), "WidescreenWallpaper");
+        assert!(parse_subreddit("../etc").is_err());
+    }
+}
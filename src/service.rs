@@ -0,0 +1,95 @@
+use color_eyre::Result;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const UNIT_NAME: &str = "omarchy-wallpaper-picker.service";
+
+pub fn get_unit_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".config"))
+        .join("systemd")
+        .join("user")
+        .join(UNIT_NAME)
+}
+
+/// A oneshot user unit that puts the current wallpaper back when the graphical session starts
+fn unit(exe: &Path, current_link: Option<&Path>) -> String {
+    let mut exec = format!("{} --apply-current", quote(exe));
+    if let Some(link) = current_link {
+        exec.push_str(&format!(" --current-link {}", quote(link)));
+    }
+    format!(
+        "# Written by omarchy-wallpaper-picker --install-service\n\
+         [Unit]\n\
+         Description=Restore the wallpaper chosen in omarchy-wallpaper-picker\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart={}\n\
+         # swaybg outlives ExecStart: keep the unit active, and leave swaybg up when it stops\n\
+         RemainAfterExit=yes\n\
+         KillMode=process\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        exec
+    )
+}
+
+/// systemd splits ExecStart on spaces, so paths containing them need quotes
+fn quote(path: &Path) -> String {
+    let text = path.display().to_string();
+    if text.contains(char::is_whitespace) {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        text
+    }
+}
+
+/// `--install-service`: write the user unit and enable it, or say how to start the restore otherwise
+pub fn install(current_link: Option<&Path>) -> Result<()> {
+    let exe = env::current_exe()?;
+    let path = get_unit_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, unit(&exe, current_link))?;
+    println!("Wrote {}", path.display());
+
+    let enabled = Command::new("systemctl")
+        .args(["--user", "daemon-reload"])
+        .status()
+        .is_ok_and(|s| s.success())
+        && Command::new("systemctl")
+            .args(["--user", "enable", UNIT_NAME])
+            .status()
+            .is_ok_and(|s| s.success());
+    if enabled {
+        println!("Enabled; the wallpaper comes back whenever the graphical session starts");
+    } else {
+        println!("Enable it with: systemctl --user enable {}", UNIT_NAME);
+    }
+    println!("Without systemd, add to hyprland.conf: exec-once = {} --apply-current", quote(&exe));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_runs_apply_current() {
+        let text = unit(Path::new("/usr/bin/omarchy-wallpaper-picker"), None);
+        assert!(text.contains("ExecStart=/usr/bin/omarchy-wallpaper-picker --apply-current\n"));
+        assert!(text.contains("WantedBy=graphical-session.target"));
+        assert!(text.contains("RemainAfterExit=yes\n"));
+        assert!(text.contains("KillMode=process\n"));
+
+        let text = unit(Path::new("/opt/my apps/picker"), Some(Path::new("/tmp/bg")));
+        assert!(text.contains("ExecStart=\"/opt/my apps/picker\" --apply-current --current-link /tmp/bg\n"));
+    }
+}
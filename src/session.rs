@@ -0,0 +1,75 @@
+use crate::app::DEFAULT_CELL_WIDTH;
+use crate::browse::SortOrder;
+use crate::ospath;
+use crate::wallpaper::FillMode;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// UI state carried over between launches, stored under `$XDG_STATE_HOME`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Session {
+    /// Directory being browsed; None means the default backgrounds dir
+    #[serde(with = "ospath::option")]
+    pub view_dir: Option<PathBuf>,
+    /// Wallpaper highlighted at exit
+    #[serde(with = "ospath::option")]
+    pub selected: Option<PathBuf>,
+    pub search_query: String,
+    pub fill_mode: FillMode,
+    pub monitor_preview: bool,
+    /// Collection being browsed, if any; takes precedence over `view_dir`
+    pub collection: Option<String>,
+    /// Minimum grid cell width chosen with +/-
+    pub cell_width: u16,
+    pub sort: SortOrder,
+    /// "Don't ask again" was chosen on the resolution warning
+    pub skip_resolution_warning: bool,
+    /// Format and file size badges toggled with `i`
+    pub show_badges: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            view_dir: None,
+            selected: None,
+            search_query: String::new(),
+            fill_mode: FillMode::default(),
+            monitor_preview: false,
+            collection: None,
+            cell_width: DEFAULT_CELL_WIDTH,
+            sort: SortOrder::default(),
+            skip_resolution_warning: false,
+            show_badges: false,
+        }
+    }
+}
+
+pub fn get_session_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("session.json")
+}
+
+impl Session {
+    /// Load the previous session; a missing or unreadable file starts fresh
+    pub fn load() -> Self {
+        fs::read_to_string(get_session_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = get_session_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use color_eyre::{eyre::eyre, Result};
+use image::{DynamicImage, RgbaImage};
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub fn is_svg(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+}
+
+/// System fonts, loaded once for SVGs containing text
+fn options() -> &'static resvg::usvg::Options<'static> {
+    static OPTIONS: OnceLock<resvg::usvg::Options<'static>> = OnceLock::new();
+    OPTIONS.get_or_init(|| {
+        let mut options = resvg::usvg::Options::default();
+        options.fontdb_mut().load_system_fonts();
+        options
+    })
+}
+
+/// Rasterize an SVG so its longer edge is `max_edge` pixels
+pub fn rasterize(path: &Path, max_edge: u32) -> Result<DynamicImage> {
+    let data = std::fs::read(path)?;
+    let tree = resvg::usvg::Tree::from_data(&data, options())?;
+
+    let size = tree.size();
+    let scale = max_edge as f32 / size.width().max(size.height());
+    let width = ((size.width() * scale).round() as u32).max(1);
+    let height = ((size.height() * scale).round() as u32).max(1);
+
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| eyre!("Invalid SVG size {}x{}", width, height))?;
+    resvg::render(
+        &tree,
+        resvg::tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    // tiny-skia stores premultiplied alpha; image expects straight alpha
+    let pixels: Vec<u8> = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    let img = RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| eyre!("Failed to rasterize {}", path.display()))?;
+    Ok(DynamicImage::ImageRgba8(img))
+}
@@ -0,0 +1,323 @@
+use crate::collections::Collections;
+use color_eyre::{eyre::eyre, Result};
+use std::fmt;
+use std::path::PathBuf;
+
+/// A boolean filter over collection names, written like `#nature & !#people | #minimal`.
+/// `!` binds tightest, then `&`, then `|`; tags side by side are joined with `&`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Tag(String),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+/// Characters that end a tag name
+const OPERATORS: &[char] = &['!', '&', '|', '(', ')'];
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '!' => tokens.push(Token::Not),
+            '&' => tokens.push(Token::And),
+            '|' => tokens.push(Token::Or),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '#' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || OPERATORS.contains(&c) {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                if name.is_empty() {
+                    return Err(eyre!("# needs a tag name"));
+                }
+                tokens.push(Token::Tag(name));
+            }
+            other => return Err(eyre!("Expected #tag, !, &, |, ( or ), got: {}", other)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or(&mut self) -> Result<TagExpr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = TagExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<TagExpr> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                // Juxtaposed terms: `#a #b` reads as `#a & #b`
+                Some(Token::Tag(_) | Token::Not | Token::Open) => {}
+                _ => return Ok(expr),
+            }
+            expr = TagExpr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<TagExpr> {
+        let token = self.tokens.get(self.pos).ok_or_else(|| eyre!("Expression ends early"))?;
+        self.pos += 1;
+        match token {
+            Token::Tag(name) => Ok(TagExpr::Tag(name.clone())),
+            Token::Not => Ok(TagExpr::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(eyre!("Missing )"));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Token::And | Token::Or => Err(eyre!("Expected a #tag before & or |")),
+            Token::Close => Err(eyre!("Unexpected )")),
+        }
+    }
+}
+
+impl TagExpr {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(eyre!("Unexpected )"));
+        }
+        Ok(expr)
+    }
+
+    /// Whether a wallpaper in the collections `tags` passes; names compare case-insensitively
+    pub fn matches(&self, tags: &[String]) -> bool {
+        match self {
+            TagExpr::Tag(name) => tags.iter().any(|tag| tag.eq_ignore_ascii_case(name)),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(left, right) => left.matches(tags) && right.matches(tags),
+            TagExpr::Or(left, right) => left.matches(tags) || right.matches(tags),
+        }
+    }
+}
+
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::Tag(name) => write!(f, "#{}", name),
+            TagExpr::Not(expr) if matches!(**expr, TagExpr::Tag(_) | TagExpr::Not(_)) => write!(f, "!{}", expr),
+            TagExpr::Not(expr) => write!(f, "!({})", expr),
+            TagExpr::And(left, right) => {
+                for (i, side) in [left, right].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " & ")?;
+                    }
+                    match **side {
+                        TagExpr::Or(..) => write!(f, "({})", side)?,
+                        _ => write!(f, "{}", side)?,
+                    }
+                }
+                Ok(())
+            }
+            TagExpr::Or(left, right) => write!(f, "{} | {}", left, right),
+        }
+    }
+}
+
+/// How many wallpapers of a selection carry a tag, in the batch tag editor
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TagState {
+    All,
+    Some,
+    None,
+}
+
+impl TagState {
+    fn of(count: usize, total: usize) -> Self {
+        match count {
+            0 => TagState::None,
+            n if n == total => TagState::All,
+            _ => TagState::Some,
+        }
+    }
+
+    /// Space in the editor: a tag only some have cycles through all and none back to as it was
+    fn toggled(self, original: Self) -> Self {
+        match (self, original) {
+            (TagState::Some, _) => TagState::All,
+            (TagState::All, _) => TagState::None,
+            (TagState::None, TagState::Some) => TagState::Some,
+            (TagState::None, _) => TagState::All,
+        }
+    }
+
+    pub fn marker(self) -> &'static str {
+        match self {
+            TagState::All => "[x]",
+            TagState::Some => "[-]",
+            TagState::None => "[ ]",
+        }
+    }
+}
+
+pub struct TagRow {
+    pub name: String,
+    pub original: TagState,
+    pub state: TagState,
+}
+
+/// `:tag` without arguments: every collection and how much of the selection is in it
+pub struct TagEditor {
+    pub paths: Vec<PathBuf>,
+    pub rows: Vec<TagRow>,
+    pub selected: usize,
+}
+
+impl TagEditor {
+    pub fn new(paths: Vec<PathBuf>, collections: &Collections) -> Self {
+        let rows = collections
+            .names()
+            .map(|name| {
+                let count = paths.iter().filter(|path| collections.contains(name, path)).count();
+                let state = TagState::of(count, paths.len());
+                TagRow { name: name.to_string(), original: state, state }
+            })
+            .collect();
+        Self { paths, rows, selected: 0 }
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(row) = self.rows.get_mut(self.selected) {
+            row.state = row.state.toggled(row.original);
+        }
+    }
+
+    /// `(tag, add)` for every row changed to all or none
+    pub fn edits(&self) -> Vec<(String, bool)> {
+        self.rows
+            .iter()
+            .filter(|row| row.state != row.original && row.state != TagState::Some)
+            .map(|row| (row.name.clone(), row.state == TagState::All))
+            .collect()
+    }
+}
+
+/// Whether `:filter` text is a tag expression rather than a name search
+pub fn is_expression(text: &str) -> bool {
+    text.starts_with(['#', '!', '(']) && text.contains('#')
+}
+
+/// Completions for the tag being typed at the end of `arg`, as whole arguments
+pub fn tag_completions<'a>(arg: &str, names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let Some(hash) = arg.rfind('#') else {
+        return Vec::new();
+    };
+    let partial = &arg[hash + 1..];
+    if partial.contains(|c: char| c.is_whitespace() || OPERATORS.contains(&c)) {
+        return Vec::new();
+    }
+    names
+        .filter(|name| name.to_lowercase().starts_with(&partial.to_lowercase()))
+        .map(|name| format!("{}#{}", &arg[..hash], name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn precedence() {
+        let expr = TagExpr::parse("#nature & !#people | #minimal").unwrap();
+        assert_eq!(expr.to_string(), "#nature & !#people | #minimal");
+        assert!(expr.matches(&tags(&["nature"])));
+        assert!(!expr.matches(&tags(&["nature", "people"])));
+        assert!(expr.matches(&tags(&["people", "minimal"])));
+        assert!(!expr.matches(&tags(&[])));
+    }
+
+    #[test]
+    fn grouping_and_juxtaposition() {
+        let expr = TagExpr::parse("#Dark (#space|#city)").unwrap();
+        assert_eq!(expr.to_string(), "#Dark & (#space | #city)");
+        assert!(expr.matches(&tags(&["dark", "city"])));
+        assert!(!expr.matches(&tags(&["dark"])));
+        assert_eq!(TagExpr::parse("!(#a | #b)").unwrap().to_string(), "!(#a | #b)");
+    }
+
+    #[test]
+    fn errors() {
+        for text in ["", "#", "#a &", "(#a", "#a)", "& #a", "nature"] {
+            assert!(TagExpr::parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn partial_tags_cycle_back() {
+        let mut collections = Collections::default();
+        let (a, b) = (PathBuf::from("/w/a.png"), PathBuf::from("/w/b.png"));
+        collections.add("dark", &a);
+        collections.add("dark", &b);
+        collections.add("sunset", &a);
+        let mut editor = TagEditor::new(vec![a, b], &collections);
+        let states: Vec<TagState> = editor.rows.iter().map(|row| row.state).collect();
+        assert_eq!(states, [TagState::All, TagState::Some]);
+
+        editor.move_selection(1);
+        editor.toggle();
+        assert_eq!(editor.edits(), [("sunset".to_string(), true)]);
+        editor.toggle();
+        assert_eq!(editor.edits(), [("sunset".to_string(), false)]);
+        editor.toggle();
+        assert!(editor.edits().is_empty());
+        assert_eq!(editor.rows[1].state, TagState::Some);
+    }
+
+    #[test]
+    fn completes_the_last_tag() {
+        let names = ["nature", "neon", "people"];
+        assert_eq!(tag_completions("#nature & !#pe", names.into_iter()), ["#nature & !#people"]);
+        assert_eq!(tag_completions("#n", names.into_iter()), ["#nature", "#neon"]);
+        assert!(tag_completions("#nature ", names.into_iter()).is_empty());
+        assert!(is_expression("!#people"));
+        assert!(!is_expression("sunset"));
+    }
+}
@@ -0,0 +1,115 @@
+use color_eyre::Result;
+use crossterm::{
+    event::{self, Event},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::prelude::*;
+use ratatui_image::protocol::StatefulProtocol;
+use std::collections::BTreeSet;
+use std::io::{self, stdout, Write};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+pub type Tui = Terminal<CrosstermBackend<io::Stdout>>;
+
+/// What the event loop needs from the terminal, so it can be driven by something else
+pub trait Screen {
+    fn draw(&mut self, render: impl FnOnce(&mut Frame)) -> Result<()>;
+
+    /// The next input event, or None if nothing arrives within `timeout`
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<Event>>;
+}
+
+impl Screen for Tui {
+    fn draw(&mut self, render: impl FnOnce(&mut Frame)) -> Result<()> {
+        Terminal::draw(self, render)?;
+        Ok(())
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<Event>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+        Ok(Some(event::read()?))
+    }
+}
+
+/// Kitty image IDs transmitted during this session, deleted again on teardown
+static KITTY_IMAGES: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+fn kitty_images() -> MutexGuard<'static, BTreeSet<u32>> {
+    // A panic while holding the lock must not stop the panic hook from cleaning up
+    KITTY_IMAGES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Kitty graphics command deleting an image and freeing its data.
+/// Terminals that don't speak the protocol ignore the APC sequence.
+fn kitty_delete(out: &mut impl Write, id: u32) -> io::Result<()> {
+    write!(out, "\x1b_Ga=d,d=I,i={}\x1b\\", id)
+}
+
+/// Remember a protocol's Kitty image so it can be deleted later
+pub fn track_image(protocol: &StatefulProtocol) {
+    if let StatefulProtocol::Kitty(kitty) = protocol {
+        kitty_images().insert(kitty.unique_id);
+    }
+}
+
+/// Delete a protocol's Kitty image from the terminal, e.g. when its state is dropped
+pub fn release_image(protocol: &StatefulProtocol) {
+    if let StatefulProtocol::Kitty(kitty) = protocol
+        && kitty_images().remove(&kitty.unique_id)
+    {
+        let mut out = stdout();
+        let _ = kitty_delete(&mut out, kitty.unique_id);
+        let _ = out.flush();
+    }
+}
+
+/// Owns raw mode + alternate screen for its lifetime and restores both on drop,
+/// including when the stack unwinds from a panic.
+pub struct TerminalGuard {
+    pub terminal: Tui,
+}
+
+impl TerminalGuard {
+    pub fn new() -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen)?;
+        let terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+        Ok(Self { terminal })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
+
+/// Put the terminal back into a usable state. Safe to call more than once.
+pub fn restore() -> Result<()> {
+    let mut out = stdout();
+    for id in std::mem::take(&mut *kitty_images()) {
+        let _ = kitty_delete(&mut out, id);
+    }
+    disable_raw_mode()?;
+    execute!(out, LeaveAlternateScreen, crossterm::cursor::Show)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Install color-eyre's hooks, restoring the terminal before the panic report is printed
+/// so the message lands on the normal screen instead of being swallowed by raw mode.
+pub fn install_hooks() -> Result<()> {
+    let (panic_hook, eyre_hook) = color_eyre::config::HookBuilder::default().into_hooks();
+    eyre_hook.install()?;
+
+    let panic_hook = panic_hook.into_panic_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        panic_hook(info);
+    }));
+    Ok(())
+}
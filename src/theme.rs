@@ -0,0 +1,259 @@
+//! Colors the TUI draws with, taken from the active omarchy theme's terminal colors so the
+//! picker matches the rest of the desktop. `[tui_colors]` in the config overrides single roles.
+
+use crate::config::Config;
+use crate::palette::{self, Rgb};
+use color_eyre::{eyre::eyre, Result};
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Draw without colors (`--no-color`)
+pub fn set_no_color() {
+    NO_COLOR.store(true, Ordering::Relaxed);
+}
+
+/// Role names accepted in `[tui_colors]`
+pub const ROLES: &[&str] = &["accent", "highlight", "current", "marked", "muted", "error", "text"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    /// Modal borders, labels and key hints
+    pub accent: Color,
+    /// The selected cell, warnings and headings
+    pub highlight: Color,
+    /// The applied wallpaper's cell and finished work
+    pub current: Color,
+    /// Newly imported files and animation badges
+    pub marked: Color,
+    /// Idle borders, hints and the status bar background
+    pub muted: Color,
+    pub error: Color,
+    pub text: Color,
+    /// No colors at all; states are told apart by markers and bold or reversed text instead
+    pub monochrome: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            accent: Color::Cyan,
+            highlight: Color::Yellow,
+            current: Color::Green,
+            marked: Color::Magenta,
+            muted: Color::DarkGray,
+            error: Color::Red,
+            text: Color::White,
+            monochrome: false,
+        }
+    }
+}
+
+/// ANSI colors 0-15 and the foreground set by a terminal config
+#[derive(Default, Debug, PartialEq)]
+struct TerminalColors {
+    ansi: [Option<Rgb>; 16],
+    foreground: Option<Rgb>,
+}
+
+type Parser = fn(&str) -> TerminalColors;
+
+/// Terminal configs an omarchy theme ships, in the order they're tried
+const TERMINAL_CONFIGS: &[(&str, Parser)] =
+    &[("alacritty.toml", parse_alacritty), ("kitty.conf", parse_kitty), ("ghostty.conf", parse_ghostty)];
+
+impl Theme {
+    /// Colors for the TUI: none with `--no-color`, `no_color` or `$NO_COLOR`, otherwise the theme's
+    pub fn for_config(config: &Config) -> Self {
+        let no_color = NO_COLOR.load(Ordering::Relaxed)
+            || config.no_color
+            || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        if no_color {
+            return Self::monochrome();
+        }
+        Self::load(&config.tui_colors).unwrap_or_default()
+    }
+
+    fn monochrome() -> Self {
+        Self {
+            accent: Color::Reset,
+            highlight: Color::Reset,
+            current: Color::Reset,
+            marked: Color::Reset,
+            muted: Color::Reset,
+            error: Color::Reset,
+            text: Color::Reset,
+            monochrome: true,
+        }
+    }
+
+    /// Text set off by a `bg` background, or reversed when there are no colors
+    pub fn inverted(&self, fg: Color, bg: Color) -> Style {
+        if self.monochrome {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(fg).bg(bg)
+        }
+    }
+
+    /// The active omarchy theme's colors with `overrides` on top; the built-in colors where
+    /// the theme has none
+    fn load(overrides: &HashMap<String, String>) -> Result<Self> {
+        let dir = palette::get_theme_dir();
+        let terminal = TERMINAL_CONFIGS
+            .iter()
+            .filter_map(|(name, parse)| fs::read_to_string(dir.join(name)).ok().map(|c| parse(&c)))
+            .find(|colors| *colors != TerminalColors::default())
+            .unwrap_or_default();
+        Self::from_terminal(&terminal).with_overrides(overrides)
+    }
+
+    fn from_terminal(terminal: &TerminalColors) -> Self {
+        let default = Self::default();
+        let pick = |index: usize, fallback: Color| terminal.ansi[index].map_or(fallback, rgb);
+        Self {
+            accent: pick(6, default.accent),
+            highlight: pick(3, default.highlight),
+            current: pick(2, default.current),
+            marked: pick(5, default.marked),
+            muted: pick(8, default.muted),
+            error: pick(1, default.error),
+            text: terminal.foreground.map_or(default.text, rgb),
+            monochrome: false,
+        }
+    }
+
+    /// Replace roles named in `overrides` with colors like `#89b4fa`, `cyan` or `8`
+    pub fn with_overrides(mut self, overrides: &HashMap<String, String>) -> Result<Self> {
+        for (role, value) in overrides {
+            let color = Color::from_str(value).map_err(|_| eyre!("tui_colors.{}: unknown color '{}'", role, value))?;
+            let slot = match role.as_str() {
+                "accent" => &mut self.accent,
+                "highlight" => &mut self.highlight,
+                "current" => &mut self.current,
+                "marked" => &mut self.marked,
+                "muted" => &mut self.muted,
+                "error" => &mut self.error,
+                "text" => &mut self.text,
+                _ => return Err(eyre!("tui_colors: unknown role '{}' ({})", role, ROLES.join(", "))),
+            };
+            *slot = color;
+        }
+        Ok(self)
+    }
+}
+
+fn rgb([r, g, b]: Rgb) -> Color {
+    Color::Rgb(r, g, b)
+}
+
+/// `#rrggbb`, `0xrrggbb` or bare `rrggbb`, optionally quoted
+fn parse_hex(value: &str) -> Option<Rgb> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let digits = value
+        .strip_prefix('#')
+        .or_else(|| value.strip_prefix("0x"))
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    if digits.len() != 6 {
+        return None;
+    }
+    let channel = |at: usize| u8::from_str_radix(digits.get(at..at + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+const ANSI_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+/// `[colors.primary]`, `[colors.normal]` and `[colors.bright]` tables
+fn parse_alacritty(contents: &str) -> TerminalColors {
+    let mut colors = TerminalColors::default();
+    let Ok(table) = contents.parse::<toml::Table>() else {
+        return colors;
+    };
+    let Some(sections) = table.get("colors").and_then(|c| c.as_table()) else {
+        return colors;
+    };
+    let color = |section: &str, key: &str| {
+        sections.get(section)?.get(key)?.as_str().and_then(parse_hex)
+    };
+    for (i, name) in ANSI_NAMES.iter().enumerate() {
+        colors.ansi[i] = color("normal", name);
+        colors.ansi[i + 8] = color("bright", name);
+    }
+    colors.foreground = color("primary", "foreground");
+    colors
+}
+
+/// `colorN #rrggbb` and `foreground #rrggbb` lines
+fn parse_kitty(contents: &str) -> TerminalColors {
+    let mut colors = TerminalColors::default();
+    for line in contents.lines() {
+        let mut words = line.split_whitespace();
+        let (Some(key), Some(value)) = (words.next(), words.next()) else {
+            continue;
+        };
+        if key == "foreground" {
+            colors.foreground = parse_hex(value);
+        } else if let Some(index) = key.strip_prefix("color").and_then(|n| n.parse::<usize>().ok())
+            && index < 16
+        {
+            colors.ansi[index] = parse_hex(value);
+        }
+    }
+    colors
+}
+
+/// `palette = N=#rrggbb` and `foreground = rrggbb` lines
+fn parse_ghostty(contents: &str) -> TerminalColors {
+    let mut colors = TerminalColors::default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key.trim() {
+            "foreground" => colors.foreground = parse_hex(value),
+            "palette" => {
+                if let Some((index, color)) = value.split_once('=')
+                    && let Ok(index) = index.trim().parse::<usize>()
+                    && index < 16
+                {
+                    colors.ansi[index] = parse_hex(color);
+                }
+            }
+            _ => {}
+        }
+    }
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terminal_configs_give_the_same_roles() {
+        let alacritty = "[colors.primary]\nforeground = '#c0caf5'\n[colors.normal]\ncyan = '0x7dcfff'\nyellow = '#e0af68'\n";
+        let kitty = "foreground #c0caf5\ncolor6 #7dcfff\ncolor3    #e0af68\n";
+        let ghostty = "foreground = c0caf5\npalette = 6=#7dcfff\npalette = 3=#e0af68\n";
+        for colors in [parse_alacritty(alacritty), parse_kitty(kitty), parse_ghostty(ghostty)] {
+            let theme = Theme::from_terminal(&colors);
+            assert_eq!(theme.accent, Color::Rgb(0x7d, 0xcf, 0xff));
+            assert_eq!(theme.highlight, Color::Rgb(0xe0, 0xaf, 0x68));
+            assert_eq!(theme.text, Color::Rgb(0xc0, 0xca, 0xf5));
+            assert_eq!(theme.error, Color::Red);
+        }
+    }
+
+    #[test]
+    fn overrides_replace_roles() {
+        let overrides = HashMap::from([("accent".to_string(), "#89b4fa".to_string())]);
+        let theme = Theme::default().with_overrides(&overrides).unwrap();
+        assert_eq!(theme.accent, Color::Rgb(0x89, 0xb4, 0xfa));
+        assert!(Theme::default().with_overrides(&HashMap::from([("border".into(), "red".into())])).is_err());
+        assert!(Theme::default().with_overrides(&HashMap::from([("accent".into(), "teal-ish".into())])).is_err());
+    }
+}
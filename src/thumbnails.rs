@@ -0,0 +1,132 @@
+use color_eyre::Result;
+use image::DynamicImage;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Subdirectory of `fail/` for markers written by this app, per the thumbnail spec
+const FAIL_DIR: &str = "omarchy-wallpaper-picker";
+/// Freedesktop size directories and their edge lengths, smallest first
+const SIZES: [(&str, u32); 4] = [("normal", 128), ("large", 256), ("x-large", 512), ("xx-large", 1024)];
+/// Edge used when the cell size isn't known, e.g. for color analysis
+pub const DEFAULT_EDGE: u32 = 256;
+
+/// The smallest standard thumbnail edge covering `pixels`, capped at the largest size
+pub fn edge_for(pixels: u32) -> u32 {
+    SIZES.iter().map(|&(_, edge)| edge).find(|&edge| edge >= pixels).unwrap_or(SIZES[SIZES.len() - 1].1)
+}
+
+fn get_thumb_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".cache"))
+        .join("thumbnails")
+}
+
+/// `file://` URI of the canonical path, as thumbnails are keyed by
+fn file_uri(original: &Path) -> String {
+    format!("file://{}", original.canonicalize().unwrap_or(original.to_path_buf()).display())
+}
+
+fn thumb_file_name(original: &Path) -> String {
+    // Freedesktop spec: MD5 hash of file URI
+    format!("{:x}.png", md5::compute(file_uri(original).as_bytes()))
+}
+
+/// Modification time in whole seconds, as stored in `Thumb::MTime`
+fn mtime_secs(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// The `Thumb::MTime` a thumbnail was made for, if it recorded one
+fn recorded_mtime(thumb_path: &Path) -> Option<u64> {
+    let file = File::open(thumb_path).ok()?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+    reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == "Thumb::MTime")
+        .and_then(|chunk| chunk.text.trim().parse().ok())
+}
+
+/// Whether a thumbnail still describes the current contents of `original`.
+/// Thumbnails without an MTime can't be checked and are trusted.
+fn is_fresh(thumb_path: &Path, original: &Path) -> bool {
+    match (recorded_mtime(thumb_path), mtime_secs(original)) {
+        (Some(recorded), Some(actual)) => recorded == actual,
+        _ => true,
+    }
+}
+
+/// Load an up-to-date cached thumbnail at least `edge` pixels across; the smallest one that's
+/// big enough, so small cells don't decode huge thumbnails and big ones don't look soft
+pub fn load(original: &Path, edge: u32) -> Option<DynamicImage> {
+    let name = thumb_file_name(original);
+    let sizes = SIZES.iter().filter(|&&(_, size)| size >= edge).map(|&(dir, _)| dir);
+    for size in sizes {
+        let thumb_path = get_thumb_dir().join(size).join(&name);
+        if thumb_path.exists()
+            && is_fresh(&thumb_path, original)
+            && let Ok(img) = image::open(&thumb_path)
+        {
+            return Some(img);
+        }
+    }
+    None
+}
+
+/// Whether an up-to-date thumbnail at least `edge` pixels across is cached, without decoding it
+pub fn is_cached(original: &Path, edge: u32) -> bool {
+    let name = thumb_file_name(original);
+    SIZES
+        .iter()
+        .filter(|&&(_, size)| size >= edge)
+        .map(|&(dir, _)| get_thumb_dir().join(dir).join(&name))
+        .any(|thumb_path| thumb_path.exists() && is_fresh(&thumb_path, original))
+}
+
+/// Cache a freshly generated thumbnail in the size directory for `edge`, with the metadata
+/// other apps expect
+pub fn save(original: &Path, img: &DynamicImage, edge: u32) -> Result<()> {
+    let (size, edge) = SIZES.iter().copied().find(|&(_, size)| size >= edge).unwrap_or(SIZES[SIZES.len() - 1]);
+    let dir = get_thumb_dir().join(size);
+    let thumb = img.thumbnail(edge, edge).to_rgba8();
+    write_png(&dir, original, &thumb)
+}
+
+/// Whether a previous attempt to thumbnail this version of `original` failed
+pub fn has_failed(original: &Path) -> bool {
+    let marker = get_thumb_dir().join("fail").join(FAIL_DIR).join(thumb_file_name(original));
+    marker.exists() && is_fresh(&marker, original)
+}
+
+/// Record that `original` can't be thumbnailed, so later launches don't retry until it changes
+pub fn mark_failed(original: &Path) -> Result<()> {
+    let dir = get_thumb_dir().join("fail").join(FAIL_DIR);
+    write_png(&dir, original, &image::RgbaImage::new(1, 1))
+}
+
+/// Write a thumbnail PNG atomically, tagged with the source's URI and mtime
+fn write_png(dir: &Path, original: &Path, img: &image::RgbaImage) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    let dest = dir.join(thumb_file_name(original));
+    let tmp = dest.with_extension(format!("png.{}.tmp", std::process::id()));
+
+    {
+        let file = BufWriter::new(File::create(&tmp)?);
+        let mut encoder = png::Encoder::new(file, img.width(), img.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.add_text_chunk("Thumb::URI".to_string(), file_uri(original))?;
+        if let Some(mtime) = mtime_secs(original) {
+            encoder.add_text_chunk("Thumb::MTime".to_string(), mtime.to_string())?;
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(img.as_raw())?;
+    }
+
+    fs::rename(&tmp, &dest)?;
+    Ok(())
+}
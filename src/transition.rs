@@ -0,0 +1,80 @@
+use crate::config::Config;
+use crate::{backend, events, monitor};
+use crate::wallpaper::{self, FillMode};
+use image::{DynamicImage, RgbImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Target time per intermediate frame; swaybg needs roughly this long to start and draw
+const FRAME_TIME: Duration = Duration::from_millis(80);
+const MAX_FRAMES: u32 = 12;
+
+/// Fades started by this process, so overlapping ones get their own frames
+static FADES: AtomicU64 = AtomicU64::new(0);
+
+/// A fresh directory for one fade's frames
+fn get_frames_dir() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("omarchy-wallpaper-picker")
+        .join("crossfade")
+        .join(format!("{}-{}", std::process::id(), FADES.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Fade from `from` to `to` by showing blended frames through the wallpaper backend,
+/// then settle on the current-background symlink. Runs in the background (see
+/// `events::wait_for_background`); `on_failure` runs there if the backend can't show the
+/// link at the end.
+pub fn crossfade(config: Config, from: PathBuf, to: PathBuf, mode: FillMode, duration: Duration, on_failure: impl FnOnce() + Send + 'static) {
+    events::spawn_background(move || {
+        let dir = get_frames_dir();
+        // Missing frames (e.g. an undecodable image) just make it a plain swap
+        let _ = run_crossfade(&config, &from, &to, mode, duration, &dir);
+        if backend::reload(&config, mode).is_err() {
+            on_failure();
+        }
+        let _ = fs::remove_dir_all(&dir);
+    });
+}
+
+fn run_crossfade(config: &Config, from: &Path, to: &Path, mode: FillMode, duration: Duration, dir: &Path) -> Option<()> {
+    let from_img = wallpaper::open_image(from).ok()?;
+    let to_img = wallpaper::open_image(to).ok()?;
+
+    // Frames are rendered at the primary output's resolution so the backend shows them 1:1
+    let (width, height) = monitor::detect_monitors()
+        .first()
+        .map(|m| (m.width, m.height))
+        .unwrap_or((to_img.width(), to_img.height()));
+    let from_rgb = wallpaper::render_for_output(&from_img, mode, width, height).to_rgb8();
+    let to_rgb = wallpaper::render_for_output(&to_img, mode, width, height).to_rgb8();
+
+    let frames = (duration.as_millis() / FRAME_TIME.as_millis()).clamp(2, MAX_FRAMES as u128) as u32;
+    let frame_delay = duration / frames;
+
+    let backend = backend::for_config(config);
+    fs::create_dir_all(dir).ok()?;
+
+    for i in 1..frames {
+        let t = i as f32 / frames as f32;
+        let frame_path = dir.join(format!("frame-{}.png", i));
+        DynamicImage::ImageRgb8(blend(&from_rgb, &to_rgb, t))
+            .save(&frame_path)
+            .ok()?;
+        backend.show(&frame_path, FillMode::Fill).ok()?;
+        thread::sleep(frame_delay);
+    }
+    Some(())
+}
+
+/// Linear blend of two equally sized images; `t` = 0 is `a`, 1 is `b`
+fn blend(a: &RgbImage, b: &RgbImage, t: f32) -> RgbImage {
+    let mut out = a.clone();
+    for (o, (pa, pb)) in out.iter_mut().zip(a.iter().zip(b.iter())) {
+        *o = (*pa as f32 * (1.0 - t) + *pb as f32 * t).round() as u8;
+    }
+    out
+}
@@ -0,0 +1,45 @@
+use crate::wallpaper;
+use color_eyre::{eyre::eyre, Result};
+use std::path::Path;
+use trash::os_limited;
+use trash::TrashItem;
+
+/// Move a file to the XDG trash, writing the `.trashinfo` needed to restore it
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    trash::delete(path)?;
+    Ok(())
+}
+
+/// Trashed images, most recently deleted first
+pub fn list_images() -> Result<Vec<TrashItem>> {
+    let mut items: Vec<TrashItem> = os_limited::list()?
+        .into_iter()
+        .filter(|item| wallpaper::is_image(Path::new(&item.name)))
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.time_deleted));
+    Ok(items)
+}
+
+/// Put a trashed file back where it was deleted from
+pub fn restore(item: TrashItem) -> Result<()> {
+    let original = item.original_path();
+    if original.exists() {
+        return Err(eyre!("{} exists again; move it away to restore", original.display()));
+    }
+    os_limited::restore_all([item])?;
+    Ok(())
+}
+
+/// Restore the file most recently trashed from `path`
+pub fn restore_path(path: &Path) -> Result<()> {
+    restore(latest(path)?)
+}
+
+/// The trash entry most recently deleted from `path`
+pub fn latest(path: &Path) -> Result<TrashItem> {
+    os_limited::list()?
+        .into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| eyre!("{} is no longer in the trash", path.display()))
+}
@@ -1,4 +1,9 @@
 use crate::app::{App, Mode};
+use crate::browse::{self, SortOrder};
+use crate::downloads::DownloadState;
+use crate::groups::{self, LayoutRow};
+use crate::theme::Theme;
+use crate::{commands, crop, keymap, queue, rating, terminal, wallpaper};
 use ratatui::{
     layout::{Alignment, Constraint, Layout, Rect},
     style::{Color, Modifier, Style},
@@ -7,54 +12,134 @@ use ratatui::{
     Frame,
 };
 use ratatui_image::{StatefulImage, Resize};
+use std::ops::Range;
+use std::sync::Arc;
 
 pub fn render(frame: &mut Frame, app: &mut App) {
     let area = frame.area();
 
-    // Main layout: content + status/search bar
+    // Main layout: content + notification line + status/search bar
     let bottom_height = if matches!(app.mode, Mode::Search) { 3 } else { 1 };
-    let chunks = Layout::vertical([Constraint::Min(0), Constraint::Length(bottom_height)]).split(area);
+    let toast_height = if app.errors.is_empty() && app.notice.is_none() { 0 } else { 1 };
+    let announcement_height = if app.announcer.is_some() { 1 } else { 0 };
+    let chunks = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(toast_height),
+        Constraint::Length(announcement_height),
+        Constraint::Length(bottom_height),
+    ])
+    .split(area);
 
     render_grid(frame, app, chunks[0]);
 
+    if !app.errors.is_empty() {
+        render_error_toast(frame, app, chunks[1]);
+    } else if let Some((notice, _)) = &app.notice {
+        let theme = app.theme;
+        let toast = Paragraph::new(format!(" ✓ {}", notice)).style(theme.inverted(theme.text, theme.current));
+        frame.render_widget(toast, chunks[1]);
+    }
+
+    // Plain text on a line of its own, so screen readers read just the change
+    if let Some(announcer) = &app.announcer {
+        frame.render_widget(Paragraph::new(announcer.message.as_str()), chunks[2]);
+    }
+
     match app.mode {
-        Mode::Search => render_search_bar(frame, app, chunks[1]),
-        _ => render_status_bar(frame, app, chunks[1]),
+        Mode::Search => render_search_bar(frame, app, chunks[3]),
+        _ => render_status_bar(frame, app, chunks[3]),
     }
 
     // Render modal overlays
     match app.mode {
         Mode::Preview => render_preview_modal(frame, app, area),
-        Mode::Help => render_help_modal(frame, area),
+        Mode::Help => render_help_modal(frame, app, area),
         Mode::Command => render_command_modal(frame, app, area),
+        Mode::Crop => render_crop_modal(frame, app, area),
+        Mode::ImportConflict => render_import_modal(frame, app, area),
+        Mode::Compare => render_compare_modal(frame, app, area),
+        Mode::Trash => render_trash_modal(frame, app, area),
+        Mode::ConfirmApply => render_confirm_apply_modal(frame, app, area),
+        Mode::ApplyFailed => render_apply_failed_modal(frame, app, area),
+        Mode::Trying => render_trial_modal(frame, app, area),
+        Mode::Downloads => render_downloads_modal(frame, app, area),
+        Mode::Settings => render_settings_modal(frame, app, area),
+        Mode::UndoList => render_undo_modal(frame, app, area),
+        Mode::TagEditor => render_tag_editor_modal(frame, app, area),
         Mode::Grid | Mode::Search => {}
     }
+
+    if app.show_diagnostics {
+        render_diagnostics(frame, app, area);
+    }
+}
+
+/// F12: where memory goes and how long frames take, in the top right corner
+fn render_diagnostics(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let (decoded, thumbnails) = app.decoded_memory();
+    let encoded: usize = app.encoder.cached_bytes().values().sum();
+    let mut lines = vec![
+        Line::from(format!("decoded   {} ({} thumbnails)", wallpaper::format_bytes(decoded as u64), thumbnails)),
+        Line::from(format!(
+            "encoded   {} ({} cells)",
+            wallpaper::format_bytes(encoded as u64),
+            app.encoder.cached_count()
+        )),
+    ];
+    // The budget covers both
+    if let Some(mb) = app.config.memory_budget_mb {
+        lines.push(Line::from(format!(
+            "budget    {} of {}",
+            wallpaper::format_bytes((decoded + encoded) as u64),
+            wallpaper::format_bytes(mb * 1024 * 1024)
+        )));
+    }
+    lines.push(Line::from(format!("pending   {} encodes", app.encoder.pending_count())));
+    lines.push(Line::from(format!("frame     {:.1}ms", app.frame_time.as_secs_f64() * 1000.0)));
+    let width = lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 4;
+    let height = lines.len() as u16 + 2;
+    let overlay = Rect::new(area.right().saturating_sub(width), area.y, width.min(area.width), height.min(area.height));
+    frame.render_widget(Clear, overlay);
+    let block = Block::default()
+        .title(" diagnostics ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.muted));
+    frame.render_widget(Paragraph::new(lines).block(block).style(Style::default().fg(theme.text)), overlay);
 }
 
 fn render_grid(frame: &mut Frame, app: &mut App, area: Rect) {
-    let title = if app.search_query.is_empty() {
+    let theme = app.theme;
+    let filtering = !app.browser.search_query.is_empty()
+        || app.tone_filter.is_some()
+        || app.min_stars.is_some()
+        || app.tag_filter.is_some();
+    let mut title = if !filtering {
         " Wallpapers ".to_string()
     } else {
-        format!(" Wallpapers ({} matches) ", app.filtered_indices.len())
+        format!(" Wallpapers ({} matches) ", app.browser.filtered_indices.len())
     };
+    if let Some(group_by) = app.group_by {
+        title.push_str(&format!("by {} ", group_by.as_str()));
+    }
 
     let block = Block::default()
         .title(title)
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(Style::default().fg(theme.accent));
 
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
-    if app.filtered_indices.is_empty() {
-        let msg = if app.search_query.is_empty() {
+    if app.browser.filtered_indices.is_empty() {
+        let msg = if !filtering {
             "No wallpapers found"
         } else {
             "No matches found"
         };
         let msg = Paragraph::new(msg)
             .alignment(Alignment::Center)
-            .style(Style::default().fg(Color::DarkGray));
+            .style(Style::default().fg(theme.muted));
         frame.render_widget(msg, inner);
         return;
     }
@@ -62,17 +147,15 @@ fn render_grid(frame: &mut Frame, app: &mut App, area: Rect) {
     // Reserve 1 column for scrollbar
     let grid_width = inner.width.saturating_sub(1);
 
-    // Calculate columns based on window width
-    // Target a minimum cell width of 30 chars for readable thumbnails
-    const MIN_CELL_WIDTH: u16 = 30;
+    // Calculate columns based on window width and the user's minimum cell width
     const MAX_COLUMNS: usize = 8;
     const MIN_COLUMNS: usize = 1;
 
-    let columns = ((grid_width / MIN_CELL_WIDTH) as usize)
+    let columns = ((grid_width / app.cell_width.max(1)) as usize)
         .clamp(MIN_COLUMNS, MAX_COLUMNS);
 
-    // Update app.columns so navigation works correctly
-    app.columns = columns;
+    // Update app.browser.columns so navigation works correctly
+    app.browser.columns = columns;
 
     let cell_width = grid_width / columns as u16;
     // Terminal cells are ~2:1 (height:width in pixels)
@@ -82,9 +165,14 @@ fn render_grid(frame: &mut Frame, app: &mut App, area: Rect) {
         return;
     }
 
-    let total_items = app.filtered_indices.len();
-    let total_rows = (total_items + columns - 1) / columns;
-    let selected_row = app.selected / columns;
+    if !app.browser.groups.is_empty() {
+        render_grouped_cells(frame, app, area, inner, columns, (cell_width, cell_height));
+        return;
+    }
+
+    let total_items = app.browser.filtered_indices.len();
+    let total_rows = total_items.div_ceil(columns);
+    let selected_row = app.browser.selected / columns;
 
     // Calculate visible rows (including partial)
     let visible_full_rows = inner.height / cell_height;
@@ -92,13 +180,22 @@ fn render_grid(frame: &mut Frame, app: &mut App, area: Rect) {
     let visible_rows = visible_full_rows as usize + if has_partial { 1 } else { 0 };
 
     // Scroll offset - keep selected row visible
-    let scroll_offset = if selected_row < visible_full_rows as usize / 2 {
+    let target_offset = if selected_row < visible_full_rows as usize / 2 {
         0
     } else if selected_row >= total_rows.saturating_sub(visible_full_rows as usize / 2) {
         total_rows.saturating_sub(visible_full_rows as usize)
     } else {
         selected_row.saturating_sub(visible_full_rows as usize / 2)
     };
+    let scroll_offset = app.animate_scroll(target_offset);
+    // The image part of a cell: inside its border, above the name
+    app.fit_thumbnails_to_cells(cell_width.saturating_sub(3), cell_height.saturating_sub(4));
+
+    // Only cells near the window keep their encoded images, however large the library
+    let visible = browse::visible_range(total_items, columns, scroll_offset, visible_rows);
+    let keep = browse::retained_indices(&app.browser.filtered_indices, visible, columns, visible_rows);
+    app.encoder.retain(&keep);
+    app.enforce_memory_budget(&keep);
 
     // Render grid cells
     for row in 0..visible_rows {
@@ -148,30 +245,121 @@ fn render_grid(frame: &mut Frame, app: &mut App, area: Rect) {
         );
 
         frame.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
+
+        // Row readout on the bottom border, under the scrollbar
+        let readout = Paragraph::new(format!(" row {}/{} ", selected_row + 1, total_rows))
+            .alignment(Alignment::Right)
+            .style(Style::default().fg(theme.accent));
+        let readout_area = Rect::new(area.x, area.y + area.height - 1, area.width.saturating_sub(1), 1);
+        frame.render_widget(readout, readout_area);
+    }
+}
+
+/// The grid under `:group-by`: each group starts a new row under a one-line header.
+/// Scrolling counts layout rows, headers included.
+fn render_grouped_cells(frame: &mut Frame, app: &mut App, area: Rect, inner: Rect, columns: usize, cell: (u16, u16)) {
+    let theme = app.theme;
+    let (cell_width, cell_height) = cell;
+    let rows = groups::layout(&app.browser.groups, columns);
+    let heights: Vec<u16> = rows.iter().map(|row| row.height(cell_height)).collect();
+    let selected_row = rows
+        .iter()
+        .position(|row| matches!(row, LayoutRow::Cells(range) if range.contains(&app.browser.selected)))
+        .unwrap_or(0);
+    let target = groups::scroll_target(&heights, selected_row, inner.height);
+    let scroll_offset = app.animate_scroll(target).min(rows.len().saturating_sub(1));
+    app.fit_thumbnails_to_cells(cell_width.saturating_sub(3), cell_height.saturating_sub(4));
+
+    let bottom = inner.y + inner.height;
+    let mut y = inner.y;
+    let mut drawn: Option<Range<usize>> = None;
+    let mut visible_rows = 0;
+    for row in &rows[scroll_offset..] {
+        if y >= bottom {
+            break;
+        }
+        visible_rows += 1;
+        match row {
+            LayoutRow::Header(g) => {
+                let group = &app.browser.groups[*g];
+                let header = Line::from(vec![
+                    Span::styled(format!("── {} ", group.label), Style::default().fg(theme.highlight)),
+                    Span::styled(format!("({})", group.range.len()), Style::default().fg(theme.muted)),
+                ]);
+                frame.render_widget(Paragraph::new(header), Rect::new(inner.x, y, inner.width.saturating_sub(1), 1));
+            }
+            LayoutRow::Cells(range) => {
+                let this_cell_height = cell_height.min(bottom - y);
+                if this_cell_height >= 3 {
+                    for (col, filtered_pos) in range.clone().enumerate() {
+                        let x = inner.x + col as u16 * cell_width;
+                        let cell_area = Rect::new(x, y, cell_width.saturating_sub(1), this_cell_height.saturating_sub(1));
+                        render_wallpaper_cell(frame, app, filtered_pos, cell_area);
+                    }
+                }
+                drawn = Some(drawn.map_or(range.clone(), |d| d.start..range.end));
+            }
+        }
+        y += row.height(cell_height);
+    }
+
+    // Only cells near the window keep their encoded images, however large the library
+    let visible = drawn.unwrap_or(0..0);
+    let keep = browse::retained_indices(&app.browser.filtered_indices, visible, columns, visible_rows);
+    app.encoder.retain(&keep);
+    app.enforce_memory_budget(&keep);
+
+    let total_height: u32 = heights.iter().map(|&h| u32::from(h)).sum();
+    if total_height > u32::from(inner.height) {
+        let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+            .begin_symbol(None)
+            .end_symbol(None)
+            .track_symbol(Some("│"))
+            .thumb_symbol("█");
+        let mut scrollbar_state = ScrollbarState::new(rows.len()).position(scroll_offset);
+        let scrollbar_area = Rect::new(inner.x + inner.width - 1, inner.y, 1, inner.height);
+        frame.render_stateful_widget(scrollbar, scrollbar_area, &mut scrollbar_state);
+    }
+
+    // Group readout on the bottom border
+    if let Some(g) = app.browser.groups.iter().position(|g| g.range.contains(&app.browser.selected)) {
+        let readout = Paragraph::new(format!(" {} · group {}/{} ", app.browser.groups[g].label, g + 1, app.browser.groups.len()))
+            .alignment(Alignment::Right)
+            .style(Style::default().fg(theme.accent));
+        let readout_area = Rect::new(area.x, area.y + area.height - 1, area.width.saturating_sub(1), 1);
+        frame.render_widget(readout, readout_area);
     }
 }
 
 fn render_wallpaper_cell(frame: &mut Frame, app: &mut App, filtered_pos: usize, area: Rect) {
+    let theme = app.theme;
     if area.width < 3 || area.height < 3 {
         return;
     }
 
-    let original_index = match app.filtered_indices.get(filtered_pos) {
+    let original_index = match app.browser.filtered_indices.get(filtered_pos) {
         Some(&idx) => idx,
         None => return,
     };
 
     // Clone what we need before mutable borrows
     let name = app.wallpapers[original_index].name.clone();
-    let is_selected = filtered_pos == app.selected;
+    let is_selected = filtered_pos == app.browser.selected;
     let is_current = app.is_current(original_index);
+    let is_highlighted = app.is_highlighted(original_index);
+    let is_compare_mark = app.is_compare_mark(original_index);
+    let is_marked = app.is_marked(original_index);
 
     let border_color = if is_selected {
-        Color::Yellow
+        theme.highlight
+    } else if is_marked {
+        theme.accent
     } else if is_current {
-        Color::Green
+        theme.current
+    } else if is_highlighted {
+        theme.marked
     } else {
-        Color::DarkGray
+        theme.muted
     };
 
     let border_style = if is_selected {
@@ -180,11 +368,63 @@ fn render_wallpaper_cell(frame: &mut Frame, app: &mut App, filtered_pos: usize,
         Style::default().fg(border_color)
     };
 
-    let title = if is_current { " ✓ " } else { "" };
-    let block = Block::default()
+    let title = if theme.monochrome {
+        // Border colors can't tell these apart, so every state gets a marker
+        let markers: String = [
+            (is_selected, "[*]"),
+            (is_marked, "[x]"),
+            (is_current, "[✓]"),
+            (is_compare_mark, "[A]"),
+            (is_highlighted, "[new]"),
+        ]
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, marker)| *marker)
+            .collect();
+        if markers.is_empty() { String::new() } else { format!(" {} ", markers) }
+    } else if is_compare_mark {
+        " A ".to_string()
+    } else if is_marked {
+        " ● ".to_string()
+    } else if is_current {
+        " ✓ ".to_string()
+    } else if is_highlighted {
+        " new ".to_string()
+    } else {
+        String::new()
+    };
+    let mut block = Block::default()
         .title(title)
         .borders(Borders::ALL)
         .border_style(border_style);
+    // Size badge on the bottom border, in red when it won't cover the largest monitor
+    if let Some((width, height)) = app.wallpapers[original_index].dimensions {
+        let low = app.is_below_monitor_resolution((width, height));
+        let color = if low { theme.error } else { theme.muted };
+        let marker = if low && theme.monochrome { " low" } else { "" };
+        let badge = Line::styled(format!(" {}×{}{} ", width, height, marker), Style::default().fg(color));
+        block = block.title_bottom(badge.right_aligned());
+    }
+
+    // Format, file size and animation badges, once the header pass has read them
+    if app.show_badges
+        && let Some(info) = &app.wallpapers[original_index].file_info
+    {
+        let mut spans = vec![Span::styled(
+            format!(" {} {} ", info.format, wallpaper::format_bytes(info.bytes)),
+            Style::default().fg(theme.muted),
+        )];
+        if info.animated {
+            spans.push(Span::styled("ANIM ", Style::default().fg(theme.marked)));
+        }
+        block = block.title(Line::from(spans).right_aligned());
+    }
+
+    // Origin badge when several library roots share the grid
+    if let Some(origin) = &app.wallpapers[original_index].origin {
+        let badge = Line::styled(format!(" {} ", origin), Style::default().fg(theme.accent));
+        block = block.title_bottom(badge.left_aligned());
+    }
 
     let inner = block.inner(area);
     frame.render_widget(block, area);
@@ -196,41 +436,86 @@ fn render_wallpaper_cell(frame: &mut Frame, app: &mut App, filtered_pos: usize,
         let image_area = Rect::new(inner.x, inner.y, inner.width, inner.height.saturating_sub(1));
 
         // Check if we have a cached protocol for this size
-        if let Some(state) = app.encoder.get_cached(original_index, image_area.width, image_area.height) {
+        if !app.shows_images() {
+            // Screen-reader mode: the name below is all there is
+        } else if app.wallpapers[original_index].unsupported {
+            let ext = app.wallpapers[original_index]
+                .path
+                .extension()
+                .map(|e| e.to_string_lossy().to_uppercase())
+                .unwrap_or_default();
+            let badge_area = Rect::new(
+                image_area.x,
+                image_area.y + image_area.height / 2,
+                image_area.width,
+                1.min(image_area.height),
+            );
+            let badge = Paragraph::new(format!("unsupported format ({})", ext))
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(badge, badge_area);
+        } else if app.wallpapers[original_index].load_failed {
+            let glyph_area = Rect::new(
+                image_area.x,
+                image_area.y + image_area.height / 2,
+                image_area.width,
+                1.min(image_area.height),
+            );
+            let glyph = Paragraph::new("⚠ can't decode")
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(glyph, glyph_area);
+        } else if let Some(state) = app.encoder.get_cached(original_index, image_area.width, image_area.height) {
             // Render cached image
             let image = StatefulImage::new(None).resize(Resize::Fit(None));
             frame.render_stateful_widget(image, image_area, state);
         } else {
-            // Request encoding in background (non-blocking)
-            if app.wallpapers[original_index].thumbnail.is_none() {
-                app.wallpapers[original_index].load_thumbnail();
-            }
-            if let Some(ref thumb) = app.wallpapers[original_index].thumbnail {
-                app.encoder.request_encode(
+            // Read the thumbnail, then encode it, both in the background
+            match app.wallpapers[original_index].thumbnail {
+                Some(ref thumb) => app.encoder.request_encode(
                     original_index,
-                    thumb.clone(),
+                    Arc::clone(thumb),
                     image_area.width,
                     image_area.height,
-                );
+                ),
+                None => app.request_thumbnail(original_index),
             }
+            render_spinner(frame, &theme, app.spinner_tick, image_area);
         }
 
         // Render filename below image
         let name_area = Rect::new(inner.x, inner.y + inner.height - 1, inner.width, 1);
-        let display_name = truncate_name(&name, inner.width as usize);
-        let name_style = if is_selected {
-            Style::default().fg(Color::Yellow)
+        let display_name = browse::truncate_label(&name, inner.width as usize);
+        let name_style = if is_selected && theme.monochrome {
+            Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else if is_selected {
+            Style::default().fg(theme.highlight)
         } else {
-            Style::default().fg(Color::White)
+            Style::default().fg(theme.text)
         };
-        let name_widget = Paragraph::new(display_name)
+        let name_widget = Paragraph::new(highlight_matches(&display_name, &name, &app.browser.search_query, &theme))
             .style(name_style)
             .alignment(Alignment::Center);
         frame.render_widget(name_widget, name_area);
     }
 }
 
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// Animated placeholder for a cell whose thumbnail is still being read or encoded
+fn render_spinner(frame: &mut Frame, theme: &Theme, tick: usize, area: Rect) {
+    if area.height == 0 {
+        return;
+    }
+    let spinner_area = Rect::new(area.x, area.y + area.height / 2, area.width, 1);
+    let spinner = Paragraph::new(format!("{} loading", SPINNER_FRAMES[tick % SPINNER_FRAMES.len()]))
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(theme.muted));
+    frame.render_widget(spinner, spinner_area);
+}
+
 fn render_preview_modal(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
     let modal_area = centered_rect(80, 80, area);
 
     frame.render_widget(Clear, modal_area);
@@ -240,163 +525,903 @@ fn render_preview_modal(frame: &mut Frame, app: &mut App, area: Rect) {
         None => return,
     };
 
+    let adjustments = if app.adjustments.is_identity() {
+        String::new()
+    } else {
+        format!("[{}] ", app.adjustments.describe())
+    };
+    let title = if app.monitor_preview {
+        format!(" {} {}— monitors, mode: {} (f) ", wallpaper.name, adjustments, app.fill_mode.as_str())
+    } else {
+        format!(" {} {}", wallpaper.name, adjustments)
+    };
     let block = Block::default()
-        .title(format!(" {} ", wallpaper.name))
+        .title(title)
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(Style::default().fg(theme.accent));
 
     let inner = block.inner(modal_area);
     frame.render_widget(block, modal_area);
 
-    // Load preview image if needed
-    if app.preview_state.is_none() {
-        if let Ok(dyn_img) = image::open(&wallpaper.path) {
-            let protocol = app.picker.new_resize_protocol(dyn_img);
-            app.preview_state = Some(protocol);
+    if app.monitor_preview && app.shows_images() {
+        render_monitor_preview(frame, app, inner);
+        return;
+    }
+
+    let details = preview_details(app, wallpaper);
+    let [inner, details_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(details.len() as u16)]).areas(inner);
+    frame.render_widget(Paragraph::new(details).style(Style::default().fg(theme.text)), details_area);
+    if !app.shows_images() {
+        return;
+    }
+
+    // Decoded in the background so paging through previews stays responsive
+    app.request_preview();
+    if app.before_after {
+        render_before_after(frame, app, inner);
+        return;
+    }
+    // The thumbnail stands in, scaled up, until the full image is decoded
+    match app.preview_state.as_mut().or(app.preview_placeholder.as_mut()) {
+        Some(state) => {
+            let image = StatefulImage::new(None).resize(Resize::Fit(None));
+            frame.render_stateful_widget(image, inner, state);
+        }
+        None => render_spinner(frame, &theme, app.spinner_tick, inner),
+    }
+}
+
+/// The current wallpaper on the left and the selection on the right (`o`)
+fn render_before_after(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
+    app.request_before();
+    let before_name = app
+        .before_path()
+        .and_then(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()));
+    let halves = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(area);
+    let titles = [
+        format!(" before: {} ", before_name.as_deref().unwrap_or("nothing applied")),
+        " after ".to_string(),
+    ];
+    let spinner_tick = app.spinner_tick;
+    let has_before = before_name.is_some();
+    for (i, (state, title)) in [&mut app.before_state, &mut app.preview_state].into_iter().zip(titles).enumerate() {
+        let block = Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.muted));
+        let half_inner = block.inner(halves[i]);
+        frame.render_widget(block, halves[i]);
+        match state.as_mut() {
+            Some(state) => {
+                let image = StatefulImage::new(None).resize(Resize::Fit(None));
+                frame.render_stateful_widget(image, half_inner, state);
+            }
+            None if i == 0 && !has_before => {}
+            None => render_spinner(frame, &theme, spinner_tick, half_inner),
+        }
+    }
+}
+
+/// Metadata lines under the preview image: location and size, then any note and source
+fn preview_details(app: &App, wallpaper: &wallpaper::Wallpaper) -> Vec<Line<'static>> {
+    let theme = app.theme;
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(theme.accent));
+    let size = wallpaper
+        .dimensions
+        .map(|(w, h)| format!("  {}×{}", w, h))
+        .unwrap_or_default();
+    let mut lines = vec![Line::from(vec![
+        label("file   "),
+        Span::raw(format!("{}{}", wallpaper.path.display(), size)),
+    ])];
+    if let Some(stars) = app.stars(wallpaper) {
+        let stars = stars as usize;
+        let shown = format!("{}{}", "★".repeat(stars), "☆".repeat(rating::MAX_STARS as usize - stars));
+        lines.push(Line::from(vec![label("rating "), Span::styled(shown, Style::default().fg(theme.highlight))]));
+    }
+    if let Some(entry) = app.metadata.get(&wallpaper.path) {
+        if let Some(note) = &entry.note {
+            lines.push(Line::from(vec![label("note   "), Span::raw(note.clone())]));
+        }
+        if let Some(source) = &entry.source {
+            lines.push(Line::from(vec![label("source "), Span::raw(source.clone())]));
+        }
+    }
+    lines
+}
+
+/// Draw each output at its layout position, showing the image as the backend would fill it
+fn render_monitor_preview(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
+    if app.monitors.is_empty() {
+        let msg = Paragraph::new("No monitors detected (hyprctl / wlr-randr unavailable)")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.muted));
+        frame.render_widget(msg, area);
+        return;
+    }
+
+    // Render each output's view at a reduced size from the decoded preview; encoded once per image/mode
+    app.request_preview();
+    if app.monitor_preview_states.is_empty() {
+        let Some(img) = app.preview_image.as_ref() else {
+            render_spinner(frame, &theme, app.spinner_tick, area);
+            return;
+        };
+        const PREVIEW_WIDTH: f64 = 640.0;
+        for monitor in &app.monitors {
+            let factor = (PREVIEW_WIDTH / monitor.width.max(1) as f64).min(1.0);
+            let source = img.resize(
+                ((img.width() as f64 * factor) as u32).max(1),
+                ((img.height() as f64 * factor) as u32).max(1),
+                image::imageops::FilterType::Triangle,
+            );
+            let out_w = (monitor.width as f64 * factor) as u32;
+            let out_h = (monitor.height as f64 * factor) as u32;
+            let rendered = wallpaper::render_for_output(&source, app.fill_mode, out_w, out_h);
+            let protocol = app.picker.new_resize_protocol(rendered);
+            terminal::track_image(&protocol);
+            app.monitor_preview_states.push(protocol);
         }
     }
 
-    if let Some(state) = app.preview_state.as_mut() {
+    // Bounding box of the whole layout in logical coordinates
+    let (min_x, min_y, max_x, max_y) = app.monitors.iter().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(min_x, min_y, max_x, max_y), m| {
+            let (w, h) = m.logical_size();
+            (
+                min_x.min(m.x as f64),
+                min_y.min(m.y as f64),
+                max_x.max(m.x as f64 + w),
+                max_y.max(m.y as f64 + h),
+            )
+        },
+    );
+    let layout_w = (max_x - min_x).max(1.0);
+    let layout_h = (max_y - min_y).max(1.0);
+
+    // Convert layout units to terminal cells, accounting for non-square cells
+    let (font_w, font_h) = app.picker.font_size();
+    let (font_w, font_h) = (font_w.max(1) as f64, font_h.max(1) as f64);
+    let scale = (area.width as f64 * font_w / layout_w).min(area.height as f64 * font_h / layout_h);
+    let offset_x = (area.width as f64 - layout_w * scale / font_w) / 2.0;
+    let offset_y = (area.height as f64 - layout_h * scale / font_h) / 2.0;
+
+    for (i, monitor) in app.monitors.iter().enumerate() {
+        let (w, h) = monitor.logical_size();
+        let rect = Rect::new(
+            area.x + (offset_x + (monitor.x as f64 - min_x) * scale / font_w) as u16,
+            area.y + (offset_y + (monitor.y as f64 - min_y) * scale / font_h) as u16,
+            ((w * scale / font_w) as u16).max(3),
+            ((h * scale / font_h) as u16).max(3),
+        )
+        .intersection(area);
+
+        let block = Block::default()
+            .title(format!(" {} {}x{} ", monitor.name, monitor.width, monitor.height))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.muted));
+        let inner = block.inner(rect);
+        frame.render_widget(block, rect);
+
+        if let Some(state) = app.monitor_preview_states.get_mut(i) {
+            let image = StatefulImage::new(None).resize(Resize::Fit(None));
+            frame.render_stateful_widget(image, inner, state);
+        }
+    }
+}
+
+fn render_crop_modal(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
+    let modal_area = centered_rect(80, 80, area);
+
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Crop: hjkl move, +/- resize, Enter apply, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight));
+
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    // Re-encode the overlay whenever the crop rectangle changes
+    if app.shows_images()
+        && app.crop_state.is_none()
+        && let (Some(source), Some(crop)) = (app.crop_source.as_ref(), app.crop.as_ref())
+    {
+        let protocol = app.picker.new_resize_protocol(crop::draw_overlay(source, crop));
+        terminal::track_image(&protocol);
+        app.crop_state = Some(protocol);
+    }
+
+    if let Some(state) = app.crop_state.as_mut() {
         let image = StatefulImage::new(None).resize(Resize::Fit(None));
         frame.render_stateful_widget(image, inner, state);
     }
 }
 
-fn render_help_modal(frame: &mut Frame, area: Rect) {
+fn render_compare_modal(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
+    let modal_area = centered_rect(90, 80, area);
+
+    frame.render_widget(Clear, modal_area);
+
+    let Some(compare) = app.compare.as_ref() else {
+        return;
+    };
+    let block = Block::default()
+        .title(format!(
+            " Compare {:.1}x: +/- zoom, hjkl pan, 0 reset, 1/2 apply, Esc close ",
+            compare.zoom
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    // Both halves are re-encoded together so they always show the same viewport
+    if app.compare_states.is_empty() && app.shows_images() {
+        for view in [compare.left_view(), compare.right_view()] {
+            let protocol = app.picker.new_resize_protocol(view);
+            terminal::track_image(&protocol);
+            app.compare_states.push(protocol);
+        }
+    }
+
+    let Some(compare) = app.compare.as_ref() else {
+        return;
+    };
+    let names = [&compare.left, &compare.right].map(|p| {
+        p.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    let halves = Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(inner);
+    for (i, (state, name)) in app.compare_states.iter_mut().zip(names).enumerate() {
+        let block = Block::default()
+            .title(format!(" {} {} ", i + 1, name))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.muted));
+        let half_inner = block.inner(halves[i]);
+        frame.render_widget(block, halves[i]);
+        let image = StatefulImage::new(None).resize(Resize::Fit(None));
+        frame.render_stateful_widget(image, half_inner, state);
+    }
+}
+
+fn render_help_modal(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
     let modal_area = centered_rect(50, 75, area);
 
     frame.render_widget(Clear, modal_area);
 
+    // Keys and commands are listed straight from their registries so help never drifts from
+    // what works
+    let keys = keymap::BINDINGS
+        .iter()
+        .filter(|b| !b.keys().is_empty())
+        .map(|b| (b.label(), b.description()));
+    let commands = commands::COMMANDS
+        .iter()
+        .map(|spec| (format!(":{} {}", spec.name, spec.usage), spec.help.to_string()));
+    let query = app.help_query.to_lowercase();
+    let matches = |(key, help): &(String, String)| {
+        query.is_empty() || key.to_lowercase().contains(&query) || help.to_lowercase().contains(&query)
+    };
+    let heading = Style::default().add_modifier(Modifier::BOLD).fg(theme.highlight);
+    let mut help_text = Vec::new();
+    for (title, entries) in [("Keys", keys.collect::<Vec<_>>()), ("Commands", commands.collect())] {
+        let entries: Vec<_> = entries.into_iter().filter(matches).collect();
+        if entries.is_empty() {
+            continue;
+        }
+        if !help_text.is_empty() {
+            help_text.push(Line::from(""));
+        }
+        help_text.push(Line::styled(title, heading));
+        help_text.push(Line::from(""));
+        help_text.extend(entries.into_iter().map(|(key, help)| {
+            Line::from(vec![
+                Span::styled(format!("  {:<7}  ", key), Style::default().fg(theme.accent)),
+                Span::raw(help),
+            ])
+        }));
+    }
+    if help_text.is_empty() {
+        help_text.push(Line::styled("  No matching keys or commands", Style::default().fg(theme.muted)));
+    }
+
     let block = Block::default()
         .title(" Help ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
-
+        .border_style(Style::default().fg(theme.accent));
     let inner = block.inner(modal_area);
     frame.render_widget(block, modal_area);
+    let [body, footer] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
 
-    let help_text = vec![
-        Line::from(vec![
-            Span::styled("Navigation", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
-        ]),
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("  ↑/k  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Move up"),
-        ]),
-        Line::from(vec![
-            Span::styled("  ↓/j  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Move down"),
-        ]),
-        Line::from(vec![
-            Span::styled("  ←/h  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Move left"),
-        ]),
+    // Clamp here, where the page size is known, so scrolling past the end doesn't stick
+    let max_scroll = help_text.len().saturating_sub(body.height as usize);
+    app.help_scroll = app.help_scroll.min(max_scroll);
+    let help = Paragraph::new(help_text).scroll((app.help_scroll as u16, 0));
+    frame.render_widget(help, body);
+
+    let footer_text = if app.help_searching || !app.help_query.is_empty() {
         Line::from(vec![
-            Span::styled("  →/l  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Move right"),
-        ]),
-        Line::from(""),
+            Span::styled("/", Style::default().fg(theme.accent)),
+            Span::raw(app.help_query.clone()),
+            Span::raw(if app.help_searching { "█" } else { "" }),
+        ])
+    } else {
+        let position = if max_scroll > 0 {
+            format!("  {}/{}", app.help_scroll + 1, max_scroll + 1)
+        } else {
+            String::new()
+        };
+        Line::styled(
+            format!("j/k scroll  / search  Esc close{}", position),
+            Style::default().fg(theme.muted),
+        )
+    };
+    frame.render_widget(Paragraph::new(footer_text), footer);
+}
+
+fn render_import_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let Some(plan) = app.pending_import.as_ref() else {
+        return;
+    };
+    let modal_width = 60.min(area.width);
+    let modal_area = Rect::new(
+        (area.width.saturating_sub(modal_width)) / 2,
+        area.height / 3,
+        modal_width,
+        4.min(area.height),
+    );
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Import ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    let text = vec![
+        Line::from(format!(
+            "{} of {} files already exist in the library",
+            plan.conflicts(),
+            plan.sources.len()
+        )),
         Line::from(vec![
-            Span::styled("Actions", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            Span::styled("r", Style::default().fg(theme.accent)),
+            Span::raw(" rename  "),
+            Span::styled("s", Style::default().fg(theme.accent)),
+            Span::raw(" skip  "),
+            Span::styled("o", Style::default().fg(theme.accent)),
+            Span::raw(" overwrite  "),
+            Span::styled("Esc", Style::default().fg(theme.accent)),
+            Span::raw(" cancel"),
         ]),
+    ];
+    frame.render_widget(Paragraph::new(text), inner);
+}
+
+fn render_confirm_apply_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let Some(warning) = app.apply_warning.as_ref() else {
+        return;
+    };
+    let modal_width = 70.min(area.width);
+    let modal_area = Rect::new(
+        (area.width.saturating_sub(modal_width)) / 2,
+        area.height / 3,
+        modal_width,
+        5.min(area.height),
+    );
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Apply anyway? ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    let text = vec![
+        Line::from(warning.as_str()),
         Line::from(""),
         Line::from(vec![
-            Span::styled("  Enter  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Apply wallpaper"),
-        ]),
-        Line::from(vec![
-            Span::styled("  Space  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Preview wallpaper"),
-        ]),
-        Line::from(vec![
-            Span::styled("  /      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Search/filter"),
-        ]),
-        Line::from(vec![
-            Span::styled("  :      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Open command mode"),
-        ]),
-        Line::from(vec![
-            Span::styled("  H      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Reset view dir"),
-        ]),
-        Line::from(vec![
-            Span::styled("  ?      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Toggle help"),
-        ]),
-        Line::from(vec![
-            Span::styled("  Esc    ", Style::default().fg(Color::Cyan)),
-            Span::raw("Close modal / Exit"),
-        ]),
-        Line::from(vec![
-            Span::styled("  q      ", Style::default().fg(Color::Cyan)),
-            Span::raw("Quit"),
+            Span::styled("y", Style::default().fg(theme.accent)),
+            Span::raw(" apply  "),
+            Span::styled("a", Style::default().fg(theme.accent)),
+            Span::raw(" apply, don't ask again  "),
+            Span::styled("n", Style::default().fg(theme.accent)),
+            Span::raw(" cancel"),
         ]),
+    ];
+    frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), inner);
+}
+
+fn render_apply_failed_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let Some(failure) = app.apply_failure.as_ref() else {
+        return;
+    };
+    let modal_width = 70.min(area.width);
+    let modal_area = Rect::new(
+        (area.width.saturating_sub(modal_width)) / 2,
+        area.height / 3,
+        modal_width,
+        7.min(area.height),
+    );
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Couldn't apply wallpaper ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.error));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    let text = vec![
+        Line::from(failure.as_str()),
         Line::from(""),
         Line::from(vec![
-            Span::styled("Commands", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            Span::styled("Enter", Style::default().fg(theme.accent)),
+            Span::raw(" close"),
         ]),
+    ];
+    frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), inner);
+}
+
+fn render_trial_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let Some(trial) = app.trial.as_ref() else {
+        return;
+    };
+    let modal_width = 50.min(area.width);
+    let modal_area = Rect::new(
+        (area.width.saturating_sub(modal_width)) / 2,
+        area.height / 3,
+        modal_width,
+        5.min(area.height),
+    );
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(format!(" Trying {} ", trial.name))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    let text = vec![
+        Line::from(format!("Restoring the previous wallpaper in {}s", trial.remaining_secs())),
         Line::from(""),
         Line::from(vec![
-            Span::styled("  :cd <path>  ", Style::default().fg(Color::Cyan)),
-            Span::raw("Browse wallpapers in directory"),
-        ]),
-        Line::from(vec![
-            Span::styled("  :cd         ", Style::default().fg(Color::Cyan)),
-            Span::raw("Reset to default directory"),
+            Span::styled("y", Style::default().fg(theme.accent)),
+            Span::raw(" keep it  "),
+            Span::styled("n", Style::default().fg(theme.accent)),
+            Span::raw(" restore now"),
         ]),
     ];
+    frame.render_widget(Paragraph::new(text).wrap(Wrap { trim: true }), inner);
+}
+
+fn render_downloads_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let modal_area = centered_rect(70, 60, area);
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Downloads — c clear finished, r retry failed, Esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    let items = app.downloads.items();
+    if items.is_empty() {
+        let msg = Paragraph::new("Nothing downloading")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.muted));
+        frame.render_widget(msg, inner);
+        return;
+    }
+
+    const BAR_WIDTH: usize = 20;
+    let lines: Vec<Line> = items
+        .iter()
+        .rev()
+        .map(|item| {
+            let name = item
+                .dest
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let (status, color) = match &item.state {
+                DownloadState::Queued => ("queued".to_string(), theme.muted),
+                DownloadState::Active => {
+                    let bar = match item.fraction() {
+                        Some(fraction) => {
+                            let filled = ((fraction * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+                            format!(
+                                "[{}{}] {:>3}%",
+                                "█".repeat(filled),
+                                "░".repeat(BAR_WIDTH - filled),
+                                (fraction * 100.0) as u32
+                            )
+                        }
+                        None => format!("{} KiB", item.received / 1024),
+                    };
+                    (bar, theme.accent)
+                }
+                DownloadState::Done => ("done".to_string(), theme.current),
+                DownloadState::Failed(e) => (format!("failed: {}", e), theme.error),
+            };
+            Line::from(vec![
+                Span::raw(format!("{} ", name)),
+                Span::styled(status, Style::default().fg(color)),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), inner);
+}
+
+fn render_settings_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let modal_area = centered_rect(50, 50, area);
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Settings — Enter change, Esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    let values = app.setting_values();
+    let width = values.iter().map(|(option, _)| option.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = values
+        .iter()
+        .enumerate()
+        .map(|(i, (option, value))| {
+            let value = if value.is_empty() { "(default)" } else { value.as_str() };
+            let text = format!("{:<width$}  {}", option, value, width = width);
+            if i == app.settings_selected {
+                Line::from(vec![
+                    Span::styled(" > ", Style::default().fg(theme.highlight)),
+                    Span::styled(text, theme.inverted(Color::Black, theme.accent)),
+                ])
+            } else {
+                Line::from(vec![Span::raw("   "), Span::raw(text)])
+            }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_undo_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let modal_area = centered_rect(60, 50, area);
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Undo — u undo latest, Esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    if app.undo.is_empty() {
+        let msg = Paragraph::new("Nothing to undo")
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.muted));
+        frame.render_widget(msg, inner);
+        return;
+    }
+    let lines: Vec<Line> = app
+        .undo
+        .iter()
+        .enumerate()
+        .map(|(i, change)| {
+            let style = if i == 0 { Style::default().fg(theme.highlight) } else { Style::default() };
+            Line::styled(format!(" {}", change.describe()), style)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_tag_editor_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let Some(editor) = app.tag_editor.as_ref() else {
+        return;
+    };
+    let modal_area = centered_rect(50, 60, area);
+    frame.render_widget(Clear, modal_area);
 
-    let help = Paragraph::new(help_text).wrap(Wrap { trim: false });
-    frame.render_widget(help, inner);
+    let count = editor.paths.len();
+    let block = Block::default()
+        .title(format!(
+            " Collections of {} wallpaper{} — Space toggle, Enter apply, Esc cancel ",
+            count,
+            if count == 1 { "" } else { "s" }
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    // Keep the selection in view
+    let visible = inner.height as usize;
+    let offset = editor.selected.saturating_sub(visible.saturating_sub(1));
+    let lines: Vec<Line> = editor
+        .rows
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .map(|(i, row)| {
+            let mut style = if row.state != row.original { Style::default().fg(theme.accent) } else { Style::default() };
+            if i == editor.selected {
+                style = style.fg(theme.highlight).add_modifier(Modifier::BOLD);
+            }
+            Line::styled(format!(" {} {}", row.state.marker(), row.name), style)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_trash_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let modal_area = centered_rect(70, 60, area);
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(" Trash — Enter restore, Esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.highlight));
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+
+    // Keep the selection in view
+    let visible = inner.height as usize;
+    let offset = app.trash_selected.saturating_sub(visible.saturating_sub(1));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let lines: Vec<Line> = app
+        .trash_items
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(visible)
+        .map(|(i, item)| {
+            let text = format!(
+                "{}  {}  ({} ago)",
+                item.name.to_string_lossy(),
+                item.original_parent.display(),
+                format_age(now - item.time_deleted)
+            );
+            if i == app.trash_selected {
+                Line::from(vec![
+                    Span::styled(" > ", Style::default().fg(theme.highlight)),
+                    Span::styled(text, theme.inverted(Color::Black, theme.accent)),
+                ])
+            } else {
+                Line::from(vec![Span::raw("   "), Span::raw(text)])
+            }
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Coarse age such as `5m`, `3h` or `2d`
+fn format_age(secs: i64) -> String {
+    match secs.max(0) {
+        s if s < 3_600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h", s / 3_600),
+        s => format!("{}d", s / 86_400),
+    }
 }
 
 fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
-    let filter_info = if app.search_query.is_empty() {
+    let theme = app.theme;
+    let mut filters = Vec::new();
+    if !app.browser.search_query.is_empty() {
+        filters.push(format!("filter: {}", app.browser.search_query));
+    }
+    if let Some(tone) = app.tone_filter {
+        filters.push(tone.as_str().to_string());
+    }
+    if let Some(expr) = app.tag_filter.as_ref() {
+        filters.push(expr.to_string());
+    }
+    let filter_info = if filters.is_empty() {
         format!("{} wallpapers", app.wallpapers.len())
     } else {
-        format!("{}/{} (filter: {})", app.filtered_indices.len(), app.wallpapers.len(), app.search_query)
+        format!("{}/{} ({})", app.browser.filtered_indices.len(), app.wallpapers.len(), filters.join(", "))
+    };
+
+    let scan_info = match app.scan.as_ref() {
+        Some(scan) if app.is_scanning() => format!(
+            "{} scanning… {}s (Esc cancels) ",
+            SPINNER_FRAMES[app.spinner_tick % SPINNER_FRAMES.len()],
+            scan.started.elapsed().as_secs()
+        ),
+        _ => match app.reddit_fetch.as_ref() {
+            Some(fetch) => format!(
+                "{} fetching r/{}… ",
+                SPINNER_FRAMES[app.spinner_tick % SPINNER_FRAMES.len()],
+                fetch.subreddit
+            ),
+            None => match app.potd_fetch.as_ref() {
+                Some(fetch) => format!(
+                    "{} fetching {} picture of the day… ",
+                    SPINNER_FRAMES[app.spinner_tick % SPINNER_FRAMES.len()],
+                    fetch.source.as_str()
+                ),
+                None => String::new(),
+            },
+        },
     };
 
-    let dir_info = if let Some(ref dir) = app.current_view_dir {
+    // Only worth a readout for batches, like :preload or a fresh directory
+    let thumbnail_info = match app.thumbnail_loader.progress() {
+        Some(progress) if progress.total > 1 => {
+            let eta = progress
+                .eta()
+                .map(|eta| format!(", ~{}s left", eta.as_secs()))
+                .unwrap_or_default();
+            let failed = if progress.failed > 0 {
+                format!(", {} failed", progress.failed)
+            } else {
+                String::new()
+            };
+            let hint = if app.preloading { " (Esc skips)" } else { "" };
+            format!(
+                "{} thumbnails {}/{}{}{}{} | ",
+                SPINNER_FRAMES[app.spinner_tick % SPINNER_FRAMES.len()],
+                progress.done,
+                progress.total,
+                eta,
+                failed,
+                hint
+            )
+        }
+        _ => String::new(),
+    };
+
+    let active_downloads = app
+        .downloads
+        .items()
+        .iter()
+        .filter(|d| matches!(d.state, DownloadState::Queued | DownloadState::Active))
+        .count();
+    let download_info = if active_downloads > 0 {
+        format!("| ⇣ {} downloading (:downloads) ", active_downloads)
+    } else {
+        String::new()
+    };
+
+    let dir_info = if let Some(ref name) = app.active_collection {
+        format!(" | collection: {} ", name)
+    } else if let Some(ref dir) = app.current_view_dir {
         format!(" | dir: {} ", dir.display())
+    } else if !wallpaper::library_roots().is_empty() {
+        format!(" | library: {} roots ", wallpaper::library_roots().len())
     } else {
         " | dir: default ".to_string()
     };
 
+    let reminder = match app.rotation_reminder() {
+        Some(days) => format!("| current wallpaper is {}d old, time for a change? ", days),
+        None => String::new(),
+    };
+
+    let queue_info = match app.queue.next_in() {
+        Some(next) => format!("| queue: {}, next in {} ", app.queue.paths.len(), queue::format_interval(next)),
+        None if !app.queue.paths.is_empty() => format!("| queue: {} (:queue play) ", app.queue.paths.len()),
+        None => String::new(),
+    };
+
+    let sort_info = if app.sort == SortOrder::default() {
+        String::new()
+    } else {
+        format!("| sort: {} ", app.sort.as_str())
+    };
+
+    let columns = app.browser.columns.max(1);
+    let position_info = if app.browser.filtered_indices.is_empty() {
+        "Selected: -".to_string()
+    } else {
+        let row = match app.browser.groups.iter().position(|g| g.range.contains(&app.browser.selected)) {
+            Some(g) => format!("group {}/{}", g + 1, app.browser.groups.len()),
+            None => format!(
+                "row {}/{}",
+                app.browser.selected / columns + 1,
+                app.browser.filtered_indices.len().div_ceil(columns)
+            ),
+        };
+        match app.browser.filtered_indices.get(app.browser.selected) {
+            // Where a filtered-in wallpaper sits in the full listing
+            Some(&index) if app.browser.filtered_indices.len() != app.wallpapers.len() => {
+                format!("Selected: {} (#{} of all) | {}", app.browser.selected + 1, index + 1, row)
+            }
+            _ => format!("Selected: {} | {}", app.browser.selected + 1, row),
+        }
+    };
+
+    let marked_info = if app.marked.is_empty() {
+        String::new()
+    } else {
+        format!("| {} marked (:tag) ", app.marked.len())
+    };
+
     let status = format!(
-        " {} | Selected: {} | / search | : cmd | ? help | q quit{}",
+        " {}{}{} | {} | / search | : cmd | ? help | q quit{}{}{}{}{}{}",
+        scan_info,
+        thumbnail_info,
         filter_info,
-        app.selected + 1,
-        dir_info
+        position_info,
+        dir_info,
+        sort_info,
+        marked_info,
+        queue_info,
+        download_info,
+        reminder
     );
 
     let status_bar = Paragraph::new(status)
-        .style(Style::default().bg(Color::DarkGray).fg(Color::White));
+        .style(theme.inverted(theme.text, theme.muted));
 
     frame.render_widget(status_bar, area);
 }
 
+fn render_error_toast(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    let Some(error) = app.errors.front() else {
+        return;
+    };
+    let count = if app.errors.len() > 1 {
+        format!(" (+{} more)", app.errors.len() - 1)
+    } else {
+        String::new()
+    };
+    let toast = Paragraph::new(format!(" ⚠ {}{} | Esc to dismiss", error, count))
+        .style(if theme.monochrome {
+            theme.inverted(theme.text, theme.error).add_modifier(Modifier::BOLD)
+        } else {
+            theme.inverted(theme.text, theme.error)
+        });
+    frame.render_widget(toast, area);
+}
+
 fn render_search_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
     let block = Block::default()
         .title(" Search ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Yellow));
+        .border_style(Style::default().fg(theme.highlight));
 
     let inner = block.inner(area);
     frame.render_widget(block, area);
 
-    let search_text = format!("/{}_", app.search_query);
+    let search_text = format!("/{}_", app.browser.search_query);
     let search = Paragraph::new(search_text)
-        .style(Style::default().fg(Color::White));
+        .style(Style::default().fg(theme.text));
 
     frame.render_widget(search, inner);
 }
 
 fn render_command_modal(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
     let modal_width = 60;
-    let modal_height = 3 + if app.completions.is_empty() { 0 } else { (app.completions.len().min(10) as u16) + 2 };
+    let modal_height = 3 + if app.browser.completions.is_empty() { 0 } else { (app.browser.completions.len().min(10) as u16) + 2 };
     
     let modal_area = Rect::new(
         (area.width.saturating_sub(modal_width)) / 2,
@@ -407,7 +1432,7 @@ fn render_command_modal(frame: &mut Frame, app: &App, area: Rect) {
 
     frame.render_widget(Clear, modal_area);
 
-    let chunks = if app.completions.is_empty() {
+    let chunks = if app.browser.completions.is_empty() {
         vec![modal_area]
     } else {
         Layout::vertical([
@@ -420,45 +1445,43 @@ fn render_command_modal(frame: &mut Frame, app: &App, area: Rect) {
     let block = Block::default()
         .title(" Command ")
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan));
+        .border_style(Style::default().fg(theme.accent));
     let inner = block.inner(chunks[0]);
     frame.render_widget(block, chunks[0]);
 
-    let command_text = format!(":{}_", app.command_query);
+    let command_text = format!(":{}_", app.browser.command_query);
     let command = Paragraph::new(command_text)
-        .style(Style::default().fg(Color::White));
+        .style(Style::default().fg(theme.text));
     frame.render_widget(command, inner);
 
     // Completions
-    if !app.completions.is_empty() {
+    if !app.browser.completions.is_empty() {
         let comp_block = Block::default()
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::DarkGray));
+            .border_style(Style::default().fg(theme.muted));
         let comp_inner = comp_block.inner(chunks[1]);
         frame.render_widget(comp_block, chunks[1]);
 
         let visible_count = comp_inner.height as usize;
-        let total_count = app.completions.len();
+        let total_count = app.browser.completions.len();
         
-        let scroll_offset = if total_count <= visible_count {
-            0
-        } else if app.completion_index < visible_count / 2 {
+        let scroll_offset = if total_count <= visible_count || app.browser.completion_index < visible_count / 2 {
             0
-        } else if app.completion_index >= total_count.saturating_sub(visible_count / 2) {
+        } else if app.browser.completion_index >= total_count.saturating_sub(visible_count / 2) {
             total_count.saturating_sub(visible_count)
         } else {
-            app.completion_index.saturating_sub(visible_count / 2)
+            app.browser.completion_index.saturating_sub(visible_count / 2)
         };
 
-        let list_items: Vec<Line> = app.completions.iter()
+        let list_items: Vec<Line> = app.browser.completions.iter()
             .enumerate()
             .skip(scroll_offset)
             .take(visible_count)
             .map(|(i, c)| {
-                if i == app.completion_index {
+                if i == app.browser.completion_index {
                     Line::from(vec![
-                        Span::styled(" > ", Style::default().fg(Color::Yellow)),
-                        Span::styled(c, Style::default().bg(Color::Cyan).fg(Color::Black)),
+                        Span::styled(" > ", Style::default().fg(theme.highlight)),
+                        Span::styled(c, theme.inverted(Color::Black, theme.accent)),
                     ])
                 } else {
                     Line::from(vec![
@@ -489,12 +1512,30 @@ fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     .split(popup_layout[1])[1]
 }
 
-fn truncate_name(name: &str, max_width: usize) -> String {
-    if name.len() <= max_width {
-        name.to_string()
-    } else if max_width > 3 {
-        format!("{}...", &name[..max_width - 3])
+/// `text`, a possibly truncated `name`, with the letters `query` matched in the name picked out,
+/// so it's clear why a cell is shown
+fn highlight_matches<'a>(text: &'a str, name: &str, query: &str, theme: &Theme) -> Line<'a> {
+    let positions = match browse::match_positions(name, query) {
+        Some(positions) if !query.is_empty() => positions,
+        _ => return Line::from(text),
+    };
+    // Reversed would vanish inside a reversed selected name
+    let matched = if theme.monochrome {
+        Style::default().add_modifier(Modifier::UNDERLINED)
     } else {
-        name[..max_width].to_string()
-    }
+        Style::default().fg(Color::Black).bg(theme.accent)
+    };
+    let spans: Vec<Span> = text
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if positions.contains(&i) {
+                Span::styled(c.to_string(), matched)
+            } else {
+                Span::raw(c.to_string())
+            }
+        })
+        .collect();
+    Line::from(spans)
 }
+
@@ -0,0 +1,149 @@
+use crate::collections::Collections;
+use crate::trashcan;
+use color_eyre::Result;
+use std::path::{Path, PathBuf};
+
+/// Changes kept for `u`; older ones drop off
+const MAX_CHANGES: usize = 100;
+
+/// A library change made in this session that `u` can take back
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// Moved to the trash from this path
+    Trashed(PathBuf),
+    /// Added to (`added`) or removed from a collection
+    Collection { name: String, path: PathBuf, added: bool },
+    /// Files written by an import, and the ones among them that replaced a trashed file
+    Imported { paths: Vec<PathBuf>, replaced: Vec<PathBuf> },
+    /// Collection changes made together by `:tag` on marked wallpapers
+    Batch(Vec<Change>),
+}
+
+impl Change {
+    /// What undoing this would reverse, for messages and `:undolist`
+    pub fn describe(&self) -> String {
+        match self {
+            Change::Trashed(path) => format!("trash {}", file_name(path)),
+            Change::Collection { name, path, added: true } => format!("add {} to {}", file_name(path), name),
+            Change::Collection { name, path, added: false } => {
+                format!("remove {} from {}", file_name(path), name)
+            }
+            Change::Imported { paths, .. } => match paths.as_slice() {
+                [one] => format!("import {}", file_name(one)),
+                many => format!("import of {} files", many.len()),
+            },
+            Change::Batch(changes) => format!("{} tag changes", changes.len()),
+        }
+    }
+}
+
+/// Take `change` back. Collection changes are made in `collections`, which the caller saves
+/// even when this fails, since a batch keeps going past a part that can't be undone and
+/// returns the first error.
+pub fn revert(change: &Change, collections: &mut Collections) -> Result<()> {
+    match change {
+        Change::Trashed(path) => trashcan::restore_path(path),
+        Change::Collection { name, path, added: true } => collections.remove(name, path),
+        Change::Collection { name, path, added: false } => {
+            collections.add(name, path);
+            Ok(())
+        }
+        Change::Imported { paths, replaced } => {
+            // Find what the import replaced before its own files join them in the trash
+            let originals = replaced.iter().map(|path| trashcan::latest(path)).collect::<Result<Vec<_>>>()?;
+            // Trashed rather than deleted: an import may have been edited since
+            for path in paths.iter().filter(|p| p.is_symlink() || p.exists()) {
+                trashcan::move_to_trash(path)?;
+            }
+            originals.into_iter().try_for_each(trashcan::restore)
+        }
+        Change::Batch(changes) => {
+            let mut first_error = None;
+            for change in changes.iter().rev() {
+                if let Err(e) = revert(change, collections) {
+                    first_error.get_or_insert(e);
+                }
+            }
+            first_error.map_or(Ok(()), Err)
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+}
+
+/// Library changes in the order they were made
+#[derive(Default)]
+pub struct UndoStack {
+    changes: Vec<Change>,
+}
+
+impl UndoStack {
+    pub fn push(&mut self, change: Change) {
+        if self.changes.len() == MAX_CHANGES {
+            self.changes.remove(0);
+        }
+        self.changes.push(change);
+    }
+
+    /// The most recent change, removed from the stack
+    pub fn pop(&mut self) -> Option<Change> {
+        self.changes.pop()
+    }
+
+    /// Newest first
+    pub fn iter(&self) -> impl Iterator<Item = &Change> {
+        self.changes.iter().rev()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pops_newest_and_forgets_the_oldest() {
+        let mut stack = UndoStack::default();
+        for n in 0..=MAX_CHANGES {
+            stack.push(Change::Trashed(PathBuf::from(format!("/w/{}.jpg", n))));
+        }
+        assert_eq!(stack.iter().count(), MAX_CHANGES);
+        assert_eq!(stack.iter().last(), Some(&Change::Trashed(PathBuf::from("/w/1.jpg"))));
+        assert_eq!(stack.pop().map(|c| c.describe()), Some(format!("trash {}.jpg", MAX_CHANGES)));
+    }
+
+    fn tagged(name: &str, path: &str, added: bool) -> Change {
+        Change::Collection { name: name.to_string(), path: PathBuf::from(path), added }
+    }
+
+    #[test]
+    fn reverts_collection_changes() {
+        let mut collections = Collections::default();
+        collections.add("dark", Path::new("/w/a.jpg"));
+        revert(&tagged("dark", "/w/a.jpg", true), &mut collections).unwrap();
+        assert!(!collections.contains("dark", Path::new("/w/a.jpg")));
+        revert(&tagged("dark", "/w/a.jpg", false), &mut collections).unwrap();
+        assert!(collections.contains("dark", Path::new("/w/a.jpg")));
+    }
+
+    #[test]
+    fn batch_undoes_what_it_can() {
+        let mut collections = Collections::default();
+        collections.add("dark", Path::new("/w/a.jpg"));
+        collections.add("dark", Path::new("/w/c.jpg"));
+        // The middle change can't be undone: "gone" was deleted since
+        let batch = Change::Batch(vec![
+            tagged("dark", "/w/a.jpg", true),
+            tagged("gone", "/w/b.jpg", true),
+            tagged("dark", "/w/c.jpg", true),
+        ]);
+        assert!(revert(&batch, &mut collections).is_err());
+        assert!(!collections.contains("dark", Path::new("/w/a.jpg")));
+        assert!(!collections.contains("dark", Path::new("/w/c.jpg")));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::adjust::Adjustments;
+use crate::config::Config;
+use crate::events::{self, AppEvent, EventBus};
+use crate::wallpaper;
+use color_eyre::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Blur sigma for `<name>-blur.png` unless configured
+const DEFAULT_VARIANT_BLUR: f32 = 16.0;
+/// Dim amount for `<name>-dim.png` unless configured
+const DEFAULT_VARIANT_DIM: f32 = 0.4;
+
+/// Where companion variants are written, for lock screens and login managers to read
+pub fn get_variants_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".cache"))
+        .join("omarchy-wallpaper-picker")
+        .join("variants")
+}
+
+/// Generate blurred and dimmed companions of every applied wallpaper, when enabled in the config
+pub fn subscribe(bus: &mut EventBus, config: &Config) {
+    if !config.generate_variants {
+        return;
+    }
+    let blur = config.variant_blur.unwrap_or(DEFAULT_VARIANT_BLUR);
+    let dim = config.variant_dim.unwrap_or(DEFAULT_VARIANT_DIM);
+    bus.subscribe(move |event| {
+        if let AppEvent::WallpaperApplied(path) = event {
+            let path = path.clone();
+            // Blurring a full-size image takes a while; keep it off the caller's thread
+            events::spawn_background(move || {
+                let _ = generate(&path, blur, dim);
+            });
+        }
+    });
+}
+
+/// Write `<name>-blur.png` and `<name>-dim.png`, skipping the work when the source and
+/// settings match the last run
+fn generate(source: &Path, blur: f32, dim: f32) -> Result<()> {
+    let dir = get_variants_dir();
+    fs::create_dir_all(&dir)?;
+    let stamp_path = dir.join(wallpaper::stem_with(source, ".stamp"));
+    let stamp = stamp(source, blur, dim)?;
+    if fs::read_to_string(&stamp_path).is_ok_and(|old| old == stamp)
+        && dir.join(wallpaper::stem_with(source, "-blur.png")).exists()
+        && dir.join(wallpaper::stem_with(source, "-dim.png")).exists()
+    {
+        return Ok(());
+    }
+
+    let img = wallpaper::open_image(source)?;
+    let variants = [
+        ("blur", Adjustments { blur, ..Adjustments::default() }),
+        ("dim", Adjustments { dim, ..Adjustments::default() }),
+    ];
+    for (suffix, adjustments) in variants {
+        let dest = dir.join(wallpaper::stem_with(source, &format!("-{}.png", suffix)));
+        let tmp = dest.with_extension("png.tmp");
+        adjustments
+            .apply(img.clone())
+            .save_with_format(&tmp, image::ImageFormat::Png)?;
+        fs::rename(&tmp, &dest)?;
+    }
+    fs::write(&stamp_path, stamp)?;
+    Ok(())
+}
+
+/// Identifies the source version and settings the variants were made from
+fn stamp(source: &Path, blur: f32, dim: f32) -> Result<String> {
+    let source = fs::canonicalize(source)?;
+    let mtime = fs::metadata(&source)?.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+    Ok(format!("{}\n{}\nblur={} dim={}\n", source.display(), mtime, blur, dim))
+}
@@ -1,84 +1,347 @@
-use color_eyre::Result;
-use image::DynamicImage;
+use crate::monitor::{self, Monitor};
+use crate::profile::{self, Phase};
+use crate::config::{Config, INSTALL_FORMATS};
+use crate::palette::ColorStats;
+use crate::{backend, formats, index, ospath, svg, thumbnails, transition};
+use color_eyre::{eyre::eyre, Result};
+use image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
 use std::fs;
-use std::os::unix::fs::symlink;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+/// SVG raster size when no monitor information is available
+const SVG_FALLBACK_EDGE: u32 = 3840;
+
+/// How the backend maps an image onto an output (mirrors swaybg's `-m` modes)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FillMode {
+    #[default]
+    Fill,
+    Fit,
+    Stretch,
+    Center,
+    Tile,
+}
+
+impl FillMode {
+    pub fn next(self) -> Self {
+        match self {
+            FillMode::Fill => FillMode::Fit,
+            FillMode::Fit => FillMode::Stretch,
+            FillMode::Stretch => FillMode::Center,
+            FillMode::Center => FillMode::Tile,
+            FillMode::Tile => FillMode::Fill,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "fill" => Some(FillMode::Fill),
+            "fit" => Some(FillMode::Fit),
+            "stretch" => Some(FillMode::Stretch),
+            "center" => Some(FillMode::Center),
+            "tile" => Some(FillMode::Tile),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FillMode::Fill => "fill",
+            FillMode::Fit => "fit",
+            FillMode::Stretch => "stretch",
+            FillMode::Center => "center",
+            FillMode::Tile => "tile",
+        }
+    }
+}
+
+/// Smallest images directory listings include, to keep icons and avatars that share a
+/// folder with wallpapers out of the grid (`min_size`, `min_file_kb`)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MinSize {
+    pub dimensions: Option<(u32, u32)>,
+    pub bytes: Option<u64>,
+}
+
+impl MinSize {
+    /// `1280x720`
+    pub fn parse_dimensions(spec: &str) -> Option<(u32, u32)> {
+        let (width, height) = spec.trim().split_once(['x', 'X', '×'])?;
+        Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+    }
+
+    /// Whether a file of `bytes` with `dimensions` (None if unreadable) is big enough.
+    /// Portrait images count when they'd fit a rotated monitor.
+    pub fn admits(&self, bytes: u64, dimensions: Option<(u32, u32)>) -> bool {
+        let big_enough = match (self.dimensions, dimensions) {
+            (Some((min_w, min_h)), Some((w, h))) => (w >= min_w && h >= min_h) || (w >= min_h && h >= min_w),
+            _ => true,
+        };
+        big_enough && self.bytes.is_none_or(|min| bytes >= min)
+    }
+}
 
 pub struct Wallpaper {
     pub path: PathBuf,
     pub name: String,
-    pub thumbnail: Option<DynamicImage>,
+    /// Shared so the encoder can borrow it without copying the pixels
+    pub thumbnail: Option<Arc<DynamicImage>>,
+    /// No decoder for this format was compiled in
+    pub unsupported: bool,
+    /// Decoding the thumbnail failed; don't keep retrying every frame
+    pub load_failed: bool,
+    /// Width and height read from the header by the background dimension pass
+    pub dimensions: Option<(u32, u32)>,
+    /// Filled in by the background color pass
+    pub colors: Option<ColorStats>,
+    /// Stars set in a photo manager (XMP or EXIF), read by the header pass
+    pub rating: Option<u8>,
+    /// Name of the library root the file was found under, when several are merged
+    pub origin: Option<String>,
+    /// Read by the header pass, for the badges
+    pub file_info: Option<FileInfo>,
+}
+
+/// Format, size on disk and animation, for the grid badges
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileInfo {
+    /// Upper-case extension, e.g. `PNG`
+    pub format: String,
+    pub bytes: u64,
+    pub animated: bool,
+}
+
+impl FileInfo {
+    pub fn read(path: &Path) -> Self {
+        let format = match path.extension().map(|e| e.to_string_lossy().to_uppercase()) {
+            Some(ext) if ext == "JPEG" => "JPG".to_string(),
+            Some(ext) => ext,
+            None => "?".to_string(),
+        };
+        let bytes = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        Self { format, bytes, animated: formats::is_animated(path) }
+    }
+}
+
+/// Compact file size for badges, e.g. `830K` or `2.4M`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "K", "M", "G"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || size >= 10.0 {
+        format!("{:.0}{}", size, UNITS[unit])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
 }
 
 impl Wallpaper {
     pub fn new(path: PathBuf) -> Self {
-        let name = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("unknown")
-            .to_string();
-        Self { path, name, thumbnail: None }
+        // For display and search only; file operations go through `path`, which keeps
+        // names that aren't UTF-8 intact
+        let name = path.file_stem().map(ospath::display_name).unwrap_or_else(|| "unknown".to_string());
+        let unsupported = !formats::is_supported(&path);
+        Self { path, name, thumbnail: None, unsupported, load_failed: false, dimensions: None, colors: None, rating: None, origin: None, file_info: None }
     }
+}
 
-    pub fn load_thumbnail(&mut self) {
-        if self.thumbnail.is_some() {
-            return;
-        }
+/// `<stem><suffix>` for a file derived from `path`, keeping the original name's bytes
+/// even when they aren't UTF-8
+pub fn stem_with(path: &Path, suffix: &str) -> OsString {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    name
+}
+
+/// Read a cached thumbnail or decode one from the original, at least `edge` pixels across
+/// (see `thumbnails::edge_for`); None if it can't be decoded.
+/// Blocks on file reads, so the UI calls this from the thumbnail loader thread.
+pub fn load_thumbnail(path: &Path, edge: u32) -> Option<DynamicImage> {
+    // Try freedesktop thumbnails big enough for `edge` first, skipping stale ones
+    if let Some(thumb) = profile::time(Phase::ThumbnailLookup, || thumbnails::load(path, edge)) {
+        return Some(thumb);
+    }
 
-        // Try freedesktop thumbnails first (x-large, large, normal)
-        if let Some(thumb) = load_freedesktop_thumbnail(&self.path) {
-            self.thumbnail = Some(thumb);
-            return;
+    // An earlier run already failed on this version of the file
+    if thumbnails::has_failed(path) {
+        return None;
+    }
+
+    // Vector images rasterize straight at thumbnail size
+    let decoded = profile::time(Phase::Decode, || {
+        if svg::is_svg(path) {
+            svg::rasterize(path, edge)
+        } else {
+            formats::decode(path).map(|img| img.thumbnail(edge, edge))
         }
+    });
 
-        // Fallback: load original and resize
-        if let Ok(img) = image::open(&self.path) {
-            let thumb = img.thumbnail(256, 256);
-            self.thumbnail = Some(thumb);
+    // Caching is best-effort; a read-only cache dir just means regenerating next time
+    match decoded {
+        Ok(thumb) => {
+            let _ = thumbnails::save(path, &thumb, edge);
+            Some(thumb)
+        }
+        Err(_) => {
+            let _ = thumbnails::mark_failed(path);
+            None
         }
     }
 }
 
-fn get_freedesktop_thumb_dir() -> PathBuf {
-    dirs::cache_dir()
-        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".cache"))
-        .join("thumbnails")
+/// Longest edge SVGs are rasterized to: enough to cover the largest connected output
+fn svg_target_edge() -> u32 {
+    monitor::detect_monitors()
+        .iter()
+        .map(|m| m.width.max(m.height))
+        .max()
+        .unwrap_or(SVG_FALLBACK_EDGE)
 }
 
-fn get_freedesktop_thumbnail_path(original: &PathBuf, size: &str) -> PathBuf {
-    // Freedesktop spec: MD5 hash of file URI
-    let uri = format!("file://{}", original.canonicalize().unwrap_or(original.clone()).display());
-    let hash = format!("{:x}", md5::compute(uri.as_bytes()));
-    get_freedesktop_thumb_dir().join(size).join(format!("{}.png", hash))
+/// Decode any supported wallpaper at full size, rasterizing SVGs for the current outputs
+pub fn open_image(path: &Path) -> Result<DynamicImage> {
+    open_image_until(path, &AtomicBool::new(false))
 }
 
-fn load_freedesktop_thumbnail(original: &PathBuf) -> Option<DynamicImage> {
-    // Try sizes from largest to smallest
-    for size in &["xx-large", "x-large", "large", "normal"] {
-        let thumb_path = get_freedesktop_thumbnail_path(original, size);
-        if thumb_path.exists() {
-            if let Ok(img) = image::open(&thumb_path) {
-                return Some(img);
+/// `open_image`, giving up partway once `stop` is set where the decoder allows it
+pub fn open_image_until(path: &Path, stop: &AtomicBool) -> Result<DynamicImage> {
+    if svg::is_svg(path) {
+        return svg::rasterize(path, svg_target_edge());
+    }
+    formats::decode_until(path, stop)
+}
+
+/// Render `img` the way the backend would draw it on a `width`x`height` output.
+/// Areas not covered by the image are left black.
+pub fn render_for_output(img: &DynamicImage, mode: FillMode, width: u32, height: u32) -> DynamicImage {
+    let (width, height) = (width.max(1), height.max(1));
+    let mut canvas = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+
+    match mode {
+        FillMode::Fill => return img.resize_to_fill(width, height, imageops::FilterType::Triangle),
+        FillMode::Stretch => return img.resize_exact(width, height, imageops::FilterType::Triangle),
+        FillMode::Fit => {
+            let scaled = img.resize(width, height, imageops::FilterType::Triangle);
+            let x = (width as i64 - scaled.width() as i64) / 2;
+            let y = (height as i64 - scaled.height() as i64) / 2;
+            imageops::overlay(&mut canvas, &scaled.to_rgba8(), x, y);
+        }
+        FillMode::Center => {
+            let (w, h) = img.dimensions();
+            let x = (width as i64 - w as i64) / 2;
+            let y = (height as i64 - h as i64) / 2;
+            imageops::overlay(&mut canvas, &img.to_rgba8(), x, y);
+        }
+        FillMode::Tile => {
+            let tile = img.to_rgba8();
+            let (w, h) = tile.dimensions();
+            for y in (0..height).step_by(h.max(1) as usize) {
+                for x in (0..width).step_by(w.max(1) as usize) {
+                    imageops::overlay(&mut canvas, &tile, x as i64, y as i64);
+                }
             }
         }
     }
-    None
+
+    DynamicImage::ImageRgba8(canvas)
+}
+
+/// Write `source` to `dest` rendered at a monitor's full resolution with the given fill mode.
+/// The output format follows `dest`'s extension.
+pub fn export_scaled(source: &Path, dest: &Path, monitor: &Monitor, mode: FillMode) -> Result<()> {
+    let img = open_image(source)?;
+    let rendered = render_for_output(&img, mode, monitor.width, monitor.height);
+    // JPEG has no alpha channel
+    DynamicImage::ImageRgb8(rendered.to_rgb8()).save(dest)?;
+    Ok(())
+}
+
+/// A layout override for setups that aren't omarchy's. The command-line flag takes
+/// precedence over the config, which is replaced whenever the config is reloaded.
+struct PathOverride {
+    flag: Option<PathBuf>,
+    config: Option<PathBuf>,
+}
+
+impl PathOverride {
+    const UNSET: Self = Self { flag: None, config: None };
+
+    fn get(&self) -> Option<PathBuf> {
+        self.flag.clone().or_else(|| self.config.clone())
+    }
+}
+
+static BACKGROUNDS_DIR: RwLock<PathOverride> = RwLock::new(PathOverride::UNSET);
+static CURRENT_LINK: RwLock<PathOverride> = RwLock::new(PathOverride::UNSET);
+/// Directories merged into the default view instead of the backgrounds dir
+static LIBRARY_ROOTS: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+
+/// Use `dir` instead of omarchy's theme backgrounds (`--backgrounds-dir`)
+pub fn set_backgrounds_dir(dir: PathBuf) {
+    BACKGROUNDS_DIR.write().unwrap_or_else(|e| e.into_inner()).flag = Some(dir);
+}
+
+/// Point `link` at applied wallpapers instead of omarchy's current background (`--current-link`)
+pub fn set_current_link(link: PathBuf) {
+    CURRENT_LINK.write().unwrap_or_else(|e| e.into_inner()).flag = Some(link);
+}
+
+/// Take the backgrounds dir, current link and library roots from the config, replacing
+/// what an earlier config set; flags still win
+pub fn configure(config: &Config) {
+    BACKGROUNDS_DIR.write().unwrap_or_else(|e| e.into_inner()).config = config.backgrounds_dir.clone();
+    CURRENT_LINK.write().unwrap_or_else(|e| e.into_inner()).config = config.current_link.clone();
+    *LIBRARY_ROOTS.write().unwrap_or_else(|e| e.into_inner()) = config.library_roots.clone();
+}
+
+/// Configured library roots; empty when the backgrounds dir is the library
+pub fn library_roots() -> Vec<PathBuf> {
+    LIBRARY_ROOTS.read().unwrap_or_else(|e| e.into_inner()).clone()
 }
 
 pub fn get_backgrounds_dir() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_default()
-        .join(".config/omarchy/current/theme/backgrounds")
+    BACKGROUNDS_DIR.read().unwrap_or_else(|e| e.into_inner()).get().unwrap_or_else(|| {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join(".config/omarchy/current/theme/backgrounds")
+    })
 }
 
 pub fn get_current_background_path() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_default()
-        .join(".config/omarchy/current/background")
+    CURRENT_LINK.read().unwrap_or_else(|e| e.into_inner()).get().unwrap_or_else(|| {
+        dirs::home_dir()
+            .unwrap_or_default()
+            .join(".config/omarchy/current/background")
+    })
+}
+
+/// Scratch file for images shown on trial that the backend can't take as they are
+pub fn get_trial_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("omarchy-wallpaper-picker")
+        .join("trial.png")
 }
 
 pub fn discover_wallpapers(dir: Option<PathBuf>) -> Result<Vec<Wallpaper>> {
+    profile::time(Phase::Discovery, || scan_dir(dir))
+}
+
+fn scan_dir(dir: Option<PathBuf>) -> Result<Vec<Wallpaper>> {
+    let roots = library_roots();
+    if dir.is_none() && !roots.is_empty() {
+        return Ok(scan_roots(&roots));
+    }
     let backgrounds_dir = dir.unwrap_or_else(get_backgrounds_dir);
     let mut wallpapers = Vec::new();
 
@@ -96,9 +359,169 @@ pub fn discover_wallpapers(dir: Option<PathBuf>) -> Result<Vec<Wallpaper>> {
     Ok(wallpapers)
 }
 
+/// Every image under each of `roots`, subdirectories included, tagged with the root's name
+/// when there are several. Roots that are missing or unreadable are skipped rather than failing
+/// the whole listing.
+fn scan_roots(roots: &[PathBuf]) -> Vec<Wallpaper> {
+    let mut wallpapers = Vec::new();
+    for (root, origin) in roots.iter().zip(origin_labels(roots)) {
+        scan_tree(root, origin.as_deref(), &mut wallpapers);
+    }
+    wallpapers.sort_by(|a, b| a.name.cmp(&b.name));
+    wallpapers
+}
+
+/// Badge for each root: its name, with as many parent dirs as it takes to tell apart roots
+/// with the same name (`work/pics`, `home/pics`). None for a lone root.
+fn origin_labels(roots: &[PathBuf]) -> Vec<Option<String>> {
+    if roots.len() < 2 {
+        return vec![None; roots.len()];
+    }
+    let parts: Vec<Vec<String>> = roots
+        .iter()
+        .map(|root| {
+            root.components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect();
+    let label = |parts: &[String], depth: usize| parts[parts.len().saturating_sub(depth)..].join("/");
+    let mut depths = vec![1; roots.len()];
+    loop {
+        let labels: Vec<String> = parts.iter().zip(&depths).map(|(parts, &depth)| label(parts, depth)).collect();
+        let mut deepened = false;
+        for (i, own) in labels.iter().enumerate() {
+            let clashes = labels.iter().enumerate().any(|(j, other)| j != i && other == own);
+            if clashes && depths[i] < parts[i].len() {
+                depths[i] += 1;
+                deepened = true;
+            }
+        }
+        if !deepened {
+            return labels
+                .into_iter()
+                .zip(roots)
+                .map(|(label, root)| Some(if label.is_empty() { root.display().to_string() } else { label }))
+                .collect();
+        }
+    }
+}
+
+fn scan_tree(dir: &Path, origin: Option<&str>, wallpapers: &mut Vec<Wallpaper>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Symlinked directories aren't followed, so a link back up the tree can't loop
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_dir && !entry.file_name().as_encoded_bytes().starts_with(b".") {
+            scan_tree(&path, origin, wallpapers);
+        } else if !is_dir && is_image(&path) {
+            let mut wallpaper = Wallpaper::new(path);
+            wallpaper.origin = origin.map(str::to_string);
+            wallpapers.push(wallpaper);
+        }
+    }
+}
+
+/// Records the source of the current background when it is a copy rather than a symlink
+fn get_copy_state_path() -> PathBuf {
+    dirs::state_dir()
+        .unwrap_or_else(|| dirs::home_dir().unwrap_or_default().join(".local/state"))
+        .join("omarchy-wallpaper-picker")
+        .join("current-source")
+}
+
+/// Fill mode the current wallpaper was last shown with
+fn get_mode_state_path() -> PathBuf {
+    get_copy_state_path().with_file_name("current-mode")
+}
+
+pub fn get_current_fill_mode() -> Option<FillMode> {
+    FillMode::parse(fs::read_to_string(get_mode_state_path()).ok()?.trim())
+}
+
+fn save_current_fill_mode(mode: FillMode) -> Result<()> {
+    let path = get_mode_state_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, mode.as_str())?;
+    Ok(())
+}
+
+/// Whether the current wallpaper, already `path`, is up in `mode` with the file's present
+/// contents; files re-rendered in place (adjusted copies) are newer than the link
+fn is_showing(path: &Path, mode: FillMode) -> bool {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    get_current_fill_mode() == Some(mode)
+        && modified.zip(get_current_wallpaper_applied_at()).is_some_and(|(m, applied)| m <= applied)
+}
+
 pub fn get_current_wallpaper() -> Option<PathBuf> {
     let current = get_current_background_path();
-    fs::read_link(&current).ok()
+    if let Ok(target) = fs::read_link(&current) {
+        return Some(target);
+    }
+    // A regular file only counts as ours if we recorded copying it
+    if current.is_file() {
+        return fs::read(get_copy_state_path()).ok().map(|mut source| {
+            while source.last() == Some(&b'\n') {
+                source.pop();
+            }
+            ospath::from_bytes(source)
+        });
+    }
+    None
+}
+
+/// When the current-background symlink was last replaced, i.e. when the wallpaper was applied
+pub fn get_current_wallpaper_applied_at() -> Option<SystemTime> {
+    let current = get_current_background_path();
+    fs::symlink_metadata(&current).and_then(|m| m.modified()).ok()
+}
+
+/// The wallpaper `offset` places away from the current one among `candidates`,
+/// wrapping around at either end. Starts from the first one if nothing is current.
+pub fn relative_wallpaper(candidates: &[PathBuf], offset: isize) -> Option<PathBuf> {
+    step_from(candidates, get_current_wallpaper().as_deref(), offset)
+}
+
+/// `relative_wallpaper` from `current`, which is usually the installed copy of a candidate
+/// that lives elsewhere (a rotation collection), so it is matched by name when not by path
+fn step_from(candidates: &[PathBuf], current: Option<&Path>, offset: isize) -> Option<PathBuf> {
+    if candidates.is_empty() {
+        return None;
+    }
+    let len = candidates.len() as isize;
+    let position = current.and_then(|c| {
+        candidates
+            .iter()
+            .position(|p| p == c)
+            .or_else(|| candidates.iter().position(|p| same_wallpaper(p, c)))
+    });
+    let index = match position {
+        Some(pos) => (pos as isize + offset).rem_euclid(len),
+        None => 0,
+    };
+    Some(candidates[index as usize].clone())
+}
+
+/// Applied wallpapers are installed copies, so a source and its copy are matched by name.
+/// Converted copies (SVG rasterized to PNG, downscaled re-encodes) keep the stem.
+pub fn same_wallpaper(a: &Path, b: &Path) -> bool {
+    a == b || (a.file_stem().is_some() && a.file_stem() == b.file_stem())
+}
+
+/// Install an arbitrary image file and make it the current wallpaper
+pub fn apply_path(path: &Path, mode: FillMode, config: &Config) -> Result<PathBuf> {
+    let installed_path = install_wallpaper(&Wallpaper::new(path.to_path_buf()))?;
+    set_wallpaper(&installed_path, mode, config)?;
+    Ok(installed_path)
 }
 
 pub fn install_wallpaper(wallpaper: &Wallpaper) -> Result<PathBuf> {
@@ -107,6 +530,22 @@ pub fn install_wallpaper(wallpaper: &Wallpaper) -> Result<PathBuf> {
         fs::create_dir_all(&backgrounds_dir)?;
     }
 
+    // Backends can't draw SVG and few read HEIC, AVIF or JXL; install a PNG alongside instead
+    if svg::is_svg(&wallpaper.path) || formats::is_optional_format(&wallpaper.path) {
+        let dest_path = backgrounds_dir.join(stem_with(&wallpaper.path, ".png"));
+        open_image(&wallpaper.path)?.save(&dest_path)?;
+        return Ok(dest_path);
+    }
+
+    // Files already in the backgrounds dir are used as they are
+    let config = Config::load().unwrap_or_default();
+    if config.downscale_on_install
+        && wallpaper.path.parent() != Some(backgrounds_dir.as_path())
+        && let Some(dest_path) = install_downscaled(wallpaper, &backgrounds_dir, &config)?
+    {
+        return Ok(dest_path);
+    }
+
     let file_name = wallpaper
         .path
         .file_name()
@@ -120,44 +559,389 @@ pub fn install_wallpaper(wallpaper: &Wallpaper) -> Result<PathBuf> {
     Ok(dest_path)
 }
 
-pub fn set_wallpaper(path: &PathBuf) -> Result<()> {
+/// Install a copy scaled down to just cover the largest monitor, re-encoded in the configured
+/// format. None when the image is no bigger than that (or no monitors are known) and can be
+/// copied as is.
+fn install_downscaled(wallpaper: &Wallpaper, backgrounds_dir: &Path, config: &Config) -> Result<Option<PathBuf>> {
+    let Some((width, height)) = formats::dimensions(&wallpaper.path) else {
+        return Ok(None);
+    };
+    let monitors = monitor::detect_monitors();
+    let (Some(max_width), Some(max_height)) = (
+        monitors.iter().map(|m| m.width).max(),
+        monitors.iter().map(|m| m.height).max(),
+    ) else {
+        return Ok(None);
+    };
+    // Keep enough pixels to fill the largest output in both directions
+    let scale = (max_width as f64 / width as f64).max(max_height as f64 / height as f64);
+    if scale >= 1.0 {
+        return Ok(None);
+    }
+    let new_width = ((width as f64 * scale).ceil() as u32).max(1);
+    let new_height = ((height as f64 * scale).ceil() as u32).max(1);
+    let img = open_image(&wallpaper.path)?.resize_exact(new_width, new_height, imageops::FilterType::Lanczos3);
+
+    let extension = config
+        .install_format
+        .clone()
+        .or_else(|| wallpaper.path.extension().and_then(|e| e.to_str()).map(str::to_lowercase))
+        // Formats we can decode but not encode (HEIC, JXL, ...) fall back to PNG
+        .filter(|e| INSTALL_FORMATS.contains(&e.as_str()))
+        .unwrap_or_else(|| "png".to_string());
+    let dest_path = backgrounds_dir.join(stem_with(&wallpaper.path, &format!(".{}", extension)));
+    match extension.as_str() {
+        "jpg" | "jpeg" => {
+            let file = std::io::BufWriter::new(fs::File::create(&dest_path)?);
+            let quality = config.install_quality.unwrap_or(90);
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(file, quality);
+            DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)?;
+        }
+        _ => img.save(&dest_path)?,
+    }
+    Ok(Some(dest_path))
+}
+
+/// Save a processed copy of `wallpaper` into the backgrounds dir as `<name>-<suffix>-<hash>.<ext>`.
+/// The hash covers the source's contents and `settings` (e.g. the crop and adjustments), so the
+/// same processing is only done once and each result keeps its own file.
+pub fn install_processed<F>(wallpaper: &Wallpaper, suffix: &str, settings: &str, process: F) -> Result<PathBuf>
+where
+    F: FnOnce(DynamicImage) -> DynamicImage,
+{
+    let backgrounds_dir = get_backgrounds_dir();
+    if !backgrounds_dir.exists() {
+        fs::create_dir_all(&backgrounds_dir)?;
+    }
+
+    // Backends can't draw SVG, HEIC and the like, so those are written as PNG
+    let extension = wallpaper
+        .path
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|_| !svg::is_svg(&wallpaper.path) && !formats::is_optional_format(&wallpaper.path))
+        .unwrap_or("png");
+    let source_hash = index::content_hash(&wallpaper.path)
+        .ok_or_else(|| eyre!("Couldn't read {}", wallpaper.path.display()))?;
+    let dest_path = backgrounds_dir.join(stem_with(
+        &wallpaper.path,
+        &format!("-{}-{}.{}", suffix, processed_hash(&source_hash, settings), extension),
+    ));
+    if dest_path.exists() {
+        return Ok(dest_path);
+    }
+
+    let img = process(open_image(&wallpaper.path)?);
+    // Written under a temporary name so an interrupted save isn't mistaken for a cached result
+    let staging = staging_path(&dest_path);
+    let format = image::ImageFormat::from_extension(extension).unwrap_or(image::ImageFormat::Png);
+    // Formats like JPEG can't store alpha
+    DynamicImage::ImageRgb8(img.to_rgb8()).save_with_format(&staging, format)?;
+    fs::rename(&staging, &dest_path)?;
+
+    Ok(dest_path)
+}
+
+/// Short name-safe hash of a source's content hash and the processing settings
+fn processed_hash(source_hash: &str, settings: &str) -> String {
+    let digest = format!("{:x}", md5::compute(format!("{}\n{}", source_hash, settings)));
+    digest[..10].to_string()
+}
+
+/// `<current>.orig`, or `.orig.1`, `.orig.2`, ... so an earlier backup is never overwritten
+fn backup_path(current: &Path) -> PathBuf {
+    let backup = current.with_extension("orig");
+    if !backup.exists() {
+        return backup;
+    }
+    (1..)
+        .map(|n| {
+            let mut name = backup.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(backup)
+}
+
+pub fn set_wallpaper(path: &PathBuf, mode: FillMode, config: &Config) -> Result<()> {
     let current = get_current_background_path();
+    let previous = get_current_wallpaper();
 
-    // Remove existing symlink
-    if current.exists() || current.is_symlink() {
-        fs::remove_file(&current)?;
+    // Re-applying what is already up would only restart the backend and flash the screen
+    let backend = backend::for_config(config);
+    if previous.as_ref() == Some(path) && is_showing(path, mode) && backend.is_alive() {
+        return Ok(());
     }
 
-    // Create new symlink
-    symlink(path, &current)?;
+    // A real file that isn't a copy we made belongs to another tool, so keep a backup
+    let mut backed_up = None;
+    if !current.is_symlink() && current.exists() && previous.is_none() {
+        let backup = backup_path(&current);
+        fs::rename(&current, &backup)?;
+        backed_up = Some(backup);
+    }
+
+    // Only reload the backend once the new link is in place
+    let previous_mode = get_current_fill_mode();
+    link_or_copy(path, &current, &get_copy_state_path())?;
+
+    let _ = save_current_fill_mode(mode);
 
-    // Reload swaybg
-    reload_swaybg()?;
+    // Reload the backend, blending from the previous wallpaper if requested and it can't animate itself
+    match (config.crossfade(), previous.clone()) {
+        (Some(duration), Some(previous))
+            if !duration.is_zero() && previous != *path && !backend.has_transitions() =>
+        {
+            // The fade ends by showing the new link; if that fails, put the old one back
+            let (link, from, backup) = (current.clone(), previous.clone(), backed_up.clone());
+            let rollback_config = config.clone();
+            transition::crossfade(config.clone(), previous, path.clone(), mode, duration, move || {
+                roll_back(&link, Some(&from), previous_mode, backup.as_deref());
+                let _ = backend::reload(&rollback_config, previous_mode.unwrap_or_default());
+            });
+        }
+        _ => {
+            if let Err(e) = backend::show_with_pins(&*backend, &current, mode) {
+                // The desktop still shows the old wallpaper, so the link has to say so too
+                roll_back(&current, previous.as_deref(), previous_mode, backed_up.as_deref());
+                return Err(eyre!(
+                    "{} couldn't show {}: {}; kept the previous wallpaper",
+                    backend.name(),
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    e
+                ));
+            }
+        }
+    }
 
     Ok(())
 }
 
-fn reload_swaybg() -> Result<()> {
-    // Kill existing swaybg
-    let _ = Command::new("killall").arg("swaybg").output();
+/// Put the current-background link back the way it was before a failed apply
+fn roll_back(current: &Path, previous: Option<&Path>, mode: Option<FillMode>, backup: Option<&Path>) {
+    restore_link(current, previous, backup, &get_copy_state_path());
+    match mode {
+        Some(mode) => {
+            let _ = save_current_fill_mode(mode);
+        }
+        None => {
+            let _ = fs::remove_file(get_mode_state_path());
+        }
+    }
+}
+
+/// Point `current` back at `previous`, or, if nothing was applied before, remove it and
+/// return the other tool's file that was moved aside to `backup`
+fn restore_link(current: &Path, previous: Option<&Path>, backup: Option<&Path>, state_path: &Path) {
+    match previous {
+        Some(previous) => {
+            let _ = link_or_copy(previous, current, state_path);
+        }
+        None => {
+            let _ = fs::remove_file(current);
+            let _ = fs::remove_file(state_path);
+            if let Some(backup) = backup {
+                let _ = fs::rename(backup, current);
+            }
+        }
+    }
+}
+
+/// Symlink `current` to `path`, falling back to a copy plus a state file recording
+/// the source where symlinks aren't available (non-Unix, or filesystems without them).
+/// Either is built under a temporary name and renamed over `current`, so the path
+/// never goes missing in between.
+fn link_or_copy(path: &Path, current: &Path, state_path: &Path) -> Result<()> {
+    if let Some(parent) = current.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let staging = staging_path(current);
+    let _ = fs::remove_file(&staging);
 
-    // Start new swaybg
-    Command::new("swaybg")
-        .arg("-i")
-        .arg(get_current_background_path())
-        .arg("-m")
-        .arg("fill")
-        .spawn()?;
+    #[cfg(unix)]
+    if std::os::unix::fs::symlink(path, &staging).is_ok() {
+        fs::rename(&staging, current)?;
+        let _ = fs::remove_file(state_path);
+        return Ok(());
+    }
 
+    fs::copy(path, &staging)?;
+    fs::rename(&staging, current)?;
+    if let Some(parent) = state_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(state_path, ospath::to_bytes(path))?;
     Ok(())
 }
 
-fn is_image(path: &PathBuf) -> bool {
+/// Hidden sibling of `current` to build the replacement in; rename(2) needs the same filesystem
+fn staging_path(current: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(current.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", std::process::id()));
+    current.with_file_name(name)
+}
+
+pub fn is_image(path: &Path) -> bool {
+    if formats::is_optional_format(path) {
+        return true;
+    }
     match path.extension().and_then(|e| e.to_str()) {
         Some(ext) => matches!(
             ext.to_lowercase().as_str(),
-            "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp"
+            "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "svg"
         ),
         None => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_sizes() {
+        assert_eq!(format_bytes(512), "512B");
+        assert_eq!(format_bytes(2_500_000), "2.4M");
+        assert_eq!(format_bytes(830 * 1024), "830K");
+    }
+
+    #[test]
+    fn min_size_dimensions() {
+        assert_eq!(MinSize::parse_dimensions("1280x720"), Some((1280, 720)));
+        assert_eq!(MinSize::parse_dimensions(" 1920 X 1080 "), Some((1920, 1080)));
+        assert_eq!(MinSize::parse_dimensions("800×600"), Some((800, 600)));
+        assert_eq!(MinSize::parse_dimensions("1280"), None);
+        assert_eq!(MinSize::parse_dimensions("wide x tall"), None);
+    }
+
+    #[test]
+    fn min_size_admits() {
+        let min = MinSize { dimensions: Some((1280, 720)), bytes: Some(50_000) };
+        assert!(min.admits(60_000, Some((1920, 1080))));
+        assert!(min.admits(60_000, Some((720, 1280))), "portrait fits a rotated monitor");
+        assert!(!min.admits(60_000, Some((256, 256))));
+        assert!(!min.admits(10_000, Some((1920, 1080))));
+        assert!(min.admits(60_000, None), "unreadable headers aren't held against the file");
+        assert!(MinSize::default().admits(0, Some((1, 1))));
+    }
+
+    #[test]
+    fn processed_names_follow_contents_and_settings() {
+        let a = processed_hash("abc", "blur 2");
+        assert_eq!(a, processed_hash("abc", "blur 2"));
+        assert_ne!(a, processed_hash("abd", "blur 2"));
+        assert_ne!(a, processed_hash("abc", "blur 3"));
+        assert_eq!(a.len(), 10);
+    }
+
+    #[test]
+    fn steps_from_the_installed_copy() {
+        let pool: Vec<PathBuf> = ["/pics/a.jpg", "/pics/b.svg", "/pics/c.png"].iter().map(PathBuf::from).collect();
+        // The current link points into the backgrounds dir, at a rasterized copy of b
+        let current = Path::new("/backgrounds/b.png");
+        assert_eq!(step_from(&pool, Some(current), 1).unwrap(), pool[2]);
+        assert_eq!(step_from(&pool, Some(current), -1).unwrap(), pool[0]);
+        assert_eq!(step_from(&pool, Some(Path::new("/pics/c.png")), 1).unwrap(), pool[0]);
+        assert_eq!(step_from(&pool, None, 1).unwrap(), pool[0]);
+        assert!(step_from(&[], Some(current), 1).is_none());
+    }
+
+    #[test]
+    fn reloaded_config_replaces_layout_but_not_flags() {
+        let mut setting = PathOverride::UNSET;
+        setting.config = Some(PathBuf::from("/first"));
+        setting.config = Some(PathBuf::from("/second"));
+        assert_eq!(setting.get(), Some(PathBuf::from("/second")));
+        setting.flag = Some(PathBuf::from("/flag"));
+        assert_eq!(setting.get(), Some(PathBuf::from("/flag")));
+        setting.flag = None;
+        setting.config = None;
+        assert_eq!(setting.get(), None);
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("owp-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn roll_back_relinks_the_previous_wallpaper() {
+        let dir = scratch("relink");
+        let (old, new, current, state) = (dir.join("old.png"), dir.join("new.png"), dir.join("background"), dir.join("state"));
+        fs::write(&old, "old").unwrap();
+        fs::write(&new, "new").unwrap();
+        link_or_copy(&new, &current, &state).unwrap();
+
+        restore_link(&current, Some(&old), None, &state);
+        assert_eq!(fs::read_link(&current).unwrap(), old);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn roots_are_scanned_recursively_and_badged() {
+        let dir = scratch("roots");
+        let (work, home) = (dir.join("work/pics"), dir.join("home/pics"));
+        fs::create_dir_all(work.join("nested")).unwrap();
+        fs::create_dir_all(home.join(".hidden")).unwrap();
+        fs::write(work.join("a.png"), "").unwrap();
+        fs::write(work.join("nested/b.jpg"), "").unwrap();
+        fs::write(work.join("notes.txt"), "").unwrap();
+        fs::write(home.join("c.webp"), "").unwrap();
+        fs::write(home.join(".hidden/d.png"), "").unwrap();
+
+        let found = |roots: &[PathBuf]| -> Vec<(String, Option<String>)> {
+            scan_roots(roots).into_iter().map(|w| (w.name, w.origin)).collect()
+        };
+        let entry = |name: &str, origin: Option<&str>| (name.to_string(), origin.map(str::to_string));
+        assert_eq!(
+            found(&[work.clone(), home.clone(), dir.join("missing")]),
+            vec![entry("a", Some("work/pics")), entry("b", Some("work/pics")), entry("c", Some("home/pics"))]
+        );
+        assert_eq!(found(std::slice::from_ref(&home)), vec![entry("c", None)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn origin_labels_tell_roots_apart() {
+        let roots = [PathBuf::from("/a/pics"), PathBuf::from("/b/pics"), PathBuf::from("/c/art")];
+        let labels: Vec<Option<String>> = ["a/pics", "b/pics", "art"].iter().map(|s| Some(s.to_string())).collect();
+        assert_eq!(origin_labels(&roots), labels);
+        assert_eq!(origin_labels(&roots[..1]), vec![None]);
+    }
+
+    #[test]
+    fn backups_never_overwrite_each_other() {
+        let dir = scratch("backup-names");
+        let current = dir.join("background");
+        assert_eq!(backup_path(&current), dir.join("background.orig"));
+        fs::write(dir.join("background.orig"), "first").unwrap();
+        assert_eq!(backup_path(&current), dir.join("background.orig.1"));
+        fs::write(dir.join("background.orig.1"), "second").unwrap();
+        assert_eq!(backup_path(&current), dir.join("background.orig.2"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn roll_back_returns_the_backup() {
+        let dir = scratch("backup");
+        let (new, current, backup, state) = (dir.join("new.png"), dir.join("background"), dir.join("background.orig"), dir.join("state"));
+        fs::write(&new, "new").unwrap();
+        fs::write(&backup, "theirs").unwrap();
+        link_or_copy(&new, &current, &state).unwrap();
+
+        restore_link(&current, None, Some(&backup), &state);
+        assert!(!current.is_symlink());
+        assert_eq!(fs::read_to_string(&current).unwrap(), "theirs");
+        assert!(!backup.exists());
+
+        // Nothing to return: the link just goes away
+        link_or_copy(&new, &current, &state).unwrap();
+        restore_link(&current, None, None, &state);
+        assert!(!current.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
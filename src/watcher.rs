@@ -0,0 +1,78 @@
+use color_eyre::{eyre::eyre, Result};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// Watches a directory for created, removed or renamed files
+pub struct DirWatcher {
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<Event>>,
+}
+
+impl DirWatcher {
+    /// Watch `dir` itself but not its subdirectories
+    pub fn new(dir: &Path) -> Result<Self> {
+        Self::watch(dir, RecursiveMode::NonRecursive)
+    }
+
+    /// Watch `dir` and everything below it
+    pub fn recursive(dir: &Path) -> Result<Self> {
+        Self::watch(dir, RecursiveMode::Recursive)
+    }
+
+    fn watch(dir: &Path, mode: RecursiveMode) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(dir, mode)?;
+        Ok(Self { _watcher: watcher, rx })
+    }
+
+    /// Drain pending events, returning true if the directory listing may have changed.
+    /// Bursts of events (e.g. a large copy) collapse into a single refresh.
+    pub fn poll_changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.rx.try_recv() {
+            if let Ok(event) = event
+                && matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_)
+                )
+            {
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Watches one file, e.g. the current-background symlink, for being replaced or rewritten.
+/// Its parent directory is watched, since symlinks are swapped rather than edited in place.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+    rx: Receiver<notify::Result<Event>>,
+    path: PathBuf,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> Result<Self> {
+        let dir = path.parent().ok_or_else(|| eyre!("{} has no parent", path.display()))?;
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self { _watcher: watcher, rx, path: path.to_path_buf() })
+    }
+
+    /// Drain pending events, returning true if any touched the watched file
+    pub fn poll_changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.rx.try_recv() {
+            if let Ok(event) = event
+                && !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|p| p.file_name() == self.path.file_name())
+            {
+                changed = true;
+            }
+        }
+        changed
+    }
+}